@@ -0,0 +1,702 @@
+//! Settings and alarms as a human-editable TOML file
+//!
+//! ```toml
+//! alarms_enabled = true
+//! max_ring_minutes = 15
+//! brightness = 8
+//!
+//! [escalation]
+//! ramp_seconds = 120
+//! start_frequency = 1319
+//! end_frequency = 3136
+//!
+//! [chime]
+//! mode = "hourly"
+//! style = "westminster"
+//! quiet_start = 22
+//! quiet_end = 7
+//!
+//! [screen]
+//! pages = ["date", "alarm"]
+//! date_format = "dmy"
+//! page_seconds = 5
+//!
+//! [log_levels]
+//! rtc = "debug"
+//!
+//! [[alarms]]
+//! time = "06:45"
+//! enabled = true
+//! sound = "gentle"
+//! ```
+//!
+//! Alarms are listed in the order of the clock's alarm table, with the empty
+//! slots left out. Tags missing from `log_levels` are logged at the default
+//! level, and a missing `screen` is the idle screen as it starts out.
+
+use crate::{
+    idle_screen::{DateFormat, PAGE_NAMES},
+    log::{Level, Tag, DEFAULT_LEVEL, TAG_COUNT},
+    settings::{
+        Settings, SettingsError, StoredAlarm, ALARM_ENABLED, ALARM_PRESENT, MAX_ALARMS,
+        SOUND_ESCALATING, SOUND_MELODY, SOUND_PRESET,
+    },
+    sound::{ChimeMode, ChimeStyle, Tune},
+    state::State,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt};
+
+/// What the firmware starts with before any settings are saved, for filling in
+/// fields missing from records saved by older firmware
+pub fn defaults() -> Settings {
+    State::new().settings()
+}
+
+/// Names of the firmware's `Tune::ALL`, by index
+fn tunes() -> [&'static str; Tune::ALL.len()] {
+    Tune::ALL.map(|tune| tune.name())
+}
+
+/// By `ChimeMode`
+fn chime_modes() -> [&'static str; ChimeMode::ALL.len()] {
+    ChimeMode::ALL.map(|mode| mode.name())
+}
+
+/// By `ChimeStyle`
+fn chime_styles() -> [&'static str; ChimeStyle::ALL.len()] {
+    ChimeStyle::ALL.map(|style| style.name())
+}
+
+/// Names of the escalation presets, by `PRESET_*`. The shell calls them
+/// `escalate gentle` and so on, which is too long for a backup.
+const PRESETS: [&str; 4] = ["gentle", "standard", "urgent", "custom"];
+
+/// By `DateFormat`
+fn date_formats() -> [&'static str; DateFormat::ALL.len()] {
+    DateFormat::ALL.map(|format| format.name())
+}
+
+const HEADER: &str = "\
+# Alarm clock settings, from `alarm-clock-cli export`
+#
+# Alarm sounds are a tune (nokia, simpsons, entertainer, or tetris) or an
+# escalation preset (gentle, standard, urgent, or custom). There's one custom
+# profile, in [escalation], which every custom alarm shares. Chimes are off,
+# hourly, or half-hourly, in the beep or westminster style. The idle screen
+# rotates through any of the date, alarm and big (time) pages, with the date
+# as dmy, mdy, iso, or text.
+
+";
+
+#[derive(Debug)]
+pub enum Error {
+    /// The file isn't valid TOML, or is missing fields
+    Parse(toml::de::Error),
+    /// A field couldn't be understood, like an unknown tune
+    Invalid(String),
+    /// A field is outside of the firmware's ranges
+    OutOfRange,
+    /// The clock's record couldn't be decoded
+    Settings(SettingsError),
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Parse(e) => write!(f, "couldn't read the backup: {e}"),
+            Error::Invalid(reason) => write!(f, "invalid backup: {reason}"),
+            Error::OutOfRange => write!(
+                f,
+                "a setting is out of range (brightness is from 1 to 8, quiet hours from 0 to 23, \
+                 alarms ring for at least a minute, the escalation has a ramp and frequencies, \
+                 and the idle screen shows at least one page for 1 to 60 seconds)"
+            ),
+            Error::Settings(SettingsError::UnsupportedVersion(version)) => write!(
+                f,
+                "the clock's settings are version {version}, newer than this understands"
+            ),
+            Error::Settings(e) => write!(f, "the clock sent bad settings: {e:?}"),
+        }
+    }
+}
+impl std::error::Error for Error {}
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Backup {
+    /// Master switch for all alarms
+    pub alarms_enabled: bool,
+    pub max_ring_minutes: u8,
+    pub brightness: u8,
+    /// The custom escalation profile, shared by every alarm whose sound is
+    /// `custom`
+    pub escalation: Escalation,
+    pub chime: Chime,
+    #[serde(default)]
+    pub screen: Screen,
+    #[serde(default)]
+    pub log_levels: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alarms: Vec<Alarm>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Escalation {
+    pub ramp_seconds: u16,
+    pub start_frequency: u16,
+    pub end_frequency: u16,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Chime {
+    pub mode: String,
+    pub style: String,
+    pub quiet_start: u8,
+    pub quiet_end: u8,
+}
+
+/// The LCD's idle screen
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Screen {
+    /// The pages to rotate through, in any order
+    pub pages: Vec<String>,
+    pub date_format: String,
+    pub page_seconds: u8,
+}
+impl Default for Screen {
+    fn default() -> Self {
+        Self::from_settings(&defaults())
+    }
+}
+impl Screen {
+    fn from_settings(settings: &Settings) -> Self {
+        Self {
+            pages: PAGE_NAMES
+                .iter()
+                .enumerate()
+                .filter(|(page, _)| settings.idle_pages & 1_u8 << page != 0_u8)
+                .map(|(_, name)| name.to_string())
+                .collect(),
+            date_format: name(&date_formats(), settings.idle_date_format),
+            page_seconds: settings.idle_page_seconds,
+        }
+    }
+
+    /// The pages as the firmware's bits
+    fn pages(&self) -> Result<u8, Error> {
+        self.pages.iter().try_fold(0_u8, |pages, page| {
+            Ok(pages | 1_u8 << index_or_invalid(&PAGE_NAMES, page, "idle screen page")?)
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Alarm {
+    /// `HH:MM`
+    pub time: String,
+    pub enabled: bool,
+    pub sound: String,
+}
+
+impl Backup {
+    pub fn from_settings(settings: &Settings) -> Self {
+        let alarms = settings
+            .alarms
+            .iter()
+            .filter(|alarm| alarm.flags & ALARM_PRESENT != 0_u8)
+            .map(|alarm| Alarm {
+                time: format!("{:02}:{:02}", alarm.hours, alarm.minutes),
+                enabled: alarm.flags & ALARM_ENABLED != 0_u8,
+                sound: match alarm.sound {
+                    sound if sound & SOUND_PRESET != 0_u8 => name(&PRESETS, sound & !SOUND_PRESET),
+                    tune => name(&tunes(), tune),
+                },
+            })
+            .collect();
+        let log_levels = Tag::ALL
+            .iter()
+            .zip(settings.log_levels)
+            .map(|(tag, level)| {
+                let level = Level::from_u8(level).unwrap_or(DEFAULT_LEVEL);
+                (tag_key(tag), level.name().to_ascii_lowercase())
+            })
+            .collect();
+        Self {
+            alarms_enabled: settings.alarm_enabled,
+            max_ring_minutes: settings.alarm_max_ring_minutes,
+            brightness: settings.brightness,
+            escalation: Escalation {
+                ramp_seconds: settings.escalation_ramp_seconds,
+                start_frequency: settings.escalation_start_frequency,
+                end_frequency: settings.escalation_end_frequency,
+            },
+            chime: Chime {
+                mode: name(&chime_modes(), settings.chime_mode),
+                style: name(&chime_styles(), settings.chime_style),
+                quiet_start: settings.quiet_start,
+                quiet_end: settings.quiet_end,
+            },
+            screen: Screen::from_settings(settings),
+            log_levels,
+            alarms,
+        }
+    }
+
+    /// Convert to settings, checked against the firmware's ranges
+    pub fn to_settings(&self) -> Result<Settings, Error> {
+        if self.alarms.len() > MAX_ALARMS {
+            return Err(Error::Invalid(format!(
+                "the clock only has room for {MAX_ALARMS} alarms"
+            )));
+        }
+        let mut alarms = [StoredAlarm::default(); MAX_ALARMS];
+        for (stored, alarm) in alarms.iter_mut().zip(&self.alarms) {
+            let (hours, minutes) = parse_time(&alarm.time)?;
+            let sound = match index(&tunes(), &alarm.sound) {
+                Some(tune) => tune,
+                None => SOUND_PRESET | index_or_invalid(&PRESETS, &alarm.sound, "alarm sound")?,
+            };
+            *stored = StoredAlarm {
+                hours,
+                minutes,
+                flags: ALARM_PRESENT | if alarm.enabled { ALARM_ENABLED } else { 0_u8 },
+                sound,
+            };
+        }
+
+        let mut log_levels = [DEFAULT_LEVEL as u8; TAG_COUNT];
+        for (tag, level) in &self.log_levels {
+            let tag = Tag::parse(tag)
+                .ok_or_else(|| Error::Invalid(format!("unknown log tag {tag:?}")))?;
+            let level = Level::parse(level)
+                .ok_or_else(|| Error::Invalid(format!("unknown log level {level:?}")))?;
+            log_levels[tag as usize] = level as u8;
+        }
+
+        // The legacy single alarm mirrors the first alarm for older firmware,
+        // like the firmware does when saving
+        let first_alarm = alarms
+            .iter()
+            .find(|alarm| alarm.flags & ALARM_PRESENT != 0_u8);
+        let (alarm_sound, alarm_tune) = match first_alarm.map(|alarm| alarm.sound) {
+            Some(sound) if sound & SOUND_PRESET != 0_u8 => (SOUND_ESCALATING, 0_u8),
+            Some(tune) => (SOUND_MELODY, tune),
+            None => (SOUND_MELODY, 0_u8),
+        };
+        let defaults = defaults();
+        let settings = Settings {
+            alarm_enabled: self.alarms_enabled,
+            alarm_hours: first_alarm.map_or(defaults.alarm_hours, |alarm| alarm.hours),
+            alarm_minutes: first_alarm.map_or(defaults.alarm_minutes, |alarm| alarm.minutes),
+            alarm_sound,
+            alarm_tune,
+            escalation_ramp_seconds: self.escalation.ramp_seconds,
+            escalation_start_frequency: self.escalation.start_frequency,
+            escalation_end_frequency: self.escalation.end_frequency,
+            alarm_max_ring_minutes: self.max_ring_minutes,
+            chime_mode: index_or_invalid(&chime_modes(), &self.chime.mode, "chime mode")?,
+            chime_style: index_or_invalid(&chime_styles(), &self.chime.style, "chime style")?,
+            quiet_start: self.chime.quiet_start,
+            quiet_end: self.chime.quiet_end,
+            brightness: self.brightness,
+            alarms,
+            log_levels,
+            idle_pages: self.screen.pages()?,
+            idle_date_format: index_or_invalid(
+                &date_formats(),
+                &self.screen.date_format,
+                "date format",
+            )?,
+            idle_page_seconds: self.screen.page_seconds,
+        };
+        settings.validate().map_err(|_| Error::OutOfRange)?;
+        Ok(settings)
+    }
+
+    /// Decode the record the clock sends
+    pub fn from_record(record: &[u8]) -> Result<Self, Error> {
+        let settings = Settings::decode(record, &defaults()).map_err(Error::Settings)?;
+        Ok(Self::from_settings(&settings))
+    }
+
+    pub fn parse(file: &str) -> Result<Self, Error> {
+        toml::from_str(file).map_err(Error::Parse)
+    }
+
+    pub fn to_file(&self) -> String {
+        let body = toml::to_string(self).expect("Backups are always valid TOML");
+        format!("{HEADER}{body}")
+    }
+}
+
+/// A field that differs between two backups
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Change {
+    /// Like `alarms[1].time`
+    pub field: String,
+    /// `None` if it was added
+    pub old: Option<String>,
+    /// `None` if it was removed
+    pub new: Option<String>,
+}
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.old, &self.new) {
+            (Some(old), Some(new)) => write!(f, "~ {}: {old} -> {new}", self.field),
+            (None, Some(new)) => write!(f, "+ {}: {new}", self.field),
+            (Some(old), None) => write!(f, "- {}: {old}", self.field),
+            (None, None) => write!(f, "  {}", self.field),
+        }
+    }
+}
+
+/// Every field that differs from `old` in `new`
+pub fn diff(old: &Backup, new: &Backup) -> Vec<Change> {
+    let (old, new) = (fields(old), fields(new));
+    let mut changes = Vec::new();
+    for (field, value) in &old {
+        match new.get(field) {
+            Some(new_value) if new_value == value => (),
+            new_value => changes.push(Change {
+                field: field.clone(),
+                old: Some(value.clone()),
+                new: new_value.cloned(),
+            }),
+        }
+    }
+    for (field, value) in &new {
+        if !old.contains_key(field) {
+            changes.push(Change {
+                field: field.clone(),
+                old: None,
+                new: Some(value.clone()),
+            });
+        }
+    }
+    changes.sort_by(|a, b| a.field.cmp(&b.field));
+    changes
+}
+
+/// Every field of a backup by its path, with its value as it's written in TOML
+fn fields(backup: &Backup) -> BTreeMap<String, String> {
+    fn flatten(path: String, value: &toml::Value, fields: &mut BTreeMap<String, String>) {
+        match value {
+            toml::Value::Table(table) => {
+                for (key, value) in table {
+                    let path = match path.is_empty() {
+                        true => key.clone(),
+                        false => format!("{path}.{key}"),
+                    };
+                    flatten(path, value, fields);
+                }
+            }
+            toml::Value::Array(array) => {
+                for (n, value) in array.iter().enumerate() {
+                    flatten(format!("{path}[{n}]"), value, fields);
+                }
+            }
+            value => {
+                fields.insert(path, value.to_string());
+            }
+        }
+    }
+
+    let mut fields = BTreeMap::new();
+    let value = toml::Value::try_from(backup).expect("Backups are always valid TOML");
+    flatten(String::new(), &value, &mut fields);
+    fields
+}
+
+/// A tag's name as a TOML key, like `shiftreg`
+fn tag_key(tag: &Tag) -> String {
+    tag.name().replace(' ', "").to_ascii_lowercase()
+}
+
+/// The name of `value`, or its number if it has none
+fn name(names: &[&str], value: u8) -> String {
+    match names.get(value as usize) {
+        Some(name) => name.to_string(),
+        None => value.to_string(),
+    }
+}
+
+fn index(names: &[&str], name: &str) -> Option<u8> {
+    names
+        .iter()
+        .position(|known| known.eq_ignore_ascii_case(name))
+        .map(|index| index as u8)
+}
+
+fn index_or_invalid(names: &[&str], name: &str, what: &str) -> Result<u8, Error> {
+    index(names, name).ok_or_else(|| {
+        Error::Invalid(format!(
+            "unknown {what} {name:?}, expected one of {}",
+            names.join(", ")
+        ))
+    })
+}
+
+/// Parse `HH:MM`
+fn parse_time(time: &str) -> Result<(u8, u8), Error> {
+    let invalid = || Error::Invalid(format!("alarm time {time:?} isn't from 00:00 to 23:59"));
+    let (hours, minutes) = time.split_once(':').ok_or_else(invalid)?;
+    let hours = hours.parse::<u8>().map_err(|_| invalid())?;
+    let minutes = minutes.parse::<u8>().map_err(|_| invalid())?;
+    match hours < 24_u8 && minutes < 60_u8 {
+        true => Ok((hours, minutes)),
+        false => Err(invalid()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        settings::{PRESET_CUSTOM, PRESET_GENTLE},
+        sound::EscalationProfile,
+        state::AlarmSound,
+    };
+
+    fn custom() -> Settings {
+        let mut settings = Settings {
+            alarm_enabled: true,
+            alarm_hours: 6_u8,
+            alarm_minutes: 45_u8,
+            alarm_sound: SOUND_ESCALATING,
+            escalation_ramp_seconds: 600_u16,
+            chime_mode: 2_u8,
+            chime_style: 1_u8,
+            brightness: 3_u8,
+            idle_pages: 0b110_u8,
+            idle_date_format: 2_u8,
+            idle_page_seconds: 10_u8,
+            ..defaults()
+        };
+        settings.alarms[0] = StoredAlarm {
+            hours: 6_u8,
+            minutes: 45_u8,
+            flags: ALARM_PRESENT | ALARM_ENABLED,
+            sound: SOUND_PRESET | PRESET_GENTLE,
+        };
+        settings.alarms[1] = StoredAlarm {
+            hours: 9_u8,
+            minutes: 30_u8,
+            flags: ALARM_PRESENT,
+            sound: 3_u8,
+        };
+        settings.log_levels[Tag::Rtc as usize] = Level::Debug as u8;
+        settings
+    }
+
+    #[test]
+    fn round_trip() {
+        for settings in [defaults(), custom()] {
+            let file = Backup::from_settings(&settings).to_file();
+            assert_eq!(
+                Backup::parse(&file).unwrap().to_settings().unwrap(),
+                settings
+            );
+        }
+        let record = custom().encode();
+        assert_eq!(
+            Backup::from_record(&record).unwrap(),
+            Backup::from_settings(&custom())
+        );
+    }
+
+    #[test]
+    fn sounds_are_named_like_the_shell() {
+        for (tune, name) in Tune::ALL.iter().zip(tunes()) {
+            assert_eq!(
+                AlarmSound::parse(Some(name)),
+                Some(AlarmSound::Melody(*tune))
+            );
+        }
+        for (profile, name) in EscalationProfile::PRESETS.iter().zip(PRESETS) {
+            assert_eq!(
+                AlarmSound::parse(Some(&format!("escalate {name}"))),
+                Some(AlarmSound::Escalating(*profile))
+            );
+        }
+        assert_eq!(PRESETS[PRESET_CUSTOM as usize], "custom");
+        assert_eq!(
+            AlarmSound::parse(Some("escalate custom")),
+            Some(AlarmSound::CustomEscalation)
+        );
+    }
+
+    #[test]
+    fn readable() {
+        let file = Backup::from_settings(&custom()).to_file();
+        for line in [
+            "brightness = 3\n",
+            "mode = \"half-hourly\"\n",
+            "rtc = \"debug\"\n",
+            "shiftreg = \"info\"\n",
+            "time = \"06:45\"\n",
+            "sound = \"gentle\"\n",
+            "sound = \"tetris\"\n",
+            "pages = [\"alarm\", \"big\"]\n",
+            "date_format = \"iso\"\n",
+        ] {
+            assert!(file.contains(line), "{line:?} not in {file}");
+        }
+    }
+
+    #[test]
+    fn hand_written() {
+        let backup = Backup::parse(
+            "alarms_enabled = true\n\
+             max_ring_minutes = 10\n\
+             brightness = 5\n\
+             [escalation]\n\
+             ramp_seconds = 60\n\
+             start_frequency = 1000\n\
+             end_frequency = 2000\n\
+             [chime]\n\
+             mode = \"Hourly\"\n\
+             style = \"beep\"\n\
+             quiet_start = 23\n\
+             quiet_end = 6\n\
+             [log_levels]\n\
+             ShiftReg = \"TRACE\"\n\
+             [[alarms]]\n\
+             time = \"7:05\"\n\
+             enabled = false\n\
+             sound = \"custom\"\n",
+        )
+        .unwrap();
+        let settings = backup.to_settings().unwrap();
+        assert_eq!(settings.chime_mode, 1_u8);
+        assert_eq!(
+            settings.log_levels[Tag::ShiftReg as usize],
+            Level::Trace as u8
+        );
+        assert_eq!(settings.log_levels[Tag::Rtc as usize], DEFAULT_LEVEL as u8);
+        assert_eq!(
+            settings.alarms[0],
+            StoredAlarm {
+                hours: 7_u8,
+                minutes: 5_u8,
+                flags: ALARM_PRESENT,
+                sound: SOUND_PRESET | PRESET_CUSTOM,
+            }
+        );
+        // Mirrored for older firmware
+        assert_eq!(
+            (
+                settings.alarm_hours,
+                settings.alarm_minutes,
+                settings.alarm_sound
+            ),
+            (7_u8, 5_u8, SOUND_ESCALATING)
+        );
+        assert_eq!(settings.alarms[1].flags, 0_u8);
+        // The idle screen wasn't written, so it's as it starts out
+        assert_eq!(settings.idle_pages, defaults().idle_pages);
+        assert_eq!(settings.idle_page_seconds, defaults().idle_page_seconds);
+    }
+
+    #[test]
+    fn invalid() {
+        let backup = Backup::from_settings(&custom());
+        let with = |change: fn(&mut Backup)| {
+            let mut backup = backup.clone();
+            change(&mut backup);
+            backup.to_settings()
+        };
+
+        assert!(matches!(
+            with(|backup| backup.brightness = 9),
+            Err(Error::OutOfRange)
+        ));
+        assert!(matches!(
+            with(|backup| backup.max_ring_minutes = 0),
+            Err(Error::OutOfRange)
+        ));
+        assert!(matches!(
+            with(|backup| backup.chime.quiet_end = 24),
+            Err(Error::OutOfRange)
+        ));
+        assert!(matches!(
+            with(|backup| backup.escalation.ramp_seconds = 0),
+            Err(Error::OutOfRange)
+        ));
+        assert!(matches!(
+            with(|backup| backup.screen.pages.clear()),
+            Err(Error::OutOfRange)
+        ));
+        assert!(matches!(
+            with(|backup| backup.screen.page_seconds = 0),
+            Err(Error::OutOfRange)
+        ));
+        assert!(matches!(
+            with(|backup| backup.screen.pages.push(String::from("weather"))),
+            Err(Error::Invalid(_))
+        ));
+        assert!(matches!(
+            with(|backup| backup.screen.date_format = String::from("ymd")),
+            Err(Error::Invalid(_))
+        ));
+        assert!(matches!(
+            with(|backup| backup.alarms[0].time = String::from("24:00")),
+            Err(Error::Invalid(_))
+        ));
+        assert!(matches!(
+            with(|backup| backup.alarms[1].sound = String::from("air horn")),
+            Err(Error::Invalid(_))
+        ));
+        assert!(matches!(
+            with(|backup| backup.chime.mode = String::from("quarter-hourly")),
+            Err(Error::Invalid(_))
+        ));
+        assert!(matches!(
+            with(|backup| {
+                backup
+                    .log_levels
+                    .insert(String::from("wifi"), String::from("info"));
+            }),
+            Err(Error::Invalid(_))
+        ));
+        assert!(matches!(
+            with(|backup| backup.alarms = vec![backup.alarms[0].clone(); MAX_ALARMS + 1]),
+            Err(Error::Invalid(_))
+        ));
+
+        assert!(matches!(
+            Backup::parse("brightness = 3\nsnooze_minutes = 5\n"),
+            Err(Error::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn differences() {
+        let old = Backup::from_settings(&custom());
+        assert_eq!(diff(&old, &old), []);
+
+        let mut new = old.clone();
+        new.brightness = 8;
+        new.alarms[1].time = String::from("10:00");
+        new.alarms.remove(0);
+        let changes = diff(&old, &new)
+            .iter()
+            .map(|change| change.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            [
+                "~ alarms[0].enabled: true -> false",
+                "~ alarms[0].sound: \"gentle\" -> \"tetris\"",
+                "~ alarms[0].time: \"06:45\" -> \"10:00\"",
+                "- alarms[1].enabled: false",
+                "- alarms[1].sound: \"tetris\"",
+                "- alarms[1].time: \"09:30\"",
+                "~ brightness: 3 -> 8",
+            ]
+        );
+    }
+}
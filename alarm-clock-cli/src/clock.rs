@@ -0,0 +1,259 @@
+//! The clock as seen over the serial port, through both its shell and the
+//! framed protocol
+
+use alarm_clock_protocol::{
+    decode_packet, encode_packet, ErrorCode, FrameReader, Request, Response, MAX_FRAME_LENGTH,
+};
+use chrono::NaiveDateTime;
+use serialport::{ClearBuffer, SerialPort};
+use std::{
+    collections::VecDeque,
+    fmt, io, thread,
+    time::{Duration, Instant},
+};
+
+/// The default baud rate, matching the firmware's `shared::BAUD_RATE`
+pub const BAUD_RATE: u32 = 57_600_u32;
+/// How long to wait for a reply to a command
+const REPLY_TIMEOUT: Duration = Duration::from_secs(1);
+/// Opening the port resets the Uno, so the shell may take a while to come up
+const STARTUP_ATTEMPTS: usize = 5_usize;
+/// Requests are sent again if a frame gets garbled
+const REQUEST_ATTEMPTS: usize = 3_usize;
+const PROMPT: &str = "> ";
+const SYNC_REPLY: &str = "SYNC ";
+pub const DATE_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+#[derive(Debug)]
+pub enum Error {
+    Serial(serialport::Error),
+    Io(io::Error),
+    /// The clock didn't reply in time
+    NoReply,
+    BadReply(String),
+    /// A response that couldn't be decoded, e.g. from a different protocol
+    /// version
+    Protocol(alarm_clock_protocol::Error),
+    /// The clock turned down the request
+    Refused(ErrorCode),
+    /// The clock's seconds aren't ticking over
+    Stopped,
+    /// The clock only has two digits for the year
+    OutOfRange(NaiveDateTime),
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Serial(e) => write!(f, "serial port error: {e}"),
+            Error::Io(e) => write!(f, "I/O error: {e}"),
+            Error::NoReply => write!(
+                f,
+                "no reply from the clock (is the firmware up to date and the baud rate right?)"
+            ),
+            Error::BadReply(reply) => write!(f, "bad reply from the clock: {reply:?}"),
+            Error::Protocol(alarm_clock_protocol::Error::UnsupportedVersion(version)) => write!(
+                f,
+                "the clock speaks protocol version {version}, but this speaks version {}",
+                alarm_clock_protocol::PROTOCOL_VERSION
+            ),
+            Error::Protocol(e) => write!(f, "bad response from the clock: {e:?}"),
+            Error::Refused(code) => write!(f, "the clock refused: {}", code.as_str()),
+            Error::Stopped => write!(f, "the clock isn't ticking (is the RTC running?)"),
+            Error::OutOfRange(time) => write!(
+                f,
+                "{time} can't be set, as the clock only goes from 2000 to 2099"
+            ),
+        }
+    }
+}
+impl std::error::Error for Error {}
+impl From<serialport::Error> for Error {
+    fn from(e: serialport::Error) -> Self {
+        Error::Serial(e)
+    }
+}
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+/// A reading of the clock, with when the command was sent and the reply
+/// received on the host
+#[derive(Clone, Copy, Debug)]
+pub struct Reading {
+    pub time: NaiveDateTime,
+    pub sent: Instant,
+    pub received: Instant,
+}
+impl Reading {
+    pub fn round_trip(&self) -> Duration {
+        self.received - self.sent
+    }
+}
+
+pub struct Clock {
+    port: Box<dyn SerialPort>,
+    baud_rate: u32,
+    /// Received bytes not yet making up a whole line
+    buffer: Vec<u8>,
+    frames: FrameReader,
+    /// Received bytes not yet fed to `frames`, left after the last response
+    pending: VecDeque<u8>,
+    /// For matching responses to requests
+    next_id: u8,
+}
+impl Clock {
+    /// Open the clock's serial port and wait for its shell to reply
+    pub fn open(path: &str, baud_rate: u32) -> Result<Self, Error> {
+        let port = serialport::new(path, baud_rate)
+            .timeout(Duration::from_millis(50))
+            .open()?;
+        let mut clock = Self {
+            port,
+            baud_rate,
+            buffer: Vec::new(),
+            frames: FrameReader::new(),
+            pending: VecDeque::new(),
+            next_id: 0_u8,
+        };
+        clock.wait_for_shell()?;
+        Ok(clock)
+    }
+
+    fn wait_for_shell(&mut self) -> Result<(), Error> {
+        for _ in 0..STARTUP_ATTEMPTS {
+            match self.read() {
+                Ok(_) => {
+                    // Let any late replies to earlier attempts arrive, so they
+                    // aren't taken as replies to later commands
+                    thread::sleep(REPLY_TIMEOUT / 4);
+                    self.port.clear(ClearBuffer::Input)?;
+                    self.buffer.clear();
+                    self.pending.clear();
+                    return Ok(());
+                }
+                Err(Error::NoReply) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Err(Error::NoReply)
+    }
+
+    /// How long one byte takes to send, with a start and stop bit
+    pub fn byte_time(&self) -> Duration {
+        Duration::from_secs(10_u64) / self.baud_rate
+    }
+
+    /// Read the clock's date and time
+    pub fn read(&mut self) -> Result<Reading, Error> {
+        self.sync_command("sync")
+    }
+
+    /// Set the clock's date and time, returning what it read back
+    pub fn set(&mut self, time: NaiveDateTime) -> Result<Reading, Error> {
+        self.sync_command(&set_command(time)?)
+    }
+
+    fn sync_command(&mut self, command: &str) -> Result<Reading, Error> {
+        let sent = Instant::now();
+        self.port.write_all(command.as_bytes())?;
+        self.port.write_all(b"\r")?;
+        self.port.flush()?;
+        let reply = self.reply(SYNC_REPLY)?;
+        let received = Instant::now();
+        let time = NaiveDateTime::parse_from_str(&reply, DATE_TIME_FORMAT)
+            .map_err(|_| Error::BadReply(reply))?;
+        Ok(Reading {
+            time,
+            sent,
+            received,
+        })
+    }
+
+    /// Wait for a line starting with `prefix`, returning the rest of it. Other
+    /// lines, like the echoed command and logs, are skipped.
+    fn reply(&mut self, prefix: &str) -> Result<String, Error> {
+        let deadline = Instant::now() + REPLY_TIMEOUT;
+        loop {
+            while let Some(end) = self.buffer.iter().position(|byte| *byte == b'\n') {
+                let line = self.buffer.drain(..=end).collect::<Vec<_>>();
+                let line = String::from_utf8_lossy(&line);
+                let line = line.trim_end().trim_start_matches(PROMPT);
+                if let Some(reply) = line.strip_prefix(prefix) {
+                    return Ok(reply.to_string());
+                }
+            }
+            if Instant::now() > deadline {
+                return Err(Error::NoReply);
+            }
+            let mut chunk = [0_u8; 64];
+            match self.port.read(&mut chunk) {
+                Ok(length) => self.buffer.extend_from_slice(&chunk[..length]),
+                Err(e) if e.kind() == io::ErrorKind::TimedOut => (),
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+impl Clock {
+    /// Make a request over the framed protocol
+    pub fn request(&mut self, request: &Request) -> Result<Response, Error> {
+        for _ in 0..REQUEST_ATTEMPTS {
+            let id = self.next_id;
+            self.next_id = self.next_id.wrapping_add(1_u8);
+            let mut out = [0_u8; MAX_FRAME_LENGTH];
+            let frame = encode_packet(id, request, &mut out).map_err(Error::Protocol)?;
+            self.port.write_all(frame)?;
+            self.port.flush()?;
+            match self.response(id) {
+                Ok(Response::Error(code)) => return Err(Error::Refused(code)),
+                Ok(response) => return Ok(response),
+                Err(Error::NoReply) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Err(Error::NoReply)
+    }
+
+    /// Wait for the response with `id`, skipping text and stale responses
+    fn response(&mut self, id: u8) -> Result<Response, Error> {
+        let deadline = Instant::now() + REPLY_TIMEOUT;
+        loop {
+            // Whatever follows the response is kept for the next one
+            while let Some(byte) = self.pending.pop_front() {
+                let Some(frame) = self.frames.push(byte) else {
+                    continue;
+                };
+                match decode_packet::<Response>(frame) {
+                    Ok(packet) if packet.id == id => {
+                        return packet.message.map_err(Error::Protocol)
+                    }
+                    Ok(_) => (),
+                    // Likely noise between two frames, so the delimiter
+                    // ending it starts the next
+                    Err(_) => self.frames.resume(),
+                }
+            }
+            if Instant::now() > deadline {
+                return Err(Error::NoReply);
+            }
+            let mut chunk = [0_u8; 64];
+            match self.port.read(&mut chunk) {
+                Ok(length) => self.pending.extend(&chunk[..length]),
+                Err(e) if e.kind() == io::ErrorKind::TimedOut => (),
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+/// The shell command setting the clock to `time`
+pub fn set_command(time: NaiveDateTime) -> Result<String, Error> {
+    use chrono::Datelike;
+    if !(2000..=2099).contains(&time.year()) {
+        return Err(Error::OutOfRange(time));
+    }
+    Ok(format!("sync {}", time.format(DATE_TIME_FORMAT)))
+}
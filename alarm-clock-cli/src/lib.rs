@@ -0,0 +1,12 @@
+//! Host companion for the alarm clock, over its serial port. `sync` and
+//! `check` time the shell's `sync` command, while `status`, `export` and
+//! `import` make requests over the framed protocol (see
+//! `alarm_clock_protocol`), which shares the port with the shell.
+
+pub mod backup;
+pub mod clock;
+pub mod sync;
+
+// The firmware's settings layout and ranges, shared so backups are checked
+// exactly as the clock would check them
+pub use alarm_clock_core::{idle_screen, log, settings, sound, state};
@@ -0,0 +1,243 @@
+//! Host companion for the alarm clock
+//!
+//! ```text
+//! alarm-clock-cli sync /dev/ttyACM0
+//! alarm-clock-cli status /dev/ttyACM0
+//! alarm-clock-cli export /dev/ttyACM0 settings.toml
+//! ```
+
+use alarm_clock_cli::{
+    backup::{self, Backup},
+    clock::{Clock, Error, BAUD_RATE},
+    sync::{self, HostClock},
+};
+use alarm_clock_protocol::{DateTime, Diagnostics, LogEntry, Request, Response, SettingsRecord};
+use chrono::TimeDelta;
+use std::{
+    env, fs,
+    io::{self, BufRead, Write},
+    process::ExitCode,
+};
+
+const USAGE: &str = "\
+Usage: alarm-clock-cli COMMAND PORT [FILE] [--baud RATE] [--yes]
+
+Commands:
+  check    show how far the clock is from this computer's local time
+  export   save the clock's settings and alarms to FILE
+  import   load settings and alarms from FILE, showing what will change and
+           asking before writing them (unless given --yes)
+  status   show the clock's diagnostics and missed alarms
+  sync     set the clock to this computer's local time";
+
+struct Arguments<'a> {
+    command: &'a str,
+    port: &'a str,
+    /// For `export` and `import`
+    file: Option<&'a str>,
+    baud_rate: u32,
+    /// Don't ask before writing
+    yes: bool,
+}
+
+fn main() -> ExitCode {
+    let arguments = env::args().skip(1).collect::<Vec<_>>();
+    let arguments = match parse_arguments(&arguments) {
+        Some(arguments) => arguments,
+        None => {
+            eprintln!("{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match run(&arguments) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn parse_arguments(arguments: &[String]) -> Option<Arguments<'_>> {
+    let mut positional = Vec::new();
+    let mut baud_rate = BAUD_RATE;
+    let mut yes = false;
+    let mut arguments = arguments.iter();
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--baud" => baud_rate = arguments.next()?.parse().ok()?,
+            "--yes" => yes = true,
+            _ => positional.push(argument.as_str()),
+        }
+    }
+    let (command, port, file) = match positional[..] {
+        [command @ ("check" | "status" | "sync"), port] => (command, port, None),
+        [command @ ("export" | "import"), port, file] => (command, port, Some(file)),
+        _ => return None,
+    };
+    Some(Arguments {
+        command,
+        port,
+        file,
+        baud_rate,
+        yes,
+    })
+}
+
+fn run(arguments: &Arguments) -> Result<(), Box<dyn std::error::Error>> {
+    let (port, baud_rate) = (arguments.port, arguments.baud_rate);
+    match arguments.command {
+        "check" => {
+            let mut clock = Clock::open(port, baud_rate)?;
+            let measurement = sync::measure(&mut clock, &HostClock::new())?;
+            println!(
+                "Clock reads {}, {}",
+                measurement.time,
+                describe(measurement.offset)
+            );
+            println!("Round trip {} ms", measurement.round_trip.as_millis());
+        }
+        "sync" => {
+            let mut clock = Clock::open(port, baud_rate)?;
+            let report = sync::sync(&mut clock)?;
+            println!("Before: {}", describe(report.before.offset));
+            println!(
+                "Set to {}, sent {} ms early for latency",
+                report.set_to,
+                report.latency.as_millis()
+            );
+            println!("After: {}", describe(report.after.offset));
+        }
+        "status" => {
+            let mut clock = Clock::open(port, baud_rate)?;
+            let Response::Diagnostics(diagnostics) = clock.request(&Request::GetDiagnostics)?
+            else {
+                return Err(Error::BadReply(String::from("expected diagnostics")).into());
+            };
+            print_diagnostics(&diagnostics);
+            let missed = missed_alarms(&mut clock)?;
+            if missed.is_empty() {
+                println!("No missed alarms");
+            } else {
+                println!("Missed alarms:");
+                for entry in missed {
+                    println!(
+                        "  {} after {} snoozes",
+                        describe_time(&entry.time),
+                        entry.snoozes
+                    );
+                }
+            }
+        }
+        "export" => {
+            let mut clock = Clock::open(port, baud_rate)?;
+            let backup = read_settings(&mut clock)?;
+            let file = arguments.file.expect("checked when parsing the arguments");
+            fs::write(file, backup.to_file())?;
+            println!("Saved the settings and {} alarms", backup.alarms.len());
+        }
+        "import" => {
+            let file = arguments.file.expect("checked when parsing the arguments");
+            // Checked before touching the clock
+            let settings = Backup::parse(&fs::read_to_string(file)?)?.to_settings()?;
+            let mut clock = Clock::open(port, baud_rate)?;
+            let changes = backup::diff(
+                &read_settings(&mut clock)?,
+                &Backup::from_settings(&settings),
+            );
+            if changes.is_empty() {
+                println!("The clock already has these settings");
+                return Ok(());
+            }
+            for change in &changes {
+                println!("{change}");
+            }
+            if !arguments.yes && !confirm("Write these changes to the clock?")? {
+                println!("Left the clock alone");
+                return Ok(());
+            }
+            let record = SettingsRecord::new(&settings.encode())
+                .expect("checked by the firmware's settings module");
+            clock.request(&Request::SetSettings(record))?;
+            println!("Wrote {} changes", changes.len());
+        }
+        _ => unreachable!("checked when parsing the arguments"),
+    }
+    Ok(())
+}
+
+fn read_settings(clock: &mut Clock) -> Result<Backup, Box<dyn std::error::Error>> {
+    let Response::Settings(record) = clock.request(&Request::GetSettings)? else {
+        return Err(Error::BadReply(String::from("expected settings")).into());
+    };
+    Ok(Backup::from_record(record.as_bytes())?)
+}
+
+/// Ask a yes or no question on the terminal, defaulting to no
+fn confirm(question: &str) -> io::Result<bool> {
+    print!("{question} [y/N] ");
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// Read the whole missed alarm log, a page at a time
+fn missed_alarms(clock: &mut Clock) -> Result<Vec<LogEntry>, Error> {
+    let mut entries = Vec::new();
+    loop {
+        let Response::MissedAlarms(page) =
+            clock.request(&Request::ReadMissedAlarms(entries.len() as u8))?
+        else {
+            return Err(Error::BadReply(String::from("expected missed alarms")));
+        };
+        entries.extend_from_slice(page.entries());
+        if page.is_last() || entries.len() > u8::MAX as usize {
+            return Ok(entries);
+        }
+    }
+}
+
+fn print_diagnostics(diagnostics: &Diagnostics) {
+    let uptime = diagnostics.uptime_seconds;
+    println!(
+        "Up {}d {:02}:{:02}:{:02}, booted {} times",
+        uptime / 86_400,
+        uptime / 3_600 % 24,
+        uptime / 60 % 60,
+        uptime % 60,
+        diagnostics.boots
+    );
+    let mode = match diagnostics.mode {
+        0_u8 => "idle",
+        1_u8 => "alarm",
+        2_u8 => "snoozed",
+        3_u8 => "setting",
+        _ => "unknown",
+    };
+    println!("Mode: {mode}");
+    println!("Snoozes: {}", diagnostics.snoozes);
+    println!("Serial bytes dropped: {}", diagnostics.serial_dropped);
+    println!("Settings version: {}", diagnostics.settings_version);
+    match &diagnostics.last_sync {
+        Some(time) => println!("Last synced {}", describe_time(time)),
+        None => println!("Never synced"),
+    }
+}
+
+fn describe_time(time: &DateTime) -> String {
+    format!(
+        "20{:02}-{:02}-{:02} {:02}:{:02}:{:02}",
+        time.year, time.month, time.day, time.hours, time.minutes, time.seconds
+    )
+}
+
+fn describe(offset: TimeDelta) -> String {
+    let seconds = offset.num_milliseconds() as f64 / 1_000.0;
+    match seconds {
+        seconds if seconds.abs() < 0.001 => String::from("in sync"),
+        seconds if seconds > 0.0 => format!("{seconds:.3} s ahead"),
+        seconds => format!("{:.3} s behind", -seconds),
+    }
+}
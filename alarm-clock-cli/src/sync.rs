@@ -0,0 +1,162 @@
+//! Setting the clock to the host's local time
+//!
+//! The clock only counts whole seconds, so a single reading says little about
+//! how far off it is. Instead, it's read repeatedly until its seconds tick
+//! over, which pins down the tick to within a round trip. When setting it, the
+//! command is sent early by the estimated latency so the clock is set right as
+//! the host reaches the whole second being set.
+
+use crate::clock::{set_command, Clock, Error, Reading};
+use chrono::{Local, NaiveDateTime, TimeDelta, Timelike};
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Give up if the clock's seconds haven't ticked over in this long
+const TICK_TIMEOUT: Duration = Duration::from_millis(1_500);
+/// Extra time to leave before the second the clock is set to
+const MARGIN: Duration = Duration::from_millis(50);
+/// `sync\r`
+const READ_COMMAND_LENGTH: usize = 5_usize;
+/// The echoed `sync` and its newline, then `SYNC YYYY-MM-DD HH:MM:SS\n`
+const READ_REPLY_LENGTH: usize = 5_usize + 25_usize;
+
+/// The host's local time, anchored to an instant so readings can be compared
+/// with it precisely
+pub struct HostClock {
+    time: NaiveDateTime,
+    instant: Instant,
+}
+impl HostClock {
+    pub fn new() -> Self {
+        Self {
+            instant: Instant::now(),
+            time: Local::now().naive_local(),
+        }
+    }
+
+    pub fn at(&self, instant: Instant) -> NaiveDateTime {
+        self.time + to_delta(instant.saturating_duration_since(self.instant))
+    }
+
+    pub fn now(&self) -> NaiveDateTime {
+        self.at(Instant::now())
+    }
+}
+impl Default for HostClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Measurement {
+    /// The clock's time less the host's; positive if the clock is ahead
+    pub offset: TimeDelta,
+    /// The shortest round trip seen
+    pub round_trip: Duration,
+    /// The clock's time when last read
+    pub time: NaiveDateTime,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Report {
+    pub before: Measurement,
+    /// What the clock was set to
+    pub set_to: NaiveDateTime,
+    /// How early the command was sent to make up for the serial link
+    pub latency: Duration,
+    pub after: Measurement,
+}
+
+/// Measure how far the clock is from the host by reading it until it ticks
+pub fn measure(clock: &mut Clock, host: &HostClock) -> Result<Measurement, Error> {
+    let start = Instant::now();
+    let mut last = clock.read()?;
+    let mut round_trip = last.round_trip();
+    loop {
+        let reading = clock.read()?;
+        round_trip = round_trip.min(reading.round_trip());
+        if reading.time != last.time {
+            // The tick was somewhere between the two readings
+            let tick = midpoint(&last) + (midpoint(&reading) - midpoint(&last)) / 2;
+            return Ok(Measurement {
+                offset: reading.time - host.at(tick),
+                round_trip,
+                time: reading.time,
+            });
+        }
+        if start.elapsed() > TICK_TIMEOUT {
+            return Err(Error::Stopped);
+        }
+        last = reading;
+    }
+}
+
+/// Set the clock to the host's time, measuring the offset before and after
+pub fn sync(clock: &mut Clock) -> Result<Report, Error> {
+    let host = HostClock::new();
+    let before = measure(clock, &host)?;
+
+    // Half of what's left of a round trip after sending the bytes each way is
+    // spent getting the command to the clock, which then has to be sent
+    let transfer = clock.byte_time() * (READ_COMMAND_LENGTH + READ_REPLY_LENGTH) as u32;
+    let set_length = set_command(host.now())?.len() + 1_usize;
+    let latency =
+        before.round_trip.saturating_sub(transfer) / 2 + clock.byte_time() * set_length as u32;
+
+    let set_to = next_second(host.now() + to_delta(latency + MARGIN));
+    let send_at = set_to - to_delta(latency);
+    if let Ok(wait) = (send_at - host.now()).to_std() {
+        thread::sleep(wait);
+    }
+    clock.set(set_to)?;
+
+    let after = measure(clock, &host)?;
+    Ok(Report {
+        before,
+        set_to,
+        latency,
+        after,
+    })
+}
+
+fn midpoint(reading: &Reading) -> Instant {
+    reading.sent + reading.round_trip() / 2
+}
+
+/// The first whole second at or after `time`
+fn next_second(time: NaiveDateTime) -> NaiveDateTime {
+    match time.nanosecond() {
+        0_u32 => time,
+        nanoseconds => time - TimeDelta::nanoseconds(nanoseconds as i64) + TimeDelta::seconds(1),
+    }
+}
+
+fn to_delta(duration: Duration) -> TimeDelta {
+    TimeDelta::from_std(duration).unwrap_or(TimeDelta::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn rounding_up_to_the_second() {
+        let time = NaiveDate::from_ymd_opt(2024, 2, 29)
+            .unwrap()
+            .and_hms_milli_opt(23, 59, 59, 1)
+            .unwrap();
+        assert_eq!(
+            next_second(time),
+            NaiveDate::from_ymd_opt(2024, 3, 1)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap()
+        );
+        let whole = time.with_nanosecond(0).unwrap();
+        assert_eq!(next_second(whole), whole);
+    }
+}
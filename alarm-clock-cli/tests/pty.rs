@@ -0,0 +1,347 @@
+//! Runs the CLI against a stand-in for the firmware's shell on a
+//! pseudo-terminal
+
+use alarm_clock_cli::{backup::defaults, settings::Settings};
+use alarm_clock_protocol::{
+    decode_packet, encode_packet, DateTime, Diagnostics, ErrorCode, FrameReader, LogEntry,
+    MissedAlarms, Request, Response, SettingsRecord, DELIMITER, MAX_FRAME_LENGTH,
+};
+use chrono::{Local, NaiveDateTime, TimeDelta, Timelike};
+use serialport::{SerialPort, TTYPort};
+use std::{
+    env, fs,
+    io::{Read, Write},
+    process::{self, Command, Output, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+/// How long the stand-in takes to run a command, like reading the RTC
+const PROCESSING_TIME: Duration = Duration::from_millis(3);
+/// More than fits in one page, to check they're all read
+const MISSED_ALARMS: u8 = 9_u8;
+
+/// A clock running `offset` from the host, which only counts whole seconds
+struct FakeClock {
+    offset: Mutex<TimeDelta>,
+    settings: Mutex<Settings>,
+    /// Set once the first command has been ignored, like the Uno would while
+    /// still in its bootloader
+    booted: AtomicBool,
+    stop: AtomicBool,
+}
+impl FakeClock {
+    fn new(offset: TimeDelta) -> Arc<Self> {
+        Arc::new(Self {
+            offset: Mutex::new(offset),
+            settings: Mutex::new(defaults()),
+            booted: AtomicBool::new(false),
+            stop: AtomicBool::new(false),
+        })
+    }
+
+    fn offset(&self) -> TimeDelta {
+        *self.offset.lock().unwrap()
+    }
+
+    fn now(&self) -> NaiveDateTime {
+        (Local::now().naive_local() + self.offset())
+            .with_nanosecond(0)
+            .unwrap()
+    }
+
+    fn run_command(&self, line: &str) -> Option<String> {
+        let mut words = line.split_ascii_whitespace();
+        if words.next() != Some("sync") {
+            return Some(String::from("Error: unknown command, try `help`\n"));
+        }
+        let rest = words.collect::<Vec<_>>().join(" ");
+        if !rest.is_empty() {
+            let time = NaiveDateTime::parse_from_str(&rest, "%Y-%m-%d %H:%M:%S").ok()?;
+            // Setting the RTC also restarts its current second
+            *self.offset.lock().unwrap() = time - Local::now().naive_local();
+        }
+        Some(format!("SYNC {}\n", self.now().format("%Y-%m-%d %H:%M:%S")))
+    }
+
+    fn run_request(&self, request: Request) -> Response {
+        match request {
+            Request::GetDiagnostics => Response::Diagnostics(Diagnostics {
+                uptime_seconds: 93_784_u32,
+                boots: 12_u16,
+                snoozes: 40_u16,
+                serial_dropped: 0_u16,
+                settings_version: 3_u8,
+                mode: 2_u8,
+                last_sync: None,
+            }),
+            Request::ReadMissedAlarms(start) => {
+                let mut page = MissedAlarms::new(start);
+                for nth in start..MISSED_ALARMS {
+                    let entry = LogEntry {
+                        time: DateTime {
+                            year: 24_u8,
+                            month: 2_u8,
+                            day: nth + 1_u8,
+                            hours: 6_u8,
+                            minutes: 30_u8,
+                            seconds: 0_u8,
+                        },
+                        snoozes: nth,
+                    };
+                    if !page.push(entry) {
+                        break;
+                    }
+                }
+                Response::MissedAlarms(page)
+            }
+            Request::GetSettings => Response::Settings(
+                SettingsRecord::new(&self.settings.lock().unwrap().encode()).unwrap(),
+            ),
+            Request::SetSettings(record) => {
+                match Settings::decode(record.as_bytes(), &defaults()) {
+                    Ok(settings) => {
+                        *self.settings.lock().unwrap() = settings;
+                        Response::Done
+                    }
+                    Err(_) => Response::Error(ErrorCode::BadSettings),
+                }
+            }
+            _ => Response::Done,
+        }
+    }
+
+    /// Act like the shell: echo what's typed and reply to commands and framed
+    /// requests
+    fn serve(&self, mut port: TTYPort) {
+        let _ =
+            port.write_all(b"Hello from the Alarm Clock!\n[DEBUG] [MAIN] Shell initialization\n> ");
+        let mut line = String::new();
+        let mut frames = FrameReader::new();
+        let mut buffer = [0_u8; 64];
+        while !self.stop.load(Ordering::SeqCst) {
+            let length = match port.read(&mut buffer) {
+                Ok(length) => length,
+                Err(_) => continue,
+            };
+            for byte in &buffer[..length] {
+                if *byte == DELIMITER || frames.in_frame() {
+                    let Some(frame) = frames.push(*byte) else {
+                        continue;
+                    };
+                    let Ok(packet) = decode_packet::<Request>(frame) else {
+                        continue;
+                    };
+                    let response = match packet.message {
+                        Ok(request) => self.run_request(request),
+                        Err(e) => Response::Error(e.into()),
+                    };
+                    let mut out = [0_u8; MAX_FRAME_LENGTH];
+                    let _ = port.write_all(encode_packet(packet.id, &response, &mut out).unwrap());
+                    continue;
+                }
+                if *byte != b'\r' {
+                    line.push(*byte as char);
+                    let _ = port.write_all(&[*byte]);
+                    continue;
+                }
+                let _ = port.write_all(b"\n");
+                if !self.booted.swap(true, Ordering::SeqCst) {
+                    line.clear();
+                    continue;
+                }
+                thread::sleep(PROCESSING_TIME);
+                if let Some(reply) = self.run_command(&line) {
+                    let _ = port.write_all(reply.as_bytes());
+                }
+                let _ = port.write_all(b"> ");
+                line.clear();
+            }
+        }
+    }
+}
+
+/// Run the CLI against a fake clock
+fn run_cli(command: &str, clock: &Arc<FakeClock>) -> Output {
+    run_cli_with(&[command], "", clock)
+}
+
+/// Run the CLI against a fake clock with more arguments after the port, and
+/// `input` typed in
+fn run_cli_with(arguments: &[&str], input: &str, clock: &Arc<FakeClock>) -> Output {
+    clock.stop.store(false, Ordering::SeqCst);
+    let (master, slave) = TTYPort::pair().unwrap();
+    let path = slave.name().unwrap();
+    let server = thread::spawn({
+        let clock = clock.clone();
+        move || clock.serve(master)
+    });
+
+    let mut cli = Command::new(env!("CARGO_BIN_EXE_alarm-clock-cli"))
+        .arg(arguments[0])
+        .arg(&path)
+        .args(&arguments[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    cli.stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = cli.wait_with_output().unwrap();
+    clock.stop.store(true, Ordering::SeqCst);
+    server.join().unwrap();
+    drop(slave);
+    output
+}
+
+/// The number of seconds in a line like `Before: 1.234 s behind`, negative if
+/// behind
+fn reported_offset(stdout: &str, prefix: &str) -> f64 {
+    let line = stdout
+        .lines()
+        .find(|line| line.starts_with(prefix))
+        .unwrap_or_else(|| panic!("no {prefix:?} line in {stdout:?}"));
+    if line.ends_with("in sync") {
+        return 0.0;
+    }
+    let words = line.split_ascii_whitespace().collect::<Vec<_>>();
+    let seconds = words[words.len() - 3].parse::<f64>().unwrap();
+    match words[words.len() - 1] {
+        "behind" => -seconds,
+        _ => seconds,
+    }
+}
+
+#[test]
+fn sync() {
+    let clock = FakeClock::new(TimeDelta::milliseconds(-125_400));
+    let output = run_cli("sync", &clock);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "{stdout}{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let before = reported_offset(&stdout, "Before:");
+    assert!((before + 125.4).abs() < 0.05, "{stdout}");
+    let after = reported_offset(&stdout, "After:");
+    assert!(after.abs() < 0.05, "{stdout}");
+    // And what the clock was actually set to
+    assert!(
+        clock.offset().num_milliseconds().abs() < 50,
+        "{:?}",
+        clock.offset()
+    );
+}
+
+#[test]
+fn check() {
+    let clock = FakeClock::new(TimeDelta::milliseconds(2_750));
+    let output = run_cli("check", &clock);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "{stdout}{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    assert!(stdout.starts_with("Clock reads "), "{stdout}");
+    let offset = reported_offset(&stdout, "Clock reads");
+    assert!((offset - 2.75).abs() < 0.05, "{stdout}");
+    // Checking leaves the clock alone
+    assert_eq!(clock.offset(), TimeDelta::milliseconds(2_750));
+}
+
+#[test]
+fn status() {
+    let clock = FakeClock::new(TimeDelta::zero());
+    let output = run_cli("status", &clock);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "{stdout}{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    assert!(
+        stdout.contains("Up 1d 02:03:04, booted 12 times\n"),
+        "{stdout}"
+    );
+    assert!(stdout.contains("Mode: snoozed\n"), "{stdout}");
+    assert!(stdout.contains("Never synced\n"), "{stdout}");
+    let missed = stdout
+        .lines()
+        .skip_while(|line| *line != "Missed alarms:")
+        .skip(1)
+        .collect::<Vec<_>>();
+    assert_eq!(missed.len(), MISSED_ALARMS as usize, "{stdout}");
+    assert_eq!(missed[8], "  2024-02-09 06:30:00 after 8 snoozes");
+}
+
+#[test]
+fn backup_and_restore() {
+    let file = env::temp_dir().join(format!("alarm-clock-backup-{}.toml", process::id()));
+    let file = file.to_str().unwrap();
+    let clock = FakeClock::new(TimeDelta::zero());
+    let output = run_cli_with(&["export", file], "", &clock);
+    assert!(output.status.success(), "{output:?}");
+    let exported = fs::read_to_string(file).unwrap();
+    assert!(exported.contains("brightness = 8\n"), "{exported}");
+
+    let edited = exported.replace("brightness = 8", "brightness = 3")
+        + "\n[[alarms]]\ntime = \"06:30\"\nenabled = true\nsound = \"tetris\"\n";
+    fs::write(file, &edited).unwrap();
+
+    // Answering no leaves it alone, but still shows what would change
+    let output = run_cli_with(&["import", file], "n\n", &clock);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{output:?}");
+    assert!(stdout.contains("~ brightness: 8 -> 3\n"), "{stdout}");
+    assert!(stdout.contains("+ alarms[0].time: \"06:30\"\n"), "{stdout}");
+    assert_eq!(*clock.settings.lock().unwrap(), defaults());
+
+    let output = run_cli_with(&["import", file, "--yes"], "", &clock);
+    assert!(output.status.success(), "{output:?}");
+    let settings = *clock.settings.lock().unwrap();
+    assert_eq!(settings.brightness, 3_u8);
+    assert_eq!(
+        (settings.alarms[0].hours, settings.alarms[0].minutes),
+        (6_u8, 30_u8)
+    );
+
+    // Out of the firmware's range, so it's never sent
+    fs::write(file, edited.replace("brightness = 3", "brightness = 0")).unwrap();
+    let output = run_cli_with(&["import", file, "--yes"], "", &clock);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("out of range"));
+    assert_eq!(clock.settings.lock().unwrap().brightness, 3_u8);
+
+    fs::remove_file(file).unwrap();
+}
+
+#[test]
+fn bad_arguments() {
+    let output = Command::new(env!("CARGO_BIN_EXE_alarm-clock-cli"))
+        .args(["launch", "/dev/null"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("Usage:"));
+
+    // Exporting needs somewhere to save to
+    let output = Command::new(env!("CARGO_BIN_EXE_alarm-clock-cli"))
+        .args(["export", "/dev/null"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("Usage:"));
+}
@@ -0,0 +1,216 @@
+//! The clock's regular update, generic over the hardware it runs on: reading
+//! the time, showing the idle screen, and moving the alarm along with the
+//! controls. Sounds and anything saved are left to the caller, following the
+//! `AlarmEvent`s.
+
+use crate::{
+    display::seconds_outputs,
+    framebuffer::Framebuffer,
+    hal::{CharacterDisplay, ClockSource, InputEvent, InputSource, SegmentDisplay},
+    idle_screen::IdleScreen,
+    state::{AlarmEvent, AlarmInputs, State},
+    time::TimeDigits,
+};
+
+pub struct App<C, S, L, I> {
+    pub state: State,
+    pub clock: C,
+    /// The seconds digits, which hold what they're given (unlike the hours and
+    /// minutes, which are multiplexed from an interrupt)
+    pub seconds: S,
+    pub lcd: L,
+    /// What the LCD should show, of which only the changes are sent to it
+    pub screen: Framebuffer,
+    /// Which of the idle screen's pages is up
+    pub idle_screen: IdleScreen,
+    pub inputs: I,
+    /// The minute the RTC's backup battery was last checked in
+    battery_checked: Option<u8>,
+}
+impl<C, S, L, I> App<C, S, L, I>
+where
+    C: ClockSource,
+    S: SegmentDisplay,
+    L: CharacterDisplay,
+    I: InputSource,
+{
+    pub fn new(state: State, clock: C, seconds: S, lcd: L, inputs: I) -> Self {
+        Self {
+            state,
+            clock,
+            seconds,
+            lcd,
+            screen: Framebuffer::new(),
+            idle_screen: IdleScreen::new(),
+            inputs,
+            battery_checked: None,
+        }
+    }
+
+    /// Bring everything up to date at `now` millis, returning what the alarm
+    /// did. Keeps the last time read if the clock can't be read.
+    pub fn update(&mut self, now: u32) -> Option<AlarmEvent> {
+        self.read_time();
+        self.show_lcd(now);
+
+        let mut inputs = AlarmInputs::default();
+        while let Some(event) = self.inputs.next_event() {
+            match event {
+                InputEvent::Push => inputs.dismiss = true,
+                InputEvent::Snooze => inputs.snooze = true,
+                InputEvent::Turn(_) => (),
+            }
+        }
+        let event = self.state.update_alarm(now, inputs);
+
+        self.seconds
+            .write(seconds_outputs(self.state.digits.seconds));
+        event
+    }
+
+    /// Update the time from the clock, if it can be read. The backup battery
+    /// is checked on the first read and then every minute.
+    pub fn read_time(&mut self) -> bool {
+        let Some(time) = self.clock.read_time() else {
+            return false;
+        };
+        if self.battery_checked != Some(time.minutes) {
+            self.state.rtc_battery_low = self.clock.battery_low();
+            self.battery_checked = Some(time.minutes);
+        }
+        self.state.time = time;
+        self.state.digits = TimeDigits::from_time(&time);
+        true
+    }
+
+    fn show_lcd(&mut self, now: u32) {
+        self.idle_screen.draw(&mut self.screen, &self.state, now);
+        self.screen.flush(&mut self.lcd);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        hal::mock::{
+            ClockCall, LcdCall, MockCharacterDisplay, MockClock, MockInputs, MockSegmentDisplay,
+        },
+        state::{Alarm, AlarmSound, OperationalMode},
+        time::Time,
+    };
+
+    type MockApp = App<MockClock, MockSegmentDisplay, MockCharacterDisplay, MockInputs>;
+
+    fn app(time: Time) -> MockApp {
+        let mut state = State::new();
+        state.alarm_enabled = true;
+        state.alarms[0] = Some(Alarm {
+            hours: 7_u8,
+            minutes: 0_u8,
+            enabled: true,
+            sound: AlarmSound::default(),
+        });
+        App::new(
+            state,
+            MockClock::new(time),
+            MockSegmentDisplay::new(),
+            MockCharacterDisplay::new(),
+            MockInputs::new(),
+        )
+    }
+
+    fn time(hours: u8, minutes: u8, seconds: u8) -> Time {
+        Time {
+            hours,
+            minutes,
+            seconds,
+            ..Time::default()
+        }
+    }
+
+    #[test]
+    fn shows_the_idle_screen() {
+        let mut app = app(time(6_u8, 59_u8, 7_u8));
+        assert_eq!(app.update(0_u32), None);
+        assert_eq!(
+            app.clock.calls,
+            [ClockCall::ReadTime, ClockCall::BatteryLow]
+        );
+        // The alarm's on and it's still quiet hours
+        assert_eq!(app.lcd.line(0_u8), "Sunday        \u{0}\u{1}");
+        assert_eq!(app.lcd.line(1_u8), "01/01/2023      ");
+        assert_eq!(app.seconds.outputs(), Some(seconds_outputs((0_u8, 7_u8))));
+
+        // Only what changed is sent, without clearing the LCD
+        app.clock.time = Some(time(6_u8, 59_u8, 8_u8));
+        app.lcd.calls.clear();
+        app.update(100_u32);
+        assert!(app.lcd.calls.is_empty());
+        app.update(5_000_u32);
+        assert_eq!(app.lcd.line(0_u8), "Alarm 07:00   \u{0}\u{1}");
+        assert_eq!(app.lcd.line(1_u8), "in 1m           ");
+        assert_eq!(
+            app.lcd.calls[..2],
+            [
+                LcdCall::SetPosition(0_u8, 0_u8),
+                LcdCall::Print(String::from("Alarm 07:00"))
+            ]
+        );
+
+        // The last time is kept when the clock can't be read
+        app.clock.time = None;
+        app.update(5_100_u32);
+        assert_eq!(app.lcd.line(1_u8), "in 1m           ");
+        assert_eq!(app.seconds.writes.len(), 4_usize);
+
+        // The battery's checked again once the minute changes
+        app.clock.time = Some(time(7_u8, 0_u8, 0_u8));
+        app.clock.battery_low = true;
+        app.clock.calls.clear();
+        app.update(5_200_u32);
+        assert_eq!(
+            app.clock.calls,
+            [ClockCall::ReadTime, ClockCall::BatteryLow]
+        );
+        assert!(app.state.rtc_battery_low);
+        app.update(5_300_u32);
+        assert_eq!(app.clock.calls.len(), 3_usize);
+    }
+
+    #[test]
+    fn alarm_with_controls() {
+        let mut app = app(time(7_u8, 0_u8, 0_u8));
+        assert_eq!(
+            app.update(0_u32),
+            Some(AlarmEvent::Started(AlarmSound::default()))
+        );
+        app.inputs.push(InputEvent::Snooze);
+        assert_eq!(app.update(100_u32), Some(AlarmEvent::Snoozed));
+        assert!(app.inputs.events.is_empty());
+
+        // Turning doesn't dismiss it
+        app.inputs
+            .push(InputEvent::Turn(crate::hal::Direction::Clockwise));
+        assert_eq!(app.update(200_u32), None);
+        app.inputs.push(InputEvent::Push);
+        assert_eq!(app.update(300_u32), Some(AlarmEvent::Dismissed));
+        assert!(matches!(app.state.mode, OperationalMode::Idle));
+    }
+
+    #[test]
+    fn shows_missed_alarms() {
+        let mut app = app(time(7_u8, 0_u8, 0_u8));
+        app.update(0_u32);
+        app.inputs.push(InputEvent::Snooze);
+        app.update(100_u32);
+        let rang_out = 100_u32 + 9_u32 * 60_000_u32 + 15_u32 * 60_000_u32;
+        app.clock.time = Some(time(7_u8, 9_u8, 0_u8));
+        app.update(100_u32 + 9_u32 * 60_000_u32);
+        // The alarm that was missed is shown, not when it gave up
+        app.clock.time = Some(time(7_u8, 24_u8, 0_u8));
+        assert!(matches!(app.update(rang_out), Some(AlarmEvent::RangOut(_))));
+        app.update(rang_out + 100_u32);
+        assert_eq!(app.lcd.line(0_u8), "Missed 07:00 Zz1");
+    }
+}
@@ -0,0 +1,91 @@
+//! Calendar math for the dates stored in `Time` (years are 2000-2099)
+
+/// Indexed by `Time::day_of_week`
+pub const DAY_NAMES: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+
+/// Indexed by `Time::month` - 1
+pub const MONTH_NAMES: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+pub fn is_leap_year(year: u8) -> bool {
+    // 2000 was a leap year and 2100 is past the RTC's range
+    year & 0b11_u8 == 0_u8
+}
+
+/// Days in the month [1, 12] of the year [0, 99]
+pub fn days_in_month(year: u8, month: u8) -> u8 {
+    match month {
+        2_u8 if is_leap_year(year) => 29_u8,
+        2_u8 => 28_u8,
+        4_u8 | 6_u8 | 9_u8 | 11_u8 => 30_u8,
+        _ => 31_u8,
+    }
+}
+
+pub fn is_valid_date(year: u8, month: u8, day: u8) -> bool {
+    year < 100_u8
+        && (1_u8..=12_u8).contains(&month)
+        && day >= 1_u8
+        && day <= days_in_month(year, month)
+}
+
+/// Day of the week from [0, 6] where 0 is Sunday (Sakamoto's method)
+pub fn day_of_week(year: u8, month: u8, day: u8) -> u8 {
+    const MONTH_OFFSETS: [u16; 12] = [0, 3, 2, 5, 0, 3, 5, 1, 4, 6, 2, 4];
+    let mut year = 2000_u16 + year as u16;
+    if month < 3_u8 {
+        year -= 1_u16;
+    }
+    ((year + year / 4_u16 - year / 100_u16
+        + year / 400_u16
+        + MONTH_OFFSETS[month as usize - 1]
+        + day as u16)
+        % 7_u16) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn days() {
+        assert_eq!(days_in_month(23_u8, 2_u8), 28_u8);
+        assert_eq!(days_in_month(24_u8, 2_u8), 29_u8);
+        assert_eq!(days_in_month(0_u8, 2_u8), 29_u8);
+        assert_eq!(days_in_month(23_u8, 9_u8), 30_u8);
+        assert!(is_valid_date(24_u8, 2_u8, 29_u8));
+        assert!(!is_valid_date(23_u8, 2_u8, 29_u8));
+        assert!(!is_valid_date(23_u8, 13_u8, 1_u8));
+        assert!(!is_valid_date(23_u8, 1_u8, 0_u8));
+    }
+
+    #[test]
+    fn weekdays() {
+        // Saturday, January 1st 2000
+        assert_eq!(day_of_week(0_u8, 1_u8, 1_u8), 6_u8);
+        // Sunday, September 17th 2023
+        assert_eq!(day_of_week(23_u8, 9_u8, 17_u8), 0_u8);
+        // Thursday, February 29th 2024
+        assert_eq!(day_of_week(24_u8, 2_u8, 29_u8), 4_u8);
+    }
+}
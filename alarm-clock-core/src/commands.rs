@@ -0,0 +1,776 @@
+//! Carrying out the shell's commands and the host tools' requests. The
+//! firmware reads them from the serial port, then sends back the response or
+//! prints the reply.
+
+use alarm_clock_protocol::{
+    decode_packet,
+    message::{Alarm as ProtocolAlarm, MAX_ALARMS as PROTOCOL_MAX_ALARMS},
+    DateTime, Diagnostics, ErrorCode, FrameReader, LogEntry, MissedAlarms, Packet, Request,
+    Response, SettingsRecord, DELIMITER,
+};
+
+use crate::{
+    calendar,
+    eeprom::Eeprom,
+    hal::{Board, ClockSource},
+    history::History,
+    idle_screen::IdleScreenSettings,
+    log::{self, Level, Tag},
+    settings::{Settings, SettingsError, StoredAlarm, MAX_ALARMS, SETTINGS_VERSION},
+    shell::{Command, ScreenSetting},
+    state::{Alarm, AlarmSound, OperationalMode, State},
+    time::{Time, TimeDigits},
+};
+
+const _: () = assert!(MAX_ALARMS == PROTOCOL_MAX_ALARMS);
+
+/// What became of a shell command, for the firmware to print. Listings are
+/// printed from the state.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Reply {
+    Help,
+    Time(Time),
+    TimeSet,
+    /// The day of the week is filled in
+    Date(Time),
+    DateSet,
+    /// The time read back after syncing, printed in a fixed format for the host
+    Sync(DateTime),
+    AlarmList,
+    AlarmAdded(u8),
+    AlarmDeleted(u8),
+    /// The alarms as a whole were switched on or off
+    AlarmsEnabled(bool),
+    AlarmEnabled {
+        alarm: u8,
+        enabled: bool,
+    },
+    Status,
+    Brightness(u8),
+    Screen(IdleScreenSettings),
+    LogList,
+    /// The level that was set, which may be more verbose than is compiled in
+    LogSet(Level),
+    LogSaved,
+    /// The clock should be reset, once the reply is out
+    Reset,
+    UnknownSound,
+    /// All `MAX_ALARMS` are set
+    AlarmsFull,
+    NoAlarm(u8),
+}
+
+/// Run a command from the shell
+pub fn run_command(
+    command: Command,
+    state: &mut State,
+    clock: &mut impl ClockSource,
+    eeprom: &mut impl Eeprom,
+    history: &mut History,
+    board: &mut impl Board,
+) -> Reply {
+    match command {
+        Command::Help => Reply::Help,
+        Command::Time => Reply::Time(state.time),
+        Command::SetTime {
+            hours,
+            minutes,
+            seconds,
+        } => {
+            state.time.hours = hours;
+            state.time.minutes = minutes;
+            state.time.seconds = seconds;
+            clock.set_time(&state.time);
+            history.record_sync(eeprom, &state.time);
+            Reply::TimeSet
+        }
+        Command::Date => Reply::Date(state.time),
+        Command::SetDate { year, month, day } => {
+            state.time.year = year;
+            state.time.month = month;
+            state.time.day = day;
+            state.time.day_of_week = calendar::day_of_week(year, month, day);
+            clock.set_time(&state.time);
+            history.record_sync(eeprom, &state.time);
+            Reply::DateSet
+        }
+        Command::Sync(date_time) => {
+            if let Some(date_time) = date_time {
+                set_clock(&date_time, state, clock, eeprom, history);
+            }
+            Reply::Sync(read_clock(state, clock))
+        }
+        Command::AlarmList => Reply::AlarmList,
+        Command::AlarmAdd {
+            hours,
+            minutes,
+            sound,
+        } => {
+            let Some(sound) = AlarmSound::parse(sound) else {
+                return Reply::UnknownSound;
+            };
+            let Some(n) = state.alarms.iter().position(|alarm| alarm.is_none()) else {
+                return Reply::AlarmsFull;
+            };
+            state.alarms[n] = Some(Alarm {
+                hours,
+                minutes,
+                enabled: true,
+                sound,
+            });
+            state.settings().store(eeprom);
+            Reply::AlarmAdded(n as u8)
+        }
+        Command::AlarmDelete(n) => match state.alarms.get_mut(n as usize) {
+            Some(alarm @ Some(_)) => {
+                *alarm = None;
+                state.settings().store(eeprom);
+                Reply::AlarmDeleted(n)
+            }
+            _ => Reply::NoAlarm(n),
+        },
+        Command::AlarmEnable {
+            alarm: None,
+            enabled,
+        } => {
+            state.alarm_enabled = enabled;
+            state.settings().store(eeprom);
+            Reply::AlarmsEnabled(enabled)
+        }
+        Command::AlarmEnable {
+            alarm: Some(n),
+            enabled,
+        } => match state.alarms.get_mut(n as usize) {
+            Some(Some(alarm)) => {
+                alarm.enabled = enabled;
+                state.settings().store(eeprom);
+                Reply::AlarmEnabled { alarm: n, enabled }
+            }
+            _ => Reply::NoAlarm(n),
+        },
+        Command::Status => Reply::Status,
+        Command::Brightness(None) => Reply::Brightness(state.brightness),
+        Command::Brightness(Some(brightness)) => {
+            state.brightness = brightness;
+            board.set_brightness(brightness);
+            state.settings().store(eeprom);
+            Reply::Brightness(brightness)
+        }
+        Command::Screen(setting) => {
+            let screen = &mut state.idle_screen;
+            match setting {
+                Some(ScreenSetting::Pages(pages)) => screen.pages = pages,
+                Some(ScreenSetting::DateFormat(format)) => screen.date_format = format,
+                Some(ScreenSetting::Rotate(seconds)) => screen.page_seconds = seconds,
+                None => (),
+            }
+            if setting.is_some() {
+                state.settings().store(eeprom);
+            }
+            Reply::Screen(state.idle_screen)
+        }
+        Command::LogList => Reply::LogList,
+        Command::LogSet { tag, level } => {
+            match tag {
+                Some(tag) => log::set_filter(tag, level),
+                None => Tag::ALL
+                    .into_iter()
+                    .for_each(|tag| log::set_filter(tag, level)),
+            }
+            Reply::LogSet(level)
+        }
+        Command::LogSave => {
+            state.log_levels = log::filters();
+            state.settings().store(eeprom);
+            Reply::LogSaved
+        }
+        Command::Reset => Reply::Reset,
+    }
+}
+
+/// Picks the host tools' requests out of the bytes received and runs them.
+/// Their frames start with a delimiter the shell never sees.
+pub struct RequestReader {
+    frames: FrameReader,
+}
+impl RequestReader {
+    pub fn new() -> Self {
+        Self {
+            frames: FrameReader::new(),
+        }
+    }
+
+    /// Whether a byte belongs to a request's frame rather than to the shell
+    pub fn wants(&self, byte: u8) -> bool {
+        byte == DELIMITER || self.frames.in_frame()
+    }
+
+    /// Feed in a byte, running the request whose frame it ends, if any, and
+    /// returning the response to send back. Bad frames are dropped, and the
+    /// host will retry.
+    pub fn push(
+        &mut self,
+        byte: u8,
+        state: &mut State,
+        clock: &mut impl ClockSource,
+        eeprom: &mut impl Eeprom,
+        history: &mut History,
+        board: &mut impl Board,
+    ) -> Option<Packet<Response>> {
+        let frame = self.frames.push(byte)?;
+        let Ok(packet) = decode_packet::<Request>(frame) else {
+            // Likely noise between two frames, so the delimiter ending it
+            // starts the next
+            self.frames.resume();
+            return None;
+        };
+        let response = match packet.message {
+            Ok(request) => run_request(request, state, clock, eeprom, history, board),
+            Err(error) => Response::Error(error.into()),
+        };
+        Some(Packet {
+            id: packet.id,
+            message: response,
+        })
+    }
+}
+impl Default for RequestReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Run a request from a host tool
+pub fn run_request(
+    request: Request,
+    state: &mut State,
+    clock: &mut impl ClockSource,
+    eeprom: &mut impl Eeprom,
+    history: &mut History,
+    board: &mut impl Board,
+) -> Response {
+    match request {
+        Request::GetTime => Response::Time(read_clock(state, clock)),
+        Request::SetTime(date_time) => {
+            let valid = calendar::is_valid_date(date_time.year, date_time.month, date_time.day)
+                && date_time.hours < 24_u8
+                && date_time.minutes < 60_u8
+                && date_time.seconds < 60_u8;
+            if !valid {
+                return Response::Error(ErrorCode::OutOfRange);
+            }
+            set_clock(&date_time, state, clock, eeprom, history);
+            Response::Time(read_clock(state, clock))
+        }
+        Request::GetAlarms => Response::Alarms(protocol_alarms(&state.settings())),
+        Request::SetAlarms(alarms) => {
+            let mut settings = state.settings();
+            for (stored, alarm) in settings.alarms.iter_mut().zip(alarms) {
+                *stored = StoredAlarm {
+                    hours: alarm.hours,
+                    minutes: alarm.minutes,
+                    flags: alarm.flags,
+                    sound: alarm.sound,
+                };
+            }
+            if settings.validate().is_err() {
+                return Response::Error(ErrorCode::OutOfRange);
+            }
+            state.apply_settings(&settings);
+            state.settings().store(eeprom);
+            Response::Alarms(protocol_alarms(&state.settings()))
+        }
+        Request::GetSettings => match SettingsRecord::new(&state.settings().encode()) {
+            Some(record) => Response::Settings(record),
+            // Ruled out at compile time in settings.rs
+            None => Response::Error(ErrorCode::BadSettings),
+        },
+        Request::SetSettings(record) => {
+            match Settings::decode(record.as_bytes(), &state.settings()) {
+                Ok(settings) => {
+                    state.apply_settings(&settings);
+                    settings.store(eeprom);
+                    board.set_brightness(state.brightness);
+                    Response::Done
+                }
+                Err(SettingsError::OutOfRange) => Response::Error(ErrorCode::OutOfRange),
+                Err(_) => Response::Error(ErrorCode::BadSettings),
+            }
+        }
+        Request::ReadMissedAlarms(start) => {
+            let mut page = MissedAlarms::new(start);
+            let mut nth = start as u16;
+            while let Some(missed_alarm) = history.missed_alarm(eeprom, nth) {
+                let entry = LogEntry {
+                    time: date_time(&missed_alarm.time),
+                    snoozes: missed_alarm.snoozes,
+                };
+                if !page.push(entry) {
+                    break;
+                }
+                nth += 1_u16;
+            }
+            Response::MissedAlarms(page)
+        }
+        Request::GetDiagnostics => {
+            let counters = history.counters();
+            Response::Diagnostics(Diagnostics {
+                uptime_seconds: board.millis() / 1_000_u32,
+                boots: counters.boots,
+                snoozes: counters.snoozes,
+                serial_dropped: board.serial_dropped(),
+                settings_version: SETTINGS_VERSION,
+                mode: match state.mode {
+                    OperationalMode::Idle => 0_u8,
+                    OperationalMode::Alarm => 1_u8,
+                    OperationalMode::Snoozed => 2_u8,
+                    _ => 3_u8,
+                },
+                last_sync: counters.last_sync.as_ref().map(date_time),
+            })
+        }
+    }
+}
+
+/// Set the date and time, as synced from outside
+fn set_clock(
+    date_time: &DateTime,
+    state: &mut State,
+    clock: &mut impl ClockSource,
+    eeprom: &mut impl Eeprom,
+    history: &mut History,
+) {
+    state.time = Time {
+        hours: date_time.hours,
+        minutes: date_time.minutes,
+        seconds: date_time.seconds,
+        day: date_time.day,
+        day_of_week: calendar::day_of_week(date_time.year, date_time.month, date_time.day),
+        month: date_time.month,
+        year: date_time.year,
+    };
+    clock.set_time(&state.time);
+    history.record_sync(eeprom, &state.time);
+}
+
+/// Read the time straight from the clock, as the last update may be a while
+/// old
+fn read_clock(state: &mut State, clock: &mut impl ClockSource) -> DateTime {
+    if let Some(new_time) = clock.read_time() {
+        state.time = new_time;
+        state.digits = TimeDigits::from_time(&new_time);
+    }
+    date_time(&state.time)
+}
+
+fn date_time(time: &Time) -> DateTime {
+    DateTime {
+        year: time.year,
+        month: time.month,
+        day: time.day,
+        hours: time.hours,
+        minutes: time.minutes,
+        seconds: time.seconds,
+    }
+}
+
+fn protocol_alarms(settings: &Settings) -> [ProtocolAlarm; PROTOCOL_MAX_ALARMS] {
+    core::array::from_fn(|n| {
+        let alarm = &settings.alarms[n];
+        ProtocolAlarm {
+            hours: alarm.hours,
+            minutes: alarm.minutes,
+            flags: alarm.flags,
+            sound: alarm.sound,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        eeprom::MemoryEeprom,
+        hal::mock::{ClockCall, MockBoard, MockClock},
+        settings::{ALARM_ENABLED, ALARM_PRESENT},
+        state::MissedAlarm,
+    };
+    use alarm_clock_protocol::{
+        encode_packet, message::MISSED_ALARMS_PER_MESSAGE, MAX_FRAME_LENGTH,
+    };
+
+    type TestEeprom = MemoryEeprom<1024>;
+
+    struct Clock {
+        state: State,
+        clock: MockClock,
+        eeprom: TestEeprom,
+        history: History,
+        board: MockBoard,
+    }
+    impl Clock {
+        fn new() -> Self {
+            let eeprom = TestEeprom::new();
+            Self {
+                state: State::new(),
+                clock: MockClock::new(time(21_u8, 43_u8)),
+                history: History::load(&eeprom),
+                eeprom,
+                board: MockBoard::new(),
+            }
+        }
+
+        fn request(&mut self, request: Request) -> Response {
+            run_request(
+                request,
+                &mut self.state,
+                &mut self.clock,
+                &mut self.eeprom,
+                &mut self.history,
+                &mut self.board,
+            )
+        }
+
+        /// Feed bytes through `requests`, returning the responses
+        fn receive(&mut self, requests: &mut RequestReader, bytes: &[u8]) -> Vec<Packet<Response>> {
+            bytes
+                .iter()
+                .filter_map(|byte| {
+                    requests.push(
+                        *byte,
+                        &mut self.state,
+                        &mut self.clock,
+                        &mut self.eeprom,
+                        &mut self.history,
+                        &mut self.board,
+                    )
+                })
+                .collect()
+        }
+
+        fn command(&mut self, command: Command) -> Reply {
+            run_command(
+                command,
+                &mut self.state,
+                &mut self.clock,
+                &mut self.eeprom,
+                &mut self.history,
+                &mut self.board,
+            )
+        }
+
+        /// What the clock would load after a reset
+        fn saved(&self) -> Settings {
+            Settings::load(&self.eeprom, &State::new().settings()).unwrap()
+        }
+    }
+
+    fn time(hours: u8, minutes: u8) -> Time {
+        Time {
+            hours,
+            minutes,
+            seconds: 0_u8,
+            day: 14_u8,
+            day_of_week: 4_u8,
+            month: 3_u8,
+            year: 24_u8,
+        }
+    }
+
+    fn date_time(year: u8, month: u8, day: u8, hours: u8) -> DateTime {
+        DateTime {
+            year,
+            month,
+            day,
+            hours,
+            minutes: 30_u8,
+            seconds: 15_u8,
+        }
+    }
+
+    #[test]
+    fn setting_the_time() {
+        let mut clock = Clock::new();
+        for bad in [
+            date_time(23_u8, 2_u8, 29_u8, 12_u8),
+            date_time(24_u8, 13_u8, 1_u8, 12_u8),
+            date_time(24_u8, 3_u8, 14_u8, 24_u8),
+        ] {
+            assert_eq!(
+                clock.request(Request::SetTime(bad)),
+                Response::Error(ErrorCode::OutOfRange)
+            );
+        }
+        assert!(clock.clock.calls.is_empty());
+        assert_eq!(clock.history.counters().last_sync, None);
+
+        let leap_day = date_time(24_u8, 2_u8, 29_u8, 12_u8);
+        assert_eq!(
+            clock.request(Request::SetTime(leap_day)),
+            Response::Time(leap_day)
+        );
+        // A Thursday
+        assert_eq!(clock.state.time.day_of_week, 4_u8);
+        assert_eq!(
+            clock.clock.calls,
+            [ClockCall::SetTime(clock.state.time), ClockCall::ReadTime]
+        );
+        assert_eq!(clock.history.counters().last_sync, Some(clock.state.time));
+    }
+
+    #[test]
+    fn resynchronises_after_bad_frames() {
+        let mut clock = Clock::new();
+        let mut requests = RequestReader::new();
+        let mut out = [0_u8; MAX_FRAME_LENGTH];
+        let frame = encode_packet(7_u8, &Request::GetTime, &mut out)
+            .unwrap()
+            .to_vec();
+        assert!(!requests.wants(b's'));
+        assert!(requests.wants(DELIMITER));
+
+        // A corrupted frame is dropped
+        let mut corrupted = frame.clone();
+        corrupted[4] ^= 0x10_u8;
+        assert!(clock.receive(&mut requests, &corrupted).is_empty());
+        // And one whose end was lost runs into the next frame's start, which
+        // is picked back up so the next frame is still read
+        let mut stream = frame[..frame.len() - 3_usize].to_vec();
+        stream.extend_from_slice(&frame);
+        let responses = clock.receive(&mut requests, &stream);
+        assert_eq!(
+            responses,
+            [Packet {
+                id: 7_u8,
+                message: Response::Time(super::date_time(&time(21_u8, 43_u8)))
+            }]
+        );
+        assert!(!requests.wants(b's'));
+    }
+
+    #[test]
+    fn setting_alarms() {
+        let mut clock = Clock::new();
+        clock.state.brightness = 3_u8;
+        let mut alarms = [ProtocolAlarm::default(); PROTOCOL_MAX_ALARMS];
+        alarms[1] = ProtocolAlarm {
+            hours: 6_u8,
+            minutes: 30_u8,
+            flags: ALARM_PRESENT | ALARM_ENABLED,
+            sound: 1_u8,
+        };
+        let Response::Alarms(set) = clock.request(Request::SetAlarms(alarms)) else {
+            panic!("The alarms weren't set");
+        };
+        assert_eq!(set, alarms);
+        assert_eq!(clock.saved().alarms[1].hours, 6_u8);
+        // The rest of the settings are left as they were
+        assert_eq!(clock.saved().brightness, 3_u8);
+
+        alarms[2] = ProtocolAlarm {
+            hours: 24_u8,
+            ..alarms[1]
+        };
+        assert_eq!(
+            clock.request(Request::SetAlarms(alarms)),
+            Response::Error(ErrorCode::OutOfRange)
+        );
+        assert!(clock.state.alarms[2].is_none());
+        assert_eq!(clock.saved().alarms[2].flags, 0_u8);
+    }
+
+    #[test]
+    fn setting_settings_sets_the_brightness() {
+        let mut clock = Clock::new();
+        let mut settings = clock.state.settings();
+        settings.brightness = 2_u8;
+        let record = SettingsRecord::new(&settings.encode()).unwrap();
+        assert_eq!(clock.request(Request::SetSettings(record)), Response::Done);
+        assert_eq!(clock.board.brightness, [2_u8]);
+        assert_eq!(clock.saved().brightness, 2_u8);
+
+        settings.brightness = 9_u8;
+        let record = SettingsRecord::new(&settings.encode()).unwrap();
+        assert_eq!(
+            clock.request(Request::SetSettings(record)),
+            Response::Error(ErrorCode::OutOfRange)
+        );
+        assert_eq!(clock.state.brightness, 2_u8);
+    }
+
+    #[test]
+    fn paging_missed_alarms() {
+        let mut clock = Clock::new();
+        let count = MISSED_ALARMS_PER_MESSAGE as u8 + 2_u8;
+        for snoozes in 0_u8..count {
+            let missed_alarm = MissedAlarm {
+                time: time(7_u8, snoozes),
+                snoozes,
+            };
+            clock
+                .history
+                .record_missed_alarm(&mut clock.eeprom, &missed_alarm);
+        }
+
+        let Response::MissedAlarms(first) = clock.request(Request::ReadMissedAlarms(0_u8)) else {
+            panic!("No missed alarms");
+        };
+        assert!(!first.is_last());
+        // Newest first
+        let snoozes = first.entries().iter().map(|entry| entry.snoozes);
+        assert!(snoozes.eq((2_u8..count).rev()));
+        assert_eq!(first.entries()[0].time.minutes, count - 1_u8);
+
+        let start = MISSED_ALARMS_PER_MESSAGE as u8;
+        let Response::MissedAlarms(rest) = clock.request(Request::ReadMissedAlarms(start)) else {
+            panic!("No missed alarms");
+        };
+        assert!(rest.is_last());
+        assert!(rest
+            .entries()
+            .iter()
+            .map(|entry| entry.snoozes)
+            .eq([1_u8, 0_u8]));
+
+        let Response::MissedAlarms(past_the_end) = clock.request(Request::ReadMissedAlarms(count))
+        else {
+            panic!("No missed alarms");
+        };
+        assert!(past_the_end.entries().is_empty());
+    }
+
+    #[test]
+    fn diagnostics() {
+        let mut clock = Clock::new();
+        clock.board.millis = 90_500_u32;
+        clock.board.serial_dropped = 3_u16;
+        clock.history.record_boot(&mut clock.eeprom);
+        clock.state.mode = OperationalMode::Snoozed;
+        let Response::Diagnostics(diagnostics) = clock.request(Request::GetDiagnostics) else {
+            panic!("No diagnostics");
+        };
+        assert_eq!(
+            diagnostics,
+            Diagnostics {
+                uptime_seconds: 90_u32,
+                boots: 1_u16,
+                snoozes: 0_u16,
+                serial_dropped: 3_u16,
+                settings_version: SETTINGS_VERSION,
+                mode: 2_u8,
+                last_sync: None,
+            }
+        );
+    }
+
+    #[test]
+    fn adding_and_deleting_alarms() {
+        let mut clock = Clock::new();
+        let add = |sound| Command::AlarmAdd {
+            hours: 7_u8,
+            minutes: 15_u8,
+            sound,
+        };
+        assert_eq!(clock.command(add(Some("kazoo"))), Reply::UnknownSound);
+        for n in 0_u8..MAX_ALARMS as u8 {
+            assert_eq!(clock.command(add(None)), Reply::AlarmAdded(n));
+        }
+        assert_eq!(clock.command(add(None)), Reply::AlarmsFull);
+        assert_eq!(clock.saved().alarms[1].minutes, 15_u8);
+
+        assert_eq!(
+            clock.command(Command::AlarmDelete(1_u8)),
+            Reply::AlarmDeleted(1_u8)
+        );
+        assert_eq!(
+            clock.command(Command::AlarmDelete(1_u8)),
+            Reply::NoAlarm(1_u8)
+        );
+        assert_eq!(clock.saved().alarms[1].flags, 0_u8);
+        // The free slot is reused
+        assert_eq!(clock.command(add(None)), Reply::AlarmAdded(1_u8));
+    }
+
+    #[test]
+    fn enabling_alarms() {
+        let mut clock = Clock::new();
+        let enable = |alarm| Command::AlarmEnable {
+            alarm,
+            enabled: true,
+        };
+        assert_eq!(clock.command(enable(Some(0_u8))), Reply::NoAlarm(0_u8));
+        assert_eq!(clock.command(enable(None)), Reply::AlarmsEnabled(true));
+        assert!(clock.saved().alarm_enabled);
+
+        clock.command(Command::AlarmAdd {
+            hours: 7_u8,
+            minutes: 0_u8,
+            sound: None,
+        });
+        assert_eq!(
+            clock.command(Command::AlarmEnable {
+                alarm: Some(0_u8),
+                enabled: false,
+            }),
+            Reply::AlarmEnabled {
+                alarm: 0_u8,
+                enabled: false,
+            }
+        );
+        assert_eq!(clock.saved().alarms[0].flags, ALARM_PRESENT);
+    }
+
+    #[test]
+    fn setting_the_clock_from_the_shell() {
+        let mut clock = Clock::new();
+        assert_eq!(
+            clock.command(Command::SetTime {
+                hours: 6_u8,
+                minutes: 5_u8,
+                seconds: 4_u8,
+            }),
+            Reply::TimeSet
+        );
+        assert_eq!(clock.clock.time.unwrap().hours, 6_u8);
+        assert_eq!(
+            clock.command(Command::SetDate {
+                year: 24_u8,
+                month: 2_u8,
+                day: 29_u8,
+            }),
+            Reply::DateSet
+        );
+        assert_eq!(clock.clock.time.unwrap().day_of_week, 4_u8);
+        assert_eq!(clock.history.counters().last_sync, Some(clock.state.time));
+
+        // Syncing reads the time back from the clock
+        clock.clock.time = Some(time(8_u8, 0_u8));
+        let synced = clock.command(Command::Sync(None));
+        assert_eq!(synced, Reply::Sync(super::date_time(&time(8_u8, 0_u8))));
+    }
+
+    #[test]
+    fn brightness_and_screen() {
+        let mut clock = Clock::new();
+        let brightness = clock.state.brightness;
+        assert_eq!(
+            clock.command(Command::Brightness(None)),
+            Reply::Brightness(brightness)
+        );
+        assert!(clock.board.brightness.is_empty());
+        assert_eq!(
+            clock.command(Command::Brightness(Some(5_u8))),
+            Reply::Brightness(5_u8)
+        );
+        assert_eq!(clock.board.brightness, [5_u8]);
+        assert_eq!(clock.saved().brightness, 5_u8);
+
+        let Reply::Screen(screen) =
+            clock.command(Command::Screen(Some(ScreenSetting::Rotate(9_u8))))
+        else {
+            panic!("The screen's settings weren't shown");
+        };
+        assert_eq!(screen.page_seconds, 9_u8);
+        assert_eq!(clock.saved().idle_page_seconds, 9_u8);
+    }
+}
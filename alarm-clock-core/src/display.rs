@@ -0,0 +1,387 @@
+//! Seven segment encoding for the time displays, down to which shift register
+//! output drives each segment. Outputs are `true` when driven high.
+
+use core::fmt;
+
+/// Brightness levels go from 1 to this (fully on)
+pub const MAX_BRIGHTNESS: u8 = 8_u8;
+/// Index of the all-off pattern in `SEVEN_SEGMENT_OUTPUT`
+pub const BLANK: u8 = 0x10_u8;
+
+/// A, B, C, D, E, F, & G pin states for a given digit index
+const SEVEN_SEGMENT_OUTPUT: [[bool; 7]; 0x10 + 1] = {
+    const HIGH: bool = true;
+    const LOW: bool = false;
+    [
+        /* Decimal */
+        [HIGH, HIGH, HIGH, HIGH, HIGH, HIGH, LOW],  // 0
+        [LOW, HIGH, HIGH, LOW, LOW, LOW, LOW],      // 1
+        [HIGH, HIGH, LOW, HIGH, HIGH, LOW, HIGH],   // 2
+        [HIGH, HIGH, HIGH, HIGH, LOW, LOW, HIGH],   // 3
+        [LOW, HIGH, HIGH, LOW, LOW, HIGH, HIGH],    // 4
+        [HIGH, LOW, HIGH, HIGH, LOW, HIGH, HIGH],   // 5
+        [HIGH, LOW, HIGH, HIGH, HIGH, HIGH, HIGH],  // 6
+        [HIGH, HIGH, HIGH, LOW, LOW, LOW, LOW],     // 7
+        [HIGH, HIGH, HIGH, HIGH, HIGH, HIGH, HIGH], // 8
+        [HIGH, HIGH, HIGH, HIGH, LOW, HIGH, HIGH],  // 9
+        /* (Scuffed) hexadecimal, where B == 8 and D == 0 */
+        [HIGH, HIGH, HIGH, LOW, HIGH, HIGH, HIGH],  // A
+        [HIGH, HIGH, HIGH, HIGH, HIGH, HIGH, HIGH], // B
+        [HIGH, LOW, LOW, HIGH, HIGH, HIGH, LOW],    // C
+        [HIGH, HIGH, HIGH, HIGH, HIGH, HIGH, LOW],  // D
+        [HIGH, LOW, LOW, HIGH, HIGH, HIGH, HIGH],   // E
+        [HIGH, LOW, LOW, LOW, HIGH, HIGH, HIGH],    // F
+        /* This is just here to reset to an off state */
+        [LOW, LOW, LOW, LOW, LOW, LOW, LOW], // NULL
+    ]
+};
+
+/// The A-G segments lit for a digit from [0, 0xF], or none for anything else
+pub fn segments(digit: u8) -> [bool; 7] {
+    SEVEN_SEGMENT_OUTPUT[digit.min(BLANK) as usize]
+}
+
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum DigitSelect {
+    DP = 1_u8 << 0,
+    Hour1 = 1_u8 << 1,
+    Hour2 = 1_u8 << 2,
+    Minute1 = 1_u8 << 3,
+    Minute2 = 1_u8 << 4,
+}
+impl DigitSelect {
+    /// The next digit to light, cycling through all five
+    fn next(self) -> Self {
+        match self {
+            DigitSelect::DP => DigitSelect::Minute2,
+            DigitSelect::Minute2 => DigitSelect::Minute1,
+            DigitSelect::Minute1 => DigitSelect::Hour2,
+            DigitSelect::Hour2 => DigitSelect::Hour1,
+            DigitSelect::Hour1 => DigitSelect::DP,
+        }
+    }
+}
+
+/// Multiplexing for the 4-digit hours and minutes display, which only lights
+/// one digit (or the colon) at a time. Each call to `next` gives the outputs
+/// for the next slot, which should be shifted out every millisecond or so to
+/// produce an "always on" effect.
+///
+/// The brightness is set by blanking some of the multiplexing slots, spread
+/// out evenly so it doesn't flicker.
+pub struct Multiplexer {
+    selected_digit: DigitSelect,
+    brightness: u8,
+    /// Slots are lit each time this overflows `MAX_BRIGHTNESS`
+    brightness_accumulator: u8,
+}
+impl Multiplexer {
+    pub fn new() -> Self {
+        Self {
+            selected_digit: DigitSelect::DP,
+            brightness: MAX_BRIGHTNESS,
+            brightness_accumulator: 0_u8,
+        }
+    }
+
+    /// Set the brightness from [1, `MAX_BRIGHTNESS`]
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.brightness = brightness.clamp(1_u8, MAX_BRIGHTNESS);
+    }
+
+    /// The 16 shift register outputs for the next slot
+    pub fn next(&mut self, hours: (u8, u8), minutes: (u8, u8)) -> [bool; 16] {
+        let selected_digit = self.selected_digit;
+        self.selected_digit = selected_digit.next();
+
+        self.brightness_accumulator += self.brightness;
+        let lit = self.brightness_accumulator >= MAX_BRIGHTNESS;
+        if lit {
+            self.brightness_accumulator -= MAX_BRIGHTNESS;
+        }
+
+        // First 5 bits of first shift register (mode)
+        // (nothing is selected in a blanked slot)
+        let selected = |digit: DigitSelect| lit && selected_digit == digit;
+
+        // Last 3 pins of first shift register (DP 1, 2, 3 & 4) and the last pin
+        // of the second shift register (DP 5)
+        let dp_pin_states: [bool; 4] = match selected_digit {
+            DigitSelect::DP => [
+                false, // DP 1 (colon 1 top)
+                false, // DP 2 (colon 1 bottom)
+                true,  // DP 3 & 4 (colon 2)
+                false, // DP 5 (random decimal point for fun :^)!)
+            ],
+            _ => [false; 4],
+        };
+
+        // First 7 pins of second shift register
+        let segment_pin_states = segments(match selected_digit {
+            DigitSelect::DP => BLANK,
+            DigitSelect::Hour1 => hours.0,
+            DigitSelect::Hour2 => hours.1,
+            DigitSelect::Minute1 => minutes.0,
+            DigitSelect::Minute2 => minutes.1,
+        });
+
+        // {dig_dp, dig_1, dig_2, dig_3, dig_4,  // Common select
+        //  dp_3_4, dp_2, dp_1, dp_5,            // Decimal points
+        //  f, g, a, b, c, d, e}                 // Seven segment
+        // Since this display is common cathode, the common selected should be GND
+        [
+            !selected(DigitSelect::DP),
+            !selected(DigitSelect::Hour1),
+            !selected(DigitSelect::Hour2),
+            !selected(DigitSelect::Minute1),
+            !selected(DigitSelect::Minute2),
+            dp_pin_states[0],
+            dp_pin_states[1],
+            dp_pin_states[2],
+            dp_pin_states[3],
+            segment_pin_states[5], // F
+            segment_pin_states[6], // G
+            segment_pin_states[0], // A
+            segment_pin_states[1], // B
+            segment_pin_states[2], // C
+            segment_pin_states[3], // D
+            segment_pin_states[4], // E
+        ]
+    }
+}
+impl Default for Multiplexer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The 16 shift register outputs for the two seconds digits. Each shift
+/// register drives one digit, which never needs multiplexing.
+pub fn seconds_outputs(seconds: (u8, u8)) -> [bool; 16] {
+    let digit_1_output = segments(seconds.0);
+    let digit_2_output = segments(seconds.1);
+    [
+        // Digit 1
+        digit_1_output[6], // G
+        digit_1_output[5], // F
+        digit_1_output[0], // A
+        digit_1_output[1], // B
+        digit_1_output[4], // E
+        digit_1_output[3], // D
+        digit_1_output[2], // C
+        false,             // DP
+        // Digit 2
+        digit_2_output[6], // G
+        digit_2_output[5], // F
+        digit_2_output[0], // A
+        digit_2_output[1], // B
+        false,             // DP
+        digit_2_output[3], // D
+        digit_2_output[2], // C
+        digit_2_output[4], // E
+    ]
+}
+
+/// What one multiplexing slot of the hours and minutes display lights
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Slot {
+    /// A digit from the left [0, 3] with its A-G segments
+    Digit(usize, [bool; 7]),
+    /// The decimal points: DP 1, DP 2, the colon (DP 3 & 4), then DP 5
+    DecimalPoints([bool; 4]),
+}
+
+/// Decode the outputs for one slot of the hours and minutes display, or `None`
+/// if it's blanked
+pub fn decode_hours_minutes(outputs: &[bool; 16]) -> Option<Slot> {
+    match outputs[..5].iter().position(|output| !output)? {
+        0_usize => Some(Slot::DecimalPoints([
+            outputs[5], outputs[6], outputs[7], outputs[8],
+        ])),
+        digit => Some(Slot::Digit(
+            digit - 1_usize,
+            [
+                outputs[11],
+                outputs[12],
+                outputs[13],
+                outputs[14],
+                outputs[15],
+                outputs[9],
+                outputs[10],
+            ],
+        )),
+    }
+}
+
+/// Decode the A-G segments of the two seconds digits from their outputs
+pub fn decode_seconds(outputs: &[bool; 16]) -> [[bool; 7]; 2] {
+    [
+        [
+            outputs[2], outputs[3], outputs[6], outputs[5], outputs[4], outputs[1], outputs[0],
+        ],
+        [
+            outputs[10],
+            outputs[11],
+            outputs[14],
+            outputs[13],
+            outputs[15],
+            outputs[9],
+            outputs[8],
+        ],
+    ]
+}
+
+/// The decimal digit shown by the A-G segments, if they show one
+pub fn digit(segments: [bool; 7]) -> Option<u8> {
+    (0_u8..10_u8).find(|digit| self::segments(*digit) == segments)
+}
+
+/// What the time displays show, read back from their shift registers'
+/// outputs. Shown as `HH:MM:SS`, with unlit digits (and the colon) as spaces
+/// and anything lit that isn't a digit as `?`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct ShownTime {
+    /// The A-G segments of the hours and minutes digits, from the left
+    pub hours_minutes: [[bool; 7]; 4],
+    pub colon: bool,
+    pub seconds: [[bool; 7]; 2],
+}
+impl ShownTime {
+    /// From the hours and minutes outputs latched over (at least) one
+    /// multiplexing cycle, where later slots win, and the seconds outputs
+    pub fn decode<'a>(
+        hours_minutes: impl IntoIterator<Item = &'a [bool; 16]>,
+        seconds: &[bool; 16],
+    ) -> Self {
+        let mut shown = Self {
+            seconds: decode_seconds(seconds),
+            ..Self::default()
+        };
+        for outputs in hours_minutes {
+            match decode_hours_minutes(outputs) {
+                Some(Slot::Digit(position, segments)) => shown.hours_minutes[position] = segments,
+                Some(Slot::DecimalPoints(decimal_points)) => shown.colon = decimal_points[2],
+                None => (),
+            }
+        }
+        shown
+    }
+}
+impl fmt::Display for ShownTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let shown = |segments: [bool; 7]| match digit(segments) {
+            Some(digit) => (b'0' + digit) as char,
+            None if segments == [false; 7] => ' ',
+            None => '?',
+        };
+        let [hour_1, hour_2, minute_1, minute_2] = self.hours_minutes.map(shown);
+        let [second_1, second_2] = self.seconds.map(shown);
+        let colon = if self.colon { ':' } else { ' ' };
+        write!(
+            f,
+            "{hour_1}{hour_2}{colon}{minute_1}{minute_2}:{second_1}{second_2}"
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The digit shown by the hours and minutes outputs, and which digit is
+    /// selected (0 for the colon)
+    fn shown(outputs: &[bool; 16]) -> Option<(usize, Option<u8>)> {
+        match decode_hours_minutes(outputs)? {
+            Slot::DecimalPoints(_) => Some((0_usize, Some(BLANK))),
+            Slot::Digit(position, segments) => Some((position + 1_usize, digit(segments))),
+        }
+    }
+
+    #[test]
+    fn multiplexing() {
+        let mut multiplexer = Multiplexer::new();
+        let slots = (0..10)
+            .map(|_| shown(&multiplexer.next((1_u8, 2_u8), (3_u8, 4_u8))))
+            .collect::<Vec<_>>();
+        let cycle = [
+            Some((0_usize, Some(BLANK))),
+            Some((4_usize, Some(4_u8))),
+            Some((3_usize, Some(3_u8))),
+            Some((2_usize, Some(2_u8))),
+            Some((1_usize, Some(1_u8))),
+        ];
+        assert_eq!(slots[..5], cycle);
+        assert_eq!(slots[5..], cycle);
+    }
+
+    #[test]
+    fn colon() {
+        let outputs = Multiplexer::new().next((0_u8, 0_u8), (0_u8, 0_u8));
+        assert_eq!(outputs[5..9], [false, false, true, false]);
+        assert_eq!(
+            decode_hours_minutes(&outputs),
+            Some(Slot::DecimalPoints([false, false, true, false]))
+        );
+    }
+
+    #[test]
+    fn brightness() {
+        let mut multiplexer = Multiplexer::new();
+        multiplexer.set_brightness(2_u8);
+        let lit = (0..40)
+            .filter(|_| shown(&multiplexer.next((8_u8, 8_u8), (8_u8, 8_u8))).is_some())
+            .count();
+        assert_eq!(lit, 10_usize);
+
+        // Clamped
+        multiplexer.set_brightness(0_u8);
+        let lit = (0..40)
+            .filter(|_| shown(&multiplexer.next((8_u8, 8_u8), (8_u8, 8_u8))).is_some())
+            .count();
+        assert_eq!(lit, 5_usize);
+    }
+
+    #[test]
+    fn seconds() {
+        let outputs = seconds_outputs((1_u8, 7_u8));
+        // 1 is B and C
+        assert_eq!(
+            outputs[..8],
+            [false, false, false, true, false, false, true, false]
+        );
+        // 7 is A, B, and C
+        assert_eq!(
+            outputs[8..],
+            [false, false, true, true, false, false, true, false]
+        );
+        assert_eq!(segments(0xFF_u8), [false; 7]);
+
+        for tens in 0_u8..6_u8 {
+            for ones in 0_u8..10_u8 {
+                let [first, second] = decode_seconds(&seconds_outputs((tens, ones)));
+                assert_eq!((digit(first), digit(second)), (Some(tens), Some(ones)));
+            }
+        }
+        assert_eq!(digit(segments(BLANK)), None);
+    }
+
+    #[test]
+    fn shown_time() {
+        let mut multiplexer = Multiplexer::new();
+        let slots = (0..5)
+            .map(|_| multiplexer.next((0_u8, 7_u8), (4_u8, 5_u8)))
+            .collect::<Vec<_>>();
+        let seconds = seconds_outputs((0_u8, 9_u8));
+        assert_eq!(ShownTime::decode(&slots, &seconds).to_string(), "07:45:09");
+
+        // Only the colon and the last minute digit were latched
+        assert_eq!(
+            ShownTime::decode(&slots[..2], &seconds).to_string(),
+            "  : 5:09"
+        );
+        let mut garbled = seconds;
+        garbled[3] = !garbled[3];
+        assert_eq!(ShownTime::decode(&[], &garbled).to_string(), "     :?9");
+    }
+}
@@ -0,0 +1,102 @@
+//! Byte-addressable EEPROM storage. The ATmega328P has 1KB of it, rated for
+//! about 100,000 writes per cell.
+//!
+//! Everything that persists goes through the `Eeprom` trait so it can be
+//! tested against `MemoryEeprom` on the host.
+
+/// Settings live at the start of the EEPROM
+pub const SETTINGS_ADDRESS: u16 = 0_u16;
+pub const SETTINGS_REGION_SIZE: u16 = 64_u16;
+/// Journals for things that change often (see `history.rs`)
+pub const COUNTERS_ADDRESS: u16 = SETTINGS_ADDRESS + SETTINGS_REGION_SIZE;
+pub const COUNTERS_REGION_SIZE: u16 = 448_u16;
+pub const MISSED_ALARMS_ADDRESS: u16 = COUNTERS_ADDRESS + COUNTERS_REGION_SIZE;
+pub const MISSED_ALARMS_REGION_SIZE: u16 = 512_u16;
+
+pub trait Eeprom {
+    fn capacity(&self) -> u16;
+    fn read_byte(&self, address: u16) -> u8;
+    /// Writes are slow (several milliseconds) and wear out the cell
+    fn write_byte(&mut self, address: u16, byte: u8);
+
+    fn read(&self, address: u16, buffer: &mut [u8]) {
+        for (offset, byte) in buffer.iter_mut().enumerate() {
+            *byte = self.read_byte(address + offset as u16);
+        }
+    }
+
+    /// Write the bytes, skipping any that are already stored to save wear
+    fn update(&mut self, address: u16, bytes: &[u8]) {
+        for (offset, byte) in bytes.iter().enumerate() {
+            let address = address + offset as u16;
+            if self.read_byte(address) != *byte {
+                self.write_byte(address, *byte);
+            }
+        }
+    }
+}
+
+/// EEPROM backed by RAM, starting out erased (all `0xFF`)
+pub struct MemoryEeprom<const N: usize> {
+    pub bytes: [u8; N],
+    /// Number of bytes written, to check that wear is being avoided
+    pub writes: usize,
+    /// Writes left before simulating a power loss
+    power_budget: Option<usize>,
+    /// Set once a write was torn by a simulated power loss. Writes are ignored
+    /// until power is restored.
+    pub power_lost: bool,
+}
+impl<const N: usize> MemoryEeprom<N> {
+    pub fn new() -> Self {
+        Self {
+            bytes: [0xFF_u8; N],
+            writes: 0_usize,
+            power_budget: None,
+            power_lost: false,
+        }
+    }
+
+    /// Let `writes` more bytes be written, then tear the next one as though
+    /// power was lost partway through it
+    pub fn lose_power_after(&mut self, writes: usize) {
+        self.power_budget = Some(writes);
+    }
+
+    pub fn restore_power(&mut self) {
+        self.power_budget = None;
+        self.power_lost = false;
+    }
+}
+impl<const N: usize> Default for MemoryEeprom<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<const N: usize> Eeprom for MemoryEeprom<N> {
+    fn capacity(&self) -> u16 {
+        N as u16
+    }
+
+    fn read_byte(&self, address: u16) -> u8 {
+        self.bytes[address as usize]
+    }
+
+    fn write_byte(&mut self, address: u16, byte: u8) {
+        if self.power_lost {
+            return;
+        }
+        match self.power_budget {
+            Some(0_usize) => {
+                // The cell was erased but never programmed
+                self.bytes[address as usize] = 0xFF_u8;
+                self.power_lost = true;
+                return;
+            }
+            Some(writes) => self.power_budget = Some(writes - 1_usize),
+            None => (),
+        }
+        self.writes += 1_usize;
+        self.bytes[address as usize] = byte;
+    }
+}
@@ -0,0 +1,422 @@
+//! What the 16x2 character LCD should show, kept in memory so only the cells
+//! that changed are sent to it. Sending a character through the LCD's shift
+//! register takes a while, and clearing the LCD to redraw it flickers.
+//!
+//! Lines are written through `Line`, which `ufmt::uwrite!` (and `write!`) can
+//! format into:
+//! `uwrite!(framebuffer.line(1_u8), "{}:{}", hours, minutes)`
+//! Glyphs written into lines are loaded into the LCD's CGRAM as needed (see
+//! `glyph`).
+
+use core::{convert::Infallible, fmt};
+use ufmt_write::uWrite;
+
+use crate::{
+    glyph::{Cgram, Glyph, BIG_DIGITS},
+    hal::CharacterDisplay,
+};
+
+pub const COLUMNS: usize = 16_usize;
+pub const ROWS: usize = 2_usize;
+/// Unchanged cells between two changed ones are sent again if there are at
+/// most this many, as that's quicker than moving the cursor past them
+const MAX_GAP: usize = 2_usize;
+
+pub struct Framebuffer {
+    cells: [[u8; COLUMNS]; ROWS],
+    /// What the LCD is showing, if it's known
+    shown: [[u8; COLUMNS]; ROWS],
+    shown_known: bool,
+    cgram: Cgram,
+}
+impl Framebuffer {
+    /// A blank screen. What the LCD shows isn't known, so all of it is sent on
+    /// the first flush.
+    pub fn new() -> Self {
+        Self {
+            cells: [[b' '; COLUMNS]; ROWS],
+            shown: [[b' '; COLUMNS]; ROWS],
+            shown_known: false,
+            cgram: Cgram::new(),
+        }
+    }
+
+    /// Blank every cell
+    pub fn clear(&mut self) {
+        self.cells = [[b' '; COLUMNS]; ROWS];
+    }
+
+    /// Blank the row [0, 1] and write into it from the start
+    pub fn line(&mut self, row: u8) -> Line<'_> {
+        self.cells[row as usize % ROWS] = [b' '; COLUMNS];
+        self.line_at(0_u8, row)
+    }
+
+    /// Write into the row [0, 1] from the column [0, 15], leaving the rest
+    /// of it as it is
+    pub fn line_at(&mut self, column: u8, row: u8) -> Line<'_> {
+        Line {
+            cells: &mut self.cells,
+            cgram: &mut self.cgram,
+            row: row as usize % ROWS,
+            column: column as usize,
+        }
+    }
+
+    /// Write the time in big digits across both rows, from the column [0, 15].
+    /// It takes 15 columns: each digit is 3 wide with a blank column between
+    /// the hours' and the minutes' digits, and the colon in the middle.
+    pub fn big_time(&mut self, column: u8, hours: (u8, u8), minutes: (u8, u8), colon: bool) {
+        let colon = if colon { "." } else { " " };
+        for row in 0_u8..ROWS as u8 {
+            let mut line = self.line_at(column, row);
+            line.big_digit(hours.0, row);
+            line.print(" ");
+            line.big_digit(hours.1, row);
+            line.print(colon);
+            line.big_digit(minutes.0, row);
+            line.print(" ");
+            line.big_digit(minutes.1, row);
+        }
+    }
+
+    /// The glyph shown by the character code, if it's a custom character
+    pub fn glyph(&self, code: u8) -> Option<Glyph> {
+        self.cgram.glyph(code)
+    }
+
+    /// The row [0, 1] as it'll be shown after the next flush
+    pub fn row(&self, row: u8) -> &[u8; COLUMNS] {
+        &self.cells[row as usize % ROWS]
+    }
+
+    /// Forget what the LCD is showing, so it's all sent on the next flush
+    /// (e.g. after it's been cleared or reset)
+    pub fn invalidate(&mut self) {
+        self.shown_known = false;
+        self.cgram.invalidate();
+    }
+
+    fn changed(&self, row: usize, column: usize) -> bool {
+        !self.shown_known || self.cells[row][column] != self.shown[row][column]
+    }
+
+    /// Send the glyphs and cells that changed since the last flush, returning
+    /// how many cells were sent. A glyph replaced in a slot isn't on screen, so
+    /// it's not seen changing.
+    pub fn flush<L: CharacterDisplay>(&mut self, lcd: &mut L) -> usize {
+        for (code, bitmap) in self.cgram.take_pending() {
+            lcd.create_char(code, bitmap);
+        }
+        let mut sent = 0_usize;
+        for row in 0_usize..ROWS {
+            let mut column = 0_usize;
+            while column < COLUMNS {
+                if !self.changed(row, column) {
+                    column += 1_usize;
+                    continue;
+                }
+                // Carry on until there's a long enough run of unchanged cells
+                let start = column;
+                let mut end = column + 1_usize;
+                let mut next = end;
+                while next < COLUMNS {
+                    if self.changed(row, next) {
+                        end = next + 1_usize;
+                        next = end;
+                    } else if next - end < MAX_GAP {
+                        next += 1_usize;
+                    } else {
+                        break;
+                    }
+                }
+
+                let cells = &self.cells[row][start..end];
+                lcd.set_position(start as u8, row as u8);
+                // Only ASCII (and custom characters) are written into the cells
+                lcd.print(core::str::from_utf8(cells).unwrap_or_default());
+                self.shown[row][start..end].copy_from_slice(cells);
+                sent += end - start;
+                column = end;
+            }
+        }
+        self.shown_known = true;
+        sent
+    }
+}
+impl Default for Framebuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Writes into a row of a `Framebuffer`. Anything past the end of the row is
+/// cut off, and anything that isn't ASCII is shown as `?`.
+pub struct Line<'a> {
+    cells: &'a mut [[u8; COLUMNS]; ROWS],
+    cgram: &'a mut Cgram,
+    row: usize,
+    column: usize,
+}
+impl Line<'_> {
+    fn put(&mut self, code: u8) {
+        if let Some(cell) = self.cells[self.row].get_mut(self.column) {
+            *cell = code;
+        }
+        self.column += 1_usize;
+    }
+
+    pub fn print(&mut self, text: &str) {
+        for character in text.chars() {
+            // Control characters would be custom characters
+            self.put(if character.is_ascii() && !character.is_ascii_control() {
+                character as u8
+            } else {
+                b'?'
+            });
+        }
+    }
+
+    /// Write a custom character, or its ASCII look-alike if every slot is
+    /// already on screen
+    pub fn glyph(&mut self, glyph: Glyph) {
+        // This cell's about to be overwritten, so what's in it doesn't count
+        let (row, column) = (self.row, self.column);
+        let cells = &*self.cells;
+        let in_use = |code: u8| {
+            cells.iter().enumerate().any(|(cells_row, cells)| {
+                cells.iter().enumerate().any(|(cells_column, &cell)| {
+                    cell == code && (cells_row, cells_column) != (row, column)
+                })
+            })
+        };
+        let code = self.cgram.code(glyph, in_use).unwrap_or(glyph.fallback());
+        self.put(code);
+    }
+
+    /// Write the top (0) or bottom (1) row of a big digit [0, 9]
+    fn big_digit(&mut self, digit: u8, row: u8) {
+        for glyph in BIG_DIGITS[digit as usize % 10_usize][row as usize % ROWS] {
+            match glyph {
+                Some(glyph) => self.glyph(glyph),
+                None => self.put(b' '),
+            }
+        }
+    }
+}
+impl uWrite for Line<'_> {
+    type Error = Infallible;
+
+    fn write_str(&mut self, text: &str) -> Result<(), Infallible> {
+        self.print(text);
+        Ok(())
+    }
+}
+impl fmt::Write for Line<'_> {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        self.print(text);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        glyph::SLOTS,
+        hal::mock::{LcdCall, MockCharacterDisplay},
+    };
+    use core::fmt::Write;
+
+    fn flushed(framebuffer: &mut Framebuffer, lcd: &mut MockCharacterDisplay) -> Vec<LcdCall> {
+        lcd.calls.clear();
+        framebuffer.flush(lcd);
+        lcd.calls.clone()
+    }
+
+    fn print(text: &str) -> LcdCall {
+        LcdCall::Print(String::from(text))
+    }
+
+    #[test]
+    fn first_flush_sends_everything() {
+        let mut framebuffer = Framebuffer::new();
+        let mut lcd = MockCharacterDisplay::new();
+        framebuffer.line(0_u8).print("alarmed clock");
+        assert_eq!(
+            flushed(&mut framebuffer, &mut lcd),
+            [
+                LcdCall::SetPosition(0_u8, 0_u8),
+                print("alarmed clock   "),
+                LcdCall::SetPosition(0_u8, 1_u8),
+                print("                "),
+            ]
+        );
+        assert!(flushed(&mut framebuffer, &mut lcd).is_empty());
+
+        // Or after being invalidated
+        framebuffer.invalidate();
+        assert_eq!(framebuffer.flush(&mut lcd), COLUMNS * ROWS);
+        assert_eq!(lcd.line(0_u8), "alarmed clock   ");
+    }
+
+    #[test]
+    fn only_changes_are_sent() {
+        let mut framebuffer = Framebuffer::new();
+        let mut lcd = MockCharacterDisplay::new();
+        framebuffer.line(1_u8).print("12:34:56");
+        framebuffer.flush(&mut lcd);
+
+        framebuffer.line(1_u8).print("12:34:57");
+        assert_eq!(
+            flushed(&mut framebuffer, &mut lcd),
+            [LcdCall::SetPosition(7_u8, 1_u8), print("7")]
+        );
+        // Rewriting a line with what's already there is no change
+        framebuffer.line(1_u8).print("12:34:57");
+        assert_eq!(framebuffer.flush(&mut lcd), 0_usize);
+        assert_eq!(lcd.line(1_u8), "12:34:57        ");
+    }
+
+    #[test]
+    fn nearby_changes_are_sent_together() {
+        let mut framebuffer = Framebuffer::new();
+        let mut lcd = MockCharacterDisplay::new();
+        framebuffer.line(1_u8).print("12:59:59");
+        framebuffer.flush(&mut lcd);
+
+        // Two unchanged cells in between are sent again
+        framebuffer.line(1_u8).print("13:00:00");
+        assert_eq!(
+            flushed(&mut framebuffer, &mut lcd),
+            [LcdCall::SetPosition(1_u8, 1_u8), print("3:00:00")]
+        );
+        // But not three
+        framebuffer.line_at(0_u8, 0_u8).print("a");
+        framebuffer.line_at(4_u8, 0_u8).print("b");
+        assert_eq!(
+            flushed(&mut framebuffer, &mut lcd),
+            [
+                LcdCall::SetPosition(0_u8, 0_u8),
+                print("a"),
+                LcdCall::SetPosition(4_u8, 0_u8),
+                print("b"),
+            ]
+        );
+        assert_eq!(lcd.line(0_u8), "a   b           ");
+    }
+
+    #[test]
+    fn writing_lines() {
+        let mut framebuffer = Framebuffer::new();
+        framebuffer.line(0_u8).print("far too long for the screen");
+        assert_eq!(framebuffer.row(0_u8), b"far too long for");
+        // Lines are blanked first, unless written from a column
+        framebuffer.line(0_u8).print("short");
+        framebuffer.line_at(14_u8, 0_u8).print("é!");
+        assert_eq!(framebuffer.row(0_u8), b"short         ?!");
+
+        write!(framebuffer.line(1_u8), "{:02}:{:02}", 7_u8, 5_u8).unwrap();
+        uWrite::write_str(&mut framebuffer.line_at(5_u8, 1_u8), " AM").unwrap();
+        assert_eq!(framebuffer.row(1_u8), b"07:05 AM        ");
+
+        framebuffer.clear();
+        assert_eq!(framebuffer.row(1_u8), &[b' '; COLUMNS]);
+    }
+
+    #[test]
+    fn glyphs_are_loaded_before_shown() {
+        let mut framebuffer = Framebuffer::new();
+        let mut lcd = MockCharacterDisplay::new();
+        let mut line = framebuffer.line(0_u8);
+        line.glyph(Glyph::Bell);
+        line.print(" 07:00 ");
+        line.glyph(Glyph::Snooze);
+        line.glyph(Glyph::Bell);
+        assert_eq!(framebuffer.row(0_u8)[..10], *b"\x00 07:00 \x01\x00");
+        assert_eq!(framebuffer.glyph(1_u8), Some(Glyph::Snooze));
+        let calls = flushed(&mut framebuffer, &mut lcd);
+        assert_eq!(
+            calls[..3],
+            [
+                LcdCall::CreateChar(0_u8, Glyph::Bell.bitmap()),
+                LcdCall::CreateChar(1_u8, Glyph::Snooze.bitmap()),
+                LcdCall::SetPosition(0_u8, 0_u8),
+            ]
+        );
+
+        // Glyphs stay loaded while they're shown
+        framebuffer.line(0_u8).glyph(Glyph::Bell);
+        assert!(flushed(&mut framebuffer, &mut lcd)
+            .iter()
+            .all(|call| !matches!(call, LcdCall::CreateChar(..))));
+
+        // And are all loaded again when the LCD's forgotten them
+        framebuffer.invalidate();
+        let calls = flushed(&mut framebuffer, &mut lcd);
+        assert_eq!(
+            calls
+                .iter()
+                .filter(|call| matches!(call, LcdCall::CreateChar(..)))
+                .count(),
+            2_usize
+        );
+        assert_eq!(lcd.cgram[1], Glyph::Snooze.bitmap());
+    }
+
+    #[test]
+    fn slots_are_reused_once_not_shown() {
+        let mut framebuffer = Framebuffer::new();
+        let arrows = [
+            Glyph::ArrowUp,
+            Glyph::ArrowDown,
+            Glyph::ArrowLeft,
+            Glyph::ArrowRight,
+        ];
+        let mut line = framebuffer.line(0_u8);
+        for glyph in &Glyph::ALL[..SLOTS] {
+            line.glyph(*glyph);
+        }
+        // Every slot is on screen
+        for glyph in arrows {
+            line.glyph(glyph);
+        }
+        assert_eq!(framebuffer.row(0_u8)[SLOTS..SLOTS + 4], *b"^v<>");
+
+        // Until the line's written again
+        let mut line = framebuffer.line(0_u8);
+        for glyph in arrows {
+            line.glyph(glyph);
+        }
+        assert_eq!(framebuffer.row(0_u8)[..4], [0_u8, 1_u8, 2_u8, 3_u8]);
+        assert_eq!(framebuffer.glyph(0_u8), Some(Glyph::ArrowUp));
+    }
+
+    #[test]
+    fn big_time() {
+        let mut framebuffer = Framebuffer::new();
+        let mut lcd = MockCharacterDisplay::new();
+        framebuffer.big_time(0_u8, (1_u8, 2_u8), (3_u8, 4_u8), true);
+        framebuffer.line_at(15_u8, 0_u8).glyph(Glyph::Bell);
+        framebuffer.flush(&mut lcd);
+
+        // Drawn with the glyphs' bitmaps, the digits read as 12.34
+        let shown = |row: u8| -> String {
+            lcd.line(row)
+                .bytes()
+                .map(|code| match code {
+                    0_u8..=7_u8 => match Glyph::ALL
+                        .into_iter()
+                        .find(|glyph| glyph.bitmap() == lcd.cgram[code as usize])
+                    {
+                        Some(glyph) => glyph.fallback() as char,
+                        None => '?',
+                    },
+                    _ => code as char,
+                })
+                .collect()
+        };
+        assert_eq!(shown(0_u8), "\"#  ==#.==# #_#!");
+        assert_eq!(shown(1_u8), "_#_ #__.__#   # ");
+    }
+}
@@ -0,0 +1,256 @@
+//! Custom characters for the LCD, which has room for 8 at a time in its CGRAM.
+//! `Framebuffer` loads them into slots as lines use them, reusing slots that
+//! nothing on screen shows anymore; when all 8 are on screen, a glyph falls
+//! back to an ASCII look-alike.
+
+/// A 5x8 custom character
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Glyph {
+    Bell,
+    /// "Zz"
+    Snooze,
+    /// From 0 (empty) to 3 (full)
+    Battery(u8),
+    Sun,
+    Moon,
+    ArrowUp,
+    ArrowDown,
+    ArrowLeft,
+    ArrowRight,
+    /// The pieces of big digits, which are 3 columns wide and both rows tall:
+    /// the top 3 pixel rows filled
+    BigUpper,
+    /// The bottom 3 pixel rows filled
+    BigLower,
+    /// The top and bottom 3 pixel rows filled
+    BigBoth,
+    /// Every pixel filled
+    BigFull,
+}
+impl Glyph {
+    /// Every glyph, e.g. to recognize one from its bitmap
+    pub const ALL: [Glyph; 16] = [
+        Glyph::Bell,
+        Glyph::Snooze,
+        Glyph::Battery(0_u8),
+        Glyph::Battery(1_u8),
+        Glyph::Battery(2_u8),
+        Glyph::Battery(3_u8),
+        Glyph::Sun,
+        Glyph::Moon,
+        Glyph::ArrowUp,
+        Glyph::ArrowDown,
+        Glyph::ArrowLeft,
+        Glyph::ArrowRight,
+        Glyph::BigUpper,
+        Glyph::BigLower,
+        Glyph::BigBoth,
+        Glyph::BigFull,
+    ];
+
+    /// The pixel rows from the top, with the leftmost pixel in bit 4
+    pub fn bitmap(self) -> [u8; 8] {
+        match self {
+            Glyph::Bell => [
+                0b00100, 0b01110, 0b01110, 0b01110, 0b11111, 0b00000, 0b00100, 0b00000,
+            ],
+            Glyph::Snooze => [
+                0b11110, 0b00100, 0b01000, 0b11110, 0b00000, 0b00111, 0b00010, 0b00111,
+            ],
+            Glyph::Battery(level) => {
+                // Filled from the bottom, two pixel rows a level
+                let filled = level.min(3_u8) as usize * 2_usize;
+                let mut bitmap = [
+                    0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b11111,
+                ];
+                for row in &mut bitmap[7_usize - filled..7_usize] {
+                    *row = 0b11111_u8;
+                }
+                bitmap
+            }
+            Glyph::Sun => [
+                0b00100, 0b10101, 0b01110, 0b11111, 0b01110, 0b10101, 0b00100, 0b00000,
+            ],
+            Glyph::Moon => [
+                0b01110, 0b11100, 0b11000, 0b11000, 0b11000, 0b11100, 0b01110, 0b00000,
+            ],
+            Glyph::ArrowUp => [
+                0b00100, 0b01110, 0b10101, 0b00100, 0b00100, 0b00100, 0b00100, 0b00000,
+            ],
+            Glyph::ArrowDown => [
+                0b00100, 0b00100, 0b00100, 0b00100, 0b10101, 0b01110, 0b00100, 0b00000,
+            ],
+            Glyph::ArrowLeft => [
+                0b00000, 0b00100, 0b01000, 0b11111, 0b01000, 0b00100, 0b00000, 0b00000,
+            ],
+            Glyph::ArrowRight => [
+                0b00000, 0b00100, 0b00010, 0b11111, 0b00010, 0b00100, 0b00000, 0b00000,
+            ],
+            Glyph::BigUpper => [
+                0b11111, 0b11111, 0b11111, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000,
+            ],
+            Glyph::BigLower => [
+                0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b11111, 0b11111, 0b11111,
+            ],
+            Glyph::BigBoth => [
+                0b11111, 0b11111, 0b11111, 0b00000, 0b00000, 0b11111, 0b11111, 0b11111,
+            ],
+            Glyph::BigFull => [0b11111; 8],
+        }
+    }
+
+    /// What's shown instead when there's no free slot for it
+    pub fn fallback(self) -> u8 {
+        match self {
+            Glyph::Bell => b'!',
+            Glyph::Snooze => b'z',
+            Glyph::Battery(level) => b'0' + level.min(3_u8),
+            Glyph::Sun => b'*',
+            Glyph::Moon => b'(',
+            Glyph::ArrowUp => b'^',
+            Glyph::ArrowDown => b'v',
+            Glyph::ArrowLeft => b'<',
+            Glyph::ArrowRight => b'>',
+            Glyph::BigUpper => b'"',
+            Glyph::BigLower => b'_',
+            Glyph::BigBoth => b'=',
+            Glyph::BigFull => b'#',
+        }
+    }
+}
+
+/// How many custom characters the LCD holds at once
+pub const SLOTS: usize = 8_usize;
+
+/// Which glyph is in each CGRAM slot, and which slots still have to be sent
+/// to the LCD. A slot's character code is its index.
+pub struct Cgram {
+    slots: [Option<Glyph>; SLOTS],
+    /// A bit for each slot that was changed since the last flush
+    pending: u8,
+}
+impl Cgram {
+    pub fn new() -> Self {
+        Self {
+            slots: [None; SLOTS],
+            pending: 0_u8,
+        }
+    }
+
+    /// The character code of a glyph, loading it into a slot if it isn't in
+    /// one already. Slots that `in_use` says are shown are kept, and if that's
+    /// all of them, there's no code.
+    pub fn code(&mut self, glyph: Glyph, in_use: impl Fn(u8) -> bool) -> Option<u8> {
+        if let Some(slot) = self.slots.iter().position(|&slot| slot == Some(glyph)) {
+            return Some(slot as u8);
+        }
+        // Empty slots first, so glyphs that might be shown again are kept
+        let slot = self
+            .slots
+            .iter()
+            .position(Option::is_none)
+            .or_else(|| (0_u8..SLOTS as u8).position(|code| !in_use(code)))?;
+        self.slots[slot] = Some(glyph);
+        self.pending |= 1_u8 << slot;
+        Some(slot as u8)
+    }
+
+    /// The glyph in a slot
+    pub fn glyph(&self, code: u8) -> Option<Glyph> {
+        *self.slots.get(code as usize)?
+    }
+
+    /// Take the slots that have to be sent to the LCD, with their bitmaps
+    pub fn take_pending(&mut self) -> impl Iterator<Item = (u8, [u8; 8])> + '_ {
+        let pending = core::mem::take(&mut self.pending);
+        self.slots
+            .iter()
+            .enumerate()
+            .filter(move |(slot, _)| pending >> slot & 1_u8 == 1_u8)
+            .filter_map(|(slot, glyph)| Some((slot as u8, glyph.as_ref()?.bitmap())))
+    }
+
+    /// Send every loaded slot again, e.g. after the LCD's been reset
+    pub fn invalidate(&mut self) {
+        self.pending = self
+            .slots
+            .iter()
+            .enumerate()
+            .filter(|(_, glyph)| glyph.is_some())
+            .fold(0_u8, |pending, (slot, _)| pending | 1_u8 << slot);
+    }
+}
+impl Default for Cgram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Each digit as its top and bottom rows of 3 cells, with `None` blank
+pub(crate) const BIG_DIGITS: [[[Option<Glyph>; 3]; 2]; 10] = {
+    const U: Option<Glyph> = Some(Glyph::BigUpper);
+    const L: Option<Glyph> = Some(Glyph::BigLower);
+    const B: Option<Glyph> = Some(Glyph::BigBoth);
+    const F: Option<Glyph> = Some(Glyph::BigFull);
+    const X: Option<Glyph> = None;
+    [
+        [[F, U, F], [F, L, F]],
+        [[U, F, X], [L, F, L]],
+        [[B, B, F], [F, L, L]],
+        [[B, B, F], [L, L, F]],
+        [[F, L, F], [X, X, F]],
+        [[F, B, B], [L, L, F]],
+        [[F, B, B], [F, L, F]],
+        [[U, U, F], [X, X, F]],
+        [[F, B, F], [F, L, F]],
+        [[F, B, F], [L, L, F]],
+    ]
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bitmaps_fit() {
+        for glyph in Glyph::ALL {
+            assert!(
+                glyph.bitmap().iter().all(|row| *row < 1_u8 << 5),
+                "{glyph:?}"
+            );
+            assert!(glyph.fallback().is_ascii_graphic(), "{glyph:?}");
+        }
+        assert_eq!(Glyph::Battery(0_u8).bitmap()[6], 0b10001_u8);
+        assert_eq!(Glyph::Battery(3_u8).bitmap()[1], 0b11111_u8);
+    }
+
+    #[test]
+    fn allocates_slots() {
+        let mut cgram = Cgram::new();
+        assert_eq!(cgram.code(Glyph::Bell, |_| true), Some(0_u8));
+        assert_eq!(cgram.code(Glyph::Moon, |_| true), Some(1_u8));
+        assert_eq!(cgram.code(Glyph::Bell, |_| true), Some(0_u8));
+        assert_eq!(
+            cgram.take_pending().collect::<Vec<_>>(),
+            [(0_u8, Glyph::Bell.bitmap()), (1_u8, Glyph::Moon.bitmap())]
+        );
+        assert_eq!(cgram.take_pending().count(), 0_usize);
+
+        // The batteries, the sun and a down arrow
+        for glyph in Glyph::ALL[2..7].iter().chain([&Glyph::ArrowDown]) {
+            cgram.code(*glyph, |_| true).unwrap();
+        }
+        assert_eq!(cgram.take_pending().count(), 6_usize);
+        // Full, so only a slot that isn't shown is reused
+        assert_eq!(cgram.code(Glyph::ArrowUp, |_| true), None);
+        assert_eq!(cgram.code(Glyph::ArrowUp, |code| code != 1_u8), Some(1_u8));
+        assert_eq!(cgram.glyph(1_u8), Some(Glyph::ArrowUp));
+        assert_eq!(
+            cgram.take_pending().collect::<Vec<_>>(),
+            [(1_u8, Glyph::ArrowUp.bitmap())]
+        );
+
+        cgram.invalidate();
+        assert_eq!(cgram.take_pending().count(), SLOTS);
+    }
+}
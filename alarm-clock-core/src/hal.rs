@@ -0,0 +1,82 @@
+//! The hardware the clock's logic drives, one trait per subsystem. The firmware
+//! implements these over its pins, shift registers, and the RTC; `mock` has
+//! implementations that record every call for tests.
+
+use crate::time::Time;
+
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+
+/// A set of seven segment digits behind shift registers, given every output at
+/// once (see `display` for what each one drives)
+pub trait SegmentDisplay {
+    fn write(&mut self, outputs: [bool; 16]);
+}
+
+/// An 8-bit output port that's written all at once, like the AVR's `PORTx`
+/// registers, for pins that have to change together or quickly
+pub trait OutputPort {
+    fn read(&self) -> u8;
+    fn write(&mut self, bits: u8);
+}
+
+/// Where the time comes from, i.e. the realtime clock
+pub trait ClockSource {
+    /// The current time, or `None` if it couldn't be read
+    fn read_time(&mut self) -> Option<Time>;
+    fn set_time(&mut self, time: &Time);
+    /// Whether its backup battery is running low
+    fn battery_low(&mut self) -> bool;
+}
+
+/// The rest of the board, as far as the shell and host tools are concerned
+pub trait Board {
+    /// Milliseconds since boot
+    fn millis(&self) -> u32;
+    /// Bytes the serial port couldn't keep up with since boot
+    fn serial_dropped(&self) -> u16;
+    /// Set the hours and minutes display's brightness from [1, 8]
+    fn set_brightness(&mut self, brightness: u8);
+}
+
+/// The buzzer
+pub trait ToneOutput {
+    /// Play a square wave of the given frequency (in Hz) until changed, or be
+    /// silent for `None`
+    fn set_tone(&mut self, frequency: Option<u16>);
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Direction {
+    Clockwise,
+    CounterClockwise,
+}
+
+/// Something done with the controls
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InputEvent {
+    /// The rotary encoder was turned by one detent
+    Turn(Direction),
+    /// The rotary encoder's button was pressed
+    Push,
+    Snooze,
+}
+
+/// The rotary encoder and snooze button
+pub trait InputSource {
+    /// The next thing the user did, oldest first, or `None` once caught up
+    fn next_event(&mut self) -> Option<InputEvent>;
+}
+
+/// The 16x2 character LCD
+pub trait CharacterDisplay {
+    fn clear(&mut self);
+    /// Move the cursor to the column [0, 15] of the row [0, 1]
+    fn set_position(&mut self, column: u8, row: u8);
+    /// Print ASCII text at the cursor, moving it along. The codes [0, 7] are
+    /// the custom characters.
+    fn print(&mut self, text: &str);
+    /// Load the custom character [0, 7] from its 8 pixel rows, top first with
+    /// the leftmost pixel in bit 4. The cursor has to be set again afterwards.
+    fn create_char(&mut self, code: u8, bitmap: [u8; 8]);
+}
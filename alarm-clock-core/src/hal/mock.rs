@@ -0,0 +1,423 @@
+//! Implementations of the hardware traits that record every call, so tests can
+//! check exactly what the logic did to the hardware
+
+extern crate std;
+
+use core::{cell::RefCell, convert::Infallible};
+use embedded_hal::{blocking::spi, digital::v2::OutputPin};
+use std::{collections::VecDeque, rc::Rc, string::String, vec::Vec};
+
+use super::{
+    Board, CharacterDisplay, ClockSource, InputEvent, InputSource, OutputPort, SegmentDisplay,
+    ToneOutput,
+};
+use crate::time::Time;
+
+#[derive(Default)]
+pub struct MockSegmentDisplay {
+    /// Every write, oldest first
+    pub writes: Vec<[bool; 16]>,
+}
+impl MockSegmentDisplay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// What's being shown now
+    pub fn outputs(&self) -> Option<[bool; 16]> {
+        self.writes.last().copied()
+    }
+}
+impl SegmentDisplay for MockSegmentDisplay {
+    fn write(&mut self, outputs: [bool; 16]) {
+        self.writes.push(outputs);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ClockCall {
+    ReadTime,
+    SetTime(Time),
+    BatteryLow,
+}
+
+/// A clock that stays at whatever time it was given, failing to be read while
+/// `time` is `None`
+#[derive(Default)]
+pub struct MockClock {
+    pub time: Option<Time>,
+    pub battery_low: bool,
+    pub calls: Vec<ClockCall>,
+}
+impl MockClock {
+    pub fn new(time: Time) -> Self {
+        Self {
+            time: Some(time),
+            battery_low: false,
+            calls: Vec::new(),
+        }
+    }
+}
+impl ClockSource for MockClock {
+    fn read_time(&mut self) -> Option<Time> {
+        self.calls.push(ClockCall::ReadTime);
+        self.time
+    }
+
+    fn set_time(&mut self, time: &Time) {
+        self.calls.push(ClockCall::SetTime(*time));
+        self.time = Some(*time);
+    }
+
+    fn battery_low(&mut self) -> bool {
+        self.calls.push(ClockCall::BatteryLow);
+        self.battery_low
+    }
+}
+
+#[derive(Default)]
+pub struct MockBoard {
+    pub millis: u32,
+    pub serial_dropped: u16,
+    /// Every brightness set, oldest first
+    pub brightness: Vec<u8>,
+}
+impl MockBoard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+impl Board for MockBoard {
+    fn millis(&self) -> u32 {
+        self.millis
+    }
+
+    fn serial_dropped(&self) -> u16 {
+        self.serial_dropped
+    }
+
+    fn set_brightness(&mut self, brightness: u8) {
+        self.brightness.push(brightness);
+    }
+}
+
+#[derive(Default)]
+pub struct MockTone {
+    /// Every frequency set, oldest first
+    pub tones: Vec<Option<u16>>,
+}
+impl MockTone {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// What's sounding now
+    pub fn tone(&self) -> Option<u16> {
+        self.tones.last().copied().flatten()
+    }
+}
+impl ToneOutput for MockTone {
+    fn set_tone(&mut self, frequency: Option<u16>) {
+        self.tones.push(frequency);
+    }
+}
+
+/// Gives back the events pushed to it, in order
+#[derive(Default)]
+pub struct MockInputs {
+    pub events: VecDeque<InputEvent>,
+    /// Number of times `next_event` was called
+    pub polls: usize,
+}
+impl MockInputs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, event: InputEvent) {
+        self.events.push_back(event);
+    }
+}
+impl InputSource for MockInputs {
+    fn next_event(&mut self) -> Option<InputEvent> {
+        self.polls += 1_usize;
+        self.events.pop_front()
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum LcdCall {
+    Clear,
+    SetPosition(u8, u8),
+    Print(String),
+    CreateChar(u8, [u8; 8]),
+}
+
+/// Keeps what a real 16x2 LCD would be showing alongside the calls made to it
+pub struct MockCharacterDisplay {
+    pub calls: Vec<LcdCall>,
+    screen: [[u8; 16]; 2],
+    cursor: (u8, u8),
+    /// The custom characters' bitmaps
+    pub cgram: [[u8; 8]; 8],
+}
+impl MockCharacterDisplay {
+    pub fn new() -> Self {
+        Self {
+            calls: Vec::new(),
+            screen: [[b' '; 16]; 2],
+            cursor: (0_u8, 0_u8),
+            cgram: [[0_u8; 8]; 8],
+        }
+    }
+
+    /// The row [0, 1] as shown, with trailing blanks
+    pub fn line(&self, row: u8) -> String {
+        self.screen[row as usize]
+            .iter()
+            .map(|&c| c as char)
+            .collect()
+    }
+}
+impl Default for MockCharacterDisplay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl CharacterDisplay for MockCharacterDisplay {
+    fn clear(&mut self) {
+        self.calls.push(LcdCall::Clear);
+        self.screen = [[b' '; 16]; 2];
+        self.cursor = (0_u8, 0_u8);
+    }
+
+    fn set_position(&mut self, column: u8, row: u8) {
+        self.calls.push(LcdCall::SetPosition(column, row));
+        self.cursor = (column, row);
+    }
+
+    fn print(&mut self, text: &str) {
+        self.calls.push(LcdCall::Print(String::from(text)));
+        for byte in text.bytes() {
+            let (column, row) = self.cursor;
+            // Past the end of the line is off screen
+            if let Some(cell) = self.screen[(row & 1_u8) as usize].get_mut(column as usize) {
+                *cell = byte;
+            }
+            self.cursor.0 = column.saturating_add(1_u8);
+        }
+    }
+
+    fn create_char(&mut self, code: u8, bitmap: [u8; 8]) {
+        self.calls.push(LcdCall::CreateChar(code, bitmap));
+        self.cgram[(code & 7_u8) as usize] = bitmap;
+    }
+}
+
+/// The pins a shift register is driven through
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ShiftRegisterPin {
+    SerialInput,
+    Clock,
+    Latch,
+    /// The output enable of the register with this index, active low
+    OutputEnable(usize),
+}
+
+struct Tpic6595<const N: usize> {
+    serial_input: bool,
+    clock: bool,
+    latch: bool,
+    /// Each register's output enable, high turning its outputs off
+    output_disabled: Vec<bool>,
+    /// Shifted in but not yet latched, with the last bit shifted in first
+    shifted: [bool; N],
+    /// Every time the outputs were latched, oldest first
+    latched: Vec<[bool; N]>,
+}
+
+/// `N` outputs' worth of daisy-chained TPIC6595 shift registers, following
+/// the pins from `pins`. The serial input is shifted in on the clock's rising
+/// edge and copied to the outputs on the latch's rising edge. Each register's
+/// output enable is tied low unless its pin from `output_enable` is used. The
+/// clear pins are tied off on the board, so they aren't modelled.
+#[derive(Clone)]
+pub struct MockShiftRegister<const N: usize>(Rc<RefCell<Tpic6595<N>>>);
+impl<const N: usize> MockShiftRegister<N> {
+    pub fn new() -> Self {
+        Self(Rc::new(RefCell::new(Tpic6595 {
+            serial_input: false,
+            clock: false,
+            latch: false,
+            output_disabled: std::vec![false; N.div_ceil(8_usize)],
+            shifted: [false; N],
+            latched: Vec::new(),
+        })))
+    }
+
+    /// The serial input, clock, and latch pins
+    pub fn pins(&self) -> [MockPin<N>; 3] {
+        [
+            ShiftRegisterPin::SerialInput,
+            ShiftRegisterPin::Clock,
+            ShiftRegisterPin::Latch,
+        ]
+        .map(|pin| MockPin {
+            register: self.clone(),
+            pin,
+        })
+    }
+
+    /// The output enable pin of the register with this index, first register
+    /// first
+    pub fn output_enable(&self, register: usize) -> MockPin<N> {
+        MockPin {
+            register: self.clone(),
+            pin: ShiftRegisterPin::OutputEnable(register),
+        }
+    }
+
+    /// What the outputs are driven to, first output of the first register
+    /// first. Outputs of disabled registers are off.
+    pub fn outputs(&self) -> [bool; N] {
+        let register = self.0.borrow();
+        let mut outputs = register.latched.last().copied().unwrap_or([false; N]);
+        for (output, state) in outputs.iter_mut().enumerate() {
+            *state &= !register.output_disabled[output / 8_usize];
+        }
+        outputs
+    }
+
+    /// Every time the outputs were latched, oldest first
+    pub fn latched(&self) -> Vec<[bool; N]> {
+        self.0.borrow().latched.clone()
+    }
+
+    fn set_pin(&self, pin: ShiftRegisterPin, high: bool) {
+        let register = &mut *self.0.borrow_mut();
+        match pin {
+            ShiftRegisterPin::SerialInput => register.serial_input = high,
+            ShiftRegisterPin::Clock => {
+                if high && !register.clock {
+                    register.shifted.rotate_right(1_usize);
+                    register.shifted[0] = register.serial_input;
+                }
+                register.clock = high;
+            }
+            ShiftRegisterPin::Latch => {
+                if high && !register.latch {
+                    let shifted = register.shifted;
+                    register.latched.push(shifted);
+                }
+                register.latch = high;
+            }
+            ShiftRegisterPin::OutputEnable(index) => register.output_disabled[index] = high,
+        }
+    }
+}
+impl<const N: usize> Default for MockShiftRegister<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// One of the pins of a `MockShiftRegister`
+pub struct MockPin<const N: usize> {
+    register: MockShiftRegister<N>,
+    pin: ShiftRegisterPin,
+}
+impl<const N: usize> OutputPin for MockPin<N> {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        self.register.set_pin(self.pin, false);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        self.register.set_pin(self.pin, true);
+        Ok(())
+    }
+}
+
+/// An SPI bus (mode 0, MSB first) wired to the serial input and clock of a
+/// `MockShiftRegister`
+pub struct MockSpi<const N: usize> {
+    register: MockShiftRegister<N>,
+}
+impl<const N: usize> MockSpi<N> {
+    pub fn new(register: &MockShiftRegister<N>) -> Self {
+        Self {
+            register: register.clone(),
+        }
+    }
+}
+impl<const N: usize> spi::Write<u8> for MockSpi<N> {
+    type Error = Infallible;
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Infallible> {
+        for word in words {
+            for bit in (0_u8..8_u8).rev() {
+                self.register
+                    .set_pin(ShiftRegisterPin::SerialInput, word >> bit & 1_u8 == 1_u8);
+                self.register.set_pin(ShiftRegisterPin::Clock, true);
+                self.register.set_pin(ShiftRegisterPin::Clock, false);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An output port with a `MockShiftRegister`'s pins on some of its bits. It
+/// panics if the serial input changes in the same write the clock rises in,
+/// as it'd break the setup or hold time.
+pub struct MockPort<const N: usize> {
+    register: MockShiftRegister<N>,
+    masks: [(u8, ShiftRegisterPin); 3],
+    /// What the port is driven to
+    pub bits: u8,
+    /// Number of times the port was written
+    pub writes: usize,
+}
+impl<const N: usize> MockPort<N> {
+    pub fn new(
+        register: &MockShiftRegister<N>,
+        serial_input_mask: u8,
+        clock_mask: u8,
+        latch_mask: u8,
+    ) -> Self {
+        Self {
+            register: register.clone(),
+            masks: [
+                (serial_input_mask, ShiftRegisterPin::SerialInput),
+                (clock_mask, ShiftRegisterPin::Clock),
+                (latch_mask, ShiftRegisterPin::Latch),
+            ],
+            bits: 0_u8,
+            writes: 0_usize,
+        }
+    }
+}
+impl<const N: usize> OutputPort for MockPort<N> {
+    fn read(&self) -> u8 {
+        self.bits
+    }
+
+    fn write(&mut self, bits: u8) {
+        let changed = self.bits ^ bits;
+        let [(serial_input_mask, _), (clock_mask, _), _] = self.masks;
+        assert!(
+            changed & serial_input_mask == 0_u8
+                || changed & clock_mask == 0_u8
+                || bits & clock_mask == 0_u8,
+            "the serial input changed as the clock rose"
+        );
+        self.bits = bits;
+        self.writes += 1_usize;
+        for (mask, pin) in self.masks {
+            if changed & mask != 0_u8 {
+                self.register.set_pin(pin, bits & mask != 0_u8);
+            }
+        }
+    }
+}
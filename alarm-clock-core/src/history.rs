@@ -0,0 +1,298 @@
+//! Frequently changing state that is kept across resets: a few counters and a
+//! log of missed alarms. Both are wear-leveled through journals (see
+//! `journal.rs`) as they are written far more often than the settings.
+
+use crate::{
+    eeprom::{
+        Eeprom, COUNTERS_ADDRESS, COUNTERS_REGION_SIZE, MISSED_ALARMS_ADDRESS,
+        MISSED_ALARMS_REGION_SIZE,
+    },
+    journal::Journal,
+    state::MissedAlarm,
+    time::Time,
+};
+
+/// Boots, snoozes, then the last sync time
+const COUNTERS_LENGTH: usize = 10_usize;
+/// Year, month, day, hours, minutes, snoozes
+const MISSED_ALARM_LENGTH: usize = 6_usize;
+/// Stored in place of the year when the clock was never synced
+const NEVER: u8 = 0xFF_u8;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Counters {
+    pub boots: u16,
+    /// Snoozes over the lifetime of the clock
+    pub snoozes: u16,
+    /// When the time was last set from outside (i.e. not by the RTC itself)
+    pub last_sync: Option<Time>,
+}
+impl Counters {
+    fn encode(&self) -> [u8; COUNTERS_LENGTH] {
+        let boots = self.boots.to_le_bytes();
+        let snoozes = self.snoozes.to_le_bytes();
+        let sync = match self.last_sync {
+            Some(time) => encode_time(&time),
+            None => [NEVER; 6],
+        };
+        [
+            boots[0], boots[1], snoozes[0], snoozes[1], sync[0], sync[1], sync[2], sync[3],
+            sync[4], sync[5],
+        ]
+    }
+
+    fn decode(record: &[u8; COUNTERS_LENGTH]) -> Self {
+        Self {
+            boots: u16::from_le_bytes([record[0], record[1]]),
+            snoozes: u16::from_le_bytes([record[2], record[3]]),
+            last_sync: match record[4] {
+                NEVER => None,
+                _ => Some(decode_time(&record[4..])),
+            },
+        }
+    }
+}
+
+fn encode_time(time: &Time) -> [u8; 6] {
+    [
+        time.year,
+        time.month,
+        time.day,
+        time.hours,
+        time.minutes,
+        time.seconds,
+    ]
+}
+
+fn decode_time(bytes: &[u8]) -> Time {
+    Time {
+        year: bytes[0],
+        month: bytes[1],
+        day: bytes[2],
+        hours: bytes[3],
+        minutes: bytes[4],
+        seconds: bytes[5],
+        // Not worth storing
+        day_of_week: 0_u8,
+    }
+}
+
+pub struct History {
+    counters: Counters,
+    counters_journal: Journal<COUNTERS_LENGTH>,
+    missed_alarms: Journal<MISSED_ALARM_LENGTH>,
+}
+impl History {
+    pub fn load(eeprom: &impl Eeprom) -> Self {
+        let counters_journal = Journal::new(eeprom, COUNTERS_ADDRESS, COUNTERS_REGION_SIZE);
+        let missed_alarms = Journal::new(eeprom, MISSED_ALARMS_ADDRESS, MISSED_ALARMS_REGION_SIZE);
+
+        let mut record = [0_u8; COUNTERS_LENGTH];
+        let counters = match counters_journal.latest(eeprom, &mut record) {
+            true => Counters::decode(&record),
+            // Nothing saved yet
+            false => Counters {
+                boots: 0_u16,
+                snoozes: 0_u16,
+                last_sync: None,
+            },
+        };
+
+        Self {
+            counters,
+            counters_journal,
+            missed_alarms,
+        }
+    }
+
+    pub fn counters(&self) -> &Counters {
+        &self.counters
+    }
+
+    pub fn record_boot(&mut self, eeprom: &mut impl Eeprom) {
+        self.counters.boots = self.counters.boots.wrapping_add(1_u16);
+        self.save_counters(eeprom);
+    }
+
+    pub fn record_snooze(&mut self, eeprom: &mut impl Eeprom) {
+        self.counters.snoozes = self.counters.snoozes.wrapping_add(1_u16);
+        self.save_counters(eeprom);
+    }
+
+    pub fn record_sync(&mut self, eeprom: &mut impl Eeprom, time: &Time) {
+        self.counters.last_sync = Some(*time);
+        self.save_counters(eeprom);
+    }
+
+    pub fn record_missed_alarm(&mut self, eeprom: &mut impl Eeprom, missed_alarm: &MissedAlarm) {
+        let time = encode_time(&missed_alarm.time);
+        self.missed_alarms.append(
+            eeprom,
+            &[
+                time[0],
+                time[1],
+                time[2],
+                time[3],
+                time[4],
+                missed_alarm.snoozes,
+            ],
+        );
+    }
+
+    /// The `nth` most recent missed alarm, where 0 is the most recent
+    pub fn missed_alarm(&self, eeprom: &impl Eeprom, nth: u16) -> Option<MissedAlarm> {
+        let mut record = [0_u8; MISSED_ALARM_LENGTH];
+        if !self.missed_alarms.recent(eeprom, nth, &mut record) {
+            return None;
+        }
+        Some(MissedAlarm {
+            time: decode_time(&[record[0], record[1], record[2], record[3], record[4], 0_u8]),
+            snoozes: record[5],
+        })
+    }
+
+    fn save_counters(&mut self, eeprom: &mut impl Eeprom) {
+        self.counters_journal
+            .append(eeprom, &self.counters.encode());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eeprom::MemoryEeprom;
+
+    type TestEeprom = MemoryEeprom<1024>;
+
+    const SYNC: Time = Time {
+        hours: 12_u8,
+        minutes: 34_u8,
+        seconds: 56_u8,
+        day: 29_u8,
+        day_of_week: 0_u8,
+        month: 2_u8,
+        year: 24_u8,
+    };
+
+    fn missed_alarm(minutes: u8, snoozes: u8) -> MissedAlarm {
+        MissedAlarm {
+            time: Time {
+                minutes,
+                seconds: 0_u8,
+                ..SYNC
+            },
+            snoozes,
+        }
+    }
+
+    #[test]
+    fn counters_round_trip() {
+        let never = Counters {
+            boots: 0x1234_u16,
+            snoozes: 0xFFFE_u16,
+            last_sync: None,
+        };
+        assert_eq!(never.encode()[4], NEVER);
+        assert_eq!(Counters::decode(&never.encode()), never);
+
+        let synced = Counters {
+            last_sync: Some(SYNC),
+            ..never
+        };
+        assert_eq!(Counters::decode(&synced.encode()), synced);
+    }
+
+    #[test]
+    fn counters_survive_reloads() {
+        let mut eeprom = TestEeprom::new();
+        let history = History::load(&eeprom);
+        assert_eq!(
+            *history.counters(),
+            Counters {
+                boots: 0_u16,
+                snoozes: 0_u16,
+                last_sync: None,
+            }
+        );
+
+        for boot in 1_u16..=40_u16 {
+            let mut history = History::load(&eeprom);
+            history.record_boot(&mut eeprom);
+            history.record_snooze(&mut eeprom);
+            history.record_snooze(&mut eeprom);
+            let counters = History::load(&eeprom).counters;
+            assert_eq!((counters.boots, counters.snoozes), (boot, boot * 2_u16));
+            assert_eq!(counters.last_sync, None);
+        }
+
+        let mut history = History::load(&eeprom);
+        history.record_sync(&mut eeprom, &SYNC);
+        let counters = History::load(&eeprom).counters;
+        assert_eq!(counters.last_sync, Some(SYNC));
+        assert_eq!(counters.boots, 40_u16);
+    }
+
+    #[test]
+    fn torn_append_keeps_the_previous_counters() {
+        for cut in 0_usize..Journal::<COUNTERS_LENGTH>::SLOT_LENGTH {
+            let mut eeprom = TestEeprom::new();
+            let mut history = History::load(&eeprom);
+            history.record_boot(&mut eeprom);
+
+            eeprom.lose_power_after(cut);
+            history.record_snooze(&mut eeprom);
+            let counters = History::load(&eeprom).counters;
+            let snoozes = match eeprom.power_lost {
+                true => 0_u16,
+                false => 1_u16,
+            };
+            assert_eq!(
+                (counters.boots, counters.snoozes),
+                (1_u16, snoozes),
+                "cut after {cut} bytes"
+            );
+        }
+    }
+
+    #[test]
+    fn missed_alarms_newest_first() {
+        let mut eeprom = TestEeprom::new();
+        let mut history = History::load(&eeprom);
+        assert_eq!(history.missed_alarm(&eeprom, 0_u16), None);
+
+        for n in 0_u8..3_u8 {
+            history.record_missed_alarm(&mut eeprom, &missed_alarm(n, n + 1_u8));
+        }
+        let history = History::load(&eeprom);
+        for nth in 0_u16..3_u16 {
+            let n = 2_u8 - nth as u8;
+            assert_eq!(
+                history.missed_alarm(&eeprom, nth),
+                Some(missed_alarm(n, n + 1_u8))
+            );
+        }
+        assert_eq!(history.missed_alarm(&eeprom, 3_u16), None);
+    }
+
+    #[test]
+    fn missed_alarms_wrap_around() {
+        let mut eeprom = TestEeprom::new();
+        let mut history = History::load(&eeprom);
+        let slots = history.missed_alarms.slots();
+        for n in 0_u8..60_u8 {
+            history.record_missed_alarm(&mut eeprom, &missed_alarm(n, 0_u8));
+        }
+        let history = History::load(&eeprom);
+        assert_eq!(
+            history.missed_alarm(&eeprom, 0_u16),
+            Some(missed_alarm(59_u8, 0_u8))
+        );
+        // Only as many as there are slots are kept
+        let oldest = 60_u8 - slots as u8;
+        assert_eq!(
+            history.missed_alarm(&eeprom, slots - 1_u16),
+            Some(missed_alarm(oldest, 0_u8))
+        );
+        assert_eq!(history.missed_alarm(&eeprom, slots), None);
+    }
+}
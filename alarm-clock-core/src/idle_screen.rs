@@ -0,0 +1,471 @@
+//! What the LCD shows while the clock is idle. The seven segment digits already
+//! show the time, so the LCD rotates through pages of everything else: the
+//! date, the next alarm, and the time in big digits for across the room. Status
+//! icons for the alarms being on, snoozing, the RTC's battery running low and
+//! night (quiet hours) are in the top right corner of each page.
+//!
+//! A missed alarm is shown instead until it's acknowledged.
+
+use crate::{
+    calendar::{DAY_NAMES, MONTH_NAMES},
+    framebuffer::{Framebuffer, Line, COLUMNS},
+    glyph::Glyph,
+    state::{OperationalMode, State},
+    time::{digits, Time},
+};
+
+/// Pages are enabled as a set of bits, with the page's index in `PAGE_NAMES`
+pub const PAGE_DATE: u8 = 1_u8 << 0;
+pub const PAGE_NEXT_ALARM: u8 = 1_u8 << 1;
+pub const PAGE_BIG_TIME: u8 = 1_u8 << 2;
+pub const ALL_PAGES: u8 = PAGE_DATE | PAGE_NEXT_ALARM | PAGE_BIG_TIME;
+pub const PAGE_NAMES: [&str; PAGE_COUNT as usize] = ["date", "alarm", "big"];
+const PAGE_COUNT: u8 = 3_u8;
+
+/// How long each page is shown for is from [1, 60] seconds
+pub const MAX_PAGE_SECONDS: u8 = 60_u8;
+
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum DateFormat {
+    /// 14/03/2024
+    #[default]
+    DayMonthYear,
+    /// 03/14/2024
+    MonthDayYear,
+    /// 2024-03-14
+    Iso,
+    /// 14 Mar 2024
+    Text,
+}
+impl DateFormat {
+    pub const ALL: [DateFormat; 4] = [
+        DateFormat::DayMonthYear,
+        DateFormat::MonthDayYear,
+        DateFormat::Iso,
+        DateFormat::Text,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DateFormat::DayMonthYear => "dmy",
+            DateFormat::MonthDayYear => "mdy",
+            DateFormat::Iso => "iso",
+            DateFormat::Text => "text",
+        }
+    }
+
+    fn write(self, line: &mut Line, time: &Time) {
+        let day = two_digits(time.day);
+        let month = two_digits(time.month);
+        let year = two_digits(time.year);
+        match self {
+            DateFormat::DayMonthYear => {
+                print_bytes(line, &[day[0], day[1], b'/', month[0], month[1], b'/']);
+                print_bytes(line, &[b'2', b'0', year[0], year[1]]);
+            }
+            DateFormat::MonthDayYear => {
+                print_bytes(line, &[month[0], month[1], b'/', day[0], day[1], b'/']);
+                print_bytes(line, &[b'2', b'0', year[0], year[1]]);
+            }
+            DateFormat::Iso => {
+                print_bytes(line, &[b'2', b'0', year[0], year[1], b'-']);
+                print_bytes(line, &[month[0], month[1], b'-', day[0], day[1]]);
+            }
+            DateFormat::Text => {
+                print_number(line, time.day);
+                line.print(" ");
+                let month = MONTH_NAMES[(time.month as usize).wrapping_sub(1_usize) % 12_usize];
+                line.print(&month[..3]);
+                print_bytes(line, &[b' ', b'2', b'0', year[0], year[1]]);
+            }
+        }
+    }
+}
+
+/// What the idle screen shows, as saved
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct IdleScreenSettings {
+    /// The `PAGE_*`s to rotate through. The date is shown if there are none.
+    pub pages: u8,
+    pub date_format: DateFormat,
+    /// How long each page is shown for, from [1, `MAX_PAGE_SECONDS`]
+    pub page_seconds: u8,
+}
+impl Default for IdleScreenSettings {
+    fn default() -> Self {
+        Self {
+            pages: PAGE_DATE | PAGE_NEXT_ALARM,
+            date_format: DateFormat::default(),
+            page_seconds: 5_u8,
+        }
+    }
+}
+
+/// Which page is shown, and since when
+pub struct IdleScreen {
+    /// Index into `PAGE_NAMES`
+    page: u8,
+    /// Millis when the page was first shown, if it has been yet
+    page_started: Option<u32>,
+}
+impl IdleScreen {
+    pub fn new() -> Self {
+        Self {
+            page: 0_u8,
+            page_started: None,
+        }
+    }
+
+    /// The page shown at `now` millis, moving on to the next enabled one once
+    /// it's been shown for long enough (or if it was disabled)
+    pub fn page(&mut self, settings: &IdleScreenSettings, now: u32) -> u8 {
+        let pages = match settings.pages & ALL_PAGES {
+            0_u8 => PAGE_DATE,
+            pages => pages,
+        };
+        let started = *self.page_started.get_or_insert(now);
+        let page_millis = settings.page_seconds.max(1_u8) as u32 * 1_000_u32;
+        if pages & 1_u8 << self.page == 0_u8 || now.wrapping_sub(started) >= page_millis {
+            self.page = (1_u8..=PAGE_COUNT)
+                .map(|step| (self.page + step) % PAGE_COUNT)
+                .find(|page| pages & 1_u8 << page != 0_u8)
+                .unwrap_or(0_u8);
+            self.page_started = Some(now);
+        }
+        1_u8 << self.page
+    }
+
+    /// Draw the idle screen for `now` millis into the framebuffer
+    pub fn draw(&mut self, screen: &mut Framebuffer, state: &State, now: u32) {
+        screen.clear();
+        if let Some(missed_alarm) = state.missed_alarm {
+            let mut line = *b"Missed 00:00 Zz0";
+            line[7] += missed_alarm.time.hours / 10_u8;
+            line[8] += missed_alarm.time.hours % 10_u8;
+            line[10] += missed_alarm.time.minutes / 10_u8;
+            line[11] += missed_alarm.time.minutes % 10_u8;
+            line[15] += missed_alarm.snoozes.min(9_u8);
+            print_bytes(&mut screen.line(0_u8), &line);
+            state
+                .idle_screen
+                .date_format
+                .write(&mut screen.line(1_u8), &state.time);
+            return;
+        }
+
+        match self.page(&state.idle_screen, now) {
+            PAGE_NEXT_ALARM => {
+                let mut top = screen.line(0_u8);
+                let until = match state.mode {
+                    OperationalMode::Snoozed => {
+                        top.print("Snoozed");
+                        Some(state.snooze_until.wrapping_sub(now) / 1_000_u32)
+                    }
+                    _ => match state.next_alarm() {
+                        Some((alarm, until)) => {
+                            top.print("Alarm ");
+                            print_time(&mut top, alarm.hours, alarm.minutes);
+                            Some(until)
+                        }
+                        None if !state.alarm_enabled => {
+                            top.print("Alarms off");
+                            None
+                        }
+                        None => {
+                            top.print("No alarms");
+                            None
+                        }
+                    },
+                };
+                if let Some(until) = until {
+                    print_countdown(&mut screen.line(1_u8), until);
+                }
+                draw_icons(screen, state, false);
+            }
+            PAGE_BIG_TIME => {
+                let digits = &state.digits;
+                let colon = digits.seconds.1 & 1_u8 == 0_u8;
+                screen.big_time(0_u8, digits.hours, digits.minutes, colon);
+                draw_icons(screen, state, true);
+            }
+            _ => {
+                screen
+                    .line(0_u8)
+                    .print(DAY_NAMES[state.time.day_of_week as usize % DAY_NAMES.len()]);
+                state
+                    .idle_screen
+                    .date_format
+                    .write(&mut screen.line(1_u8), &state.time);
+                draw_icons(screen, state, false);
+            }
+        }
+    }
+}
+impl Default for IdleScreen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The status icons that apply, in the order they're shown
+fn icons(state: &State) -> impl Iterator<Item = Glyph> + Clone {
+    [
+        (state.next_alarm().is_some(), Glyph::Bell),
+        (
+            matches!(state.mode, OperationalMode::Snoozed),
+            Glyph::Snooze,
+        ),
+        (state.rtc_battery_low, Glyph::Battery(0_u8)),
+        (state.chime.is_quiet(state.time.hours), Glyph::Moon),
+    ]
+    .into_iter()
+    .filter_map(|(shown, glyph)| shown.then_some(glyph))
+}
+
+/// Right-aligned on the top row, or down the last column beside big digits
+/// (where only two fit)
+fn draw_icons(screen: &mut Framebuffer, state: &State, column: bool) {
+    let icons = icons(state);
+    if column {
+        for (row, icon) in icons.take(2_usize).enumerate() {
+            screen.line_at(COLUMNS as u8 - 1_u8, row as u8).glyph(icon);
+        }
+    } else {
+        let start = COLUMNS - icons.clone().count();
+        let mut line = screen.line_at(start as u8, 0_u8);
+        for icon in icons {
+            line.glyph(icon);
+        }
+    }
+}
+
+/// "in 9h 05m", or "in 15m" under an hour, rounding up to the minute
+fn print_countdown(line: &mut Line, seconds: u32) {
+    let minutes = 1_u32 + seconds.saturating_sub(1_u32) / 60_u32;
+    let hours = (minutes / 60_u32).min(99_u32) as u8;
+    line.print("in ");
+    if hours > 0_u8 {
+        print_number(line, hours);
+        line.print("h ");
+        print_bytes(line, &two_digits((minutes % 60_u32) as u8));
+    } else {
+        print_number(line, minutes as u8);
+    }
+    line.print("m");
+}
+
+fn print_time(line: &mut Line, hours: u8, minutes: u8) {
+    let hours = two_digits(hours);
+    let minutes = two_digits(minutes);
+    print_bytes(line, &[hours[0], hours[1], b':', minutes[0], minutes[1]]);
+}
+
+/// [0, 99] without a leading zero
+fn print_number(line: &mut Line, value: u8) {
+    match two_digits(value) {
+        [b'0', ones] => print_bytes(line, &[ones]),
+        tens_and_ones => print_bytes(line, &tens_and_ones),
+    }
+}
+
+/// [0, 99] as two ASCII digits
+fn two_digits(value: u8) -> [u8; 2] {
+    let (tens, ones) = digits(value % 100_u8);
+    [b'0' + tens, b'0' + ones]
+}
+
+fn print_bytes(line: &mut Line, bytes: &[u8]) {
+    // Only ever ASCII digits and punctuation
+    line.print(core::str::from_utf8(bytes).unwrap_or_default());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{Alarm, AlarmSound, MissedAlarm};
+
+    /// Thursday 2024-03-14 06:58:30, with an alarm at 07:00 and quiet hours
+    /// from 22 to 7
+    fn state() -> State {
+        let mut state = State::new();
+        state.time = Time {
+            hours: 6_u8,
+            minutes: 58_u8,
+            seconds: 30_u8,
+            day: 14_u8,
+            day_of_week: 4_u8,
+            month: 3_u8,
+            year: 24_u8,
+        };
+        state.digits = crate::time::TimeDigits::from_time(&state.time);
+        state.alarm_enabled = true;
+        state.alarms[2] = Some(Alarm {
+            hours: 7_u8,
+            minutes: 0_u8,
+            enabled: true,
+            sound: AlarmSound::default(),
+        });
+        state
+    }
+
+    /// The row with custom characters as their glyphs' fallbacks
+    fn row(screen: &Framebuffer, row: u8) -> String {
+        screen
+            .row(row)
+            .iter()
+            .map(|&code| match screen.glyph(code) {
+                Some(glyph) => glyph.fallback() as char,
+                None => code as char,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn formats_dates() {
+        let state = state();
+        let mut screen = Framebuffer::new();
+        for (format, date) in [
+            (DateFormat::DayMonthYear, "14/03/2024      "),
+            (DateFormat::MonthDayYear, "03/14/2024      "),
+            (DateFormat::Iso, "2024-03-14      "),
+            (DateFormat::Text, "14 Mar 2024     "),
+        ] {
+            format.write(&mut screen.line(1_u8), &state.time);
+            assert_eq!(row(&screen, 1_u8), date, "{}", format.name());
+        }
+        let time = Time {
+            day: 1_u8,
+            month: 12_u8,
+            ..state.time
+        };
+        DateFormat::Text.write(&mut screen.line(1_u8), &time);
+        assert_eq!(row(&screen, 1_u8), "1 Dec 2024      ");
+    }
+
+    #[test]
+    fn rotates_through_enabled_pages() {
+        let mut idle_screen = IdleScreen::new();
+        let mut settings = IdleScreenSettings {
+            pages: PAGE_DATE | PAGE_BIG_TIME,
+            ..IdleScreenSettings::default()
+        };
+        assert_eq!(idle_screen.page(&settings, 10_000_u32), PAGE_DATE);
+        assert_eq!(idle_screen.page(&settings, 14_999_u32), PAGE_DATE);
+        assert_eq!(idle_screen.page(&settings, 15_000_u32), PAGE_BIG_TIME);
+        assert_eq!(idle_screen.page(&settings, 20_000_u32), PAGE_DATE);
+
+        // A page that's been disabled is moved off of right away
+        settings.pages = PAGE_NEXT_ALARM;
+        assert_eq!(idle_screen.page(&settings, 20_001_u32), PAGE_NEXT_ALARM);
+        assert_eq!(idle_screen.page(&settings, 30_000_u32), PAGE_NEXT_ALARM);
+        settings.pages = 0_u8;
+        assert_eq!(idle_screen.page(&settings, 30_001_u32), PAGE_DATE);
+    }
+
+    #[test]
+    fn draws_pages() {
+        let mut state = state();
+        state.idle_screen.pages = ALL_PAGES;
+        let mut idle_screen = IdleScreen::new();
+        let mut screen = Framebuffer::new();
+
+        idle_screen.draw(&mut screen, &state, 0_u32);
+        assert_eq!(row(&screen, 0_u8), "Thursday      !(");
+        assert_eq!(row(&screen, 1_u8), "14/03/2024      ");
+
+        idle_screen.draw(&mut screen, &state, 5_000_u32);
+        assert_eq!(row(&screen, 0_u8), "Alarm 07:00   !(");
+        assert_eq!(row(&screen, 1_u8), "in 2m           ");
+
+        // The colon blinks with the seconds
+        idle_screen.draw(&mut screen, &state, 10_000_u32);
+        assert_eq!(row(&screen, 0_u8), "#\"# #==.#== #=#!");
+        assert_eq!(row(&screen, 1_u8), "#_# #_#.__# #_#(");
+        state.digits.seconds.1 = 1_u8;
+        idle_screen.draw(&mut screen, &state, 10_001_u32);
+        assert_eq!(row(&screen, 0_u8), "#\"# #== #== #=#!");
+    }
+
+    #[test]
+    fn counts_down_to_the_next_alarm() {
+        let mut state = state();
+        state.idle_screen.pages = PAGE_NEXT_ALARM;
+        state.alarms[0] = Some(Alarm {
+            hours: 5_u8,
+            minutes: 0_u8,
+            enabled: true,
+            sound: AlarmSound::default(),
+        });
+        let mut idle_screen = IdleScreen::new();
+        let mut screen = Framebuffer::new();
+
+        // Due in a minute and a half, which rounds up
+        idle_screen.draw(&mut screen, &state, 0_u32);
+        assert_eq!(row(&screen, 1_u8), "in 2m           ");
+
+        // Past 07:00, so it's the 05:00 alarm tomorrow
+        state.time.hours = 7_u8;
+        state.time.minutes = 0_u8;
+        state.time.seconds = 0_u8;
+        idle_screen.draw(&mut screen, &state, 0_u32);
+        assert_eq!(row(&screen, 0_u8), "Alarm 05:00    !");
+        assert_eq!(row(&screen, 1_u8), "in 22h 00m      ");
+
+        state.mode = OperationalMode::Snoozed;
+        state.snooze_until = 9_u32 * 60_000_u32;
+        idle_screen.draw(&mut screen, &state, 30_000_u32);
+        assert_eq!(row(&screen, 0_u8), "Snoozed       !z");
+        assert_eq!(row(&screen, 1_u8), "in 9m           ");
+
+        state.alarms = [None; 4];
+        state.mode = OperationalMode::Idle;
+        idle_screen.draw(&mut screen, &state, 0_u32);
+        assert_eq!(row(&screen, 0_u8), "No alarms       ");
+        assert_eq!(row(&screen, 1_u8), "                ");
+        state.alarm_enabled = false;
+        idle_screen.draw(&mut screen, &state, 0_u32);
+        assert_eq!(row(&screen, 0_u8), "Alarms off      ");
+    }
+
+    #[test]
+    fn shows_status_icons() {
+        let mut state = state();
+        state.time.hours = 23_u8;
+        state.rtc_battery_low = true;
+        let mut idle_screen = IdleScreen::new();
+        let mut screen = Framebuffer::new();
+        idle_screen.draw(&mut screen, &state, 0_u32);
+        assert_eq!(row(&screen, 0_u8), "Thursday     !0(");
+
+        // Only the first two fit beside big digits
+        state.idle_screen.pages = PAGE_BIG_TIME;
+        idle_screen.draw(&mut screen, &state, 0_u32);
+        assert_eq!(screen.glyph(screen.row(0_u8)[15]), Some(Glyph::Bell));
+        assert_eq!(
+            screen.glyph(screen.row(1_u8)[15]),
+            Some(Glyph::Battery(0_u8))
+        );
+    }
+
+    #[test]
+    fn missed_alarm_stays() {
+        let mut state = state();
+        state.idle_screen.date_format = DateFormat::Iso;
+        state.missed_alarm = Some(MissedAlarm {
+            time: Time {
+                hours: 7_u8,
+                minutes: 15_u8,
+                ..state.time
+            },
+            snoozes: 2_u8,
+        });
+        let mut idle_screen = IdleScreen::new();
+        let mut screen = Framebuffer::new();
+        for now in [0_u32, 60_000_u32] {
+            idle_screen.draw(&mut screen, &state, now);
+            assert_eq!(row(&screen, 0_u8), "Missed 07:15 Zz2");
+            assert_eq!(row(&screen, 1_u8), "2024-03-14      ");
+        }
+    }
+}
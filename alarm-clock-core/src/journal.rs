@@ -0,0 +1,225 @@
+//! Wear-leveled storage for records that change often
+//!
+//! A journal owns a region of the EEPROM split into equally sized slots:
+//! `[sequence low, sequence high, record..., CRC-16 low, CRC-16 high]`.
+//! Every append goes to the slot after the newest one with the next sequence
+//! number, so each cell is only written once per trip around the region. The
+//! CRC covers the sequence number and the record.
+//!
+//! The newest slot is never written over, so losing power mid-write only ever
+//! loses the record being written: the torn slot fails its CRC and the newest
+//! intact record wins on the next scan. Older records stay around until they
+//! are written over, so a journal doubles as a log of the last few records.
+
+use alarm_clock_protocol::crc::crc16;
+
+use crate::eeprom::Eeprom;
+
+const SEQUENCE_LENGTH: usize = 2_usize;
+const CRC_LENGTH: usize = 2_usize;
+/// Slots are staged in a buffer on the stack this big
+const MAX_SLOT_LENGTH: usize = 32_usize;
+
+/// Whether sequence number `a` comes after `b`, allowing for wrapping
+fn is_newer(a: u16, b: u16) -> bool {
+    (a.wrapping_sub(b) as i16) > 0_i16
+}
+
+pub struct Journal<const RECORD_LENGTH: usize> {
+    start: u16,
+    slots: u16,
+    /// Slot and sequence number of the newest record, if any
+    head: Option<(u16, u16)>,
+}
+impl<const RECORD_LENGTH: usize> Journal<RECORD_LENGTH> {
+    pub const SLOT_LENGTH: usize = SEQUENCE_LENGTH + RECORD_LENGTH + CRC_LENGTH;
+
+    /// Find the newest record in the region starting at `start` of `length` bytes
+    pub fn new(eeprom: &impl Eeprom, start: u16, length: u16) -> Self {
+        let mut journal = Self {
+            start,
+            slots: length / Self::SLOT_LENGTH as u16,
+            head: None,
+        };
+        assert!(Self::SLOT_LENGTH <= MAX_SLOT_LENGTH && journal.slots >= 2_u16);
+
+        for slot in 0_u16..journal.slots {
+            let mut record = [0_u8; RECORD_LENGTH];
+            let Some(sequence) = journal.read_slot(eeprom, slot, &mut record) else {
+                continue;
+            };
+            match journal.head {
+                Some((_, newest)) if !is_newer(sequence, newest) => (),
+                _ => journal.head = Some((slot, sequence)),
+            }
+        }
+        journal
+    }
+
+    pub fn slots(&self) -> u16 {
+        self.slots
+    }
+
+    /// Read the newest record, returning false if there are none
+    pub fn latest(&self, eeprom: &impl Eeprom, record: &mut [u8; RECORD_LENGTH]) -> bool {
+        self.recent(eeprom, 0_u16, record)
+    }
+
+    /// Read the `nth` newest record, where 0 is the newest. Returns false if it
+    /// was never written, has been written over, or was torn.
+    pub fn recent(&self, eeprom: &impl Eeprom, nth: u16, record: &mut [u8; RECORD_LENGTH]) -> bool {
+        let Some((head_slot, head_sequence)) = self.head else {
+            return false;
+        };
+        if nth >= self.slots {
+            return false;
+        }
+        let slot = (head_slot + self.slots - nth) % self.slots;
+        self.read_slot(eeprom, slot, record) == Some(head_sequence.wrapping_sub(nth))
+    }
+
+    pub fn append(&mut self, eeprom: &mut impl Eeprom, record: &[u8; RECORD_LENGTH]) {
+        let (slot, sequence) = match self.head {
+            Some((slot, sequence)) => ((slot + 1_u16) % self.slots, sequence.wrapping_add(1_u16)),
+            None => (0_u16, 0_u16),
+        };
+
+        let mut buffer = [0_u8; MAX_SLOT_LENGTH];
+        let buffer = &mut buffer[..Self::SLOT_LENGTH];
+        buffer[..SEQUENCE_LENGTH].copy_from_slice(&sequence.to_le_bytes());
+        buffer[SEQUENCE_LENGTH..SEQUENCE_LENGTH + RECORD_LENGTH].copy_from_slice(record);
+        let crc = crc16(&buffer[..SEQUENCE_LENGTH + RECORD_LENGTH]);
+        buffer[SEQUENCE_LENGTH + RECORD_LENGTH..].copy_from_slice(&crc.to_le_bytes());
+
+        eeprom.update(self.slot_address(slot), buffer);
+        self.head = Some((slot, sequence));
+    }
+
+    fn slot_address(&self, slot: u16) -> u16 {
+        self.start + slot * Self::SLOT_LENGTH as u16
+    }
+
+    /// Read a slot, returning its sequence number if it is intact
+    fn read_slot(
+        &self,
+        eeprom: &impl Eeprom,
+        slot: u16,
+        record: &mut [u8; RECORD_LENGTH],
+    ) -> Option<u16> {
+        let mut buffer = [0_u8; MAX_SLOT_LENGTH];
+        let buffer = &mut buffer[..Self::SLOT_LENGTH];
+        eeprom.read(self.slot_address(slot), buffer);
+
+        let (contents, crc) = buffer.split_at(SEQUENCE_LENGTH + RECORD_LENGTH);
+        if crc16(contents) != u16::from_le_bytes([crc[0], crc[1]]) {
+            return None;
+        }
+        record.copy_from_slice(&contents[SEQUENCE_LENGTH..]);
+        Some(u16::from_le_bytes([contents[0], contents[1]]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eeprom::MemoryEeprom;
+
+    const START: u16 = 16_u16;
+    const LENGTH: u16 = 40_u16;
+    type TestJournal = Journal<4>;
+
+    fn latest(journal: &TestJournal, eeprom: &impl Eeprom) -> Option<[u8; 4]> {
+        let mut record = [0_u8; 4];
+        journal.latest(eeprom, &mut record).then_some(record)
+    }
+
+    #[test]
+    fn blank() {
+        let eeprom = MemoryEeprom::<64>::new();
+        let journal = TestJournal::new(&eeprom, START, LENGTH);
+        assert_eq!(journal.slots(), 5_u16);
+        assert_eq!(latest(&journal, &eeprom), None);
+    }
+
+    #[test]
+    fn survives_reload_and_wraps_around() {
+        let mut eeprom = MemoryEeprom::<64>::new();
+        let mut journal = TestJournal::new(&eeprom, START, LENGTH);
+        for n in 0_u8..12_u8 {
+            journal.append(&mut eeprom, &[n; 4]);
+            let reloaded = TestJournal::new(&eeprom, START, LENGTH);
+            assert_eq!(latest(&reloaded, &eeprom), Some([n; 4]));
+        }
+
+        // Nothing outside of the region was touched
+        assert!(eeprom.bytes[..START as usize].iter().all(|b| *b == 0xFF_u8));
+        assert!(eeprom.bytes[(START + LENGTH) as usize..]
+            .iter()
+            .all(|b| *b == 0xFF_u8));
+    }
+
+    #[test]
+    fn spreads_writes_over_slots() {
+        let mut eeprom = MemoryEeprom::<64>::new();
+        let mut journal = TestJournal::new(&eeprom, START, LENGTH);
+        for n in 0_u8..5_u8 {
+            journal.append(&mut eeprom, &[n; 4]);
+        }
+        // Every slot has been used once
+        let mut record = [0_u8; 4];
+        for nth in 0_u16..5_u16 {
+            assert!(journal.recent(&eeprom, nth, &mut record));
+            assert_eq!(record, [4_u8 - nth as u8; 4]);
+        }
+        assert!(!journal.recent(&eeprom, 5_u16, &mut record));
+    }
+
+    #[test]
+    fn sequence_numbers_wrap() {
+        let mut eeprom = MemoryEeprom::<64>::new();
+        let mut journal = TestJournal::new(&eeprom, START, LENGTH);
+        journal.head = Some((0_u16, u16::MAX - 2_u16));
+        for n in 0_u8..6_u8 {
+            journal.append(&mut eeprom, &[n; 4]);
+        }
+        let reloaded = TestJournal::new(&eeprom, START, LENGTH);
+        assert_eq!(latest(&reloaded, &eeprom), Some([5_u8; 4]));
+    }
+
+    #[test]
+    fn torn_writes_keep_the_previous_record() {
+        for append_count in 1_u8..8_u8 {
+            for cut in 0_usize..TestJournal::SLOT_LENGTH {
+                let mut eeprom = MemoryEeprom::<64>::new();
+                let mut journal = TestJournal::new(&eeprom, START, LENGTH);
+                for n in 0_u8..append_count {
+                    journal.append(&mut eeprom, &[n; 4]);
+                }
+
+                // Lose power partway through the next append
+                eeprom.lose_power_after(cut);
+                journal.append(&mut eeprom, &[0xA5_u8; 4]);
+
+                // Either the old record survives, or the new one made it in full
+                // (when fewer bytes needed writing than the cut)
+                let reloaded = TestJournal::new(&eeprom, START, LENGTH);
+                let expected = match eeprom.power_lost {
+                    true => [append_count - 1_u8; 4],
+                    false => [0xA5_u8; 4],
+                };
+                assert_eq!(
+                    latest(&reloaded, &eeprom),
+                    Some(expected),
+                    "{append_count} appends, cut after {cut} bytes"
+                );
+
+                // And the journal carries on afterwards
+                eeprom.restore_power();
+                let mut reloaded = reloaded;
+                reloaded.append(&mut eeprom, &[0x5A_u8; 4]);
+                let reloaded = TestJournal::new(&eeprom, START, LENGTH);
+                assert_eq!(latest(&reloaded, &eeprom), Some([0x5A_u8; 4]));
+            }
+        }
+    }
+}
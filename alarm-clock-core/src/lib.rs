@@ -0,0 +1,28 @@
+//! Everything about the alarm clock that doesn't touch the hardware: time and
+//! calendar math, the alarm state machine, display encoding, the serial shell,
+//! and what's kept in the EEPROM. The `alarm-clock` firmware wires these up to
+//! the pins, timers, and RTC through the traits in `hal`; keeping them here lets
+//! them be tested on the host.
+
+#![cfg_attr(not(test), no_std)]
+
+pub mod app;
+pub mod calendar;
+pub mod commands;
+pub mod display;
+pub mod eeprom;
+pub mod framebuffer;
+pub mod glyph;
+pub mod hal;
+pub mod history;
+pub mod idle_screen;
+pub mod journal;
+pub mod log;
+pub mod rtc;
+pub mod rtttl;
+pub mod settings;
+pub mod shell;
+pub mod shift_register;
+pub mod sound;
+pub mod state;
+pub mod time;
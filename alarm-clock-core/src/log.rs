@@ -0,0 +1,185 @@
+//! Log levels, per-module tags, and the runtime filters over them
+//!
+//! The macros that format and send messages live in the firmware's `log.rs`,
+//! as they depend on how it talks to the console.
+
+use core::sync::atomic::{AtomicU8, Ordering::SeqCst};
+
+pub const TAG_COUNT: usize = 10_usize;
+/// What every tag is filtered to unless changed
+pub const DEFAULT_LEVEL: Level = Level::Info;
+
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Level {
+    /// Only for filtering; nothing is logged at this level
+    Off = 0_u8,
+    Error = 1_u8,
+    Warn = 2_u8,
+    Info = 3_u8,
+    Debug = 4_u8,
+    Trace = 5_u8,
+}
+impl Level {
+    pub const ALL: [Level; 6] = [
+        Level::Off,
+        Level::Error,
+        Level::Warn,
+        Level::Info,
+        Level::Debug,
+        Level::Trace,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Level::Off => "OFF",
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        }
+    }
+
+    pub fn from_u8(level: u8) -> Option<Self> {
+        Self::ALL.get(level as usize).copied()
+    }
+
+    /// Parse a level's name, ignoring case
+    pub fn parse(word: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|level| level.name().eq_ignore_ascii_case(word))
+    }
+}
+
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Tag {
+    Main = 0_u8,
+    Rtc = 1_u8,
+    ShiftReg = 2_u8,
+    RotEnc = 3_u8,
+    Snooze = 4_u8,
+    Display = 5_u8,
+    Melody = 6_u8,
+    Escalate = 7_u8,
+    Chime = 8_u8,
+    History = 9_u8,
+}
+impl Tag {
+    pub const ALL: [Tag; TAG_COUNT] = [
+        Tag::Main,
+        Tag::Rtc,
+        Tag::ShiftReg,
+        Tag::RotEnc,
+        Tag::Snooze,
+        Tag::Display,
+        Tag::Melody,
+        Tag::Escalate,
+        Tag::Chime,
+        Tag::History,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tag::Main => "MAIN",
+            Tag::Rtc => "RTC",
+            Tag::ShiftReg => "SHIFT REG",
+            Tag::RotEnc => "ROT ENC",
+            Tag::Snooze => "SNOOZE",
+            Tag::Display => "DISPLAY",
+            Tag::Melody => "MELODY",
+            Tag::Escalate => "ESCALATE",
+            Tag::Chime => "CHIME",
+            Tag::History => "HISTORY",
+        }
+    }
+
+    /// Parse a tag's name, ignoring case and spaces (so `shiftreg` is `SHIFT REG`)
+    pub fn parse(word: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|tag| {
+            let mut name = tag.name().bytes().filter(|b| *b != b' ');
+            let mut word = word.bytes();
+            loop {
+                match (name.next(), word.next()) {
+                    (Some(a), Some(b)) if a.eq_ignore_ascii_case(&b) => (),
+                    (None, None) => return true,
+                    _ => return false,
+                }
+            }
+        })
+    }
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const DEFAULT_FILTER: AtomicU8 = AtomicU8::new(DEFAULT_LEVEL as u8);
+/// The most verbose level logged for each tag
+static FILTERS: [AtomicU8; TAG_COUNT] = [DEFAULT_FILTER; TAG_COUNT];
+
+/// Whether a message at `level` for `tag` passes the runtime filter
+pub fn enabled(tag: Tag, level: Level) -> bool {
+    level as u8 <= FILTERS[tag as usize].load(SeqCst)
+}
+
+pub fn filter(tag: Tag) -> Level {
+    Level::from_u8(FILTERS[tag as usize].load(SeqCst)).unwrap_or(DEFAULT_LEVEL)
+}
+
+pub fn set_filter(tag: Tag, level: Level) {
+    FILTERS[tag as usize].store(level as u8, SeqCst);
+}
+
+/// Every tag's filter as stored in the settings
+pub fn filters() -> [u8; TAG_COUNT] {
+    core::array::from_fn(|n| FILTERS[n].load(SeqCst))
+}
+
+pub fn set_filters(levels: &[u8; TAG_COUNT]) {
+    for (tag, level) in Tag::ALL.into_iter().zip(levels) {
+        set_filter(tag, Level::from_u8(*level).unwrap_or(DEFAULT_LEVEL));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing() {
+        assert_eq!(Level::parse("debug"), Some(Level::Debug));
+        assert_eq!(Level::parse("OFF"), Some(Level::Off));
+        assert_eq!(Level::parse("loud"), None);
+        assert_eq!(Tag::parse("rtc"), Some(Tag::Rtc));
+        assert_eq!(Tag::parse("shiftreg"), Some(Tag::ShiftReg));
+        assert_eq!(Tag::parse("RotEnc"), Some(Tag::RotEnc));
+        assert_eq!(Tag::parse("shift"), None);
+        assert_eq!(Tag::parse("shiftregs"), None);
+        for (n, tag) in Tag::ALL.into_iter().enumerate() {
+            assert_eq!(tag as usize, n);
+        }
+    }
+
+    #[test]
+    fn filtering() {
+        assert!(enabled(Tag::Rtc, Level::Error));
+        assert!(enabled(Tag::Rtc, DEFAULT_LEVEL));
+        assert!(!enabled(Tag::Rtc, Level::Debug));
+
+        set_filter(Tag::Rtc, Level::Trace);
+        assert!(enabled(Tag::Rtc, Level::Trace));
+        assert!(!enabled(Tag::Chime, Level::Trace));
+
+        set_filter(Tag::Rtc, Level::Off);
+        assert!(!enabled(Tag::Rtc, Level::Error));
+
+        let mut levels = filters();
+        assert_eq!(levels[Tag::Rtc as usize], Level::Off as u8);
+        levels[Tag::Chime as usize] = Level::Warn as u8;
+        // Garbage falls back to the default
+        levels[Tag::Rtc as usize] = 0xFF_u8;
+        set_filters(&levels);
+        assert_eq!(filter(Tag::Chime), Level::Warn);
+        assert_eq!(filter(Tag::Rtc), DEFAULT_LEVEL);
+    }
+}
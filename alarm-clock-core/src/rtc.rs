@@ -0,0 +1,120 @@
+//! NXP PCF8523 RTC driver, over any blocking `embedded-hal` I2C bus
+
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+
+use crate::{
+    calendar::is_valid_date,
+    time::{bcd_decode, bcd_encode, Time},
+};
+
+pub const ADDRESS: u8 = 0x68_u8;
+/// Power management and the battery flags
+pub const CONTROL_3_REGISTER: u8 = 0x02_u8;
+/// Set in Control_3 while the backup battery is low, if that's being detected
+pub const BATTERY_LOW: u8 = 1_u8 << 2;
+/// The seconds register, which the time registers start at
+pub const SECONDS_REGISTER: u8 = 0x03_u8;
+/// Set in the seconds register when the oscillator has stopped (e.g. the
+/// backup battery ran out), and cleared by setting the time
+pub const OSCILLATOR_STOPPED: u8 = 0b1000_0000_u8;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Error<E> {
+    Bus(E),
+    /// What was read isn't a time, e.g. from a corrupted byte
+    InvalidTime,
+}
+
+pub struct Pcf8523<I2C> {
+    pub i2c: I2C,
+}
+impl<I2C, E> Pcf8523<I2C>
+where
+    I2C: Write<Error = E> + WriteRead<Error = E>,
+{
+    pub fn new(i2c: I2C) -> Self {
+        Self { i2c }
+    }
+
+    pub fn read_time(&mut self) -> Result<Time, Error<E>> {
+        let mut time_buffer = [0_u8; 7];
+        self.i2c
+            .write_read(ADDRESS, &[SECONDS_REGISTER], &mut time_buffer)
+            .map_err(Error::Bus)?;
+
+        time_buffer[0] &= !OSCILLATOR_STOPPED;
+        if !time_buffer.iter().all(|&byte| is_bcd(byte)) {
+            return Err(Error::InvalidTime);
+        }
+        let [seconds, minutes, hours, day, day_of_week, month, year] = time_buffer.map(bcd_decode);
+        let time = Time {
+            hours,
+            minutes,
+            seconds,
+            day,
+            day_of_week,
+            month,
+            year,
+        };
+        if hours >= 24_u8
+            || minutes >= 60_u8
+            || seconds >= 60_u8
+            || day_of_week >= 7_u8
+            || !is_valid_date(year, month, day)
+        {
+            return Err(Error::InvalidTime);
+        }
+        Ok(time)
+    }
+
+    /// Switch over to the backup battery when the supply drops, and detect it
+    /// running low (both are off at power on)
+    pub fn enable_battery_switch_over(&mut self) -> Result<(), Error<E>> {
+        self.i2c
+            .write(ADDRESS, &[CONTROL_3_REGISTER, 0x00_u8])
+            .map_err(Error::Bus)
+    }
+
+    /// Whether the backup battery is low
+    pub fn battery_low(&mut self) -> Result<bool, Error<E>> {
+        let mut control_3 = [0_u8; 1];
+        self.i2c
+            .write_read(ADDRESS, &[CONTROL_3_REGISTER], &mut control_3)
+            .map_err(Error::Bus)?;
+        Ok(control_3[0] & BATTERY_LOW != 0_u8)
+    }
+
+    /// Whether the oscillator has stopped since the time was last set, so the
+    /// time it keeps is lost
+    pub fn oscillator_stopped(&mut self) -> Result<bool, Error<E>> {
+        let mut seconds = [0_u8; 1];
+        self.i2c
+            .write_read(ADDRESS, &[SECONDS_REGISTER], &mut seconds)
+            .map_err(Error::Bus)?;
+        Ok(seconds[0] & OSCILLATOR_STOPPED != 0_u8)
+    }
+
+    /// Set the time, which also clears the oscillator stop flag
+    pub fn set_time(&mut self, time: &Time) -> Result<(), Error<E>> {
+        self.i2c
+            .write(
+                ADDRESS,
+                &[
+                    SECONDS_REGISTER,
+                    bcd_encode(time.seconds),
+                    bcd_encode(time.minutes),
+                    bcd_encode(time.hours),
+                    bcd_encode(time.day),
+                    bcd_encode(time.day_of_week),
+                    bcd_encode(time.month),
+                    bcd_encode(time.year),
+                ],
+            )
+            .map_err(Error::Bus)
+    }
+}
+
+/// Whether both nibbles are decimal digits
+fn is_bcd(byte: u8) -> bool {
+    byte >> 4 < 10_u8 && byte & 0x0F_u8 < 10_u8
+}
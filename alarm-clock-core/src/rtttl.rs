@@ -0,0 +1,356 @@
+//! RTTTL (Nokia ringtone text transfer language) parser
+//!
+//! A ringtone looks like `Nokia:d=4,o=5,b=225:8e6,8d6,f#,g#,...` where the
+//! first section is the name, the second section holds the default duration,
+//! octave, and beats per minute, and the last section is a list of notes.
+//!
+//! Parsing is done lazily over any byte iterator so that tunes can be read
+//! straight out of program memory without copying them into RAM first.
+
+use core::iter::Peekable;
+
+/// Frequencies (in Hz) of C8 through B8. Lower octaves are found by halving.
+const OCTAVE_8_FREQUENCIES: [u16; 12] = [
+    4186_u16, 4435_u16, 4699_u16, 4978_u16, 5274_u16, 5588_u16, // C, C#, D, D#, E, F
+    5920_u16, 6272_u16, 6645_u16, 7040_u16, 7459_u16, 7902_u16, // F#, G, G#, A, A#, B
+];
+const MIN_OCTAVE: u8 = 1_u8;
+const MAX_OCTAVE: u8 = 8_u8;
+
+/// Defaults used when the ringtone doesn't specify them (as per the spec)
+const DEFAULT_DURATION: u16 = 4_u16;
+const DEFAULT_OCTAVE: u16 = 6_u16;
+const DEFAULT_BPM: u16 = 63_u16;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RtttlError {
+    /// The name or defaults section was not terminated by a `:`
+    MissingSection,
+    /// A `d=`, `o=`, or `b=` value was missing or out of range
+    InvalidDefault,
+    /// A note could not be understood
+    InvalidNote,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Note {
+    /// `None` is a pause
+    pub frequency: Option<u16>,
+    pub duration_ms: u16,
+}
+
+/// A parsed ringtone header which yields notes as it is iterated over
+pub struct Rtttl<I>
+where
+    I: Iterator<Item = u8>,
+{
+    bytes: Peekable<I>,
+    default_duration: u16,
+    default_octave: u8,
+    whole_note_ms: u32,
+}
+impl<I> Rtttl<I>
+where
+    I: Iterator<Item = u8>,
+{
+    /// Parse the name and defaults sections, leaving the notes to be iterated over
+    pub fn new(bytes: I) -> Result<Self, RtttlError> {
+        let mut bytes = bytes.peekable();
+
+        // Name (ignored)
+        loop {
+            match bytes.next() {
+                Some(b':') => break,
+                Some(_) => continue,
+                None => return Err(RtttlError::MissingSection),
+            }
+        }
+
+        // Defaults
+        let (mut duration, mut octave, mut bpm) = (DEFAULT_DURATION, DEFAULT_OCTAVE, DEFAULT_BPM);
+        loop {
+            skip_whitespace(&mut bytes);
+            let key = match bytes.next() {
+                Some(b':') => break,
+                Some(b',') => continue,
+                Some(key) => key.to_ascii_lowercase(),
+                None => return Err(RtttlError::MissingSection),
+            };
+            skip_whitespace(&mut bytes);
+            if bytes.next() != Some(b'=') {
+                return Err(RtttlError::InvalidDefault);
+            }
+            skip_whitespace(&mut bytes);
+            let value = parse_number(&mut bytes).ok_or(RtttlError::InvalidDefault)?;
+            match key {
+                b'd' => duration = value,
+                b'o' => octave = value,
+                b'b' => bpm = value,
+                _ => return Err(RtttlError::InvalidDefault),
+            }
+        }
+
+        if !is_valid_duration(duration)
+            || !(MIN_OCTAVE as u16..=MAX_OCTAVE as u16).contains(&octave)
+            || bpm == 0_u16
+        {
+            return Err(RtttlError::InvalidDefault);
+        }
+
+        Ok(Self {
+            bytes,
+            default_duration: duration,
+            default_octave: octave as u8,
+            // A beat is a quarter note
+            whole_note_ms: 4_u32 * 60_000_u32 / bpm as u32,
+        })
+    }
+
+    fn parse_note(&mut self) -> Result<Note, RtttlError> {
+        let duration = match parse_number(&mut self.bytes) {
+            Some(duration) if is_valid_duration(duration) => duration,
+            Some(_) => return Err(RtttlError::InvalidNote),
+            None => self.default_duration,
+        };
+
+        // Semitones above C, or `None` for a pause
+        let mut semitone = match self.bytes.next().map(|b| b.to_ascii_lowercase()) {
+            Some(b'c') => Some(0_u8),
+            Some(b'd') => Some(2_u8),
+            Some(b'e') => Some(4_u8),
+            Some(b'f') => Some(5_u8),
+            Some(b'g') => Some(7_u8),
+            Some(b'a') => Some(9_u8),
+            Some(b'b') | Some(b'h') => Some(11_u8),
+            Some(b'p') => None,
+            _ => return Err(RtttlError::InvalidNote),
+        };
+        if self.bytes.peek() == Some(&b'#') {
+            self.bytes.next();
+            semitone = semitone.map(|semitone| semitone + 1_u8);
+        }
+
+        // The dot is allowed both before and after the octave in the wild
+        let mut dotted = self.next_if_eq(b'.');
+        let mut octave = match self.bytes.peek() {
+            Some(digit) if digit.is_ascii_digit() => {
+                let octave = digit - b'0';
+                self.bytes.next();
+                octave
+            }
+            _ => self.default_octave,
+        };
+        dotted |= self.next_if_eq(b'.');
+
+        // Separator
+        skip_whitespace(&mut self.bytes);
+        match self.bytes.next() {
+            Some(b',') | None => (),
+            Some(_) => return Err(RtttlError::InvalidNote),
+        }
+
+        // B# and E# roll over
+        if semitone == Some(12_u8) {
+            semitone = Some(0_u8);
+            octave += 1_u8;
+        }
+        if !(MIN_OCTAVE..=MAX_OCTAVE).contains(&octave) {
+            return Err(RtttlError::InvalidNote);
+        }
+
+        let mut duration_ms = self.whole_note_ms / duration as u32;
+        if dotted {
+            duration_ms += duration_ms / 2_u32;
+        }
+
+        Ok(Note {
+            frequency: semitone.map(|semitone| frequency(semitone, octave)),
+            duration_ms: duration_ms.min(u16::MAX as u32) as u16,
+        })
+    }
+
+    fn next_if_eq(&mut self, expected: u8) -> bool {
+        if self.bytes.peek() == Some(&expected) {
+            self.bytes.next();
+            true
+        } else {
+            false
+        }
+    }
+}
+impl<I> Iterator for Rtttl<I>
+where
+    I: Iterator<Item = u8>,
+{
+    type Item = Result<Note, RtttlError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        skip_whitespace(&mut self.bytes);
+        self.bytes.peek()?;
+        Some(self.parse_note())
+    }
+}
+
+/// Frequency of a note in Hz, rounded to the nearest integer
+fn frequency(semitone: u8, octave: u8) -> u16 {
+    let shift = MAX_OCTAVE - octave;
+    let octave_8 = OCTAVE_8_FREQUENCIES[semitone as usize];
+    match shift {
+        0_u8 => octave_8,
+        _ => (octave_8 + (1_u16 << (shift - 1_u8))) >> shift,
+    }
+}
+
+fn is_valid_duration(duration: u16) -> bool {
+    matches!(duration, 1 | 2 | 4 | 8 | 16 | 32 | 64)
+}
+
+fn skip_whitespace<I: Iterator<Item = u8>>(bytes: &mut Peekable<I>) {
+    while let Some(b' ' | b'\t' | b'\r' | b'\n') = bytes.peek() {
+        bytes.next();
+    }
+}
+
+/// Parse a decimal number, returning `None` if there were no digits
+fn parse_number<I: Iterator<Item = u8>>(bytes: &mut Peekable<I>) -> Option<u16> {
+    let mut number: Option<u16> = None;
+    while let Some(digit) = bytes.peek().filter(|b| b.is_ascii_digit()).copied() {
+        bytes.next();
+        number = Some(
+            number
+                .unwrap_or(0_u16)
+                .saturating_mul(10_u16)
+                .saturating_add((digit - b'0') as u16),
+        );
+    }
+    number
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(tune: &str) -> Result<Rtttl<impl Iterator<Item = u8> + '_>, RtttlError> {
+        Rtttl::new(tune.bytes())
+    }
+
+    fn notes(tune: &str) -> Result<([Note; 64], usize), RtttlError> {
+        let mut notes = [Note {
+            frequency: None,
+            duration_ms: 0_u16,
+        }; 64];
+        let mut count = 0_usize;
+        for note in parse(tune)? {
+            notes[count] = note?;
+            count += 1;
+        }
+        Ok((notes, count))
+    }
+
+    #[test]
+    fn nokia() {
+        let (notes, count) =
+            notes("Nokia:d=4,o=5,b=225:8e6,8d6,f#,g#,8c#6,8b,d,e,8b,8a,c#,e,2a").unwrap();
+        assert_eq!(count, 13);
+        // 225 bpm is a 1066ms whole note
+        assert_eq!(
+            notes[0],
+            Note {
+                frequency: Some(1319),
+                duration_ms: 133,
+            }
+        );
+        assert_eq!(notes[1].frequency, Some(1175));
+        assert_eq!(
+            notes[2],
+            Note {
+                frequency: Some(740),
+                duration_ms: 266,
+            }
+        );
+        assert_eq!(notes[4].frequency, Some(1109));
+        assert_eq!(
+            notes[12],
+            Note {
+                frequency: Some(880),
+                duration_ms: 533,
+            }
+        );
+    }
+
+    #[test]
+    fn simpsons() {
+        let (notes, count) = notes(
+            "The Simpsons:d=4,o=5,b=160:c.6,e6,f#6,8a6,g.6,e6,c6,8a,8f#,8f#,8f#,2g,8p,8p,8f#,8f#,8f#,8g,a#.,8c6,8c6,8c6,c6",
+        )
+        .unwrap();
+        assert_eq!(count, 23);
+        // Dotted quarter note at 160 bpm
+        assert_eq!(
+            notes[0],
+            Note {
+                frequency: Some(1047),
+                duration_ms: 562,
+            }
+        );
+        assert_eq!(
+            notes[12],
+            Note {
+                frequency: None,
+                duration_ms: 187,
+            }
+        );
+        assert_eq!(notes[18].frequency, Some(932));
+        assert_eq!(notes[18].duration_ms, 562);
+    }
+
+    #[test]
+    fn defaults_and_whitespace() {
+        // No defaults at all means d=4, o=6, b=63
+        let (notes, count) = notes("x::a, 8p ,\n2c.").unwrap();
+        assert_eq!(count, 3);
+        assert_eq!(
+            notes[0],
+            Note {
+                frequency: Some(1760),
+                duration_ms: 952,
+            }
+        );
+        assert_eq!(notes[1].frequency, None);
+        assert_eq!(notes[2].duration_ms, 2856);
+    }
+
+    #[test]
+    fn dot_after_octave() {
+        let (notes, _) = notes("x:d=4,o=5,b=120:c6.,c.6").unwrap();
+        assert_eq!(notes[0], notes[1]);
+        assert_eq!(notes[0].duration_ms, 750);
+    }
+
+    #[test]
+    fn sharp_rolls_over_octave() {
+        let (notes, _) = notes("x:d=4,o=5,b=120:b#,c6").unwrap();
+        assert_eq!(notes[0], notes[1]);
+    }
+
+    /// The buzzer's timer has to be able to play everything from here up
+    #[test]
+    fn lowest_note() {
+        let (lowest, _) = notes("x:d=4,o=1,b=120:c").unwrap();
+        assert_eq!(lowest[0].frequency, Some(33_u16));
+        assert_eq!(notes("x::c0").err(), Some(RtttlError::InvalidNote));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(parse("no sections").err(), Some(RtttlError::MissingSection));
+        assert_eq!(parse("x:d=4,o=5").err(), Some(RtttlError::MissingSection));
+        assert_eq!(parse("x:d=3:c").err(), Some(RtttlError::InvalidDefault));
+        assert_eq!(parse("x:b=0:c").err(), Some(RtttlError::InvalidDefault));
+        assert_eq!(parse("x:q=1:c").err(), Some(RtttlError::InvalidDefault));
+        assert_eq!(notes("x::z").err(), Some(RtttlError::InvalidNote));
+        assert_eq!(notes("x::c9").err(), Some(RtttlError::InvalidNote));
+        assert_eq!(notes("x::7c").err(), Some(RtttlError::InvalidNote));
+        assert_eq!(notes("x::c;d").err(), Some(RtttlError::InvalidNote));
+    }
+}
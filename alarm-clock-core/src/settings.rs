@@ -0,0 +1,612 @@
+//! Settings that persist across resets, stored in the EEPROM
+//!
+//! The stored record is laid out as:
+//! `[MAGIC, version, payload length, payload..., CRC-16 low, CRC-16 high]`
+//! where the CRC covers the version, length, and payload.
+//!
+//! Layouts only ever grow by appending fields to the payload, so a record
+//! written by older firmware is migrated forward by decoding the fields it has
+//! and taking the rest from the defaults. A record from *newer* firmware, a bad
+//! CRC, or an erased EEPROM is rejected and the caller falls back to defaults.
+//!
+//! Version 1 only had a single alarm. Its fields are kept (mirroring the first
+//! alarm) so the layout stays a prefix, and a version 1 record has its alarm
+//! moved into the alarm table when it is migrated.
+
+use alarm_clock_protocol::{crc::crc16, message::MAX_SETTINGS_RECORD_LENGTH};
+
+use crate::{
+    eeprom::{Eeprom, SETTINGS_ADDRESS, SETTINGS_REGION_SIZE},
+    idle_screen::{DateFormat, ALL_PAGES, MAX_PAGE_SECONDS},
+    log::{Level, TAG_COUNT},
+    sound::{ChimeMode, ChimeStyle, Tune},
+};
+
+const MAGIC: u8 = 0xAC_u8;
+pub const SETTINGS_VERSION: u8 = 4_u8;
+const HEADER_LENGTH: usize = 3_usize;
+const CRC_LENGTH: usize = 2_usize;
+/// Payload length of every layout version, indexed by version - 1
+const PAYLOAD_LENGTHS: [usize; SETTINGS_VERSION as usize] =
+    [16_usize, 33_usize, 43_usize, 46_usize];
+const PAYLOAD_LENGTH: usize = PAYLOAD_LENGTHS[SETTINGS_VERSION as usize - 1];
+const RECORD_LENGTH: usize = HEADER_LENGTH + PAYLOAD_LENGTH + CRC_LENGTH;
+// Host tools back up and restore the whole record
+const _: () = assert!(RECORD_LENGTH <= MAX_SETTINGS_RECORD_LENGTH);
+
+pub const SOUND_MELODY: u8 = 0_u8;
+pub const SOUND_ESCALATING: u8 = 1_u8;
+
+pub const MAX_ALARMS: usize = 4_usize;
+const STORED_ALARM_LENGTH: usize = 4_usize;
+pub const ALARM_PRESENT: u8 = 1_u8 << 0;
+pub const ALARM_ENABLED: u8 = 1_u8 << 1;
+/// Set in an alarm's sound for an escalation preset, otherwise it's a tune index
+pub const SOUND_PRESET: u8 = 1_u8 << 7;
+pub const PRESET_GENTLE: u8 = 0_u8;
+pub const PRESET_STANDARD: u8 = 1_u8;
+pub const PRESET_URGENT: u8 = 2_u8;
+/// The profile in the `escalation_*` fields
+pub const PRESET_CUSTOM: u8 = 3_u8;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SettingsError {
+    /// Nothing was ever saved (or the EEPROM was erased)
+    Blank,
+    /// Saved by newer firmware that we don't understand
+    UnsupportedVersion(u8),
+    /// The length doesn't match the version
+    BadLength,
+    BadCrc,
+    /// A field was outside of its range
+    OutOfRange,
+}
+
+/// One entry of the alarm table, in the plain form it is stored in
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct StoredAlarm {
+    /// Ranges from [0, 23]
+    pub hours: u8,
+    /// Ranges from [0, 59]
+    pub minutes: u8,
+    /// `ALARM_PRESENT` and `ALARM_ENABLED`
+    pub flags: u8,
+    /// A tune index or `SOUND_PRESET` with one of the `PRESET_*`s
+    pub sound: u8,
+}
+
+/// Everything that is saved, in the plain form it is stored in. Conversion to
+/// and from the runtime types happens in `State`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Settings {
+    /// Master switch for all alarms
+    pub alarm_enabled: bool,
+    /// The legacy single alarm, mirroring the first alarm. Ranges from [0, 23]
+    pub alarm_hours: u8,
+    /// Ranges from [0, 59]
+    pub alarm_minutes: u8,
+    /// `SOUND_MELODY` or `SOUND_ESCALATING`
+    pub alarm_sound: u8,
+    /// Index into `Tune::ALL`
+    pub alarm_tune: u8,
+    /// Profile used by the legacy alarm and `PRESET_CUSTOM`
+    pub escalation_ramp_seconds: u16,
+    pub escalation_start_frequency: u16,
+    pub escalation_end_frequency: u16,
+    pub alarm_max_ring_minutes: u8,
+    /// `ChimeMode` as a `u8`
+    pub chime_mode: u8,
+    /// `ChimeStyle` as a `u8`
+    pub chime_style: u8,
+    /// Ranges from [0, 23]
+    pub quiet_start: u8,
+    /// Ranges from [0, 23]
+    pub quiet_end: u8,
+    /// Hours and minutes display brightness from [1, 8]
+    pub brightness: u8,
+    pub alarms: [StoredAlarm; MAX_ALARMS],
+    /// `Level` as a `u8` for each `Tag`
+    pub log_levels: [u8; TAG_COUNT],
+    /// The idle screen's `PAGE_*`s
+    pub idle_pages: u8,
+    /// `DateFormat` as a `u8`
+    pub idle_date_format: u8,
+    /// Ranges from [1, `MAX_PAGE_SECONDS`]
+    pub idle_page_seconds: u8,
+}
+impl Settings {
+    fn encode_payload(&self) -> [u8; PAYLOAD_LENGTH] {
+        let ramp = self.escalation_ramp_seconds.to_le_bytes();
+        let start_frequency = self.escalation_start_frequency.to_le_bytes();
+        let end_frequency = self.escalation_end_frequency.to_le_bytes();
+        let alarm = |n: usize| &self.alarms[n];
+        let mut payload = [0_u8; PAYLOAD_LENGTH];
+        payload[..PAYLOAD_LENGTHS[1]].copy_from_slice(&[
+            /* Version 1 */
+            self.alarm_enabled as u8,
+            self.alarm_hours,
+            self.alarm_minutes,
+            self.alarm_sound,
+            self.alarm_tune,
+            ramp[0],
+            ramp[1],
+            start_frequency[0],
+            start_frequency[1],
+            end_frequency[0],
+            end_frequency[1],
+            self.alarm_max_ring_minutes,
+            self.chime_mode,
+            self.chime_style,
+            self.quiet_start,
+            self.quiet_end,
+            /* Version 2 */
+            self.brightness,
+            alarm(0).hours,
+            alarm(0).minutes,
+            alarm(0).flags,
+            alarm(0).sound,
+            alarm(1).hours,
+            alarm(1).minutes,
+            alarm(1).flags,
+            alarm(1).sound,
+            alarm(2).hours,
+            alarm(2).minutes,
+            alarm(2).flags,
+            alarm(2).sound,
+            alarm(3).hours,
+            alarm(3).minutes,
+            alarm(3).flags,
+            alarm(3).sound,
+        ]);
+        /* Version 3 */
+        payload[PAYLOAD_LENGTHS[1]..PAYLOAD_LENGTHS[2]].copy_from_slice(&self.log_levels);
+        /* Version 4 */
+        payload[PAYLOAD_LENGTHS[2]..].copy_from_slice(&[
+            self.idle_pages,
+            self.idle_date_format,
+            self.idle_page_seconds,
+        ]);
+        payload
+    }
+
+    /// Decode a payload of any version up to the current one. Fields that
+    /// didn't exist in that version are taken from `defaults`.
+    fn decode_payload(payload: &[u8], defaults: &Settings) -> Result<Self, SettingsError> {
+        // Older layouts are a prefix of the current one
+        let mut padded = defaults.encode_payload();
+        padded[..payload.len()].copy_from_slice(payload);
+        let p = &padded;
+
+        let mut settings = Self {
+            alarm_enabled: p[0] != 0_u8,
+            alarm_hours: p[1],
+            alarm_minutes: p[2],
+            alarm_sound: p[3],
+            alarm_tune: p[4],
+            escalation_ramp_seconds: u16::from_le_bytes([p[5], p[6]]),
+            escalation_start_frequency: u16::from_le_bytes([p[7], p[8]]),
+            escalation_end_frequency: u16::from_le_bytes([p[9], p[10]]),
+            alarm_max_ring_minutes: p[11],
+            chime_mode: p[12],
+            chime_style: p[13],
+            quiet_start: p[14],
+            quiet_end: p[15],
+            brightness: p[16],
+            alarms: core::array::from_fn(|n| {
+                let alarm = &p[17 + n * STORED_ALARM_LENGTH..];
+                StoredAlarm {
+                    hours: alarm[0],
+                    minutes: alarm[1],
+                    flags: alarm[2],
+                    sound: alarm[3],
+                }
+            }),
+            log_levels: core::array::from_fn(|n| p[33 + n]),
+            idle_pages: p[43],
+            idle_date_format: p[44],
+            idle_page_seconds: p[45],
+        };
+        if payload.len() < PAYLOAD_LENGTHS[1] {
+            settings.alarms = Self::migrate_legacy_alarm(&settings);
+        }
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    /// Version 1 only had the one alarm, which becomes the first in the table
+    fn migrate_legacy_alarm(settings: &Settings) -> [StoredAlarm; MAX_ALARMS] {
+        let mut alarms = [StoredAlarm::default(); MAX_ALARMS];
+        alarms[0] = StoredAlarm {
+            hours: settings.alarm_hours,
+            minutes: settings.alarm_minutes,
+            // Whether it sounds was always down to the (now master) switch
+            flags: ALARM_PRESENT | ALARM_ENABLED,
+            sound: match settings.alarm_sound {
+                SOUND_ESCALATING => SOUND_PRESET | PRESET_CUSTOM,
+                _ => settings.alarm_tune,
+            },
+        };
+        alarms
+    }
+
+    pub fn validate(&self) -> Result<(), SettingsError> {
+        let alarms_in_range = self.alarms.iter().all(|alarm| {
+            alarm.flags & ALARM_PRESENT == 0_u8
+                || (alarm.hours < 24_u8
+                    && alarm.minutes < 60_u8
+                    && match alarm.sound & SOUND_PRESET {
+                        0_u8 => (alarm.sound as usize) < Tune::ALL.len(),
+                        _ => alarm.sound & !SOUND_PRESET <= PRESET_CUSTOM,
+                    })
+        });
+        let in_range = alarms_in_range
+            && (1_u8..=8_u8).contains(&self.brightness)
+            && self
+                .log_levels
+                .iter()
+                .all(|level| *level <= Level::Trace as u8)
+            && self.alarm_hours < 24_u8
+            && self.alarm_minutes < 60_u8
+            && matches!(self.alarm_sound, SOUND_MELODY | SOUND_ESCALATING)
+            && (self.alarm_tune as usize) < Tune::ALL.len()
+            && self.escalation_ramp_seconds > 0_u16
+            && self.escalation_start_frequency > 0_u16
+            && self.escalation_end_frequency > 0_u16
+            && self.alarm_max_ring_minutes > 0_u8
+            && self.chime_mode <= ChimeMode::HalfHourly as u8
+            && self.chime_style <= ChimeStyle::Westminster as u8
+            && self.quiet_start < 24_u8
+            && self.quiet_end < 24_u8
+            && self.idle_pages != 0_u8
+            && self.idle_pages & !ALL_PAGES == 0_u8
+            && (self.idle_date_format as usize) < DateFormat::ALL.len()
+            && (1_u8..=MAX_PAGE_SECONDS).contains(&self.idle_page_seconds);
+        match in_range {
+            true => Ok(()),
+            false => Err(SettingsError::OutOfRange),
+        }
+    }
+
+    /// Encode the whole record, header and CRC included
+    pub fn encode(&self) -> [u8; RECORD_LENGTH] {
+        let mut record = [0_u8; RECORD_LENGTH];
+        record[0] = MAGIC;
+        record[1] = SETTINGS_VERSION;
+        record[2] = PAYLOAD_LENGTH as u8;
+        record[HEADER_LENGTH..HEADER_LENGTH + PAYLOAD_LENGTH]
+            .copy_from_slice(&self.encode_payload());
+        let crc = crc16(&record[1..HEADER_LENGTH + PAYLOAD_LENGTH]);
+        record[HEADER_LENGTH + PAYLOAD_LENGTH..].copy_from_slice(&crc.to_le_bytes());
+        record
+    }
+
+    /// Decode a record, migrating it forward if it is from older firmware
+    pub fn decode(record: &[u8], defaults: &Settings) -> Result<Self, SettingsError> {
+        if record.len() < HEADER_LENGTH || record[0] != MAGIC {
+            return Err(SettingsError::Blank);
+        }
+        let version = record[1];
+        let length = record[2] as usize;
+        if version == 0_u8 || version > SETTINGS_VERSION {
+            return Err(SettingsError::UnsupportedVersion(version));
+        }
+        if length != PAYLOAD_LENGTHS[version as usize - 1]
+            || record.len() < HEADER_LENGTH + length + CRC_LENGTH
+        {
+            return Err(SettingsError::BadLength);
+        }
+
+        let crc_start = HEADER_LENGTH + length;
+        let crc = u16::from_le_bytes([record[crc_start], record[crc_start + 1]]);
+        if crc16(&record[1..crc_start]) != crc {
+            return Err(SettingsError::BadCrc);
+        }
+
+        Self::decode_payload(&record[HEADER_LENGTH..crc_start], defaults)
+    }
+
+    pub fn load(eeprom: &impl Eeprom, defaults: &Settings) -> Result<Self, SettingsError> {
+        let mut record = [0_u8; SETTINGS_REGION_SIZE as usize];
+        eeprom.read(SETTINGS_ADDRESS, &mut record);
+        Self::decode(&record, defaults)
+    }
+
+    /// Save the settings, only writing the bytes that changed
+    pub fn store(&self, eeprom: &mut impl Eeprom) {
+        eeprom.update(SETTINGS_ADDRESS, &self.encode());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eeprom::MemoryEeprom;
+
+    const DEFAULTS: Settings = Settings {
+        alarm_enabled: false,
+        alarm_hours: 5_u8,
+        alarm_minutes: 0_u8,
+        alarm_sound: SOUND_MELODY,
+        alarm_tune: 0_u8,
+        escalation_ramp_seconds: 120_u16,
+        escalation_start_frequency: 1_319_u16,
+        escalation_end_frequency: 3_136_u16,
+        alarm_max_ring_minutes: 15_u8,
+        chime_mode: 0_u8,
+        chime_style: 0_u8,
+        quiet_start: 22_u8,
+        quiet_end: 7_u8,
+        brightness: 8_u8,
+        alarms: [StoredAlarm {
+            hours: 0_u8,
+            minutes: 0_u8,
+            flags: 0_u8,
+            sound: 0_u8,
+        }; MAX_ALARMS],
+        log_levels: [Level::Info as u8; TAG_COUNT],
+        idle_pages: 0b011_u8,
+        idle_date_format: 0_u8,
+        idle_page_seconds: 5_u8,
+    };
+
+    fn custom() -> Settings {
+        Settings {
+            alarm_enabled: true,
+            alarm_hours: 6_u8,
+            alarm_minutes: 45_u8,
+            alarm_sound: SOUND_ESCALATING,
+            escalation_ramp_seconds: 600_u16,
+            quiet_start: 23_u8,
+            brightness: 3_u8,
+            alarms: [
+                StoredAlarm {
+                    hours: 6_u8,
+                    minutes: 45_u8,
+                    flags: ALARM_PRESENT | ALARM_ENABLED,
+                    sound: SOUND_PRESET | PRESET_CUSTOM,
+                },
+                StoredAlarm::default(),
+                StoredAlarm {
+                    hours: 9_u8,
+                    minutes: 30_u8,
+                    flags: ALARM_PRESENT,
+                    sound: 2_u8,
+                },
+                StoredAlarm::default(),
+            ],
+            log_levels: [
+                Level::Debug as u8,
+                Level::Trace as u8,
+                Level::Off as u8,
+                Level::Info as u8,
+                Level::Info as u8,
+                Level::Info as u8,
+                Level::Info as u8,
+                Level::Info as u8,
+                Level::Info as u8,
+                Level::Error as u8,
+            ],
+            idle_pages: 0b101_u8,
+            idle_date_format: 3_u8,
+            idle_page_seconds: 20_u8,
+            ..DEFAULTS
+        }
+    }
+
+    /// A record as written by version 1 firmware
+    fn version_1_record(settings: &Settings) -> [u8; HEADER_LENGTH + 16 + CRC_LENGTH] {
+        let mut record = [0_u8; HEADER_LENGTH + 16 + CRC_LENGTH];
+        record[0] = MAGIC;
+        record[1] = 1_u8;
+        record[2] = 16_u8;
+        record[HEADER_LENGTH..HEADER_LENGTH + 16].copy_from_slice(&settings.encode_payload()[..16]);
+        let crc = crc16(&record[1..HEADER_LENGTH + 16]);
+        record[HEADER_LENGTH + 16..].copy_from_slice(&crc.to_le_bytes());
+        record
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut eeprom = MemoryEeprom::<1024>::new();
+        custom().store(&mut eeprom);
+        assert_eq!(Settings::load(&eeprom, &DEFAULTS), Ok(custom()));
+    }
+
+    #[test]
+    fn blank_eeprom() {
+        let eeprom = MemoryEeprom::<1024>::new();
+        assert_eq!(
+            Settings::load(&eeprom, &DEFAULTS),
+            Err(SettingsError::Blank)
+        );
+    }
+
+    #[test]
+    fn corrupted() {
+        let mut eeprom = MemoryEeprom::<1024>::new();
+        custom().store(&mut eeprom);
+        eeprom.bytes[HEADER_LENGTH + 2] ^= 0b1_u8;
+        assert_eq!(
+            Settings::load(&eeprom, &DEFAULTS),
+            Err(SettingsError::BadCrc)
+        );
+    }
+
+    #[test]
+    fn newer_version() {
+        let mut record = custom().encode();
+        record[1] = SETTINGS_VERSION + 1_u8;
+        assert_eq!(
+            Settings::decode(&record, &DEFAULTS),
+            Err(SettingsError::UnsupportedVersion(SETTINGS_VERSION + 1_u8))
+        );
+    }
+
+    #[test]
+    fn wrong_length() {
+        let mut record = custom().encode();
+        record[2] -= 1_u8;
+        assert_eq!(
+            Settings::decode(&record, &DEFAULTS),
+            Err(SettingsError::BadLength)
+        );
+    }
+
+    #[test]
+    fn out_of_range() {
+        let settings = Settings {
+            alarm_hours: 24_u8,
+            ..custom()
+        };
+        assert_eq!(
+            Settings::decode(&settings.encode(), &DEFAULTS),
+            Err(SettingsError::OutOfRange)
+        );
+
+        let mut settings = custom();
+        settings.log_levels[3] = Level::Trace as u8 + 1_u8;
+        assert_eq!(
+            Settings::decode(&settings.encode(), &DEFAULTS),
+            Err(SettingsError::OutOfRange)
+        );
+
+        for settings in [
+            Settings {
+                alarm_tune: 4_u8,
+                ..custom()
+            },
+            Settings {
+                chime_mode: 3_u8,
+                ..custom()
+            },
+            Settings {
+                chime_style: 2_u8,
+                ..custom()
+            },
+            Settings {
+                escalation_ramp_seconds: 0_u16,
+                ..custom()
+            },
+            Settings {
+                escalation_start_frequency: 0_u16,
+                ..custom()
+            },
+            Settings {
+                escalation_end_frequency: 0_u16,
+                ..custom()
+            },
+            Settings {
+                idle_pages: 0_u8,
+                ..custom()
+            },
+            Settings {
+                idle_date_format: 4_u8,
+                ..custom()
+            },
+            Settings {
+                idle_page_seconds: 61_u8,
+                ..custom()
+            },
+        ] {
+            assert_eq!(
+                Settings::decode(&settings.encode(), &DEFAULTS),
+                Err(SettingsError::OutOfRange)
+            );
+        }
+    }
+
+    #[test]
+    fn older_layout_takes_new_fields_from_defaults() {
+        // Only the first fields of the current layout
+        let payload = &custom().encode_payload()[..5];
+        let migrated = Settings::decode_payload(payload, &DEFAULTS).unwrap();
+        assert_eq!(migrated.alarm_hours, 6_u8);
+        assert_eq!(migrated.alarm_minutes, 45_u8);
+        assert_eq!(migrated.escalation_ramp_seconds, 120_u16);
+        assert_eq!(migrated.quiet_start, 22_u8);
+        assert_eq!(migrated.brightness, 8_u8);
+        assert_eq!(migrated.log_levels, DEFAULTS.log_levels);
+        assert_eq!(migrated.idle_page_seconds, 5_u8);
+    }
+
+    #[test]
+    fn version_1_alarm_moves_into_the_table() {
+        let legacy = Settings {
+            alarm_enabled: false,
+            alarm_hours: 7_u8,
+            alarm_minutes: 15_u8,
+            alarm_sound: SOUND_MELODY,
+            alarm_tune: 3_u8,
+            ..DEFAULTS
+        };
+        let migrated = Settings::decode(&version_1_record(&legacy), &DEFAULTS).unwrap();
+        assert!(!migrated.alarm_enabled);
+        assert_eq!(
+            migrated.alarms[0],
+            StoredAlarm {
+                hours: 7_u8,
+                minutes: 15_u8,
+                flags: ALARM_PRESENT | ALARM_ENABLED,
+                sound: 3_u8,
+            }
+        );
+        assert!(migrated.alarms[1..]
+            .iter()
+            .all(|alarm| alarm.flags & ALARM_PRESENT == 0_u8));
+
+        // An escalating alarm keeps its profile as the custom preset
+        let legacy = Settings {
+            alarm_sound: SOUND_ESCALATING,
+            escalation_ramp_seconds: 42_u16,
+            ..legacy
+        };
+        let migrated = Settings::decode(&version_1_record(&legacy), &DEFAULTS).unwrap();
+        assert_eq!(migrated.alarms[0].sound, SOUND_PRESET | PRESET_CUSTOM);
+        assert_eq!(migrated.escalation_ramp_seconds, 42_u16);
+    }
+
+    #[test]
+    fn alarms_out_of_range() {
+        let mut settings = custom();
+        settings.alarms[1].hours = 25_u8;
+        // Only present alarms are checked
+        assert!(Settings::decode(&settings.encode(), &DEFAULTS).is_ok());
+        settings.alarms[1].flags = ALARM_PRESENT;
+        assert_eq!(
+            Settings::decode(&settings.encode(), &DEFAULTS),
+            Err(SettingsError::OutOfRange)
+        );
+
+        let mut settings = custom();
+        settings.alarms[0].sound = SOUND_PRESET | 4_u8;
+        assert_eq!(
+            Settings::decode(&settings.encode(), &DEFAULTS),
+            Err(SettingsError::OutOfRange)
+        );
+
+        // A tune that doesn't exist
+        let mut settings = custom();
+        settings.alarms[2].sound = 9_u8;
+        assert_eq!(
+            Settings::decode(&settings.encode(), &DEFAULTS),
+            Err(SettingsError::OutOfRange)
+        );
+    }
+
+    #[test]
+    fn only_changed_bytes_are_written() {
+        let mut eeprom = MemoryEeprom::<1024>::new();
+        custom().store(&mut eeprom);
+        let writes = eeprom.writes;
+        custom().store(&mut eeprom);
+        assert_eq!(eeprom.writes, writes);
+
+        // The alarm minute plus the CRC
+        Settings {
+            alarm_minutes: 46_u8,
+            ..custom()
+        }
+        .store(&mut eeprom);
+        assert!(eeprom.writes - writes <= 3_usize);
+    }
+}
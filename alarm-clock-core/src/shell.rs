@@ -0,0 +1,597 @@
+//! Line-oriented command shell over the serial console. This is only the line
+//! editing and parsing; the commands are run by `commands`.
+
+use alarm_clock_protocol::DateTime;
+use heapless::Vec;
+
+use crate::{
+    calendar::is_valid_date,
+    idle_screen::{DateFormat, MAX_PAGE_SECONDS, PAGE_NAMES},
+    log::{Level, Tag},
+};
+
+pub const MAX_LINE_LENGTH: usize = 48_usize;
+pub const PROMPT: &str = "> ";
+
+pub const HELP: &str = "\
+help                          this listing
+time                          show the time
+settime HH:MM[:SS]            set the time
+date                          show the date
+setdate YYYY-MM-DD            set the date
+sync [YYYY-MM-DD HH:MM:SS]    read or set the date and time at once, for
+                              alarm-clock-cli
+alarm list                    list the alarms
+alarm add HH:MM [SOUND]       add an alarm; SOUND is a tune name or
+                              `escalate [gentle|standard|urgent|custom]`,
+                              where every custom alarm shares the one
+                              profile set by alarm-clock-cli import
+alarm del N                   delete alarm N
+alarm on|off [N]              enable or disable alarm N, or all alarms
+status                        show the clock's status
+brightness [1-8]              show or set the display brightness
+screen                        show the idle screen's settings
+screen pages PAGE[,PAGE...]   rotate through the date, alarm and big (time)
+                              pages
+screen date dmy|mdy|iso|text  set the date format
+screen rotate SECONDS         show each page for 1-60 seconds
+log                           show the log level of each tag
+log TAG|all LEVEL             set the log level (off, error, warn, info,
+                              debug or trace) of one or all tags
+log save                      keep the log levels across resets
+reset                         reset the clock
+Backspace erases, ^U clears the line, ^C cancels";
+
+const BACKSPACE: u8 = 0x08_u8;
+const DELETE: u8 = 0x7F_u8;
+const CTRL_C: u8 = 0x03_u8;
+const CTRL_U: u8 = 0x15_u8;
+
+/// What the terminal should be sent after a byte was received
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LineEvent {
+    /// Nothing to echo (e.g. an ignored control character)
+    Nothing,
+    Echo(u8),
+    /// Erase the last character on the terminal
+    Erase,
+    /// Erase this many characters from the terminal
+    Clear(usize),
+    /// The line was cancelled and should be ended without running
+    Cancel,
+    /// The line is complete and can be read with `LineEditor::line`
+    Submit,
+}
+
+pub struct LineEditor {
+    buffer: Vec<u8, MAX_LINE_LENGTH>,
+    /// Set after a CR so the LF of a CRLF doesn't submit an empty line
+    last_was_cr: bool,
+}
+impl LineEditor {
+    pub fn new() -> Self {
+        Self {
+            buffer: Vec::new(),
+            last_was_cr: false,
+        }
+    }
+
+    pub fn push(&mut self, byte: u8) -> LineEvent {
+        let last_was_cr = core::mem::replace(&mut self.last_was_cr, byte == b'\r');
+        match byte {
+            b'\n' if last_was_cr => LineEvent::Nothing,
+            b'\r' | b'\n' => LineEvent::Submit,
+            BACKSPACE | DELETE => match self.buffer.pop() {
+                Some(_) => LineEvent::Erase,
+                None => LineEvent::Nothing,
+            },
+            CTRL_U => {
+                let length = self.buffer.len();
+                self.buffer.clear();
+                LineEvent::Clear(length)
+            }
+            CTRL_C => {
+                self.buffer.clear();
+                LineEvent::Cancel
+            }
+            b' '..=b'~' => match self.buffer.push(byte) {
+                Ok(()) => LineEvent::Echo(byte),
+                // Full; the terminal shouldn't show what we didn't keep
+                Err(_) => LineEvent::Nothing,
+            },
+            _ => LineEvent::Nothing,
+        }
+    }
+
+    /// The submitted line. Only printable ASCII is ever buffered.
+    pub fn line(&self) -> &str {
+        core::str::from_utf8(&self.buffer).unwrap_or_default()
+    }
+
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+    }
+}
+impl Default for LineEditor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Command<'a> {
+    Help,
+    Time,
+    SetTime {
+        hours: u8,
+        minutes: u8,
+        seconds: u8,
+    },
+    Date,
+    /// The year is from 20[00-99]
+    SetDate {
+        year: u8,
+        month: u8,
+        day: u8,
+    },
+    /// Read the clock, or set it first, replying in a fixed format for the host
+    Sync(Option<DateTime>),
+    AlarmList,
+    AlarmAdd {
+        hours: u8,
+        minutes: u8,
+        /// The rest of the line, naming the sound
+        sound: Option<&'a str>,
+    },
+    AlarmDelete(u8),
+    /// Enable or disable one alarm, or the alarms as a whole if no alarm is given
+    AlarmEnable {
+        alarm: Option<u8>,
+        enabled: bool,
+    },
+    Status,
+    Brightness(Option<u8>),
+    /// Show the idle screen's settings, or change one
+    Screen(Option<ScreenSetting>),
+    LogList,
+    /// Set the log level of one tag, or all of them if no tag is given
+    LogSet {
+        tag: Option<Tag>,
+        level: Level,
+    },
+    LogSave,
+    Reset,
+}
+
+/// A change to the idle screen's settings
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ScreenSetting {
+    /// The `PAGE_*`s to rotate through
+    Pages(u8),
+    DateFormat(DateFormat),
+    /// Seconds each page is shown for
+    Rotate(u8),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ParseError {
+    Empty,
+    UnknownCommand,
+    MissingArgument,
+    BadArgument,
+    TooManyArguments,
+}
+impl ParseError {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ParseError::Empty => "empty line",
+            ParseError::UnknownCommand => "unknown command, try `help`",
+            ParseError::MissingArgument => "missing argument",
+            ParseError::BadArgument => "bad argument",
+            ParseError::TooManyArguments => "too many arguments",
+        }
+    }
+}
+
+pub fn parse(line: &str) -> Result<Command<'_>, ParseError> {
+    let mut words = line.split_ascii_whitespace();
+    let command = match words.next().ok_or(ParseError::Empty)? {
+        "help" | "?" => Command::Help,
+        "time" => Command::Time,
+        "settime" => {
+            let (hours, minutes, seconds) = parse_time(next(&mut words)?)?;
+            Command::SetTime {
+                hours,
+                minutes,
+                seconds,
+            }
+        }
+        "date" => Command::Date,
+        "setdate" => {
+            let (year, month, day) = parse_date(next(&mut words)?)?;
+            Command::SetDate { year, month, day }
+        }
+        "sync" => match words.next() {
+            Some(date) => {
+                let (year, month, day) = parse_date(date)?;
+                let (hours, minutes, seconds) = parse_time(next(&mut words)?)?;
+                Command::Sync(Some(DateTime {
+                    year,
+                    month,
+                    day,
+                    hours,
+                    minutes,
+                    seconds,
+                }))
+            }
+            None => Command::Sync(None),
+        },
+        "alarm" => match next(&mut words)? {
+            "list" => Command::AlarmList,
+            "add" => {
+                let (hours, minutes, seconds) = parse_time(next(&mut words)?)?;
+                if seconds != 0_u8 {
+                    return Err(ParseError::BadArgument);
+                }
+                // The sound may be several words, so it's the rest of the line
+                return Ok(Command::AlarmAdd {
+                    hours,
+                    minutes,
+                    sound: sound_argument(line),
+                });
+            }
+            "del" | "delete" => Command::AlarmDelete(parse_number(next(&mut words)?, 255_u8)?),
+            "on" | "off" => {
+                let enabled = line.split_ascii_whitespace().nth(1) == Some("on");
+                let alarm = match words.next() {
+                    Some(alarm) => Some(parse_number(alarm, 255_u8)?),
+                    None => None,
+                };
+                Command::AlarmEnable { alarm, enabled }
+            }
+            _ => return Err(ParseError::UnknownCommand),
+        },
+        "status" => Command::Status,
+        "brightness" => match words.next() {
+            Some(level) => match parse_number(level, 8_u8)? {
+                0_u8 => return Err(ParseError::BadArgument),
+                level => Command::Brightness(Some(level)),
+            },
+            None => Command::Brightness(None),
+        },
+        "screen" => match words.next() {
+            None => Command::Screen(None),
+            Some("pages") => {
+                let pages =
+                    next(&mut words)?
+                        .split(',')
+                        .try_fold(0_u8, |pages, name| {
+                            match PAGE_NAMES.iter().position(|page| *page == name) {
+                                Some(page) => Ok(pages | 1_u8 << page),
+                                None => Err(ParseError::BadArgument),
+                            }
+                        })?;
+                Command::Screen(Some(ScreenSetting::Pages(pages)))
+            }
+            Some("date") => {
+                let name = next(&mut words)?;
+                let format = DateFormat::ALL
+                    .iter()
+                    .find(|format| format.name() == name)
+                    .ok_or(ParseError::BadArgument)?;
+                Command::Screen(Some(ScreenSetting::DateFormat(*format)))
+            }
+            Some("rotate") => match parse_number(next(&mut words)?, MAX_PAGE_SECONDS)? {
+                0_u8 => return Err(ParseError::BadArgument),
+                seconds => Command::Screen(Some(ScreenSetting::Rotate(seconds))),
+            },
+            Some(_) => return Err(ParseError::UnknownCommand),
+        },
+        "log" => match words.next() {
+            None => Command::LogList,
+            Some("save") => Command::LogSave,
+            Some(tag) => {
+                let tag = match tag {
+                    "all" => None,
+                    tag => Some(Tag::parse(tag).ok_or(ParseError::BadArgument)?),
+                };
+                let level = Level::parse(next(&mut words)?).ok_or(ParseError::BadArgument)?;
+                Command::LogSet { tag, level }
+            }
+        },
+        "reset" => Command::Reset,
+        _ => return Err(ParseError::UnknownCommand),
+    };
+
+    match words.next() {
+        Some(_) => Err(ParseError::TooManyArguments),
+        None => Ok(command),
+    }
+}
+
+fn next<'a>(words: &mut impl Iterator<Item = &'a str>) -> Result<&'a str, ParseError> {
+    words.next().ok_or(ParseError::MissingArgument)
+}
+
+/// Everything after `alarm add HH:MM`, with the whitespace trimmed
+fn sound_argument(line: &str) -> Option<&str> {
+    let mut rest = line.trim_start();
+    for _ in 0..3 {
+        let end = rest.find(|c: char| c.is_ascii_whitespace())?;
+        rest = rest[end..].trim_start();
+    }
+    match rest.trim_end() {
+        "" => None,
+        sound => Some(sound),
+    }
+}
+
+/// Parse a decimal number no bigger than `max`
+fn parse_number(word: &str, max: u8) -> Result<u8, ParseError> {
+    if word.is_empty() || word.len() > 3 || !word.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(ParseError::BadArgument);
+    }
+    let number = word.bytes().fold(0_u16, |number, digit| {
+        number * 10_u16 + (digit - b'0') as u16
+    });
+    match number <= max as u16 {
+        true => Ok(number as u8),
+        false => Err(ParseError::BadArgument),
+    }
+}
+
+/// HH:MM or HH:MM:SS
+fn parse_time(word: &str) -> Result<(u8, u8, u8), ParseError> {
+    let mut parts = word.split(':');
+    let hours = parse_number(parts.next().unwrap_or_default(), 23_u8)?;
+    let minutes = parse_number(parts.next().ok_or(ParseError::BadArgument)?, 59_u8)?;
+    let seconds = match parts.next() {
+        Some(seconds) => parse_number(seconds, 59_u8)?,
+        None => 0_u8,
+    };
+    match parts.next() {
+        Some(_) => Err(ParseError::BadArgument),
+        None => Ok((hours, minutes, seconds)),
+    }
+}
+
+/// YYYY-MM-DD where the year is from 2000 to 2099
+fn parse_date(word: &str) -> Result<(u8, u8, u8), ParseError> {
+    let mut parts = word.split('-');
+    let century = parts.next().unwrap_or_default();
+    if century.len() != 4 || !century.starts_with("20") {
+        return Err(ParseError::BadArgument);
+    }
+    let year = parse_number(&century[2..], 99_u8)?;
+    let month = parse_number(parts.next().ok_or(ParseError::BadArgument)?, 12_u8)?;
+    let day = parse_number(parts.next().ok_or(ParseError::BadArgument)?, 31_u8)?;
+    if parts.next().is_some() || !is_valid_date(year, month, day) {
+        return Err(ParseError::BadArgument);
+    }
+    Ok((year, month, day))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_line(editor: &mut LineEditor, text: &[u8]) -> LineEvent {
+        let mut last = LineEvent::Nothing;
+        for byte in text {
+            last = editor.push(*byte);
+        }
+        last
+    }
+
+    #[test]
+    fn line_editing() {
+        let mut editor = LineEditor::new();
+        assert_eq!(editor.push(b't'), LineEvent::Echo(b't'));
+        assert_eq!(type_line(&mut editor, b"imx"), LineEvent::Echo(b'x'));
+        assert_eq!(editor.push(DELETE), LineEvent::Erase);
+        assert_eq!(editor.push(b'e'), LineEvent::Echo(b'e'));
+        assert_eq!(editor.push(b'\r'), LineEvent::Submit);
+        assert_eq!(editor.line(), "time");
+
+        // The LF of the CRLF is swallowed
+        editor.clear();
+        assert_eq!(editor.push(b'\n'), LineEvent::Nothing);
+        assert_eq!(editor.push(b'\n'), LineEvent::Submit);
+        assert_eq!(editor.line(), "");
+    }
+
+    #[test]
+    fn clearing_and_cancelling() {
+        let mut editor = LineEditor::new();
+        type_line(&mut editor, b"status");
+        assert_eq!(editor.push(CTRL_U), LineEvent::Clear(6));
+        assert_eq!(editor.line(), "");
+        assert_eq!(editor.push(BACKSPACE), LineEvent::Nothing);
+
+        type_line(&mut editor, b"reset");
+        assert_eq!(editor.push(CTRL_C), LineEvent::Cancel);
+        assert_eq!(editor.line(), "");
+
+        // Other control characters are ignored
+        assert_eq!(editor.push(0x1B_u8), LineEvent::Nothing);
+    }
+
+    #[test]
+    fn overlong_lines_are_truncated() {
+        let mut editor = LineEditor::new();
+        type_line(&mut editor, &[b'a'; MAX_LINE_LENGTH]);
+        assert_eq!(editor.push(b'b'), LineEvent::Nothing);
+        assert_eq!(editor.push(b'\n'), LineEvent::Submit);
+        assert_eq!(editor.line().len(), MAX_LINE_LENGTH);
+    }
+
+    #[test]
+    fn simple_commands() {
+        assert_eq!(parse("help"), Ok(Command::Help));
+        assert_eq!(parse("  time  "), Ok(Command::Time));
+        assert_eq!(parse("date"), Ok(Command::Date));
+        assert_eq!(parse("status"), Ok(Command::Status));
+        assert_eq!(parse("reset"), Ok(Command::Reset));
+        assert_eq!(parse("alarm list"), Ok(Command::AlarmList));
+        assert_eq!(parse(""), Err(ParseError::Empty));
+        assert_eq!(parse("launch"), Err(ParseError::UnknownCommand));
+        assert_eq!(parse("time now"), Err(ParseError::TooManyArguments));
+    }
+
+    #[test]
+    fn time_and_date() {
+        assert_eq!(
+            parse("settime 7:05"),
+            Ok(Command::SetTime {
+                hours: 7_u8,
+                minutes: 5_u8,
+                seconds: 0_u8
+            })
+        );
+        assert_eq!(
+            parse("settime 23:59:58"),
+            Ok(Command::SetTime {
+                hours: 23_u8,
+                minutes: 59_u8,
+                seconds: 58_u8
+            })
+        );
+        assert_eq!(parse("settime 24:00"), Err(ParseError::BadArgument));
+        assert_eq!(parse("settime 12"), Err(ParseError::BadArgument));
+        assert_eq!(parse("settime 1:2:3:4"), Err(ParseError::BadArgument));
+        assert_eq!(parse("settime"), Err(ParseError::MissingArgument));
+
+        assert_eq!(
+            parse("setdate 2024-02-29"),
+            Ok(Command::SetDate {
+                year: 24_u8,
+                month: 2_u8,
+                day: 29_u8
+            })
+        );
+        assert_eq!(parse("setdate 2023-02-29"), Err(ParseError::BadArgument));
+        assert_eq!(parse("setdate 1999-01-01"), Err(ParseError::BadArgument));
+        assert_eq!(parse("setdate 23-01-01"), Err(ParseError::BadArgument));
+    }
+
+    #[test]
+    fn sync() {
+        assert_eq!(parse("sync"), Ok(Command::Sync(None)));
+        assert_eq!(
+            parse("sync 2024-02-29 23:59:58"),
+            Ok(Command::Sync(Some(DateTime {
+                year: 24_u8,
+                month: 2_u8,
+                day: 29_u8,
+                hours: 23_u8,
+                minutes: 59_u8,
+                seconds: 58_u8
+            })))
+        );
+        assert_eq!(parse("sync 2024-02-29"), Err(ParseError::MissingArgument));
+        assert_eq!(parse("sync 23:59:58"), Err(ParseError::BadArgument));
+        assert_eq!(
+            parse("sync 2024-02-29 23:59:58 now"),
+            Err(ParseError::TooManyArguments)
+        );
+    }
+
+    #[test]
+    fn alarms() {
+        assert_eq!(
+            parse("alarm add 6:30"),
+            Ok(Command::AlarmAdd {
+                hours: 6_u8,
+                minutes: 30_u8,
+                sound: None
+            })
+        );
+        assert_eq!(
+            parse("alarm  add 06:30   escalate gentle "),
+            Ok(Command::AlarmAdd {
+                hours: 6_u8,
+                minutes: 30_u8,
+                sound: Some("escalate gentle")
+            })
+        );
+        assert_eq!(parse("alarm add 6:30:01"), Err(ParseError::BadArgument));
+        assert_eq!(parse("alarm del 2"), Ok(Command::AlarmDelete(2_u8)));
+        assert_eq!(parse("alarm del two"), Err(ParseError::BadArgument));
+        assert_eq!(
+            parse("alarm off"),
+            Ok(Command::AlarmEnable {
+                alarm: None,
+                enabled: false
+            })
+        );
+        assert_eq!(
+            parse("alarm on 1"),
+            Ok(Command::AlarmEnable {
+                alarm: Some(1_u8),
+                enabled: true
+            })
+        );
+        assert_eq!(parse("alarm snooze"), Err(ParseError::UnknownCommand));
+        assert_eq!(parse("alarm"), Err(ParseError::MissingArgument));
+    }
+
+    #[test]
+    fn brightness() {
+        assert_eq!(parse("brightness"), Ok(Command::Brightness(None)));
+        assert_eq!(parse("brightness 8"), Ok(Command::Brightness(Some(8_u8))));
+        assert_eq!(parse("brightness 0"), Err(ParseError::BadArgument));
+        assert_eq!(parse("brightness 9"), Err(ParseError::BadArgument));
+    }
+
+    #[test]
+    fn screen() {
+        use crate::idle_screen::{PAGE_BIG_TIME, PAGE_DATE};
+
+        assert_eq!(parse("screen"), Ok(Command::Screen(None)));
+        assert_eq!(
+            parse("screen pages date,big"),
+            Ok(Command::Screen(Some(ScreenSetting::Pages(
+                PAGE_DATE | PAGE_BIG_TIME
+            ))))
+        );
+        assert_eq!(parse("screen pages date,"), Err(ParseError::BadArgument));
+        assert_eq!(parse("screen pages"), Err(ParseError::MissingArgument));
+        assert_eq!(
+            parse("screen date iso"),
+            Ok(Command::Screen(Some(ScreenSetting::DateFormat(
+                DateFormat::Iso
+            ))))
+        );
+        assert_eq!(parse("screen date ymd"), Err(ParseError::BadArgument));
+        assert_eq!(
+            parse("screen rotate 10"),
+            Ok(Command::Screen(Some(ScreenSetting::Rotate(10_u8))))
+        );
+        assert_eq!(parse("screen rotate 0"), Err(ParseError::BadArgument));
+        assert_eq!(parse("screen rotate 61"), Err(ParseError::BadArgument));
+        assert_eq!(parse("screen blink"), Err(ParseError::UnknownCommand));
+    }
+
+    #[test]
+    fn log() {
+        assert_eq!(parse("log"), Ok(Command::LogList));
+        assert_eq!(parse("log save"), Ok(Command::LogSave));
+        assert_eq!(
+            parse("log rtc trace"),
+            Ok(Command::LogSet {
+                tag: Some(Tag::Rtc),
+                level: Level::Trace
+            })
+        );
+        assert_eq!(
+            parse("log all off"),
+            Ok(Command::LogSet {
+                tag: None,
+                level: Level::Off
+            })
+        );
+        assert_eq!(parse("log rtc"), Err(ParseError::MissingArgument));
+        assert_eq!(parse("log lcd debug"), Err(ParseError::BadArgument));
+        assert_eq!(parse("log rtc loud"), Err(ParseError::BadArgument));
+    }
+}
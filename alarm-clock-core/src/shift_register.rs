@@ -0,0 +1,604 @@
+//! An arbitrarily-lengthed latching shift register that supports updating all
+//! output drains at once, unlike the `shift-register-driver` crate (see issue #1
+//! of their crate)
+//!
+//! `ChainedShiftRegister` bit-bangs any three pins, `PortShiftRegister`
+//! bit-bangs three pins on the same port by writing the whole port at once,
+//! and `SpiShiftRegister` shifts a byte at a time through an SPI peripheral
+//! and only toggles the latch itself. They share `ShiftRegister`, which keeps
+//! the outputs and only has them shifted out when one changed.
+
+use core::{convert::Infallible, fmt};
+use embedded_hal::{blocking::spi, digital::v2::OutputPin};
+use ufmt_write::uWrite;
+
+use crate::hal::{OutputPort, SegmentDisplay};
+
+// Defined on page 4 of https://www.ti.com/lit/ds/symlink/tpic6595.pdf
+const SERIAL_SETUP_NS: u32 = 10_u32; // Tsu, SER before SRCK rises
+const SERIAL_HOLD_NS: u32 = 10_u32; // Th, SER after SRCK rises
+const CLOCK_WIDTH_NS: u32 = 20_u32; // Tw, SRCK and RCK high or low
+/// One cycle of the ATmega328P at 16MHz, the least a pin write can take
+const CYCLE_NS: u32 = 62_u32;
+// So consecutive pin writes are always far enough apart, without waiting
+const _: () = assert!(
+    SERIAL_SETUP_NS <= CYCLE_NS && SERIAL_HOLD_NS <= CYCLE_NS && CLOCK_WIDTH_NS <= CYCLE_NS
+);
+
+/// An output enable pin that's tied low on the board, so the outputs are
+/// always enabled
+pub struct TiedOff;
+impl OutputPin for TiedOff {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
+}
+
+/// The outputs of `R` shift registers as they've been set and as they were
+/// last latched, which every driver keeps the same way
+pub struct Outputs<const R: usize> {
+    /// Each register's outputs, first output in the least significant bit
+    bits: [u8; R],
+    latched: [u8; R],
+    /// Whether anything's been latched, as the outputs aren't known before
+    committed: bool,
+}
+impl<const R: usize> Outputs<R> {
+    fn new() -> Self {
+        Self {
+            bits: [0_u8; R],
+            latched: [0_u8; R],
+            committed: false,
+        }
+    }
+}
+
+/// `R` latching shift registers of 8 outputs each. Outputs are set with
+/// `set_bit` and only shifted out by `commit`, and only if any changed, so
+/// setting many at once costs a single shift. Drivers only differ in how they
+/// shift the outputs out and latch them.
+pub trait ShiftRegister<const R: usize> {
+    fn outputs(&self) -> &Outputs<R>;
+    fn outputs_mut(&mut self) -> &mut Outputs<R>;
+
+    /// Shift out every register's outputs, the last register's first, and
+    /// latch them
+    fn shift_out(&mut self, bits: [u8; R]);
+
+    /// Which registers have their outputs enabled
+    fn enabled(&self) -> [bool; R] {
+        [true; R]
+    }
+
+    /// Set an output, which is shown on the next commit. Panics if there's no
+    /// such output.
+    fn set_bit(&mut self, index: usize, state: bool) {
+        let outputs = self.outputs_mut();
+        let (register, mask) = (index / 8_usize, 1_u8 << (index % 8_usize));
+        let bits = if state {
+            outputs.bits[register] | mask
+        } else {
+            outputs.bits[register] & !mask
+        };
+        outputs.bits[register] = bits;
+    }
+
+    /// An output as it'll be after the next commit
+    fn bit(&self, index: usize) -> bool {
+        self.outputs().bits[index / 8_usize] >> (index % 8_usize) & 1_u8 == 1_u8
+    }
+
+    /// Whether any output will change on the next commit
+    fn is_dirty(&self) -> bool {
+        let outputs = self.outputs();
+        !outputs.committed || outputs.bits != outputs.latched
+    }
+
+    /// Shift out and latch every output if any changed since the last commit,
+    /// returning whether they were
+    fn commit(&mut self) -> bool {
+        if !self.is_dirty() {
+            return false;
+        }
+        let bits = self.outputs().bits;
+        self.shift_out(bits);
+        let outputs = self.outputs_mut();
+        outputs.latched = bits;
+        outputs.committed = true;
+        true
+    }
+
+    /// What every output pin should be right now, to compare with the hardware
+    fn expected_outputs(&self) -> ExpectedOutputs<R> {
+        ExpectedOutputs {
+            latched: self.outputs().latched,
+            enabled: self.enabled(),
+            uncommitted: self.is_dirty(),
+        }
+    }
+}
+
+/// Time displays, each a pair of shift registers
+impl<T: ShiftRegister<2_usize>> SegmentDisplay for T {
+    fn write(&mut self, outputs: [bool; 16]) {
+        for (index, state) in outputs.into_iter().enumerate() {
+            self.set_bit(index, state);
+        }
+        self.commit();
+    }
+}
+
+/// `R` daisy-chained shift registers of 8 outputs each, bit-banged through
+/// any three pins, with each register's output enable on its own pin (or
+/// `TiedOff`). Output 0 is the first output of the register nearest the
+/// serial input.
+pub struct ChainedShiftRegister<const R: usize, SerialInput, Clock, Latch, OutputEnable = TiedOff>
+where
+    SerialInput: OutputPin,
+    Clock: OutputPin,
+    Latch: OutputPin,
+    OutputEnable: OutputPin,
+{
+    pub serial_input_pin: SerialInput,
+    pub clock_pin: Clock,
+    pub latch_pin: Latch,
+    output_enable_pins: [OutputEnable; R],
+    outputs: Outputs<R>,
+    enabled: [bool; R],
+}
+impl<const R: usize, SerialInput, Clock, Latch, OutputEnable>
+    ChainedShiftRegister<R, SerialInput, Clock, Latch, OutputEnable>
+where
+    SerialInput: OutputPin,
+    Clock: OutputPin,
+    Latch: OutputPin,
+    OutputEnable: OutputPin,
+{
+    /// The registers' outputs are enabled, but are whatever they powered on
+    /// with until the first commit
+    pub fn new(
+        serial_input_pin: SerialInput,
+        clock_pin: Clock,
+        latch_pin: Latch,
+        output_enable_pins: [OutputEnable; R],
+    ) -> Self {
+        let mut shift_register = Self {
+            serial_input_pin,
+            clock_pin,
+            latch_pin,
+            output_enable_pins,
+            outputs: Outputs::new(),
+            enabled: [false; R],
+        };
+        for register in 0_usize..R {
+            shift_register.set_output_enabled(register, true);
+        }
+        shift_register
+    }
+
+    /// Turn a register's outputs on or off, keeping what's latched in it.
+    /// This takes effect straight away.
+    pub fn set_output_enabled(&mut self, register: usize, enabled: bool) {
+        // Active low
+        let pin = &mut self.output_enable_pins[register];
+        let _ = if enabled {
+            pin.set_low()
+        } else {
+            pin.set_high()
+        };
+        self.enabled[register] = enabled;
+    }
+
+    pub fn output_enabled(&self, register: usize) -> bool {
+        self.enabled[register]
+    }
+}
+impl<const R: usize, SerialInput, Clock, Latch, OutputEnable> ShiftRegister<R>
+    for ChainedShiftRegister<R, SerialInput, Clock, Latch, OutputEnable>
+where
+    SerialInput: OutputPin,
+    Clock: OutputPin,
+    Latch: OutputPin,
+    OutputEnable: OutputPin,
+{
+    fn outputs(&self) -> &Outputs<R> {
+        &self.outputs
+    }
+
+    fn outputs_mut(&mut self) -> &mut Outputs<R> {
+        &mut self.outputs
+    }
+
+    fn shift_out(&mut self, bits: [u8; R]) {
+        // The last output is shifted in first. Each pin write is at least a
+        // cycle apart, which is longer than any of the datasheet's timings, so
+        // there's no waiting in between.
+        for byte in bits.iter().rev() {
+            for bit in (0_u8..8_u8).rev() {
+                if byte >> bit & 1_u8 == 1_u8 {
+                    let _ = self.serial_input_pin.set_high();
+                } else {
+                    let _ = self.serial_input_pin.set_low();
+                }
+                let _ = self.clock_pin.set_high();
+                let _ = self.clock_pin.set_low();
+            }
+        }
+        let _ = self.latch_pin.set_low();
+        let _ = self.latch_pin.set_high();
+    }
+
+    fn enabled(&self) -> [bool; R] {
+        self.enabled
+    }
+}
+
+/// What a `ShiftRegister`'s outputs should be. It's shown as each
+/// register's outputs from the first, as `1` (on) and `0` (off), or `-` if
+/// the register is disabled, e.g. `10000001 --------` (and ` *` if there
+/// are uncommitted changes).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ExpectedOutputs<const R: usize> {
+    /// Each register's latched outputs, first output in the least significant
+    /// bit, whether or not the register is enabled (and all off before the
+    /// first commit)
+    pub latched: [u8; R],
+    pub enabled: [bool; R],
+    /// Whether outputs have been set since the last commit
+    pub uncommitted: bool,
+}
+impl<const R: usize> ExpectedOutputs<R> {
+    /// Whether an output's drain should be on, which it isn't if its register
+    /// is disabled
+    pub fn output(&self, index: usize) -> bool {
+        let register = index / 8_usize;
+        self.enabled[register] && self.latched[register] >> (index % 8_usize) & 1_u8 == 1_u8
+    }
+}
+impl<const R: usize> ExpectedOutputs<R> {
+    /// Write them as `Display` does, for `ufmt`
+    pub fn write_to<W: uWrite + ?Sized>(&self, out: &mut W) -> Result<(), W::Error> {
+        for register in 0_usize..R {
+            if register > 0_usize {
+                out.write_char(' ')?;
+            }
+            for output in 0_usize..8_usize {
+                out.write_char(match self.enabled[register] {
+                    false => '-',
+                    true if self.output(register * 8_usize + output) => '1',
+                    true => '0',
+                })?;
+            }
+        }
+        if self.uncommitted {
+            out.write_str(" *")?;
+        }
+        Ok(())
+    }
+}
+impl<const R: usize> fmt::Display for ExpectedOutputs<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write_to(&mut Formatted(f))
+    }
+}
+
+/// A `Formatter` as a `uWrite`
+struct Formatted<'a, 'b>(&'a mut fmt::Formatter<'b>);
+impl uWrite for Formatted<'_, '_> {
+    type Error = fmt::Error;
+
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        fmt::Write::write_str(self.0, s)
+    }
+}
+
+/// `R` shift registers with all three pins on one port, given as masks of it,
+/// set and committed like `ChainedShiftRegister` (without output enables).
+/// Each edge is a single write of the port, so a bit takes three writes.
+///
+/// The port is read once per commit and written back with only these pins
+/// changed, so nothing else may write it during a commit (e.g. commit from the
+/// main loop with interrupts off if an interrupt writes the port).
+pub struct PortShiftRegister<const R: usize, Port: OutputPort> {
+    pub port: Port,
+    serial_input_mask: u8,
+    clock_mask: u8,
+    latch_mask: u8,
+    outputs: Outputs<R>,
+    pub is_latched: bool,
+}
+impl<const R: usize, Port: OutputPort> PortShiftRegister<R, Port> {
+    pub fn new(port: Port, serial_input_mask: u8, clock_mask: u8, latch_mask: u8) -> Self {
+        Self {
+            port,
+            serial_input_mask,
+            clock_mask,
+            latch_mask,
+            outputs: Outputs::new(),
+            is_latched: false,
+        }
+    }
+}
+impl<const R: usize, Port: OutputPort> ShiftRegister<R> for PortShiftRegister<R, Port> {
+    fn outputs(&self) -> &Outputs<R> {
+        &self.outputs
+    }
+
+    fn outputs_mut(&mut self) -> &mut Outputs<R> {
+        &mut self.outputs
+    }
+
+    fn shift_out(&mut self, bits: [u8; R]) {
+        self.is_latched = false;
+        // Serial input and clock low
+        let idle = self.port.read() & !(self.serial_input_mask | self.clock_mask);
+        for byte in bits.iter().rev() {
+            for bit in (0_u8..8_u8).rev() {
+                let serial_input = if byte >> bit & 1_u8 == 1_u8 {
+                    idle | self.serial_input_mask
+                } else {
+                    idle
+                };
+                self.port.write(serial_input);
+                self.port.write(serial_input | self.clock_mask);
+                self.port.write(idle);
+            }
+        }
+
+        self.port.write(idle & !self.latch_mask);
+        self.port.write(idle | self.latch_mask);
+        self.is_latched = true;
+    }
+}
+
+/// `R` daisy-chained shift registers behind an SPI bus (mode 0, MSB first),
+/// set and committed like `ChainedShiftRegister` (without output enables).
+///
+/// The bus may be shared with other shift registers as long as each has its
+/// own latch: everything shifted through is overwritten by the next commit
+/// before it's latched.
+pub struct SpiShiftRegister<const R: usize, Spi, Latch>
+where
+    Spi: spi::Write<u8>,
+    Latch: OutputPin,
+{
+    pub spi: Spi,
+    pub latch_pin: Latch,
+    outputs: Outputs<R>,
+    pub is_latched: bool,
+}
+impl<const R: usize, Spi, Latch> SpiShiftRegister<R, Spi, Latch>
+where
+    Spi: spi::Write<u8>,
+    Latch: OutputPin,
+{
+    pub fn new(spi: Spi, latch_pin: Latch) -> Self {
+        Self {
+            spi,
+            latch_pin,
+            outputs: Outputs::new(),
+            is_latched: false,
+        }
+    }
+
+    /// Latch whatever was last shifted in. The latch pulse doesn't need
+    /// padding, as it's longer than the datasheet's 20ns after one instruction.
+    pub fn latch(&mut self) {
+        self.is_latched = true;
+        let _ = self.latch_pin.set_low();
+        let _ = self.latch_pin.set_high();
+    }
+}
+impl<const R: usize, Spi, Latch> ShiftRegister<R> for SpiShiftRegister<R, Spi, Latch>
+where
+    Spi: spi::Write<u8>,
+    Latch: OutputPin,
+{
+    fn outputs(&self) -> &Outputs<R> {
+        &self.outputs
+    }
+
+    fn outputs_mut(&mut self) -> &mut Outputs<R> {
+        &mut self.outputs
+    }
+
+    fn shift_out(&mut self, bits: [u8; R]) {
+        self.is_latched = false;
+        // The last output is shifted out first, so goes out as the MSB
+        for byte in bits.iter().rev() {
+            let _ = self.spi.write(&[*byte]);
+        }
+        self.latch();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        display::{seconds_outputs, Multiplexer, ShownTime},
+        hal::mock::{MockPin, MockPort, MockShiftRegister, MockSpi},
+    };
+
+    type MockRegister =
+        ChainedShiftRegister<2_usize, MockPin<16>, MockPin<16>, MockPin<16>, MockPin<16>>;
+
+    fn mock_register() -> (MockRegister, MockShiftRegister<16>) {
+        let register = MockShiftRegister::new();
+        let [serial_input, clock, latch] = register.pins();
+        let output_enables = [0_usize, 1_usize].map(|index| register.output_enable(index));
+        (
+            ChainedShiftRegister::new(serial_input, clock, latch, output_enables),
+            register,
+        )
+    }
+
+    #[test]
+    fn only_commits_changes() {
+        let (mut shift_register, register) = mock_register();
+        // The first commit always shifts, as the outputs aren't known before
+        assert!(shift_register.commit());
+        assert_eq!(register.latched(), [[false; 16]]);
+        assert!(!shift_register.commit());
+
+        let mut outputs = [false; 16];
+        for index in [0_usize, 9_usize, 15_usize] {
+            shift_register.set_bit(index, true);
+            outputs[index] = true;
+        }
+        assert!(shift_register.bit(9_usize));
+        // Nothing's shown until it's committed, and then all at once
+        assert_eq!(register.outputs(), [false; 16]);
+        assert!(shift_register.commit());
+        assert_eq!(register.latched(), [[false; 16], outputs]);
+
+        // Setting an output to what it already is isn't a change
+        shift_register.set_bit(9_usize, true);
+        shift_register.set_bit(1_usize, false);
+        assert!(!shift_register.commit());
+        // Nor is changing it back before committing
+        shift_register.set_bit(3_usize, true);
+        shift_register.set_bit(3_usize, false);
+        assert!(!shift_register.commit());
+        assert_eq!(register.latched().len(), 2_usize);
+
+        shift_register.set_bit(0_usize, false);
+        assert!(shift_register.commit());
+        outputs[0] = false;
+        assert_eq!(register.outputs(), outputs);
+    }
+
+    #[test]
+    fn output_enable() {
+        let (mut shift_register, register) = mock_register();
+        for index in [2_usize, 8_usize, 12_usize] {
+            shift_register.set_bit(index, true);
+        }
+        shift_register.commit();
+        shift_register.set_output_enabled(1_usize, false);
+        assert!(!shift_register.output_enabled(1_usize));
+        let expected = shift_register.expected_outputs();
+        assert_eq!(
+            core::array::from_fn::<_, 16, _>(|index| expected.output(index)),
+            register.outputs()
+        );
+        assert_eq!(expected.to_string(), "00100000 --------");
+
+        // What's latched in a disabled register is kept, and changes to it are
+        // still shifted
+        shift_register.set_bit(13_usize, true);
+        assert_eq!(
+            shift_register.expected_outputs().to_string(),
+            "00100000 -------- *"
+        );
+        shift_register.commit();
+        shift_register.set_output_enabled(1_usize, true);
+        let expected = shift_register.expected_outputs();
+        assert_eq!(expected.to_string(), "00100000 10001100");
+        assert_eq!(
+            core::array::from_fn::<_, 16, _>(|index| expected.output(index)),
+            register.outputs()
+        );
+    }
+
+    #[test]
+    fn displays_the_time() {
+        let (mut hours_minutes, hours_minutes_register) = mock_register();
+        let (mut seconds, seconds_register) = mock_register();
+        let mut multiplexer = Multiplexer::new();
+        for _ in 0_usize..5_usize {
+            hours_minutes.write(multiplexer.next((1_u8, 2_u8), (3_u8, 4_u8)));
+        }
+        seconds.write(seconds_outputs((5_u8, 6_u8)));
+
+        let shown = ShownTime::decode(
+            &hours_minutes_register.latched(),
+            &seconds_register.outputs(),
+        );
+        assert_eq!(shown.to_string(), "12:34:56");
+    }
+
+    #[test]
+    fn spi_matches_bit_banging() {
+        let (mut bit_banged, bit_banged_register) = mock_register();
+        let register = MockShiftRegister::<16>::new();
+        let [_, _, latch] = register.pins();
+        let mut spi = SpiShiftRegister::new(MockSpi::new(&register), latch);
+
+        let mut multiplexer = Multiplexer::new();
+        for _ in 0_usize..5_usize {
+            let outputs = multiplexer.next((0_u8, 9_u8), (4_u8, 1_u8));
+            bit_banged.write(outputs);
+            spi.write(outputs);
+        }
+        assert!(spi.is_latched);
+        assert_eq!(register.latched(), bit_banged_register.latched());
+        assert_eq!(
+            ShownTime::decode(&register.latched(), &seconds_outputs((3_u8, 7_u8))).to_string(),
+            "09:41:37"
+        );
+    }
+
+    #[test]
+    fn port_matches_bit_banging() {
+        let (mut bit_banged, bit_banged_register) = mock_register();
+        let register = MockShiftRegister::<16>::new();
+        // Wired like the hours and minutes display, with the rest of the port
+        // left as it is
+        let mut port = MockPort::new(&register, 1_u8 << 2, 1_u8 << 3, 1_u8 << 4);
+        port.bits = 0b1100_0001_u8;
+        let mut fast = PortShiftRegister::new(port, 1_u8 << 2, 1_u8 << 3, 1_u8 << 4);
+
+        let mut multiplexer = Multiplexer::new();
+        for _ in 0_usize..5_usize {
+            let outputs = multiplexer.next((2_u8, 3_u8), (5_u8, 9_u8));
+            bit_banged.write(outputs);
+            fast.write(outputs);
+        }
+        assert!(fast.is_latched);
+        assert_eq!(register.latched(), bit_banged_register.latched());
+        assert_eq!(fast.port.bits & 0b1110_0011_u8, 0b1100_0001_u8);
+        // Each bit is three writes, then two for the latch
+        assert_eq!(fast.port.writes, 5_usize * (16_usize * 3_usize + 2_usize));
+    }
+
+    #[test]
+    fn port_and_spi_only_commit_changes() {
+        let port_register = MockShiftRegister::<16>::new();
+        let port = MockPort::new(&port_register, 1_u8 << 5, 1_u8 << 6, 1_u8 << 7);
+        let mut port = PortShiftRegister::<2_usize, _>::new(port, 1_u8 << 5, 1_u8 << 6, 1_u8 << 7);
+        let spi_register = MockShiftRegister::<16>::new();
+        let [_, _, latch] = spi_register.pins();
+        let mut spi = SpiShiftRegister::<2_usize, _, _>::new(MockSpi::new(&spi_register), latch);
+
+        let outputs = seconds_outputs((4_u8, 2_u8));
+        for _ in 0_usize..3_usize {
+            port.write(outputs);
+            spi.write(outputs);
+        }
+        assert_eq!(port_register.latched(), [outputs]);
+        assert_eq!(spi_register.latched(), [outputs]);
+        assert!(!port.commit() && !spi.commit());
+
+        // Expected outputs are what's latched, with any pending changes flagged
+        port.set_bit(15_usize, !port.bit(15_usize));
+        spi.set_bit(15_usize, !spi.bit(15_usize));
+        for expected in [port.expected_outputs(), spi.expected_outputs()] {
+            assert_eq!(
+                core::array::from_fn::<_, 16, _>(|index| expected.output(index)),
+                outputs
+            );
+            assert!(expected.to_string().ends_with(" *"));
+        }
+        assert!(port.commit() && spi.commit());
+        assert_eq!(port_register.latched().len(), 2_usize);
+        assert_eq!(port.expected_outputs(), spi.expected_outputs());
+        assert!(!port.expected_outputs().uncommitted);
+    }
+}
@@ -0,0 +1,124 @@
+//! What the clock's sounds are: the built-in tunes, escalation profiles, and
+//! the chime settings. The players that make them are in the firmware.
+
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Tune {
+    #[default]
+    Nokia,
+    Simpsons,
+    Entertainer,
+    Tetris,
+}
+impl Tune {
+    pub const ALL: [Tune; 4] = [Tune::Nokia, Tune::Simpsons, Tune::Entertainer, Tune::Tetris];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tune::Nokia => "nokia",
+            Tune::Simpsons => "simpsons",
+            Tune::Entertainer => "entertainer",
+            Tune::Tetris => "tetris",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct EscalationProfile {
+    /// Seconds from the first chirp until the tone is continuous
+    pub ramp_seconds: u16,
+    /// Pitch of the first chirp in Hz
+    pub start_frequency: u16,
+    /// Pitch once fully escalated in Hz
+    pub end_frequency: u16,
+}
+impl EscalationProfile {
+    pub const GENTLE: Self = Self {
+        ramp_seconds: 300_u16,
+        start_frequency: 880_u16,
+        end_frequency: 2_637_u16,
+    };
+    pub const STANDARD: Self = Self {
+        ramp_seconds: 120_u16,
+        start_frequency: 1_319_u16,
+        end_frequency: 3_136_u16,
+    };
+    pub const URGENT: Self = Self {
+        ramp_seconds: 30_u16,
+        start_frequency: 2_093_u16,
+        end_frequency: 4_186_u16,
+    };
+    /// By `PRESET_*`
+    pub const PRESETS: [Self; 3] = [Self::GENTLE, Self::STANDARD, Self::URGENT];
+}
+impl Default for EscalationProfile {
+    fn default() -> Self {
+        Self::STANDARD
+    }
+}
+
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ChimeMode {
+    Off = 0_u8,
+    Hourly = 1_u8,
+    HalfHourly = 2_u8,
+}
+impl ChimeMode {
+    pub const ALL: [ChimeMode; 3] = [ChimeMode::Off, ChimeMode::Hourly, ChimeMode::HalfHourly];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChimeMode::Off => "off",
+            ChimeMode::Hourly => "hourly",
+            ChimeMode::HalfHourly => "half-hourly",
+        }
+    }
+}
+
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ChimeStyle {
+    Beep = 0_u8,
+    Westminster = 1_u8,
+}
+impl ChimeStyle {
+    pub const ALL: [ChimeStyle; 2] = [ChimeStyle::Beep, ChimeStyle::Westminster];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChimeStyle::Beep => "beep",
+            ChimeStyle::Westminster => "westminster",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ChimeSettings {
+    pub mode: ChimeMode,
+    pub style: ChimeStyle,
+    /// Hour [0, 23] when quiet hours start
+    pub quiet_start: u8,
+    /// Hour [0, 23] when quiet hours end. Quiet hours may wrap past midnight
+    /// and are disabled if equal to `quiet_start`.
+    pub quiet_end: u8,
+}
+impl ChimeSettings {
+    pub fn is_quiet(&self, hours: u8) -> bool {
+        if self.quiet_start <= self.quiet_end {
+            (self.quiet_start..self.quiet_end).contains(&hours)
+        } else {
+            hours >= self.quiet_start || hours < self.quiet_end
+        }
+    }
+}
+impl Default for ChimeSettings {
+    fn default() -> Self {
+        Self {
+            mode: ChimeMode::Off,
+            style: ChimeStyle::Beep,
+            quiet_start: 22_u8,
+            quiet_end: 7_u8,
+        }
+    }
+}
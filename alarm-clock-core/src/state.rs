@@ -0,0 +1,643 @@
+//! The alarm clock's state, and the alarm's state machine. The firmware wires
+//! the hardware to it in main.rs.
+
+use crate::{
+    idle_screen::{DateFormat, IdleScreenSettings},
+    log::{self, DEFAULT_LEVEL, TAG_COUNT},
+    settings::{
+        Settings, StoredAlarm, ALARM_ENABLED, ALARM_PRESENT, MAX_ALARMS, PRESET_CUSTOM,
+        PRESET_GENTLE, PRESET_STANDARD, PRESET_URGENT, SOUND_ESCALATING, SOUND_MELODY,
+        SOUND_PRESET,
+    },
+    sound::{ChimeMode, ChimeSettings, ChimeStyle, EscalationProfile, Tune},
+    time::{Time, TimeDigits},
+};
+
+/// Snoozing silences the alarm for this long
+pub const SNOOZE_MINUTES: u32 = 9_u32;
+
+/// Seconds should be tared.
+pub enum TimeSetState {
+    Hours(u8),
+    Minutes(u8),
+}
+
+/// Everything is stored the same way with the same ranges as defined in `Time`
+pub enum DateSetState {
+    Day(u8),
+    /// We could have this calculated, though having the user enter it is an easier
+    /// solution that puts the onus on them and ensures it's always right
+    DayOfWeek(u8),
+    Month(u8),
+    Year(u8),
+}
+
+pub enum OperationalMode {
+    TimeSet(TimeSetState),
+    AlarmSet(TimeSetState),
+    DateSet(DateSetState),
+    Idle,
+    Alarm,
+    /// The alarm was snoozed and will sound again at `State::snooze_until`
+    Snoozed,
+}
+
+pub enum Menu {
+    Idle,
+    TimeSet,
+    AlarmSet,
+    DateSet,
+    Launcher,
+}
+
+/// What the alarm sounds like when it goes off
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AlarmSound {
+    Melody(Tune),
+    /// One of the `EscalationProfile::PRESETS`
+    Escalating(EscalationProfile),
+    /// `State::custom_escalation`, the one custom profile that every alarm
+    /// choosing it shares
+    CustomEscalation,
+}
+impl Default for AlarmSound {
+    fn default() -> Self {
+        AlarmSound::Melody(Tune::default())
+    }
+}
+impl AlarmSound {
+    /// Parse the sound given to `alarm add`, defaulting to the default tune
+    pub fn parse(sound: Option<&str>) -> Option<Self> {
+        let Some(sound) = sound else {
+            return Some(AlarmSound::default());
+        };
+        let mut words = sound.split_ascii_whitespace();
+        match (words.next(), words.next(), words.next()) {
+            (Some("escalate"), Some("custom"), None) => Some(AlarmSound::CustomEscalation),
+            (Some("escalate"), profile, None) => Some(AlarmSound::Escalating(match profile {
+                None | Some("standard") => EscalationProfile::STANDARD,
+                Some("gentle") => EscalationProfile::GENTLE,
+                Some("urgent") => EscalationProfile::URGENT,
+                Some(_) => return None,
+            })),
+            (Some(name), None, None) => Tune::ALL
+                .iter()
+                .find(|tune| tune.name() == name)
+                .map(|tune| AlarmSound::Melody(*tune)),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AlarmSound::Melody(tune) => tune.name(),
+            AlarmSound::Escalating(EscalationProfile::GENTLE) => "escalate gentle",
+            AlarmSound::Escalating(EscalationProfile::STANDARD) => "escalate standard",
+            AlarmSound::Escalating(EscalationProfile::URGENT) => "escalate urgent",
+            AlarmSound::Escalating(_) | AlarmSound::CustomEscalation => "escalate custom",
+        }
+    }
+}
+
+/// One of the alarms in the alarm table
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Alarm {
+    /// Ranges from [0, 23]
+    pub hours: u8,
+    /// Ranges from [0, 59]
+    pub minutes: u8,
+    pub enabled: bool,
+    pub sound: AlarmSound,
+}
+
+/// The controls that affect the alarm, as read this update
+#[derive(Clone, Copy, Default)]
+pub struct AlarmInputs {
+    /// The rotary encoder's button, which dismisses the alarm
+    pub dismiss: bool,
+    pub snooze: bool,
+}
+
+/// A change in the alarm's state that the hardware has to follow
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AlarmEvent {
+    /// An alarm is due, so start its sound
+    Started(AlarmSound),
+    /// Start the sound again
+    SnoozeOver(AlarmSound),
+    Dismissed,
+    Snoozed,
+    /// The alarm rang for the maximum duration without being dismissed
+    RangOut(MissedAlarm),
+    MissedAlarmAcknowledged,
+}
+
+/// An alarm that rang for the maximum duration without being dismissed
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MissedAlarm {
+    /// When the alarm was due: its hours and minutes, on the day it went off
+    pub time: Time,
+    pub snoozes: u8,
+}
+
+pub struct State {
+    pub time: Time,
+    pub digits: TimeDigits,
+    pub mode: OperationalMode,
+    pub menu: Menu,
+    /// Master switch for all of the alarms
+    pub alarm_enabled: bool,
+    pub alarms: [Option<Alarm>; MAX_ALARMS],
+    /// What the alarm that is ringing (or snoozed) sounds like
+    pub ringing_sound: AlarmSound,
+    /// When the alarm that is ringing (or snoozed) was due, to the minute
+    pub ringing_alarm: Time,
+    /// The escalation profile of every alarm with `AlarmSound::CustomEscalation`
+    pub custom_escalation: EscalationProfile,
+    /// Set once the alarm has gone off and cleared once the alarm minute has
+    /// passed, so that dismissing the alarm doesn't immediately retrigger it
+    pub alarm_latched: bool,
+    /// The alarm stops by itself after ringing this long without being dismissed
+    pub alarm_max_ring_minutes: u8,
+    /// Millis when the alarm most recently started ringing
+    pub alarm_started: u32,
+    /// Millis when a snoozed alarm should ring again
+    pub snooze_until: u32,
+    /// Snoozes since the alarm first went off
+    pub snoozes: u8,
+    /// Shown on the character LCD until acknowledged with the rotary button
+    pub missed_alarm: Option<MissedAlarm>,
+    pub chime: ChimeSettings,
+    /// Hours and minutes display brightness from [1, 8]
+    pub brightness: u8,
+    pub idle_screen: IdleScreenSettings,
+    /// Whether the RTC's backup battery was low when last checked
+    pub rtc_battery_low: bool,
+    /// Log levels as saved, which the runtime filters only differ from until
+    /// they are saved from the shell
+    pub log_levels: [u8; TAG_COUNT],
+    /// The next time everything *aside* from the display should update
+    pub next_update: u32,
+}
+
+impl State {
+    pub fn new() -> Self {
+        Self {
+            alarm_enabled: false,
+            alarms: [None; MAX_ALARMS],
+            ringing_sound: AlarmSound::default(),
+            ringing_alarm: Time::default(),
+            custom_escalation: EscalationProfile::default(),
+            alarm_latched: false,
+            alarm_max_ring_minutes: 15_u8,
+            alarm_started: 0_u32,
+            snooze_until: 0_u32,
+            snoozes: 0_u8,
+            missed_alarm: None,
+            chime: ChimeSettings::default(),
+            brightness: 8_u8,
+            idle_screen: IdleScreenSettings::default(),
+            rtc_battery_low: false,
+            log_levels: [DEFAULT_LEVEL as u8; TAG_COUNT],
+            time: Time::default(),
+            digits: TimeDigits::default(),
+            mode: OperationalMode::Idle,
+            menu: Menu::Idle,
+            next_update: 0_u32,
+        }
+    }
+
+    /// The parts of the state that are saved across resets
+    pub fn settings(&self) -> Settings {
+        let alarms = self.alarms.map(|alarm| match alarm {
+            Some(alarm) => StoredAlarm {
+                hours: alarm.hours,
+                minutes: alarm.minutes,
+                flags: ALARM_PRESENT | if alarm.enabled { ALARM_ENABLED } else { 0_u8 },
+                sound: match alarm.sound {
+                    AlarmSound::Melody(tune) => tune as u8,
+                    AlarmSound::Escalating(EscalationProfile::GENTLE) => {
+                        SOUND_PRESET | PRESET_GENTLE
+                    }
+                    AlarmSound::Escalating(EscalationProfile::STANDARD) => {
+                        SOUND_PRESET | PRESET_STANDARD
+                    }
+                    AlarmSound::Escalating(EscalationProfile::URGENT) => {
+                        SOUND_PRESET | PRESET_URGENT
+                    }
+                    AlarmSound::Escalating(_) | AlarmSound::CustomEscalation => {
+                        SOUND_PRESET | PRESET_CUSTOM
+                    }
+                },
+            },
+            None => StoredAlarm::default(),
+        });
+
+        // The legacy single alarm mirrors the first alarm for older firmware
+        let first_alarm = self.alarms.iter().flatten().next();
+        let (alarm_hours, alarm_minutes) = first_alarm
+            .map(|alarm| (alarm.hours, alarm.minutes))
+            .unwrap_or((5_u8, 0_u8));
+        let (alarm_sound, alarm_tune) = match first_alarm.map(|alarm| alarm.sound) {
+            Some(AlarmSound::Escalating(_) | AlarmSound::CustomEscalation) => {
+                (SOUND_ESCALATING, Tune::default())
+            }
+            Some(AlarmSound::Melody(tune)) => (SOUND_MELODY, tune),
+            None => (SOUND_MELODY, Tune::default()),
+        };
+
+        Settings {
+            alarm_enabled: self.alarm_enabled,
+            alarm_hours,
+            alarm_minutes,
+            alarm_sound,
+            alarm_tune: alarm_tune as u8,
+            escalation_ramp_seconds: self.custom_escalation.ramp_seconds,
+            escalation_start_frequency: self.custom_escalation.start_frequency,
+            escalation_end_frequency: self.custom_escalation.end_frequency,
+            alarm_max_ring_minutes: self.alarm_max_ring_minutes,
+            chime_mode: self.chime.mode as u8,
+            chime_style: self.chime.style as u8,
+            quiet_start: self.chime.quiet_start,
+            quiet_end: self.chime.quiet_end,
+            brightness: self.brightness,
+            alarms,
+            log_levels: self.log_levels,
+            idle_pages: self.idle_screen.pages,
+            idle_date_format: self.idle_screen.date_format as u8,
+            idle_page_seconds: self.idle_screen.page_seconds,
+        }
+    }
+
+    /// Apply loaded settings. They should have already been validated.
+    pub fn apply_settings(&mut self, settings: &Settings) {
+        self.alarm_enabled = settings.alarm_enabled;
+        self.custom_escalation = EscalationProfile {
+            ramp_seconds: settings.escalation_ramp_seconds,
+            start_frequency: settings.escalation_start_frequency,
+            end_frequency: settings.escalation_end_frequency,
+        };
+        self.alarms = settings.alarms.map(|alarm| {
+            if alarm.flags & ALARM_PRESENT == 0_u8 {
+                return None;
+            }
+            Some(Alarm {
+                hours: alarm.hours,
+                minutes: alarm.minutes,
+                enabled: alarm.flags & ALARM_ENABLED != 0_u8,
+                sound: match alarm.sound {
+                    sound if sound & SOUND_PRESET == 0_u8 => AlarmSound::Melody(
+                        Tune::ALL.get(sound as usize).copied().unwrap_or_default(),
+                    ),
+                    sound => match sound & !SOUND_PRESET {
+                        PRESET_GENTLE => AlarmSound::Escalating(EscalationProfile::GENTLE),
+                        PRESET_STANDARD => AlarmSound::Escalating(EscalationProfile::STANDARD),
+                        PRESET_URGENT => AlarmSound::Escalating(EscalationProfile::URGENT),
+                        _ => AlarmSound::CustomEscalation,
+                    },
+                },
+            })
+        });
+        self.alarm_max_ring_minutes = settings.alarm_max_ring_minutes;
+        self.chime = ChimeSettings {
+            mode: match settings.chime_mode {
+                1_u8 => ChimeMode::Hourly,
+                2_u8 => ChimeMode::HalfHourly,
+                _ => ChimeMode::Off,
+            },
+            style: match settings.chime_style {
+                1_u8 => ChimeStyle::Westminster,
+                _ => ChimeStyle::Beep,
+            },
+            quiet_start: settings.quiet_start,
+            quiet_end: settings.quiet_end,
+        };
+        self.brightness = settings.brightness;
+        self.idle_screen = IdleScreenSettings {
+            pages: settings.idle_pages,
+            date_format: DateFormat::ALL
+                .get(settings.idle_date_format as usize)
+                .copied()
+                .unwrap_or_default(),
+            page_seconds: settings.idle_page_seconds,
+        };
+        self.log_levels = settings.log_levels;
+        log::set_filters(&settings.log_levels);
+    }
+
+    /// Move the alarm along for an update at `now` millis, ringing when an
+    /// alarm is due and stopping when it is dismissed, snoozed, or rings out
+    pub fn update_alarm(&mut self, now: u32, inputs: AlarmInputs) -> Option<AlarmEvent> {
+        let due_sound = self.due_alarm().map(|alarm| alarm.sound);
+        if due_sound.is_none() {
+            self.alarm_latched = false;
+        }
+        match self.mode {
+            OperationalMode::Idle
+                if self.alarm_enabled && due_sound.is_some() && !self.alarm_latched =>
+            {
+                self.alarm_latched = true;
+                self.snoozes = 0_u8;
+                self.mode = OperationalMode::Alarm;
+                self.alarm_started = now;
+                self.ringing_sound = due_sound.unwrap_or_default();
+                self.ringing_alarm = Time {
+                    seconds: 0_u8,
+                    ..self.time
+                };
+                Some(AlarmEvent::Started(self.ringing_sound))
+            }
+            OperationalMode::Snoozed if (now.wrapping_sub(self.snooze_until) as i32) >= 0_i32 => {
+                self.mode = OperationalMode::Alarm;
+                self.alarm_started = now;
+                Some(AlarmEvent::SnoozeOver(self.ringing_sound))
+            }
+            OperationalMode::Alarm | OperationalMode::Snoozed if inputs.dismiss => {
+                self.mode = OperationalMode::Idle;
+                Some(AlarmEvent::Dismissed)
+            }
+            OperationalMode::Alarm if inputs.snooze => {
+                self.mode = OperationalMode::Snoozed;
+                self.snoozes = self.snoozes.saturating_add(1_u8);
+                self.snooze_until = now.wrapping_add(SNOOZE_MINUTES * 60_000_u32);
+                Some(AlarmEvent::Snoozed)
+            }
+            OperationalMode::Alarm
+                if now.wrapping_sub(self.alarm_started)
+                    >= self.alarm_max_ring_minutes as u32 * 60_000_u32 =>
+            {
+                self.mode = OperationalMode::Idle;
+                let missed_alarm = MissedAlarm {
+                    time: self.ringing_alarm,
+                    snoozes: self.snoozes,
+                };
+                self.missed_alarm = Some(missed_alarm);
+                Some(AlarmEvent::RangOut(missed_alarm))
+            }
+            OperationalMode::Idle if self.missed_alarm.is_some() && inputs.dismiss => {
+                self.missed_alarm = None;
+                Some(AlarmEvent::MissedAlarmAcknowledged)
+            }
+            _ => None,
+        }
+    }
+
+    /// The enabled alarm set for the current minute, if any
+    pub fn due_alarm(&self) -> Option<&Alarm> {
+        self.alarms.iter().flatten().find(|alarm| {
+            alarm.enabled && alarm.hours == self.time.hours && alarm.minutes == self.time.minutes
+        })
+    }
+
+    /// The enabled alarm that goes off next and the seconds until it does, if
+    /// the alarms are on. One going off now is next due tomorrow.
+    pub fn next_alarm(&self) -> Option<(&Alarm, u32)> {
+        const DAY: u32 = 24_u32 * 60_u32 * 60_u32;
+        if !self.alarm_enabled {
+            return None;
+        }
+        let now = self.time.hours as u32 * 3_600_u32
+            + self.time.minutes as u32 * 60_u32
+            + self.time.seconds as u32;
+        self.alarms
+            .iter()
+            .flatten()
+            .filter(|alarm| alarm.enabled)
+            .map(|alarm| {
+                let at = alarm.hours as u32 * 3_600_u32 + alarm.minutes as u32 * 60_u32;
+                match (at + DAY - now) % DAY {
+                    0_u32 => (alarm, DAY),
+                    until => (alarm, until),
+                }
+            })
+            .min_by_key(|(_, until)| *until)
+    }
+}
+impl Default for State {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DISMISS: AlarmInputs = AlarmInputs {
+        dismiss: true,
+        snooze: false,
+    };
+    const SNOOZE: AlarmInputs = AlarmInputs {
+        dismiss: false,
+        snooze: true,
+    };
+    const MINUTE: u32 = 60_000_u32;
+
+    fn state_with_alarm(sound: AlarmSound) -> State {
+        let mut state = State::new();
+        state.alarm_enabled = true;
+        state.alarms[1] = Some(Alarm {
+            hours: 6_u8,
+            minutes: 30_u8,
+            enabled: true,
+            sound,
+        });
+        state.time.hours = 6_u8;
+        state.time.minutes = 29_u8;
+        state
+    }
+
+    #[test]
+    fn rings_once_per_minute() {
+        let gentle = AlarmSound::Escalating(EscalationProfile::GENTLE);
+        let mut state = state_with_alarm(gentle);
+        assert_eq!(state.update_alarm(0_u32, AlarmInputs::default()), None);
+
+        state.time.minutes = 30_u8;
+        assert_eq!(
+            state.update_alarm(1_000_u32, AlarmInputs::default()),
+            Some(AlarmEvent::Started(gentle))
+        );
+        assert_eq!(
+            state.update_alarm(2_000_u32, DISMISS),
+            Some(AlarmEvent::Dismissed)
+        );
+        // Not again until the alarm minute comes back around
+        assert_eq!(state.update_alarm(3_000_u32, AlarmInputs::default()), None);
+        state.time.minutes = 31_u8;
+        assert_eq!(state.update_alarm(MINUTE, AlarmInputs::default()), None);
+        assert!(!state.alarm_latched);
+
+        // Nor with the master switch off
+        state.alarm_enabled = false;
+        state.time.minutes = 30_u8;
+        assert_eq!(state.update_alarm(MINUTE, AlarmInputs::default()), None);
+    }
+
+    #[test]
+    fn snoozing() {
+        let mut state = state_with_alarm(AlarmSound::default());
+        state.time.minutes = 30_u8;
+        state.update_alarm(0_u32, AlarmInputs::default());
+        assert_eq!(
+            state.update_alarm(10_u32, SNOOZE),
+            Some(AlarmEvent::Snoozed)
+        );
+        assert_eq!(state.snoozes, 1_u8);
+
+        let snooze_over = 10_u32 + SNOOZE_MINUTES * MINUTE;
+        assert_eq!(
+            state.update_alarm(snooze_over - 1_u32, AlarmInputs::default()),
+            None
+        );
+        assert_eq!(
+            state.update_alarm(snooze_over, AlarmInputs::default()),
+            Some(AlarmEvent::SnoozeOver(AlarmSound::default()))
+        );
+        assert_eq!(
+            state.update_alarm(snooze_over + 10_u32, SNOOZE),
+            Some(AlarmEvent::Snoozed)
+        );
+        assert_eq!(state.snoozes, 2_u8);
+        // Snoozed alarms can be dismissed too
+        assert_eq!(
+            state.update_alarm(snooze_over + 20_u32, DISMISS),
+            Some(AlarmEvent::Dismissed)
+        );
+    }
+
+    #[test]
+    fn ringing_out() {
+        let mut state = state_with_alarm(AlarmSound::default());
+        state.time.minutes = 30_u8;
+        state.update_alarm(u32::MAX - 10_u32, AlarmInputs::default());
+        state.update_alarm(u32::MAX, SNOOZE);
+        let start = u32::MAX.wrapping_add(SNOOZE_MINUTES * MINUTE);
+        state.update_alarm(start, AlarmInputs::default());
+
+        let ring_out = start.wrapping_add(state.alarm_max_ring_minutes as u32 * MINUTE);
+        assert_eq!(
+            state.update_alarm(ring_out - 1_u32, AlarmInputs::default()),
+            None
+        );
+        // The alarm that was missed, not when it gave up
+        state.time.minutes = 54_u8;
+        state.time.seconds = 12_u8;
+        let missed_alarm = MissedAlarm {
+            time: Time {
+                minutes: 30_u8,
+                seconds: 0_u8,
+                ..state.time
+            },
+            snoozes: 1_u8,
+        };
+        assert_eq!(
+            state.update_alarm(ring_out, AlarmInputs::default()),
+            Some(AlarmEvent::RangOut(missed_alarm))
+        );
+        assert_eq!(state.missed_alarm, Some(missed_alarm));
+        assert_eq!(
+            state.update_alarm(ring_out + 1_u32, DISMISS),
+            Some(AlarmEvent::MissedAlarmAcknowledged)
+        );
+        assert_eq!(state.missed_alarm, None);
+    }
+
+    #[test]
+    fn alarm_sounds() {
+        assert_eq!(AlarmSound::parse(None), Some(AlarmSound::default()));
+        assert_eq!(
+            AlarmSound::parse(Some("tetris")),
+            Some(AlarmSound::Melody(Tune::Tetris))
+        );
+        assert_eq!(
+            AlarmSound::parse(Some("escalate")),
+            Some(AlarmSound::Escalating(EscalationProfile::STANDARD))
+        );
+        assert_eq!(
+            AlarmSound::parse(Some("escalate urgent")),
+            Some(AlarmSound::Escalating(EscalationProfile::URGENT))
+        );
+        assert_eq!(AlarmSound::parse(Some("escalate loudly")), None);
+        assert_eq!(AlarmSound::parse(Some("kazoo")), None);
+        assert_eq!(
+            AlarmSound::parse(Some("escalate custom")),
+            Some(AlarmSound::CustomEscalation)
+        );
+        for sound in ["nokia", "escalate gentle", "escalate custom"] {
+            assert_eq!(AlarmSound::parse(Some(sound)).unwrap().name(), sound);
+        }
+    }
+
+    #[test]
+    fn settings_round_trip() {
+        let mut state = state_with_alarm(AlarmSound::Escalating(EscalationProfile::URGENT));
+        state.alarms[3] = Some(Alarm {
+            hours: 22_u8,
+            minutes: 0_u8,
+            enabled: false,
+            sound: AlarmSound::Melody(Tune::Entertainer),
+        });
+        state.brightness = 4_u8;
+        state.idle_screen = IdleScreenSettings {
+            pages: crate::idle_screen::ALL_PAGES,
+            date_format: DateFormat::Text,
+            page_seconds: 12_u8,
+        };
+        let settings = state.settings();
+        assert_eq!(settings.validate(), Ok(()));
+
+        let mut restored = State::new();
+        restored.apply_settings(&settings);
+        assert_eq!(restored.alarms, state.alarms);
+        assert_eq!(restored.brightness, 4_u8);
+        assert_eq!(restored.idle_screen, state.idle_screen);
+        assert_eq!(restored.settings(), settings);
+    }
+
+    #[test]
+    fn preset_first_alarm_keeps_the_custom_profile() {
+        let mut state = state_with_alarm(AlarmSound::CustomEscalation);
+        state.custom_escalation = EscalationProfile {
+            ramp_seconds: 45_u16,
+            start_frequency: 1_000_u16,
+            end_frequency: 2_000_u16,
+        };
+        state.alarms[0] = Some(Alarm {
+            hours: 5_u8,
+            minutes: 45_u8,
+            enabled: true,
+            sound: AlarmSound::Escalating(EscalationProfile::GENTLE),
+        });
+        let settings = state.settings();
+        assert_eq!(settings.escalation_ramp_seconds, 45_u16);
+
+        let mut restored = State::new();
+        restored.apply_settings(&settings);
+        assert_eq!(restored.alarms, state.alarms);
+    }
+
+    #[test]
+    fn alarms_share_the_custom_profile() {
+        let mut state = state_with_alarm(AlarmSound::CustomEscalation);
+        state.alarms[2] = Some(Alarm {
+            hours: 7_u8,
+            minutes: 15_u8,
+            enabled: true,
+            sound: AlarmSound::CustomEscalation,
+        });
+        let custom = EscalationProfile {
+            ramp_seconds: 300_u16,
+            start_frequency: 440_u16,
+            end_frequency: 1_760_u16,
+        };
+        state.custom_escalation = custom;
+        let settings = state.settings();
+
+        let mut restored = State::new();
+        restored.apply_settings(&settings);
+        assert_eq!(restored.alarms, state.alarms);
+        assert_eq!(restored.custom_escalation, custom);
+        assert_eq!(restored.settings(), settings);
+    }
+}
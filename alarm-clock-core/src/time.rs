@@ -0,0 +1,104 @@
+//! Time as the clock keeps it, and the digits it's shown with
+
+/// Time, as reported from the realtime clock
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Time {
+    /// Ranges from [0, 23]
+    pub hours: u8,
+    /// Ranges from [0, 59]
+    pub minutes: u8,
+    /// Ranges from [0, 59]
+    pub seconds: u8,
+    /// Ranges from [1, 31]
+    pub day: u8,
+    /// The day of the week from [0, 6] where 0 is Sunday and 6 is Saturday
+    pub day_of_week: u8,
+    pub month: u8,
+    /// The year from 20[00-99] (Y2.1K!)
+    pub year: u8,
+}
+impl Default for Time {
+    fn default() -> Self {
+        Self {
+            hours: 5_u8,
+            minutes: 0_u8,
+            seconds: 0_u8,
+            day: 1_u8,
+            day_of_week: 0_u8,
+            month: 1_u8,
+            year: 23_u8,
+        }
+    }
+}
+
+/// The tens and ones digits of each part of the time, as displayed
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct TimeDigits {
+    pub hours: (u8, u8),
+    pub minutes: (u8, u8),
+    pub seconds: (u8, u8),
+}
+impl TimeDigits {
+    pub fn from_time(time: &Time) -> Self {
+        Self {
+            hours: digits(time.hours),
+            minutes: digits(time.minutes),
+            seconds: digits(time.seconds),
+        }
+    }
+}
+
+/// The tens and ones digits of a number from [0, 99]
+pub fn digits(x: u8) -> (u8, u8) {
+    (x / 10_u8 % 10_u8, x % 10_u8)
+}
+
+pub fn bcd_decode(x: u8) -> u8 {
+    (((x & 0b11110000) >> 4) * 10) + (x & 0b00001111)
+}
+
+pub fn bcd_encode(mut x: u8) -> u8 {
+    assert!(x < 100);
+    let mut shift = 0_u8;
+    let mut res = 0_u8;
+    while x > 0 {
+        res |= (x % 10) << (shift << 2);
+        shift += 1;
+        x /= 10;
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bcd() {
+        assert_eq!(bcd_encode(0_u8), 0x00_u8);
+        assert_eq!(bcd_encode(7_u8), 0x07_u8);
+        assert_eq!(bcd_encode(59_u8), 0x59_u8);
+        assert_eq!(bcd_decode(0x23_u8), 23_u8);
+        for x in 0_u8..100_u8 {
+            assert_eq!(bcd_decode(bcd_encode(x)), x);
+        }
+    }
+
+    #[test]
+    fn time_digits() {
+        let time = Time {
+            hours: 23_u8,
+            minutes: 5_u8,
+            seconds: 40_u8,
+            ..Time::default()
+        };
+        assert_eq!(
+            TimeDigits::from_time(&time),
+            TimeDigits {
+                hours: (2_u8, 3_u8),
+                minutes: (0_u8, 5_u8),
+                seconds: (4_u8, 0_u8),
+            }
+        );
+    }
+}
@@ -0,0 +1,116 @@
+//! Consistent Overhead Byte Stuffing, which removes every zero from a packet
+//! so zeros can delimit the frames on the wire
+
+/// The longest an encoded `length` bytes can get
+pub const fn max_encoded_length(length: usize) -> usize {
+    length + length / 254_usize + 1_usize
+}
+
+/// Encode `data` into `out`, returning the encoded length, or `None` if it
+/// doesn't fit
+pub fn encode(data: &[u8], out: &mut [u8]) -> Option<usize> {
+    let mut code_index = 0_usize;
+    let mut length = 1_usize;
+    let mut code = 1_u8;
+    for byte in data {
+        if *byte != 0_u8 {
+            *out.get_mut(length)? = *byte;
+            length += 1;
+            code += 1;
+        }
+        // A zero ends the block, as does running out of room for the code
+        if *byte == 0_u8 || code == 0xFF_u8 {
+            *out.get_mut(code_index)? = code;
+            code_index = length;
+            length += 1;
+            code = 1_u8;
+        }
+    }
+    *out.get_mut(code_index)? = code;
+    Some(length)
+}
+
+/// Decode `data` (without its delimiter) into `out`, returning the decoded
+/// length, or `None` if it's malformed or doesn't fit
+pub fn decode(data: &[u8], out: &mut [u8]) -> Option<usize> {
+    let mut length = 0_usize;
+    let mut n = 0_usize;
+    while n < data.len() {
+        let code = data[n];
+        if code == 0_u8 || n + code as usize > data.len() {
+            return None;
+        }
+        for byte in &data[n + 1..n + code as usize] {
+            if *byte == 0_u8 {
+                return None;
+            }
+            *out.get_mut(length)? = *byte;
+            length += 1;
+        }
+        n += code as usize;
+        // Every block but full ones and the last ends in a zero
+        if code != 0xFF_u8 && n < data.len() {
+            *out.get_mut(length)? = 0_u8;
+            length += 1;
+        }
+    }
+    Some(length)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(data: &[u8]) {
+        let mut encoded = [0_u8; 600];
+        let length = encode(data, &mut encoded).unwrap();
+        assert!(length <= max_encoded_length(data.len()));
+        assert!(!encoded[..length].contains(&0_u8));
+        let mut decoded = [0_u8; 600];
+        let decoded_length = decode(&encoded[..length], &mut decoded).unwrap();
+        assert_eq!(&decoded[..decoded_length], data);
+    }
+
+    #[test]
+    fn encoding() {
+        let mut out = [0_u8; 8];
+        assert_eq!(encode(&[], &mut out), Some(1));
+        assert_eq!(out[0], 1_u8);
+        assert_eq!(encode(&[0_u8], &mut out), Some(2));
+        assert_eq!(out[..2], [1_u8, 1_u8]);
+        assert_eq!(
+            encode(&[0x11_u8, 0x22_u8, 0_u8, 0x33_u8], &mut out),
+            Some(5)
+        );
+        assert_eq!(out[..5], [3_u8, 0x11_u8, 0x22_u8, 2_u8, 0x33_u8]);
+        assert_eq!(encode(&[1_u8; 8], &mut out), None);
+    }
+
+    #[test]
+    fn round_trips() {
+        round_trip(&[]);
+        round_trip(&[0_u8, 0_u8, 0_u8]);
+        round_trip(b"Hello from the Alarm Clock!");
+        // Either side of a full block
+        for length in [253_usize, 254_usize, 255_usize, 508_usize, 509_usize] {
+            let data = (0..length)
+                .map(|n| (n % 255) as u8 + 1_u8)
+                .collect::<Vec<_>>();
+            round_trip(&data);
+        }
+        let data = (0..300).map(|n| (n % 7) as u8).collect::<Vec<_>>();
+        round_trip(&data);
+    }
+
+    #[test]
+    fn malformed() {
+        let mut out = [0_u8; 8];
+        // Runs past the end
+        assert_eq!(decode(&[5_u8, 1_u8], &mut out), None);
+        // Zeros are never encoded
+        assert_eq!(decode(&[3_u8, 0_u8, 1_u8], &mut out), None);
+        assert_eq!(decode(&[0_u8], &mut out), None);
+        // Doesn't fit
+        assert_eq!(decode(&[9_u8, 1, 2, 3, 4, 5, 6, 7, 8, 9], &mut out), None);
+    }
+}
@@ -0,0 +1,34 @@
+//! CRC-16/CCITT-FALSE (polynomial 0x1021, initial value 0xFFFF) for checking
+//! data stored in the EEPROM or sent over the serial link
+
+pub const CRC16_INITIAL: u16 = 0xFFFF_u16;
+const POLYNOMIAL: u16 = 0x1021_u16;
+
+/// Feed one more byte into a running CRC. Start with `CRC16_INITIAL`.
+pub fn crc16_update(mut crc: u16, byte: u8) -> u16 {
+    crc ^= (byte as u16) << 8;
+    for _ in 0..8 {
+        crc = match crc & 0x8000_u16 {
+            0_u16 => crc << 1,
+            _ => (crc << 1) ^ POLYNOMIAL,
+        };
+    }
+    crc
+}
+
+pub fn crc16(bytes: &[u8]) -> u16 {
+    bytes
+        .iter()
+        .fold(CRC16_INITIAL, |crc, byte| crc16_update(crc, *byte))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_value() {
+        assert_eq!(crc16(b"123456789"), 0x29B1_u16);
+        assert_eq!(crc16(&[]), CRC16_INITIAL);
+    }
+}
@@ -0,0 +1,333 @@
+//! Packets and the zero-delimited frames they're sent in
+
+use crate::{
+    cobs,
+    crc::crc16,
+    message::Message,
+    wire::{Reader, Writer},
+    Error, PROTOCOL_VERSION,
+};
+
+pub const MAX_PAYLOAD_LENGTH: usize = 60_usize;
+const HEADER_LENGTH: usize = 3_usize;
+const CRC_LENGTH: usize = 2_usize;
+pub const MAX_PACKET_LENGTH: usize = HEADER_LENGTH + MAX_PAYLOAD_LENGTH + CRC_LENGTH;
+/// A packet once COBS-encoded
+pub const MAX_ENCODED_LENGTH: usize = cobs::max_encoded_length(MAX_PACKET_LENGTH);
+/// An encoded packet between its delimiters
+pub const MAX_FRAME_LENGTH: usize = MAX_ENCODED_LENGTH + 2_usize;
+pub const DELIMITER: u8 = 0_u8;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Packet<M> {
+    /// Chosen by the host and echoed back by the clock
+    pub id: u8,
+    pub message: M,
+}
+
+/// Encode a message as a whole frame, delimiters included
+pub fn encode_packet<'a, M: Message>(
+    id: u8,
+    message: &M,
+    out: &'a mut [u8; MAX_FRAME_LENGTH],
+) -> Result<&'a [u8], Error> {
+    let mut packet = [0_u8; MAX_PACKET_LENGTH];
+    let mut writer = Writer::new(&mut packet[..MAX_PACKET_LENGTH - CRC_LENGTH]);
+    writer.bytes(&[PROTOCOL_VERSION, message.kind(), id])?;
+    message.encode(&mut writer)?;
+    let length = writer.length();
+    let crc = crc16(&packet[..length]);
+    packet[length..length + CRC_LENGTH].copy_from_slice(&crc.to_le_bytes());
+
+    out[0] = DELIMITER;
+    let encoded_length = cobs::encode(
+        &packet[..length + CRC_LENGTH],
+        &mut out[1..MAX_FRAME_LENGTH - 1],
+    )
+    .ok_or(Error::TooLong)?;
+    out[encoded_length + 1] = DELIMITER;
+    Ok(&out[..encoded_length + 2])
+}
+
+/// Decode a frame from `FrameReader`. The outer error means the frame itself
+/// was bad, so there's no knowing who sent it or what to reply. The inner one
+/// means its message couldn't be understood, and can be replied to.
+pub fn decode_packet<M: Message>(frame: &[u8]) -> Result<Packet<Result<M, Error>>, Error> {
+    let mut packet = [0_u8; MAX_PACKET_LENGTH];
+    let length = cobs::decode(frame, &mut packet).ok_or(Error::Cobs)?;
+    if length < HEADER_LENGTH + CRC_LENGTH {
+        return Err(Error::Truncated);
+    }
+    let crc_start = length - CRC_LENGTH;
+    let crc = u16::from_le_bytes([packet[crc_start], packet[crc_start + 1]]);
+    if crc16(&packet[..crc_start]) != crc {
+        return Err(Error::BadCrc);
+    }
+
+    let (version, kind, id) = (packet[0], packet[1], packet[2]);
+    let message = match version {
+        PROTOCOL_VERSION => {
+            let mut reader = Reader::new(&packet[HEADER_LENGTH..crc_start]);
+            M::decode(kind, &mut reader).and_then(|message| {
+                reader.finish()?;
+                Ok(message)
+            })
+        }
+        version => Err(Error::UnsupportedVersion(version)),
+    };
+    Ok(Packet { id, message })
+}
+
+/// Picks frames out of the bytes received. Bytes outside of frames (like the
+/// shell's text) are left to the caller.
+pub struct FrameReader {
+    buffer: [u8; MAX_ENCODED_LENGTH],
+    length: usize,
+    in_frame: bool,
+}
+impl FrameReader {
+    pub fn new() -> Self {
+        Self {
+            buffer: [0_u8; MAX_ENCODED_LENGTH],
+            length: 0_usize,
+            in_frame: false,
+        }
+    }
+
+    /// Whether a frame has been started, so the next bytes belong to it
+    pub fn in_frame(&self) -> bool {
+        self.in_frame
+    }
+
+    /// Feed in a byte, returning the frame it ends, if any. A delimiter both
+    /// ends a frame and can start one, so runs of them are fine.
+    pub fn push(&mut self, byte: u8) -> Option<&[u8]> {
+        if byte == DELIMITER {
+            if self.in_frame && self.length > 0_usize {
+                self.in_frame = false;
+                return Some(&self.buffer[..core::mem::take(&mut self.length)]);
+            }
+            self.in_frame = true;
+            return None;
+        }
+        if !self.in_frame {
+            return None;
+        }
+        match self.buffer.get_mut(self.length) {
+            Some(slot) => {
+                *slot = byte;
+                self.length += 1;
+            }
+            // Too long to be a frame, so it was likely a stray delimiter
+            None => {
+                self.in_frame = false;
+                self.length = 0_usize;
+            }
+        }
+        None
+    }
+
+    /// Treat the delimiter that ended the last frame as starting the next, for
+    /// when that "frame" turned out to be noise between two real ones
+    pub fn resume(&mut self) {
+        self.in_frame = true;
+    }
+}
+impl Default for FrameReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::{
+        Alarm, DateTime, Diagnostics, ErrorCode, LogEntry, MissedAlarms, Request, Response,
+        SettingsRecord, MAX_SETTINGS_RECORD_LENGTH, MISSED_ALARMS_PER_MESSAGE,
+    };
+
+    /// Encode a message and read it back through a `FrameReader`
+    fn round_trip<M: Message + PartialEq + core::fmt::Debug>(id: u8, message: M) {
+        let mut out = [0_u8; MAX_FRAME_LENGTH];
+        let frame = encode_packet(id, &message, &mut out).unwrap().to_vec();
+        let mut reader = FrameReader::new();
+        let mut frames = frame
+            .iter()
+            .filter_map(|byte| reader.push(*byte).map(<[u8]>::to_vec));
+        let decoded = decode_packet::<M>(&frames.next().unwrap()).unwrap();
+        assert_eq!(decoded.id, id);
+        assert_eq!(decoded.message, Ok(message));
+    }
+
+    fn time() -> DateTime {
+        DateTime {
+            year: 24_u8,
+            month: 2_u8,
+            day: 29_u8,
+            hours: 23_u8,
+            minutes: 59_u8,
+            seconds: 0_u8,
+        }
+    }
+
+    #[test]
+    fn requests() {
+        let alarms = [
+            Alarm {
+                hours: 6_u8,
+                minutes: 30_u8,
+                flags: 3_u8,
+                sound: 0x81_u8,
+            },
+            Alarm::default(),
+            Alarm::default(),
+            Alarm {
+                hours: 0_u8,
+                minutes: 0_u8,
+                flags: 1_u8,
+                sound: 0_u8,
+            },
+        ];
+        round_trip(0_u8, Request::GetTime);
+        round_trip(1_u8, Request::SetTime(time()));
+        round_trip(2_u8, Request::GetAlarms);
+        round_trip(3_u8, Request::SetAlarms(alarms));
+        round_trip(4_u8, Request::GetSettings);
+        let record = SettingsRecord::new(&[0_u8; MAX_SETTINGS_RECORD_LENGTH]).unwrap();
+        round_trip(5_u8, Request::SetSettings(record));
+        round_trip(6_u8, Request::ReadMissedAlarms(7_u8));
+        round_trip(0xFF_u8, Request::GetDiagnostics);
+    }
+
+    #[test]
+    fn responses() {
+        round_trip(0_u8, Response::Time(time()));
+        round_trip(1_u8, Response::Done);
+        round_trip(2_u8, Response::Error(ErrorCode::BadSettings));
+        round_trip(
+            3_u8,
+            Response::Settings(SettingsRecord::new(&[0xAC_u8, 3_u8]).unwrap()),
+        );
+
+        let mut page = MissedAlarms::new(7_u8);
+        round_trip(4_u8, Response::MissedAlarms(page));
+        for snoozes in 0..MISSED_ALARMS_PER_MESSAGE as u8 {
+            assert!(page.push(LogEntry {
+                time: time(),
+                snoozes
+            }));
+        }
+        assert!(!page.push(LogEntry::default()));
+        assert!(!page.is_last());
+        round_trip(5_u8, Response::MissedAlarms(page));
+
+        let mut diagnostics = Diagnostics {
+            uptime_seconds: 86_400_u32,
+            boots: 3_u16,
+            snoozes: 1_000_u16,
+            serial_dropped: 0_u16,
+            settings_version: 3_u8,
+            mode: 0_u8,
+            last_sync: None,
+        };
+        round_trip(6_u8, Response::Diagnostics(diagnostics));
+        diagnostics.last_sync = Some(time());
+        round_trip(7_u8, Response::Diagnostics(diagnostics));
+    }
+
+    #[test]
+    fn bad_packets() {
+        let mut out = [0_u8; MAX_FRAME_LENGTH];
+        let frame = encode_packet(9_u8, &Request::GetTime, &mut out).unwrap();
+        let body = frame[1..frame.len() - 1].to_vec();
+        assert!(SettingsRecord::new(&[0_u8; MAX_SETTINGS_RECORD_LENGTH + 1]).is_none());
+
+        // A flipped bit
+        let mut corrupted = body.clone();
+        corrupted[2] ^= 0x10_u8;
+        assert_eq!(decode_packet::<Request>(&corrupted), Err(Error::BadCrc));
+        assert_eq!(
+            decode_packet::<Request>(&[2_u8, 1_u8]),
+            Err(Error::Truncated)
+        );
+        assert_eq!(decode_packet::<Request>(&[9_u8, 1_u8]), Err(Error::Cobs));
+
+        // Wrong direction, version or length can still be replied to
+        let decoded = decode_packet::<Response>(&body).unwrap();
+        assert_eq!(
+            (decoded.id, decoded.message),
+            (9_u8, Err(Error::UnknownKind(0x01_u8)))
+        );
+
+        let mut packet = [PROTOCOL_VERSION + 1_u8, 0x01_u8, 9_u8, 0_u8, 0_u8];
+        let crc = crc16(&packet[..3]).to_le_bytes();
+        packet[3..].copy_from_slice(&crc);
+        let mut encoded = [0_u8; 8];
+        let length = cobs::encode(&packet, &mut encoded).unwrap();
+        let decoded = decode_packet::<Request>(&encoded[..length]).unwrap();
+        assert_eq!(
+            decoded.message,
+            Err(Error::UnsupportedVersion(PROTOCOL_VERSION + 1_u8))
+        );
+        assert_eq!(
+            ErrorCode::from(decoded.message.unwrap_err()),
+            ErrorCode::UnsupportedVersion
+        );
+
+        let mut packet = [PROTOCOL_VERSION, 0x01_u8, 9_u8, 42_u8, 0_u8, 0_u8];
+        let crc = crc16(&packet[..4]).to_le_bytes();
+        packet[4..].copy_from_slice(&crc);
+        let length = cobs::encode(&packet, &mut encoded).unwrap();
+        let decoded = decode_packet::<Request>(&encoded[..length]).unwrap();
+        assert_eq!(decoded.message, Err(Error::TooLong));
+    }
+
+    #[test]
+    fn reading_frames() {
+        let mut out = [0_u8; MAX_FRAME_LENGTH];
+        let frame = encode_packet(1_u8, &Response::Done, &mut out)
+            .unwrap()
+            .to_vec();
+
+        let mut reader = FrameReader::new();
+        // Text outside of frames is left alone
+        for byte in b"> status\n" {
+            assert_eq!(reader.push(*byte), None);
+            assert!(!reader.in_frame());
+        }
+        let mut stream = vec![DELIMITER];
+        stream.extend_from_slice(&frame);
+        let frames = stream
+            .iter()
+            .filter_map(|byte| reader.push(*byte).map(<[u8]>::to_vec))
+            .collect::<Vec<_>>();
+        assert_eq!(frames, [frame[1..frame.len() - 1].to_vec()]);
+        assert!(!reader.in_frame());
+
+        // Noise after a stray delimiter ends at the real frame's first
+        // delimiter, which is picked back up with `resume`
+        let mut stream = vec![DELIMITER];
+        stream.extend_from_slice(b"noise");
+        stream.extend_from_slice(&frame);
+        let mut frames = Vec::new();
+        for byte in stream {
+            if let Some(frame) = reader.push(byte) {
+                let frame = frame.to_vec();
+                if decode_packet::<Response>(&frame).is_err() {
+                    reader.resume();
+                }
+                frames.push(frame);
+            }
+        }
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[1], frame[1..frame.len() - 1]);
+
+        // Overlong frames are dropped
+        reader.push(DELIMITER);
+        for _ in 0..MAX_ENCODED_LENGTH + 1 {
+            reader.push(1_u8);
+        }
+        assert!(!reader.in_frame());
+    }
+}
@@ -0,0 +1,45 @@
+//! The alarm clock's framed serial protocol, shared by the firmware and the
+//! host tools so both sides stay in lockstep
+//!
+//! Requests and responses are sent as packets of
+//! `[version, kind, id, payload..., CRC-16 (little endian)]`, where `kind`
+//! names the message and `id` is echoed back in the response. Each packet is
+//! COBS-encoded and sent between zeros, which never appear in the shell's text
+//! or the firmware's binary log frames (whose contents are COBS-encoded too),
+//! so they can all share the serial port. A response to an unknown kind or a packet
+//! of a different version is an `Error` response carrying this side's version.
+//!
+//! Changes that older firmware or tools can't decode need a new
+//! `PROTOCOL_VERSION`.
+
+#![cfg_attr(not(test), no_std)]
+
+pub mod cobs;
+pub mod crc;
+pub mod frame;
+pub mod message;
+pub mod wire;
+
+pub use frame::{decode_packet, encode_packet, FrameReader, Packet, DELIMITER, MAX_FRAME_LENGTH};
+pub use message::{
+    Alarm, DateTime, Diagnostics, ErrorCode, LogEntry, MissedAlarms, Request, Response,
+    SettingsRecord,
+};
+
+pub const PROTOCOL_VERSION: u8 = 1_u8;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Error {
+    /// The frame isn't valid COBS
+    Cobs,
+    BadCrc,
+    /// The packet is from a different version of the protocol
+    UnsupportedVersion(u8),
+    UnknownKind(u8),
+    /// A field has a value this side doesn't know
+    BadValue,
+    /// The packet ended before its message did
+    Truncated,
+    /// The packet went on after its message, or doesn't fit in a frame
+    TooLong,
+}
@@ -0,0 +1,371 @@
+//! The requests the host can make and the clock's responses
+
+use crate::{
+    wire::{Reader, Writer},
+    Error,
+};
+
+pub const MAX_ALARMS: usize = 4_usize;
+/// Room for the firmware's settings record, which grows with each version
+pub const MAX_SETTINGS_RECORD_LENGTH: usize = 56_usize;
+pub const MISSED_ALARMS_PER_MESSAGE: usize = 7_usize;
+
+/// Encoding and decoding of a message's payload. `kind` names the message.
+pub trait Message: Sized {
+    fn kind(&self) -> u8;
+    fn encode(&self, writer: &mut Writer) -> Result<(), Error>;
+    fn decode(kind: u8, reader: &mut Reader) -> Result<Self, Error>;
+}
+
+/// The year is from 20[00-99]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct DateTime {
+    pub year: u8,
+    pub month: u8,
+    pub day: u8,
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+}
+impl DateTime {
+    fn encode(&self, writer: &mut Writer) -> Result<(), Error> {
+        writer.bytes(&[
+            self.year,
+            self.month,
+            self.day,
+            self.hours,
+            self.minutes,
+            self.seconds,
+        ])
+    }
+
+    fn decode(reader: &mut Reader) -> Result<Self, Error> {
+        let bytes = reader.bytes(6)?;
+        Ok(Self {
+            year: bytes[0],
+            month: bytes[1],
+            day: bytes[2],
+            hours: bytes[3],
+            minutes: bytes[4],
+            seconds: bytes[5],
+        })
+    }
+}
+
+/// One entry of the alarm table, as the firmware stores it in its settings
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Alarm {
+    pub hours: u8,
+    pub minutes: u8,
+    /// Whether the entry is present and enabled
+    pub flags: u8,
+    /// A tune index, or a high bit and an escalation preset
+    pub sound: u8,
+}
+
+fn encode_alarms(alarms: &[Alarm; MAX_ALARMS], writer: &mut Writer) -> Result<(), Error> {
+    for alarm in alarms {
+        writer.bytes(&[alarm.hours, alarm.minutes, alarm.flags, alarm.sound])?;
+    }
+    Ok(())
+}
+
+fn decode_alarms(reader: &mut Reader) -> Result<[Alarm; MAX_ALARMS], Error> {
+    let mut alarms = [Alarm::default(); MAX_ALARMS];
+    for alarm in &mut alarms {
+        let bytes = reader.bytes(4)?;
+        *alarm = Alarm {
+            hours: bytes[0],
+            minutes: bytes[1],
+            flags: bytes[2],
+            sound: bytes[3],
+        };
+    }
+    Ok(alarms)
+}
+
+/// The firmware's settings as it stores them in the EEPROM: versioned, so
+/// older records can be migrated, and with their own CRC
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SettingsRecord {
+    length: u8,
+    bytes: [u8; MAX_SETTINGS_RECORD_LENGTH],
+}
+impl SettingsRecord {
+    /// `None` if the record is too long
+    pub fn new(record: &[u8]) -> Option<Self> {
+        let mut bytes = [0_u8; MAX_SETTINGS_RECORD_LENGTH];
+        bytes.get_mut(..record.len())?.copy_from_slice(record);
+        Some(Self {
+            length: record.len() as u8,
+            bytes,
+        })
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.length as usize]
+    }
+
+    fn encode(&self, writer: &mut Writer) -> Result<(), Error> {
+        writer.u8(self.length)?;
+        writer.bytes(self.as_bytes())
+    }
+
+    fn decode(reader: &mut Reader) -> Result<Self, Error> {
+        let length = reader.u8()? as usize;
+        Self::new(reader.bytes(length)?).ok_or(Error::TooLong)
+    }
+}
+
+/// An alarm that rang out without being dismissed
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct LogEntry {
+    /// When the alarm went off (the seconds are always 0)
+    pub time: DateTime,
+    pub snoozes: u8,
+}
+
+/// A page of the missed alarm log, most recent first
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MissedAlarms {
+    /// How many more recent entries were skipped
+    pub start: u8,
+    count: u8,
+    entries: [LogEntry; MISSED_ALARMS_PER_MESSAGE],
+}
+impl MissedAlarms {
+    pub fn new(start: u8) -> Self {
+        Self {
+            start,
+            count: 0_u8,
+            entries: [LogEntry::default(); MISSED_ALARMS_PER_MESSAGE],
+        }
+    }
+
+    /// Add an entry, returning false if the page is full
+    pub fn push(&mut self, entry: LogEntry) -> bool {
+        match self.entries.get_mut(self.count as usize) {
+            Some(slot) => {
+                *slot = entry;
+                self.count += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn entries(&self) -> &[LogEntry] {
+        &self.entries[..self.count as usize]
+    }
+
+    /// A page that isn't full is the last one
+    pub fn is_last(&self) -> bool {
+        (self.count as usize) < MISSED_ALARMS_PER_MESSAGE
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Diagnostics {
+    pub uptime_seconds: u32,
+    pub boots: u16,
+    /// Snoozes over the lifetime of the clock
+    pub snoozes: u16,
+    /// Bytes the serial port couldn't keep up with since boot
+    pub serial_dropped: u16,
+    /// The settings version the firmware writes
+    pub settings_version: u8,
+    /// Idle, alarm, snoozed or setting (0-3)
+    pub mode: u8,
+    pub last_sync: Option<DateTime>,
+}
+
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ErrorCode {
+    UnsupportedVersion = 1_u8,
+    UnknownKind = 2_u8,
+    /// The request didn't decode
+    Malformed = 3_u8,
+    /// A value in the request was out of range
+    OutOfRange = 4_u8,
+    /// Settings that couldn't be decoded, e.g. from newer firmware
+    BadSettings = 5_u8,
+}
+impl ErrorCode {
+    pub fn from_u8(code: u8) -> Option<Self> {
+        [
+            ErrorCode::UnsupportedVersion,
+            ErrorCode::UnknownKind,
+            ErrorCode::Malformed,
+            ErrorCode::OutOfRange,
+            ErrorCode::BadSettings,
+        ]
+        .into_iter()
+        .find(|error| *error as u8 == code)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::UnsupportedVersion => "unsupported protocol version",
+            ErrorCode::UnknownKind => "unknown request",
+            ErrorCode::Malformed => "malformed request",
+            ErrorCode::OutOfRange => "value out of range",
+            ErrorCode::BadSettings => "bad settings",
+        }
+    }
+}
+impl From<Error> for ErrorCode {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::UnsupportedVersion(_) => ErrorCode::UnsupportedVersion,
+            Error::UnknownKind(_) => ErrorCode::UnknownKind,
+            _ => ErrorCode::Malformed,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Request {
+    GetTime,
+    SetTime(DateTime),
+    GetAlarms,
+    /// Replace the whole alarm table
+    SetAlarms([Alarm; MAX_ALARMS]),
+    GetSettings,
+    SetSettings(SettingsRecord),
+    /// A page of the missed alarm log, skipping this many recent entries
+    ReadMissedAlarms(u8),
+    GetDiagnostics,
+}
+impl Message for Request {
+    fn kind(&self) -> u8 {
+        match self {
+            Request::GetTime => 0x01_u8,
+            Request::SetTime(_) => 0x02_u8,
+            Request::GetAlarms => 0x03_u8,
+            Request::SetAlarms(_) => 0x04_u8,
+            Request::GetSettings => 0x05_u8,
+            Request::SetSettings(_) => 0x06_u8,
+            Request::ReadMissedAlarms(_) => 0x07_u8,
+            Request::GetDiagnostics => 0x08_u8,
+        }
+    }
+
+    fn encode(&self, writer: &mut Writer) -> Result<(), Error> {
+        match self {
+            Request::GetTime
+            | Request::GetAlarms
+            | Request::GetSettings
+            | Request::GetDiagnostics => Ok(()),
+            Request::SetTime(time) => time.encode(writer),
+            Request::SetAlarms(alarms) => encode_alarms(alarms, writer),
+            Request::SetSettings(record) => record.encode(writer),
+            Request::ReadMissedAlarms(start) => writer.u8(*start),
+        }
+    }
+
+    fn decode(kind: u8, reader: &mut Reader) -> Result<Self, Error> {
+        Ok(match kind {
+            0x01_u8 => Request::GetTime,
+            0x02_u8 => Request::SetTime(DateTime::decode(reader)?),
+            0x03_u8 => Request::GetAlarms,
+            0x04_u8 => Request::SetAlarms(decode_alarms(reader)?),
+            0x05_u8 => Request::GetSettings,
+            0x06_u8 => Request::SetSettings(SettingsRecord::decode(reader)?),
+            0x07_u8 => Request::ReadMissedAlarms(reader.u8()?),
+            0x08_u8 => Request::GetDiagnostics,
+            kind => return Err(Error::UnknownKind(kind)),
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Response {
+    /// The clock's time, after setting it if asked to
+    Time(DateTime),
+    /// The alarm table, after replacing it if asked to
+    Alarms([Alarm; MAX_ALARMS]),
+    Settings(SettingsRecord),
+    /// The request was carried out
+    Done,
+    MissedAlarms(MissedAlarms),
+    Diagnostics(Diagnostics),
+    Error(ErrorCode),
+}
+impl Message for Response {
+    fn kind(&self) -> u8 {
+        match self {
+            Response::Time(_) => 0x81_u8,
+            Response::Alarms(_) => 0x83_u8,
+            Response::Settings(_) => 0x85_u8,
+            Response::Done => 0x86_u8,
+            Response::MissedAlarms(_) => 0x87_u8,
+            Response::Diagnostics(_) => 0x88_u8,
+            Response::Error(_) => 0xFF_u8,
+        }
+    }
+
+    fn encode(&self, writer: &mut Writer) -> Result<(), Error> {
+        match self {
+            Response::Time(time) => time.encode(writer),
+            Response::Alarms(alarms) => encode_alarms(alarms, writer),
+            Response::Settings(record) => record.encode(writer),
+            Response::Done => Ok(()),
+            Response::MissedAlarms(page) => {
+                writer.u8(page.start)?;
+                writer.u8(page.count)?;
+                for entry in page.entries() {
+                    entry.time.encode(writer)?;
+                    writer.u8(entry.snoozes)?;
+                }
+                Ok(())
+            }
+            Response::Diagnostics(diagnostics) => {
+                writer.u32(diagnostics.uptime_seconds)?;
+                writer.u16(diagnostics.boots)?;
+                writer.u16(diagnostics.snoozes)?;
+                writer.u16(diagnostics.serial_dropped)?;
+                writer.u8(diagnostics.settings_version)?;
+                writer.u8(diagnostics.mode)?;
+                writer.u8(diagnostics.last_sync.is_some() as u8)?;
+                diagnostics.last_sync.unwrap_or_default().encode(writer)
+            }
+            Response::Error(code) => writer.u8(*code as u8),
+        }
+    }
+
+    fn decode(kind: u8, reader: &mut Reader) -> Result<Self, Error> {
+        Ok(match kind {
+            0x81_u8 => Response::Time(DateTime::decode(reader)?),
+            0x83_u8 => Response::Alarms(decode_alarms(reader)?),
+            0x85_u8 => Response::Settings(SettingsRecord::decode(reader)?),
+            0x86_u8 => Response::Done,
+            0x87_u8 => {
+                let mut page = MissedAlarms::new(reader.u8()?);
+                for _ in 0..reader.u8()? {
+                    let time = DateTime::decode(reader)?;
+                    let snoozes = reader.u8()?;
+                    if !page.push(LogEntry { time, snoozes }) {
+                        return Err(Error::TooLong);
+                    }
+                }
+                Response::MissedAlarms(page)
+            }
+            0x88_u8 => Response::Diagnostics(Diagnostics {
+                uptime_seconds: reader.u32()?,
+                boots: reader.u16()?,
+                snoozes: reader.u16()?,
+                serial_dropped: reader.u16()?,
+                settings_version: reader.u8()?,
+                mode: reader.u8()?,
+                last_sync: {
+                    let synced = reader.u8()? != 0_u8;
+                    let time = DateTime::decode(reader)?;
+                    synced.then_some(time)
+                },
+            }),
+            0xFF_u8 => Response::Error(ErrorCode::from_u8(reader.u8()?).ok_or(Error::BadValue)?),
+            kind => return Err(Error::UnknownKind(kind)),
+        })
+    }
+}
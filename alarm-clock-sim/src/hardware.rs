@@ -0,0 +1,220 @@
+//! Simulated stand-ins for the clock's hardware
+
+use alarm_clock_core::{
+    glyph::Glyph,
+    hal::{CharacterDisplay, ClockSource, InputEvent, InputSource, SegmentDisplay},
+    time::Time,
+};
+use chrono::{Datelike, NaiveDate, NaiveDateTime, TimeDelta, Timelike};
+use std::collections::VecDeque;
+
+/// The RTC, counting simulated time from whenever it was last set
+pub struct SimClock {
+    set_to: NaiveDateTime,
+    /// Simulated millis when it was set
+    set_at: u64,
+    /// Simulated millis now, kept up to date by the simulator
+    pub now: u64,
+    pub battery_low: bool,
+}
+impl SimClock {
+    pub fn new(date_time: NaiveDateTime) -> Self {
+        Self {
+            set_to: date_time,
+            set_at: 0_u64,
+            now: 0_u64,
+            battery_low: false,
+        }
+    }
+
+    pub fn date_time(&self) -> NaiveDateTime {
+        self.set_to + TimeDelta::milliseconds(self.now.saturating_sub(self.set_at) as i64)
+    }
+}
+impl ClockSource for SimClock {
+    fn read_time(&mut self) -> Option<Time> {
+        to_time(&self.date_time())
+    }
+
+    fn set_time(&mut self, time: &Time) {
+        if let Some(date_time) = from_time(time) {
+            self.set_to = date_time;
+            self.set_at = self.now;
+        }
+    }
+
+    fn battery_low(&mut self) -> bool {
+        self.battery_low
+    }
+}
+
+/// The time as the RTC keeps it, if it's in the RTC's range (2000-2099)
+pub fn to_time(date_time: &NaiveDateTime) -> Option<Time> {
+    Some(Time {
+        hours: date_time.hour() as u8,
+        minutes: date_time.minute() as u8,
+        seconds: date_time.second() as u8,
+        day: date_time.day() as u8,
+        day_of_week: date_time.weekday().num_days_from_sunday() as u8,
+        month: date_time.month() as u8,
+        year: u8::try_from(date_time.year().checked_sub(2000)?)
+            .ok()
+            .filter(|year| *year < 100_u8)?,
+    })
+}
+
+pub fn from_time(time: &Time) -> Option<NaiveDateTime> {
+    NaiveDate::from_ymd_opt(2000 + time.year as i32, time.month as u32, time.day as u32)?
+        .and_hms_opt(time.hours as u32, time.minutes as u32, time.seconds as u32)
+}
+
+/// Shift registers that hold the last outputs written, like the seconds'
+#[derive(Default)]
+pub struct SegmentLatch {
+    pub outputs: [bool; 16],
+}
+impl SegmentDisplay for SegmentLatch {
+    fn write(&mut self, outputs: [bool; 16]) {
+        self.outputs = outputs;
+    }
+}
+
+/// A 16x2 LCD's screen
+pub struct SimLcd {
+    screen: [[u8; 16]; 2],
+    cursor: (u8, u8),
+    cgram: [[u8; 8]; 8],
+}
+impl SimLcd {
+    pub fn new() -> Self {
+        Self {
+            screen: [[b' '; 16]; 2],
+            cursor: (0_u8, 0_u8),
+            cgram: [[0_u8; 8]; 8],
+        }
+    }
+
+    /// The row [0, 1] as shown, with custom characters drawn as the glyph
+    /// their bitmap is
+    pub fn line(&self, row: u8) -> String {
+        self.screen[row as usize]
+            .iter()
+            .map(|&c| match c {
+                b' '..=b'~' => c as char,
+                0_u8..=7_u8 => Glyph::ALL
+                    .into_iter()
+                    .find(|glyph| glyph.bitmap() == self.cgram[c as usize])
+                    .map_or('?', symbol),
+                _ => '?',
+            })
+            .collect()
+    }
+}
+
+/// A glyph as one terminal column
+fn symbol(glyph: Glyph) -> char {
+    match glyph {
+        Glyph::Bell => '♪',
+        Glyph::Snooze => 'z',
+        Glyph::Battery(0_u8) => '▁',
+        Glyph::Battery(1_u8) => '▃',
+        Glyph::Battery(2_u8) => '▅',
+        Glyph::Battery(_) => '▇',
+        Glyph::Sun => '☼',
+        Glyph::Moon => '☾',
+        Glyph::ArrowUp => '↑',
+        Glyph::ArrowDown => '↓',
+        Glyph::ArrowLeft => '←',
+        Glyph::ArrowRight => '→',
+        Glyph::BigUpper => '▀',
+        Glyph::BigLower => '▄',
+        Glyph::BigBoth => '☰',
+        Glyph::BigFull => '█',
+    }
+}
+impl Default for SimLcd {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl CharacterDisplay for SimLcd {
+    fn clear(&mut self) {
+        self.screen = [[b' '; 16]; 2];
+        self.cursor = (0_u8, 0_u8);
+    }
+
+    fn set_position(&mut self, column: u8, row: u8) {
+        self.cursor = (column, row);
+    }
+
+    fn print(&mut self, text: &str) {
+        for byte in text.bytes() {
+            let (column, row) = self.cursor;
+            if let Some(cell) = self.screen[(row & 1_u8) as usize].get_mut(column as usize) {
+                *cell = byte;
+            }
+            self.cursor.0 = column.saturating_add(1_u8);
+        }
+    }
+
+    fn create_char(&mut self, code: u8, bitmap: [u8; 8]) {
+        self.cgram[(code & 7_u8) as usize] = bitmap;
+    }
+}
+
+/// Key presses waiting to be read
+#[derive(Default)]
+pub struct SimInputs {
+    pub events: VecDeque<InputEvent>,
+}
+impl InputSource for SimInputs {
+    fn next_event(&mut self) -> Option<InputEvent> {
+        self.events.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clock() {
+        let start = NaiveDate::from_ymd_opt(2024, 3, 10)
+            .and_then(|date| date.and_hms_opt(1, 59, 58))
+            .unwrap();
+        let mut clock = SimClock::new(start);
+        let time = clock.read_time().unwrap();
+        assert_eq!((time.year, time.month, time.day), (24_u8, 3_u8, 10_u8));
+        // A Sunday
+        assert_eq!(time.day_of_week, 0_u8);
+
+        clock.now = 3_000_u64;
+        let time = clock.read_time().unwrap();
+        assert_eq!((time.hours, time.minutes, time.seconds), (2_u8, 0_u8, 1_u8));
+
+        clock.set_time(&Time {
+            hours: 7_u8,
+            ..time
+        });
+        clock.now = 63_000_u64;
+        let time = clock.read_time().unwrap();
+        assert_eq!((time.hours, time.minutes, time.seconds), (7_u8, 1_u8, 1_u8));
+        assert_eq!(from_time(&time), Some(clock.date_time()));
+
+        let too_late = NaiveDate::from_ymd_opt(2100, 1, 1)
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .unwrap();
+        assert_eq!(to_time(&too_late), None);
+    }
+
+    #[test]
+    fn lcd() {
+        let mut lcd = SimLcd::new();
+        lcd.set_position(14_u8, 1_u8);
+        lcd.print("abc");
+        assert_eq!(lcd.line(1_u8), "              ab");
+        lcd.clear();
+        lcd.print("\u{1}");
+        assert_eq!(lcd.line(0_u8), "?               ");
+    }
+}
@@ -0,0 +1,6 @@
+//! Runs the alarm clock's logic against simulated hardware, so changes can be
+//! tried out without flashing an Uno
+
+pub mod hardware;
+pub mod render;
+pub mod simulator;
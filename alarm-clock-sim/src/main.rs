@@ -0,0 +1,220 @@
+//! Runs the clock in a terminal
+//!
+//! ```text
+//! alarm-clock-sim --speed 60 --alarm 07:00
+//! alarm-clock-sim --start "2024-03-10 01:58" --speed 600
+//! ```
+
+use alarm_clock_core::{
+    hal::{Direction, InputEvent},
+    settings::MAX_ALARMS,
+    state::{Alarm, AlarmSound, State},
+};
+use alarm_clock_sim::{
+    render::render,
+    simulator::{Simulator, MESSAGES},
+};
+use chrono::{Local, NaiveDateTime, NaiveTime, Timelike};
+use crossterm::{
+    cursor::{self, MoveTo},
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute, queue,
+    style::Print,
+    terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use std::{
+    env,
+    io::{self, Write},
+    process::ExitCode,
+    time::{Duration, Instant},
+};
+
+const USAGE: &str = "\
+Usage: alarm-clock-sim [--speed N] [--start \"YYYY-MM-DD HH:MM[:SS]\"] [--alarm HH:MM]...
+
+Options:
+  --speed  how many times faster than real time the clock runs (default 1)
+  --start  the date and time the clock starts at (default now)
+  --alarm  an alarm to set (up to 4), which also turns the alarm on";
+
+const KEYS: &str = "\
+left/right: turn   enter: push   space: snooze   a: alarm on/off
++/-: speed   q: quit";
+
+/// The speeds `+` and `-` step through
+const SPEEDS: [u32; 5] = [1_u32, 10_u32, 60_u32, 600_u32, 3_600_u32];
+/// How long to wait for a key before drawing again
+const FRAME: Duration = Duration::from_millis(50);
+
+struct Arguments {
+    speed: u32,
+    start: NaiveDateTime,
+    alarms: Vec<NaiveTime>,
+}
+
+fn main() -> ExitCode {
+    let arguments = env::args().skip(1).collect::<Vec<_>>();
+    let arguments = match parse_arguments(&arguments) {
+        Some(arguments) => arguments,
+        None => {
+            eprintln!("{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match run(arguments) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn parse_arguments(arguments: &[String]) -> Option<Arguments> {
+    let mut speed = 1_u32;
+    let mut start = Local::now().naive_local();
+    let mut alarms = Vec::new();
+    let mut arguments = arguments.iter();
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--speed" => speed = arguments.next()?.parse().ok().filter(|&speed| speed > 0)?,
+            "--start" => {
+                let start_argument = arguments.next()?;
+                start = NaiveDateTime::parse_from_str(start_argument, "%Y-%m-%d %H:%M:%S")
+                    .or_else(|_| NaiveDateTime::parse_from_str(start_argument, "%Y-%m-%d %H:%M"))
+                    .ok()?;
+            }
+            "--alarm" => alarms.push(NaiveTime::parse_from_str(arguments.next()?, "%H:%M").ok()?),
+            _ => return None,
+        }
+    }
+    if alarms.len() > MAX_ALARMS {
+        return None;
+    }
+    Some(Arguments {
+        speed,
+        start,
+        alarms,
+    })
+}
+
+/// Puts the terminal back however the simulator stops
+struct Terminal;
+impl Terminal {
+    fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen, cursor::Hide)?;
+        Ok(Self)
+    }
+}
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), cursor::Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+fn run(arguments: Arguments) -> io::Result<()> {
+    let mut state = State::new();
+    for (slot, time) in state.alarms.iter_mut().zip(&arguments.alarms) {
+        *slot = Some(Alarm {
+            hours: time.hour() as u8,
+            minutes: time.minute() as u8,
+            enabled: true,
+            sound: AlarmSound::default(),
+        });
+    }
+    state.alarm_enabled = !arguments.alarms.is_empty();
+
+    let mut simulator = Simulator::new(state, arguments.start);
+    let mut speed = arguments.speed;
+    let _terminal = Terminal::enter()?;
+    let mut stdout = io::stdout();
+
+    let mut last_frame = Instant::now();
+    // Simulated micros not yet run, so slow speeds don't lose time to rounding
+    let mut carry = 0_u128;
+    loop {
+        if event::poll(FRAME)? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Release && !handle_key(&mut simulator, &mut speed, key)
+                {
+                    return Ok(());
+                }
+            }
+        }
+
+        let elapsed = last_frame.elapsed();
+        last_frame += elapsed;
+        carry += elapsed.as_micros() * speed as u128;
+        simulator.run_for((carry / 1_000_u128) as u64);
+        carry %= 1_000_u128;
+
+        draw(&mut stdout, &mut simulator, speed)?;
+    }
+}
+
+/// Follow a key press, returning whether to keep running
+fn handle_key(simulator: &mut Simulator, speed: &mut u32, key: KeyEvent) -> bool {
+    match key.code {
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+        KeyCode::Char('q') | KeyCode::Esc => return false,
+        KeyCode::Left => simulator.input(InputEvent::Turn(Direction::CounterClockwise)),
+        KeyCode::Right => simulator.input(InputEvent::Turn(Direction::Clockwise)),
+        KeyCode::Enter => simulator.input(InputEvent::Push),
+        KeyCode::Char(' ' | 's') => simulator.input(InputEvent::Snooze),
+        KeyCode::Char('a') => {
+            let state = &mut simulator.app.state;
+            state.alarm_enabled = !state.alarm_enabled;
+        }
+        KeyCode::Char('+' | '=') => {
+            *speed = SPEEDS
+                .into_iter()
+                .find(|&faster| faster > *speed)
+                .unwrap_or(*speed);
+        }
+        KeyCode::Char('-') => {
+            *speed = SPEEDS
+                .into_iter()
+                .rev()
+                .find(|&slower| slower < *speed)
+                .unwrap_or(*speed);
+        }
+        _ => (),
+    }
+    true
+}
+
+fn draw(stdout: &mut io::Stdout, simulator: &mut Simulator, speed: u32) -> io::Result<()> {
+    let mut lines = render(simulator);
+    lines.push(String::new());
+    lines.push(format!(
+        "{}   x{speed}   alarm {}{}",
+        simulator.app.clock.date_time().format("%a %Y-%m-%d"),
+        if simulator.app.state.alarm_enabled {
+            "on"
+        } else {
+            "off"
+        },
+        simulator
+            .ringing
+            .map(|sound| format!(" (ringing: {})", sound.name()))
+            .unwrap_or_default(),
+    ));
+    lines.push(String::new());
+    lines.extend(simulator.messages.iter().cloned());
+    lines.extend((simulator.messages.len()..MESSAGES).map(|_| String::new()));
+    lines.push(String::new());
+    lines.extend(KEYS.lines().map(String::from));
+
+    for (row, line) in lines.iter().enumerate() {
+        queue!(
+            stdout,
+            MoveTo(0_u16, row as u16),
+            Print(line),
+            Clear(ClearType::UntilNewLine)
+        )?;
+    }
+    stdout.flush()
+}
@@ -0,0 +1,93 @@
+//! ASCII art of the clock's front
+
+use crate::simulator::Simulator;
+
+/// The three rows of a seven segment digit lit with the A-G segments
+pub fn digit_rows(segments: [bool; 7]) -> [String; 3] {
+    let [a, b, c, d, e, f, g] = segments;
+    let lit = |on: bool, segment: char| if on { segment } else { ' ' };
+    [
+        format!(" {} ", lit(a, '_')),
+        format!("{}{}{}", lit(f, '|'), lit(g, '_'), lit(b, '|')),
+        format!("{}{}{}", lit(e, '|'), lit(d, '_'), lit(c, '|')),
+    ]
+}
+
+/// The displays, LEDs, and LCD, a line at a time
+pub fn render(simulator: &mut Simulator) -> Vec<String> {
+    let (hours_minutes, colon) = simulator.hours_minutes();
+    let seconds = simulator.seconds();
+    let hours_minutes = hours_minutes.map(digit_rows);
+    let seconds = seconds.map(digit_rows);
+
+    let mut lines = (0_usize..3_usize)
+        .map(|row| {
+            let colon = match (row, colon) {
+                (1_usize | 2_usize, true) => '.',
+                _ => ' ',
+            };
+            format!(
+                "{}{} {} {}{}   {}{}",
+                hours_minutes[0][row],
+                hours_minutes[1][row],
+                colon,
+                hours_minutes[2][row],
+                hours_minutes[3][row],
+                seconds[0][row],
+                seconds[1][row],
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let led = |on: bool| if on { '*' } else { ' ' };
+    lines.push(String::new());
+    lines.push(format!(
+        "[{}] PM   [{}] ALARM",
+        led(simulator.pm_led()),
+        led(simulator.alarm_led())
+    ));
+    lines.push(String::new());
+    lines.push(String::from("+----------------+"));
+    lines.push(format!("|{}|", simulator.app.lcd.line(0_u8)));
+    lines.push(format!("|{}|", simulator.app.lcd.line(1_u8)));
+    lines.push(String::from("+----------------+"));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alarm_clock_core::{display::segments, state::State};
+    use chrono::NaiveDate;
+
+    #[test]
+    fn digits() {
+        assert_eq!(digit_rows(segments(8_u8)), [" _ ", "|_|", "|_|"]);
+        assert_eq!(digit_rows(segments(1_u8)), ["   ", "  |", "  |"]);
+        assert_eq!(digit_rows(segments(7_u8)), [" _ ", "  |", "  |"]);
+    }
+
+    #[test]
+    fn clock_face() {
+        let start = NaiveDate::from_ymd_opt(2024, 3, 10)
+            .and_then(|date| date.and_hms_opt(13, 42, 7))
+            .unwrap();
+        let mut simulator = Simulator::new(State::new(), start);
+        simulator.run_for(0_u64);
+        assert_eq!(
+            render(&mut simulator),
+            [
+                "    _        _     _  _ ",
+                "  | _| . |_| _|   | |  |",
+                "  | _| .   ||_    |_|  |",
+                "",
+                "[*] PM   [ ] ALARM",
+                "",
+                "+----------------+",
+                "|Sunday          |",
+                "|10/03/2024      |",
+                "+----------------+",
+            ]
+        );
+    }
+}
@@ -0,0 +1,224 @@
+//! The clock's logic running against the simulated hardware, on simulated time
+//! that can run faster than real time
+
+use alarm_clock_core::{
+    app::App,
+    display::{decode_hours_minutes, decode_seconds, Multiplexer, Slot, MAX_BRIGHTNESS},
+    hal::InputEvent,
+    state::{AlarmEvent, AlarmSound, State},
+};
+use chrono::NaiveDateTime;
+use std::collections::VecDeque;
+
+use crate::hardware::{SegmentLatch, SimClock, SimInputs, SimLcd};
+
+/// How often the firmware's main loop updates
+pub const UPDATE_MS: u64 = 100_u64;
+/// Messages kept for showing
+pub const MESSAGES: usize = 4_usize;
+
+pub type SimApp = App<SimClock, SegmentLatch, SimLcd, SimInputs>;
+
+pub struct Simulator {
+    pub app: SimApp,
+    multiplexer: Multiplexer,
+    /// Simulated millis since starting
+    now: u64,
+    next_update: u64,
+    /// The alarm sound that's playing, if any
+    pub ringing: Option<AlarmSound>,
+    /// What happened recently, newest last
+    pub messages: VecDeque<String>,
+}
+impl Simulator {
+    pub fn new(state: State, start: NaiveDateTime) -> Self {
+        Self {
+            app: App::new(
+                state,
+                SimClock::new(start),
+                SegmentLatch::default(),
+                SimLcd::new(),
+                SimInputs::default(),
+            ),
+            multiplexer: Multiplexer::new(),
+            now: 0_u64,
+            next_update: 0_u64,
+            ringing: None,
+            messages: VecDeque::new(),
+        }
+    }
+
+    /// Simulated millis since starting
+    pub fn now(&self) -> u64 {
+        self.now
+    }
+
+    /// Queue up something done with the controls, read on the next update
+    pub fn input(&mut self, event: InputEvent) {
+        self.app.inputs.events.push_back(event);
+    }
+
+    /// Run the clock for `ms` simulated millis, updating it as often as the
+    /// firmware would
+    pub fn run_for(&mut self, ms: u64) {
+        let end = self.now + ms;
+        while self.next_update <= end {
+            self.now = self.next_update;
+            self.app.clock.now = self.now;
+            // The firmware's millis wrap, so these do too
+            let event = self.app.update(self.now as u32);
+            if let Some(event) = event {
+                self.follow(event);
+            }
+            self.next_update += UPDATE_MS;
+        }
+        self.now = end;
+        self.app.clock.now = end;
+    }
+
+    /// Do what the firmware does for each alarm event, as far as it can be seen
+    fn follow(&mut self, event: AlarmEvent) {
+        let message = match event {
+            AlarmEvent::Started(sound) => {
+                self.ringing = Some(sound);
+                format!("Alarm! ({})", sound.name())
+            }
+            AlarmEvent::SnoozeOver(sound) => {
+                self.ringing = Some(sound);
+                String::from("Snooze over")
+            }
+            AlarmEvent::Dismissed => {
+                self.ringing = None;
+                String::from("Alarm dismissed")
+            }
+            AlarmEvent::Snoozed => {
+                self.ringing = None;
+                String::from("Alarm snoozed")
+            }
+            AlarmEvent::RangOut(_) => {
+                self.ringing = None;
+                String::from("Alarm rang out, nobody home?")
+            }
+            AlarmEvent::MissedAlarmAcknowledged => String::from("Missed alarm acknowledged"),
+        };
+        let time = self.app.clock.date_time().format("%H:%M:%S");
+        self.messages.push_back(format!("{time} {message}"));
+        if self.messages.len() > MESSAGES {
+            self.messages.pop_front();
+        }
+    }
+
+    /// The segments lit on each of the hours and minutes digits, and whether
+    /// the colon is lit, over a full round of multiplexing (as the eye sees it)
+    pub fn hours_minutes(&mut self) -> ([[bool; 7]; 4], bool) {
+        let digits = &self.app.state.digits;
+        self.multiplexer.set_brightness(self.app.state.brightness);
+        let mut lit = [[false; 7]; 4];
+        let mut colon = false;
+        for _ in 0_u8..5_u8 * MAX_BRIGHTNESS {
+            match decode_hours_minutes(&self.multiplexer.next(digits.hours, digits.minutes)) {
+                Some(Slot::Digit(position, segments)) => {
+                    for (lit, segment) in lit[position].iter_mut().zip(segments) {
+                        *lit |= segment;
+                    }
+                }
+                Some(Slot::DecimalPoints(decimal_points)) => colon |= decimal_points[2],
+                None => (),
+            }
+        }
+        (lit, colon)
+    }
+
+    /// The segments lit on each of the seconds digits
+    pub fn seconds(&self) -> [[bool; 7]; 2] {
+        decode_seconds(&self.app.seconds.outputs)
+    }
+
+    /// The alarm LED, which the firmware only lights while the alarm sounds
+    pub fn alarm_led(&self) -> bool {
+        self.ringing.is_some()
+    }
+
+    pub fn pm_led(&self) -> bool {
+        self.app.state.time.hours >= 12_u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alarm_clock_core::{
+        display::digit,
+        state::{Alarm, SNOOZE_MINUTES},
+    };
+    use chrono::NaiveDate;
+
+    const MINUTE: u64 = 60_000_u64;
+
+    fn simulator() -> Simulator {
+        let mut state = State::new();
+        state.alarm_enabled = true;
+        state.alarms[0] = Some(Alarm {
+            hours: 7_u8,
+            minutes: 0_u8,
+            enabled: true,
+            sound: AlarmSound::default(),
+        });
+        let start = NaiveDate::from_ymd_opt(2024, 3, 10)
+            .and_then(|date| date.and_hms_opt(6, 58, 30))
+            .unwrap();
+        Simulator::new(state, start)
+    }
+
+    #[test]
+    fn displays() {
+        let mut simulator = simulator();
+        simulator.run_for(0_u64);
+        let (lit, colon) = simulator.hours_minutes();
+        assert_eq!(
+            lit.map(digit),
+            [Some(0_u8), Some(6_u8), Some(5_u8), Some(8_u8)]
+        );
+        assert!(colon);
+        assert_eq!(simulator.seconds().map(digit), [Some(3_u8), Some(0_u8)]);
+        // The alarm's on and it's still quiet hours
+        assert_eq!(simulator.app.lcd.line(0_u8), "Sunday        ♪☾");
+        assert_eq!(simulator.app.lcd.line(1_u8), "10/03/2024      ");
+        assert!(!simulator.pm_led());
+    }
+
+    #[test]
+    fn alarm() {
+        let mut simulator = simulator();
+        simulator.run_for(MINUTE);
+        assert_eq!(simulator.ringing, None);
+        simulator.run_for(MINUTE);
+        assert_eq!(simulator.ringing, Some(AlarmSound::default()));
+        assert!(simulator.alarm_led());
+
+        simulator.input(InputEvent::Snooze);
+        simulator.run_for(UPDATE_MS);
+        assert_eq!(simulator.ringing, None);
+        simulator.run_for(SNOOZE_MINUTES as u64 * MINUTE);
+        assert_eq!(simulator.ringing, Some(AlarmSound::default()));
+
+        simulator.input(InputEvent::Push);
+        simulator.run_for(UPDATE_MS);
+        assert_eq!(simulator.ringing, None);
+        assert_eq!(
+            simulator.messages.back().map(|message| &message[9..]),
+            Some("Alarm dismissed")
+        );
+    }
+
+    #[test]
+    fn long_runs() {
+        let mut simulator = simulator();
+        // Updates are as often as the firmware's, however long the step
+        simulator.run_for(24_u64 * 60_u64 * MINUTE + 1_u64);
+        assert_eq!(simulator.now(), 24_u64 * 60_u64 * MINUTE + 1_u64);
+        assert_eq!(simulator.app.lcd.line(1_u8), "11/03/2024      ");
+        // Rang out without anyone there, after ringing for the longest it can
+        assert_eq!(simulator.app.lcd.line(0_u8), "Missed 07:00 Zz0");
+    }
+}
@@ -0,0 +1,38 @@
+//! Builds the C side of the harness if simavr is installed. Without it, the
+//! virtual hardware still builds (and is tested), but the firmware tests are
+//! skipped.
+
+use std::{env, path::PathBuf};
+
+fn main() {
+    println!("cargo:rustc-check-cfg=cfg(simavr)");
+    println!("cargo:rerun-if-changed=harness.c");
+    println!("cargo:rerun-if-env-changed=SIMAVR_DIR");
+
+    // A build from source (`make install DESTDIR=...`) can be pointed to,
+    // otherwise the packaged library is found with pkg-config
+    let include_paths = match env::var_os("SIMAVR_DIR").map(PathBuf::from) {
+        Some(dir) => {
+            println!(
+                "cargo:rustc-link-search=native={}",
+                dir.join("lib").display()
+            );
+            println!("cargo:rustc-link-lib=simavr");
+            println!("cargo:rustc-link-lib=elf");
+            vec![dir.join("include").join("simavr")]
+        }
+        None => match pkg_config::probe_library("simavr") {
+            Ok(library) => library.include_paths,
+            // The tests say why they're ignored
+            Err(_) => return,
+        },
+    };
+
+    // Packages differ on whether the headers are in a `simavr` directory
+    let mut build = cc::Build::new();
+    for path in include_paths {
+        build.include(path.join("simavr")).include(path);
+    }
+    build.file("harness.c").compile("harness");
+    println!("cargo:rustc-cfg=simavr");
+}
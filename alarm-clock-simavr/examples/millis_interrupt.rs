@@ -0,0 +1,48 @@
+//! How long the firmware's millis interrupt takes, which shifts out a digit
+//! of the time display each milli: `cargo run --release --example
+//! millis_interrupt` once the firmware is built (see `Board::firmware`), with
+//! how many seconds to run for as the argument (10 by default).
+
+#[cfg(simavr)]
+fn main() {
+    use alarm_clock_simavr::board::{Board, CYCLES_PER_MS, FREQUENCY};
+    use std::{env, process};
+
+    let seconds = match env::args().nth(1).map(|seconds| seconds.parse::<u64>()) {
+        None => 10_u64,
+        Some(Ok(seconds)) => seconds,
+        Some(Err(error)) => {
+            eprintln!("Invalid number of seconds: {error}");
+            process::exit(2);
+        }
+    };
+    let Some(elf) = Board::firmware() else {
+        eprintln!("The firmware isn't built (or set ALARM_CLOCK_ELF)");
+        process::exit(1);
+    };
+    let mut board = Board::new(&elf, None).expect("Failed to load the firmware");
+    if let Err(error) = board.run_for(seconds * 1_000_u64) {
+        eprintln!("{error}");
+        process::exit(1);
+    }
+
+    let times = board.devices.millis_interrupt;
+    let us = |cycles: u64| cycles as f64 * 1_000_000_f64 / FREQUENCY as f64;
+    println!("Ran {} times in {seconds}s", times.count);
+    for (name, cycles) in [("Mean", times.mean_cycles()), ("Max", times.max_cycles)] {
+        println!(
+            "{name}: {cycles} cycles ({:.1}us, {:.1}% of a milli)",
+            us(cycles),
+            cycles as f64 * 100_f64 / CYCLES_PER_MS as f64
+        );
+    }
+    if times.max_cycles >= CYCLES_PER_MS {
+        process::exit(1);
+    }
+}
+
+#[cfg(not(simavr))]
+fn main() {
+    eprintln!("simavr isn't installed, so the firmware can't be run");
+    std::process::exit(1);
+}
@@ -0,0 +1,190 @@
+// The C side of the harness: loads the firmware into simavr and forwards the
+// pins, UART and TWI it's wired to over to the virtual hardware in Rust, so
+// that none of simavr's structs have to be laid out on the Rust side.
+
+#include <stdlib.h>
+
+#include "sim_avr.h"
+#include "sim_elf.h"
+#include "sim_io.h"
+#include "sim_irq.h"
+#include "sim_cycle_timers.h"
+#include "sim_interrupts.h"
+#include "avr_ioport.h"
+#include "avr_uart.h"
+#include "avr_twi.h"
+
+typedef void (*harness_pin_callback)(void *param, char port, uint8_t pin, uint32_t high);
+typedef void (*harness_uart_callback)(void *param, uint8_t byte);
+// Called as an interrupt's handler starts running, and as it returns
+typedef void (*harness_interrupt_callback)(void *param, uint8_t vector, uint32_t running,
+                                           uint64_t cycle);
+// Returns whether to reply with `reply_msg` and `reply_data`
+typedef int (*harness_twi_callback)(void *param, uint8_t msg, uint8_t addr, uint8_t data,
+                                    uint8_t *reply_msg, uint8_t *reply_data);
+
+#define MAX_WATCHED_PINS 24
+#define MAX_WATCHED_INTERRUPTS 4
+
+typedef struct pin_watch_t {
+    struct harness_t *harness;
+    char port;
+    uint8_t pin;
+} pin_watch_t;
+
+typedef struct interrupt_watch_t {
+    struct harness_t *harness;
+    uint8_t vector;
+} interrupt_watch_t;
+
+typedef struct harness_t {
+    avr_t *avr;
+    void *param;
+    harness_pin_callback pin_callback;
+    harness_uart_callback uart_callback;
+    harness_twi_callback twi_callback;
+    harness_interrupt_callback interrupt_callback;
+    avr_irq_t *twi_input;
+    pin_watch_t watches[MAX_WATCHED_PINS];
+    int watch_count;
+    interrupt_watch_t interrupt_watches[MAX_WATCHED_INTERRUPTS];
+    int interrupt_watch_count;
+    // Set by the cycle timer that ends `harness_run_for`
+    int reached;
+} harness_t;
+
+harness_t *harness_new(const char *elf_path, uint32_t frequency, void *param) {
+    elf_firmware_t firmware = {0};
+    if (elf_read_firmware(elf_path, &firmware) != 0) {
+        return NULL;
+    }
+    avr_t *avr = avr_make_mcu_by_name("atmega328p");
+    if (!avr) {
+        return NULL;
+    }
+    avr_init(avr);
+    firmware.frequency = frequency;
+    avr_load_firmware(avr, &firmware);
+
+    // The UART's output goes to the harness, not stdout
+    uint32_t flags = 0;
+    avr_ioctl(avr, AVR_IOCTL_UART_GET_FLAGS('0'), &flags);
+    flags &= ~AVR_UART_FLAG_STDIO;
+    avr_ioctl(avr, AVR_IOCTL_UART_SET_FLAGS('0'), &flags);
+
+    harness_t *harness = calloc(1, sizeof(harness_t));
+    harness->avr = avr;
+    harness->param = param;
+    return harness;
+}
+
+void harness_free(harness_t *harness) {
+    avr_terminate(harness->avr);
+    free(harness->avr);
+    free(harness);
+}
+
+static avr_cycle_count_t reached(avr_t *avr, avr_cycle_count_t when, void *param) {
+    ((harness_t *)param)->reached = 1;
+    return 0;
+}
+
+// Run for `cycles`, returning 0 if the firmware crashed or stopped
+int harness_run_for(harness_t *harness, uint64_t cycles) {
+    harness->reached = 0;
+    avr_cycle_timer_register(harness->avr, cycles, reached, harness);
+    int state = cpu_Running;
+    while (!harness->reached) {
+        state = avr_run(harness->avr);
+        if (state == cpu_Done || state == cpu_Crashed) {
+            avr_cycle_timer_cancel(harness->avr, reached, harness);
+            return 0;
+        }
+    }
+    return 1;
+}
+
+uint64_t harness_cycle(harness_t *harness) {
+    return harness->avr->cycle;
+}
+
+static void pin_changed(struct avr_irq_t *irq, uint32_t value, void *param) {
+    pin_watch_t *watch = param;
+    watch->harness->pin_callback(watch->harness->param, watch->port, watch->pin, value);
+}
+
+// Follow an output pin, returning 0 if too many are followed
+int harness_watch_pin(harness_t *harness, char port, uint8_t pin, harness_pin_callback callback) {
+    if (harness->watch_count == MAX_WATCHED_PINS) {
+        return 0;
+    }
+    pin_watch_t *watch = &harness->watches[harness->watch_count++];
+    watch->harness = harness;
+    watch->port = port;
+    watch->pin = pin;
+    harness->pin_callback = callback;
+    avr_irq_register_notify(avr_io_getirq(harness->avr, AVR_IOCTL_IOPORT_GETIRQ(port), pin),
+                            pin_changed, watch);
+    return 1;
+}
+
+// Drive an input pin from the outside, like a button would
+void harness_set_pin(harness_t *harness, char port, uint8_t pin, uint32_t high) {
+    avr_raise_irq(avr_io_getirq(harness->avr, AVR_IOCTL_IOPORT_GETIRQ(port), pin), high);
+}
+
+static void uart_output(struct avr_irq_t *irq, uint32_t value, void *param) {
+    harness_t *harness = param;
+    harness->uart_callback(harness->param, value);
+}
+
+void harness_watch_uart(harness_t *harness, harness_uart_callback callback) {
+    harness->uart_callback = callback;
+    avr_irq_register_notify(avr_io_getirq(harness->avr, AVR_IOCTL_UART_GETIRQ('0'), UART_IRQ_OUTPUT),
+                            uart_output, harness);
+}
+
+// Queue a byte to be received, at the UART's baud rate
+void harness_send_uart(harness_t *harness, uint8_t byte) {
+    avr_raise_irq(avr_io_getirq(harness->avr, AVR_IOCTL_UART_GETIRQ('0'), UART_IRQ_INPUT), byte);
+}
+
+static void twi_message(struct avr_irq_t *irq, uint32_t value, void *param) {
+    harness_t *harness = param;
+    avr_twi_msg_irq_t message = {.u.v = value};
+    uint8_t reply_msg = 0;
+    uint8_t reply_data = 0;
+    if (harness->twi_callback(harness->param, message.u.twi.msg, message.u.twi.addr,
+                              message.u.twi.data, &reply_msg, &reply_data)) {
+        avr_raise_irq(harness->twi_input,
+                      avr_twi_irq_msg(reply_msg, message.u.twi.addr, reply_data));
+    }
+}
+
+void harness_attach_twi(harness_t *harness, harness_twi_callback callback) {
+    harness->twi_callback = callback;
+    harness->twi_input = avr_io_getirq(harness->avr, AVR_IOCTL_TWI_GETIRQ(0), TWI_IRQ_INPUT);
+    avr_irq_register_notify(avr_io_getirq(harness->avr, AVR_IOCTL_TWI_GETIRQ(0), TWI_IRQ_OUTPUT),
+                            twi_message, harness);
+}
+
+static void interrupt_running(struct avr_irq_t *irq, uint32_t value, void *param) {
+    interrupt_watch_t *watch = param;
+    harness_t *harness = watch->harness;
+    harness->interrupt_callback(harness->param, watch->vector, value, harness->avr->cycle);
+}
+
+// Follow when an interrupt's handler runs, returning 0 if too many are followed
+int harness_watch_interrupt(harness_t *harness, uint8_t vector,
+                            harness_interrupt_callback callback) {
+    if (harness->interrupt_watch_count == MAX_WATCHED_INTERRUPTS) {
+        return 0;
+    }
+    interrupt_watch_t *watch = &harness->interrupt_watches[harness->interrupt_watch_count++];
+    watch->harness = harness;
+    watch->vector = vector;
+    harness->interrupt_callback = callback;
+    avr_irq_register_notify(avr_get_interrupt_irq(harness->avr, vector) + AVR_INT_IRQ_RUNNING,
+                            interrupt_running, watch);
+    return 1;
+}
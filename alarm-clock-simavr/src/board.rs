@@ -0,0 +1,408 @@
+//! The firmware running in simavr on a virtual board, wired up as in
+//! `alarm-clock/src/pins.rs`
+
+use alarm_clock_core::{
+    display::{decode_hours_minutes, decode_seconds, digit, Slot},
+    time::Time,
+};
+use std::{
+    env,
+    ffi::{c_char, c_int, c_void, CString},
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    hd44780::Hd44780,
+    pcf8523::Pcf8523,
+    shift_register::{Pin, VirtualShiftRegister},
+};
+
+/// The Uno's crystal
+pub const FREQUENCY: u64 = 16_000_000_u64;
+/// How many cycles there are in a simulated milli, which is all the time the
+/// millis interrupt has
+pub const CYCLES_PER_MS: u64 = FREQUENCY / 1_000_u64;
+/// How often the RTC is caught up with the firmware
+const RTC_STEP_MS: u64 = 10_u64;
+/// The millis interrupt, which also multiplexes the displays (its vector on
+/// the ATmega328P)
+const TIMER0_COMPA: u8 = 14_u8;
+
+mod ffi {
+    use std::ffi::{c_char, c_int, c_void};
+
+    #[repr(C)]
+    pub struct Harness {
+        _private: [u8; 0],
+    }
+
+    pub type PinCallback = extern "C" fn(param: *mut c_void, port: c_char, pin: u8, high: u32);
+    pub type UartCallback = extern "C" fn(param: *mut c_void, byte: u8);
+    pub type TwiCallback = extern "C" fn(
+        param: *mut c_void,
+        msg: u8,
+        addr: u8,
+        data: u8,
+        reply_msg: *mut u8,
+        reply_data: *mut u8,
+    ) -> c_int;
+    pub type InterruptCallback =
+        extern "C" fn(param: *mut c_void, vector: u8, running: u32, cycle: u64);
+
+    extern "C" {
+        pub fn harness_new(
+            elf_path: *const c_char,
+            frequency: u32,
+            param: *mut c_void,
+        ) -> *mut Harness;
+        pub fn harness_free(harness: *mut Harness);
+        pub fn harness_run_for(harness: *mut Harness, cycles: u64) -> c_int;
+        pub fn harness_cycle(harness: *mut Harness) -> u64;
+        pub fn harness_watch_pin(
+            harness: *mut Harness,
+            port: c_char,
+            pin: u8,
+            callback: PinCallback,
+        ) -> c_int;
+        pub fn harness_set_pin(harness: *mut Harness, port: c_char, pin: u8, high: u32);
+        pub fn harness_watch_uart(harness: *mut Harness, callback: UartCallback);
+        pub fn harness_send_uart(harness: *mut Harness, byte: u8);
+        pub fn harness_attach_twi(harness: *mut Harness, callback: TwiCallback);
+        pub fn harness_watch_interrupt(
+            harness: *mut Harness,
+            vector: u8,
+            callback: InterruptCallback,
+        ) -> c_int;
+    }
+
+    // From simavr's `avr_twi.h`
+    pub const TWI_COND_START: u8 = 1_u8 << 0;
+    pub const TWI_COND_STOP: u8 = 1_u8 << 1;
+    pub const TWI_COND_ACK: u8 = 1_u8 << 3;
+    pub const TWI_COND_WRITE: u8 = 1_u8 << 4;
+    pub const TWI_COND_READ: u8 = 1_u8 << 5;
+}
+
+/// A pin as (port, bit)
+type PortPin = (u8, u8);
+
+/// The output pins followed, from `pins.rs`
+mod outputs {
+    use super::PortPin;
+
+    pub const HOURS_MINUTES: [PortPin; 3] = [(b'D', 2_u8), (b'D', 3_u8), (b'D', 4_u8)];
+    pub const SECONDS: [PortPin; 3] = [(b'D', 5_u8), (b'D', 6_u8), (b'D', 7_u8)];
+    pub const CHARACTER_LCD: [PortPin; 3] = [(b'B', 0_u8), (b'B', 1_u8), (b'B', 2_u8)];
+    pub const ALARM_LED: PortPin = (b'C', 1_u8);
+    pub const PM_LED: PortPin = (b'C', 2_u8);
+}
+
+/// The serial input, clock and latch pins, in that order
+const SHIFT_REGISTER_PINS: [Pin; 3] = [Pin::SerialInput, Pin::Clock, Pin::Latch];
+
+/// The buttons, which are tied to GND when pressed
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Button {
+    Snooze,
+    /// The rotary encoder's
+    Rotary,
+}
+impl Button {
+    fn pin(self) -> PortPin {
+        match self {
+            Button::Snooze => (b'B', 3_u8),
+            Button::Rotary => (b'B', 4_u8),
+        }
+    }
+}
+/// The rotary encoder's A and B pins, which idle high
+const ROTARY_A: PortPin = (b'B', 5_u8);
+const ROTARY_B: PortPin = (b'C', 0_u8);
+
+#[derive(Debug)]
+pub enum Error {
+    /// The ELF couldn't be read, or isn't for the ATmega328P
+    Load(PathBuf),
+    /// The firmware crashed or stopped (e.g. `sleep` with interrupts off)
+    Stopped,
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Load(path) => write!(f, "couldn't load the firmware from {}", path.display()),
+            Error::Stopped => write!(f, "the firmware crashed or stopped"),
+        }
+    }
+}
+impl std::error::Error for Error {}
+
+/// Everything the firmware is wired to. Kept boxed so its address can be
+/// given to simavr's callbacks.
+/// How long an interrupt's handler took each time it ran, from when it was
+/// called to when it returned
+#[derive(Clone, Copy, Debug, Default)]
+pub struct InterruptTimes {
+    pub count: u64,
+    pub total_cycles: u64,
+    pub max_cycles: u64,
+    started: Option<u64>,
+}
+impl InterruptTimes {
+    fn running(&mut self, running: bool, cycle: u64) {
+        if running {
+            self.started = Some(cycle);
+        } else if let Some(started) = self.started.take() {
+            let cycles = cycle - started;
+            self.count += 1_u64;
+            self.total_cycles += cycles;
+            self.max_cycles = self.max_cycles.max(cycles);
+        }
+    }
+
+    pub fn mean_cycles(&self) -> u64 {
+        self.total_cycles.checked_div(self.count).unwrap_or(0_u64)
+    }
+}
+
+#[derive(Default)]
+pub struct Devices {
+    pub hours_minutes: VirtualShiftRegister<16>,
+    pub seconds: VirtualShiftRegister<16>,
+    pub character_lcd_shift_register: VirtualShiftRegister<8>,
+    pub lcd: Hd44780,
+    pub rtc: Option<Pcf8523>,
+    /// Everything sent over the UART
+    pub serial: Vec<u8>,
+    pub alarm_led: bool,
+    pub pm_led: bool,
+    pub millis_interrupt: InterruptTimes,
+    /// The segments last lit on each hours and minutes digit, as multiplexed
+    hours_minutes_digits: [[bool; 7]; 4],
+    colon: bool,
+}
+impl Devices {
+    fn pin_changed(&mut self, pin: PortPin, high: bool) {
+        if let Some(index) = outputs::HOURS_MINUTES.iter().position(|&p| p == pin) {
+            if self.hours_minutes.set_pin(SHIFT_REGISTER_PINS[index], high) {
+                match decode_hours_minutes(&self.hours_minutes.outputs) {
+                    Some(Slot::Digit(position, segments)) => {
+                        self.hours_minutes_digits[position] = segments;
+                    }
+                    Some(Slot::DecimalPoints(decimal_points)) => self.colon = decimal_points[2],
+                    None => (),
+                }
+            }
+        } else if let Some(index) = outputs::SECONDS.iter().position(|&p| p == pin) {
+            self.seconds.set_pin(SHIFT_REGISTER_PINS[index], high);
+        } else if let Some(index) = outputs::CHARACTER_LCD.iter().position(|&p| p == pin) {
+            let register = &mut self.character_lcd_shift_register;
+            if register.set_pin(SHIFT_REGISTER_PINS[index], high) {
+                // Refer to KiCad schematic for pin layout: the outputs are
+                // [_, RS, _, E, D4, D5, D6, D7]
+                let outputs = register.outputs;
+                let data = outputs[4..]
+                    .iter()
+                    .rev()
+                    .fold(0_u8, |data, &bit| data << 1 | bit as u8);
+                self.lcd.set_pins(outputs[1], outputs[3], data);
+            }
+        } else if pin == outputs::ALARM_LED {
+            self.alarm_led = high;
+        } else if pin == outputs::PM_LED {
+            self.pm_led = high;
+        }
+    }
+
+    /// Follow a TWI message, returning the reply (as the condition and data)
+    fn twi(&mut self, msg: u8, addr: u8, data: u8) -> Option<(u8, u8)> {
+        let rtc = self.rtc.as_mut()?;
+        if msg & ffi::TWI_COND_STOP != 0_u8 {
+            rtc.stop();
+        }
+        if msg & ffi::TWI_COND_START != 0_u8 {
+            return rtc.start(addr).then_some((ffi::TWI_COND_ACK, 1_u8));
+        }
+        if msg & ffi::TWI_COND_WRITE != 0_u8 {
+            return rtc.write(data).then_some((ffi::TWI_COND_ACK, 1_u8));
+        }
+        if msg & ffi::TWI_COND_READ != 0_u8 {
+            return Some((ffi::TWI_COND_READ, rtc.read()));
+        }
+        None
+    }
+}
+
+extern "C" fn pin_changed(param: *mut c_void, port: c_char, pin: u8, high: u32) {
+    // Safety: `param` is the boxed devices, which outlive the harness
+    let devices = unsafe { &mut *(param as *mut Devices) };
+    devices.pin_changed((port as u8, pin), high != 0_u32);
+}
+
+extern "C" fn uart_output(param: *mut c_void, byte: u8) {
+    let devices = unsafe { &mut *(param as *mut Devices) };
+    devices.serial.push(byte);
+}
+
+extern "C" fn twi_message(
+    param: *mut c_void,
+    msg: u8,
+    addr: u8,
+    data: u8,
+    reply_msg: *mut u8,
+    reply_data: *mut u8,
+) -> c_int {
+    let devices = unsafe { &mut *(param as *mut Devices) };
+    match devices.twi(msg, addr, data) {
+        Some((msg, data)) => {
+            unsafe {
+                *reply_msg = msg;
+                *reply_data = data;
+            }
+            1
+        }
+        None => 0,
+    }
+}
+
+extern "C" fn interrupt_running(param: *mut c_void, vector: u8, running: u32, cycle: u64) {
+    let devices = unsafe { &mut *(param as *mut Devices) };
+    if vector == TIMER0_COMPA {
+        devices.millis_interrupt.running(running != 0_u32, cycle);
+    }
+}
+
+/// The firmware on a virtual board
+pub struct Board {
+    harness: *mut ffi::Harness,
+    pub devices: Box<Devices>,
+}
+impl Board {
+    /// Load the firmware with the RTC set to `time` (or without an RTC)
+    pub fn new(elf: &Path, time: Option<&Time>) -> Result<Self, Error> {
+        let mut devices = Box::new(Devices {
+            rtc: time.map(Pcf8523::with_time),
+            ..Devices::default()
+        });
+        let path = CString::new(elf.as_os_str().as_encoded_bytes())
+            .map_err(|_| Error::Load(elf.to_path_buf()))?;
+        let param = devices.as_mut() as *mut Devices as *mut c_void;
+        let harness = unsafe { ffi::harness_new(path.as_ptr(), FREQUENCY as u32, param) };
+        if harness.is_null() {
+            return Err(Error::Load(elf.to_path_buf()));
+        }
+
+        let mut board = Self { harness, devices };
+        for (port, pin) in outputs::HOURS_MINUTES
+            .into_iter()
+            .chain(outputs::SECONDS)
+            .chain(outputs::CHARACTER_LCD)
+            .chain([outputs::ALARM_LED, outputs::PM_LED])
+        {
+            unsafe { ffi::harness_watch_pin(board.harness, port as c_char, pin, pin_changed) };
+        }
+        unsafe {
+            ffi::harness_watch_uart(board.harness, uart_output);
+            ffi::harness_attach_twi(board.harness, twi_message);
+            ffi::harness_watch_interrupt(board.harness, TIMER0_COMPA, interrupt_running);
+        }
+        // Nothing is pressed, and the pull-ups hold the encoder's pins high
+        for pin in [
+            Button::Snooze.pin(),
+            Button::Rotary.pin(),
+            ROTARY_A,
+            ROTARY_B,
+        ] {
+            board.set_pin(pin, true);
+        }
+        Ok(board)
+    }
+
+    /// The firmware built from `alarm-clock`, or the ELF at `ALARM_CLOCK_ELF`.
+    /// Release builds are preferred as they're closer to what's flashed.
+    pub fn firmware() -> Option<PathBuf> {
+        if let Some(elf) = env::var_os("ALARM_CLOCK_ELF") {
+            return Some(PathBuf::from(elf));
+        }
+        let target =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("../alarm-clock/target/avr-atmega328p");
+        ["release", "debug"]
+            .into_iter()
+            .map(|profile| target.join(profile).join("alarm-clock.elf"))
+            .find(|elf| elf.exists())
+    }
+
+    fn set_pin(&mut self, (port, pin): PortPin, high: bool) {
+        unsafe { ffi::harness_set_pin(self.harness, port as c_char, pin, high as u32) };
+    }
+
+    /// Simulated millis since the firmware started
+    pub fn millis(&self) -> u64 {
+        let cycle = unsafe { ffi::harness_cycle(self.harness) };
+        cycle / CYCLES_PER_MS
+    }
+
+    /// Run the firmware for `ms` simulated millis, with the RTC counting the
+    /// seconds
+    pub fn run_for(&mut self, ms: u64) -> Result<(), Error> {
+        let end = unsafe { ffi::harness_cycle(self.harness) } + ms * CYCLES_PER_MS;
+        loop {
+            let cycle = unsafe { ffi::harness_cycle(self.harness) };
+            if cycle >= end {
+                return Ok(());
+            }
+            let step = (end - cycle).min(RTC_STEP_MS * CYCLES_PER_MS);
+            if unsafe { ffi::harness_run_for(self.harness, step) } == 0 {
+                return Err(Error::Stopped);
+            }
+            if let Some(rtc) = self.devices.rtc.as_mut() {
+                rtc.elapse((step / CYCLES_PER_MS) as u32);
+            }
+        }
+    }
+
+    /// Send text to the firmware's console, at the UART's baud rate
+    pub fn send(&mut self, text: &str) {
+        for byte in text.bytes() {
+            unsafe { ffi::harness_send_uart(self.harness, byte) };
+        }
+    }
+
+    /// Everything the firmware has sent, as text
+    pub fn serial_output(&self) -> String {
+        String::from_utf8_lossy(&self.devices.serial).into_owned()
+    }
+
+    pub fn press(&mut self, button: Button) {
+        self.set_pin(button.pin(), false);
+    }
+
+    pub fn release(&mut self, button: Button) {
+        self.set_pin(button.pin(), true);
+    }
+
+    /// The time displays as text, e.g. `12:34 56`, with digits that aren't
+    /// lit (or aren't digits) as spaces and the colon as a space when it's off
+    pub fn displayed_time(&self) -> String {
+        let devices = &self.devices;
+        let shown =
+            |segments: [bool; 7]| digit(segments).map_or(' ', |digit| char::from(b'0' + digit));
+        let hours_minutes = devices.hours_minutes_digits.map(shown);
+        let seconds = decode_seconds(&devices.seconds.outputs).map(shown);
+        format!(
+            "{}{}{}{}{} {}{}",
+            hours_minutes[0],
+            hours_minutes[1],
+            if devices.colon { ':' } else { ' ' },
+            hours_minutes[2],
+            hours_minutes[3],
+            seconds[0],
+            seconds[1],
+        )
+    }
+}
+impl Drop for Board {
+    fn drop(&mut self) {
+        unsafe { ffi::harness_free(self.harness) };
+    }
+}
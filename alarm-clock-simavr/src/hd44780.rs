@@ -0,0 +1,255 @@
+//! A 16x2 HD44780 character LCD, as seen from its pins. The clock drives it
+//! in 4-bit mode through a shift register, so only D4-D7 are followed.
+
+/// The DDRAM address of the start of each row
+const ROW_ADDRESSES: [u8; 2] = [0x00_u8, 0x40_u8];
+/// Columns shown of the 40 in DDRAM for each row
+const COLUMNS: usize = 16_usize;
+
+pub struct Hd44780 {
+    enable: bool,
+    /// In 4-bit mode, the high nibble while the low one is waited for
+    high_nibble: Option<u8>,
+    four_bit: bool,
+    ddram: [u8; 0x80],
+    /// Where data is written, as the DDRAM address or the CGRAM address
+    address: u8,
+    writing_cgram: bool,
+    /// The 8 custom characters, 8 rows of 5 pixels each
+    pub cgram: [u8; 64],
+    increment: bool,
+    pub display_on: bool,
+    pub cursor_on: bool,
+}
+impl Hd44780 {
+    /// The LCD as it powers up, in 8-bit mode
+    pub fn new() -> Self {
+        Self {
+            enable: false,
+            high_nibble: None,
+            four_bit: false,
+            ddram: [b' '; 0x80],
+            address: 0_u8,
+            writing_cgram: false,
+            cgram: [0_u8; 64],
+            increment: true,
+            display_on: false,
+            cursor_on: false,
+        }
+    }
+
+    /// Follow the control pins and D4-D7 (as the low nibble of `data`). Data
+    /// is read on the falling edge of enable.
+    pub fn set_pins(&mut self, register_select: bool, enable: bool, data: u8) {
+        let falling = self.enable && !enable;
+        self.enable = enable;
+        if !falling {
+            return;
+        }
+
+        let nibble = data & 0x0F_u8;
+        if !self.four_bit {
+            // Only the high half of the bus is wired up
+            self.write(register_select, nibble << 4);
+            return;
+        }
+        match self.high_nibble.take() {
+            None => self.high_nibble = Some(nibble),
+            Some(high_nibble) => self.write(register_select, high_nibble << 4 | nibble),
+        }
+    }
+
+    fn write(&mut self, register_select: bool, byte: u8) {
+        if register_select {
+            self.write_data(byte);
+        } else {
+            self.command(byte);
+        }
+    }
+
+    fn write_data(&mut self, byte: u8) {
+        if self.writing_cgram {
+            self.cgram[self.address as usize] = byte;
+            self.address = (self.address + 1_u8) & 0x3F_u8;
+            return;
+        }
+        self.ddram[self.address as usize] = byte;
+        self.address = if self.increment {
+            match self.address {
+                0x27_u8 => 0x40_u8,
+                0x67_u8 => 0x00_u8,
+                address => address + 1_u8,
+            }
+        } else {
+            match self.address {
+                0x00_u8 => 0x67_u8,
+                0x40_u8 => 0x27_u8,
+                address => address - 1_u8,
+            }
+        };
+    }
+
+    fn command(&mut self, byte: u8) {
+        match byte.leading_zeros() {
+            // Set DDRAM address
+            0_u32 => {
+                self.writing_cgram = false;
+                self.address = byte & 0x7F_u8;
+            }
+            // Set CGRAM address
+            1_u32 => {
+                self.writing_cgram = true;
+                self.address = byte & 0x3F_u8;
+            }
+            // Function set
+            2_u32 => {
+                self.four_bit = byte & 0x10_u8 == 0_u8;
+                self.high_nibble = None;
+            }
+            // Cursor or display shift, which the clock doesn't use
+            3_u32 => (),
+            // Display on/off control
+            4_u32 => {
+                self.display_on = byte & 0x04_u8 != 0_u8;
+                self.cursor_on = byte & 0x02_u8 != 0_u8;
+            }
+            // Entry mode set
+            5_u32 => self.increment = byte & 0x02_u8 != 0_u8,
+            // Return home
+            6_u32 => {
+                self.writing_cgram = false;
+                self.address = 0_u8;
+            }
+            // Clear display
+            7_u32 => {
+                self.ddram = [b' '; 0x80];
+                self.writing_cgram = false;
+                self.address = 0_u8;
+                self.increment = true;
+            }
+            _ => (),
+        }
+    }
+
+    /// The character codes shown on a row [0, 1]
+    pub fn row(&self, row: u8) -> [u8; COLUMNS] {
+        let start = ROW_ADDRESSES[row as usize] as usize;
+        let mut characters = [b' '; COLUMNS];
+        characters.copy_from_slice(&self.ddram[start..start + COLUMNS]);
+        characters
+    }
+
+    /// A row [0, 1] as text, with custom characters and the like shown as `?`
+    pub fn line(&self, row: u8) -> String {
+        self.row(row)
+            .iter()
+            .map(|&c| match c {
+                b' '..=b'~' => c as char,
+                _ => '?',
+            })
+            .collect()
+    }
+
+    /// Where the next character goes, as (column, row), if it's in DDRAM
+    pub fn cursor(&self) -> Option<(u8, u8)> {
+        if self.writing_cgram {
+            return None;
+        }
+        let row = (self.address >= ROW_ADDRESSES[1]) as u8;
+        Some((self.address - ROW_ADDRESSES[row as usize], row))
+    }
+}
+impl Default for Hd44780 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Send a nibble like the firmware (through ag-lcd) does
+    fn nibble(lcd: &mut Hd44780, register_select: bool, nibble: u8) {
+        lcd.set_pins(register_select, true, nibble);
+        lcd.set_pins(register_select, false, nibble);
+    }
+
+    fn byte(lcd: &mut Hd44780, register_select: bool, byte: u8) {
+        nibble(lcd, register_select, byte >> 4);
+        nibble(lcd, register_select, byte & 0x0F_u8);
+    }
+
+    /// The 4-bit initialization sequence from the datasheet
+    fn initialized() -> Hd44780 {
+        let mut lcd = Hd44780::new();
+        for _ in 0_u8..3_u8 {
+            nibble(&mut lcd, false, 0x03_u8);
+        }
+        nibble(&mut lcd, false, 0x02_u8);
+        // 4-bit, 2 lines, display on, clear, increment
+        for command in [0x28_u8, 0x0C_u8, 0x01_u8, 0x06_u8] {
+            byte(&mut lcd, false, command);
+        }
+        lcd
+    }
+
+    #[test]
+    fn writes_text() {
+        let mut lcd = initialized();
+        assert!(lcd.display_on);
+        assert!(!lcd.cursor_on);
+        for c in "Hello".bytes() {
+            byte(&mut lcd, true, c);
+        }
+        byte(&mut lcd, false, 0x80_u8 | 0x43_u8);
+        assert_eq!(lcd.cursor(), Some((3_u8, 1_u8)));
+        for c in "world\x01".bytes() {
+            byte(&mut lcd, true, c);
+        }
+        assert_eq!(lcd.line(0_u8), "Hello           ");
+        assert_eq!(lcd.line(1_u8), "   world?       ");
+
+        byte(&mut lcd, false, 0x01_u8);
+        assert_eq!(lcd.line(0_u8), " ".repeat(16));
+        assert_eq!(lcd.cursor(), Some((0_u8, 0_u8)));
+    }
+
+    #[test]
+    fn wraps_rows() {
+        let mut lcd = initialized();
+        byte(&mut lcd, false, 0x80_u8 | 0x27_u8);
+        byte(&mut lcd, true, b'a');
+        byte(&mut lcd, true, b'b');
+        assert_eq!(lcd.line(1_u8), "b               ");
+    }
+
+    #[test]
+    fn custom_characters() {
+        let mut lcd = initialized();
+        byte(&mut lcd, false, 0x40_u8 | 0x08_u8);
+        for row in [0x04_u8, 0x0E_u8] {
+            byte(&mut lcd, true, row);
+        }
+        assert_eq!(lcd.cgram[8..10], [0x04_u8, 0x0E_u8]);
+        assert_eq!(lcd.cursor(), None);
+        // Back to the text
+        byte(&mut lcd, false, 0x80_u8);
+        byte(&mut lcd, true, 0x01_u8);
+        assert_eq!(lcd.row(0_u8)[0], 0x01_u8);
+    }
+
+    #[test]
+    fn resynchronizes() {
+        let mut lcd = initialized();
+        // Left halfway through a byte, e.g. by the firmware resetting
+        nibble(&mut lcd, true, 0x04_u8);
+        for _ in 0_u8..3_u8 {
+            nibble(&mut lcd, false, 0x03_u8);
+        }
+        nibble(&mut lcd, false, 0x02_u8);
+        byte(&mut lcd, false, 0x01_u8);
+        byte(&mut lcd, true, b'x');
+        assert_eq!(lcd.line(0_u8), "x               ");
+    }
+}
@@ -0,0 +1,10 @@
+//! Runs the real firmware in simavr, with the clock's hardware simulated
+//! around it: the shift registers and what they drive, the character LCD, and
+//! the RTC. The virtual hardware doesn't need simavr and is tested on its own;
+//! `Board` (and the firmware tests) only build if simavr is installed.
+
+#[cfg(simavr)]
+pub mod board;
+pub mod hd44780;
+pub mod pcf8523;
+pub mod shift_register;
@@ -0,0 +1,758 @@
+//! The NXP PCF8523 RTC, register by register, as seen from the I2C bus. It's
+//! driven a byte at a time by simavr's TWI, or a transfer at a time through
+//! the `embedded-hal` blocking I2C traits, and can be made to misbehave.
+//!
+//! The timers and CLKOUT are only stored, not run.
+
+use alarm_clock_core::{
+    calendar::days_in_month,
+    time::{bcd_decode, bcd_encode, Time},
+};
+use embedded_hal::blocking::i2c::{Read, Write, WriteRead};
+
+/// The 7-bit I2C address
+pub const ADDRESS: u8 = 0x68_u8;
+/// Control_1 through Tmr_B_reg, after which the pointer wraps around
+const REGISTERS: usize = 0x14_usize;
+
+/// Register addresses
+pub mod register {
+    pub const CONTROL_1: u8 = 0x00_u8;
+    pub const CONTROL_2: u8 = 0x01_u8;
+    pub const CONTROL_3: u8 = 0x02_u8;
+    pub const SECONDS: u8 = 0x03_u8;
+    pub const MINUTES: u8 = 0x04_u8;
+    pub const HOURS: u8 = 0x05_u8;
+    pub const DAYS: u8 = 0x06_u8;
+    pub const WEEKDAYS: u8 = 0x07_u8;
+    pub const MONTHS: u8 = 0x08_u8;
+    pub const YEARS: u8 = 0x09_u8;
+    pub const MINUTE_ALARM: u8 = 0x0A_u8;
+    pub const HOUR_ALARM: u8 = 0x0B_u8;
+    pub const DAY_ALARM: u8 = 0x0C_u8;
+    pub const WEEKDAY_ALARM: u8 = 0x0D_u8;
+    pub const OFFSET: u8 = 0x0E_u8;
+    pub const TMR_CLKOUT_CTRL: u8 = 0x0F_u8;
+    pub const TMR_A_FREQ_CTRL: u8 = 0x10_u8;
+    pub const TMR_A_REG: u8 = 0x11_u8;
+    pub const TMR_B_FREQ_CTRL: u8 = 0x12_u8;
+    pub const TMR_B_REG: u8 = 0x13_u8;
+}
+
+/// Control_1 bits
+pub mod control_1 {
+    pub const CAP_SEL: u8 = 1_u8 << 7;
+    /// Stops the time from counting
+    pub const STOP: u8 = 1_u8 << 5;
+    /// Writing this to Control_1 resets every register
+    pub const SOFTWARE_RESET: u8 = 0x58_u8;
+    /// 12-hour mode, with the hours' bit 5 as PM
+    pub const HOUR_12: u8 = 1_u8 << 3;
+    /// Second interrupt enable
+    pub const SIE: u8 = 1_u8 << 2;
+    /// Alarm interrupt enable
+    pub const AIE: u8 = 1_u8 << 1;
+    /// Correction interrupt enable
+    pub const CIE: u8 = 1_u8 << 0;
+}
+
+/// Control_2 bits
+pub mod control_2 {
+    /// Watchdog timer A flag, which is cleared by reading it
+    pub const WTAF: u8 = 1_u8 << 7;
+    /// Countdown timer A flag
+    pub const CTAF: u8 = 1_u8 << 6;
+    /// Countdown timer B flag
+    pub const CTBF: u8 = 1_u8 << 5;
+    /// Second flag
+    pub const SF: u8 = 1_u8 << 4;
+    /// Alarm flag
+    pub const AF: u8 = 1_u8 << 3;
+}
+
+/// Control_3 bits
+pub mod control_3 {
+    /// Power management: battery switch-over and low detection, 0b111 turns
+    /// both off
+    pub const PM: u8 = 0b111_u8 << 5;
+    /// Battery switch-over flag
+    pub const BSF: u8 = 1_u8 << 3;
+    /// Battery low flag, which can't be written
+    pub const BLF: u8 = 1_u8 << 2;
+    /// Battery switch-over interrupt enable
+    pub const BSIE: u8 = 1_u8 << 1;
+    /// Battery low interrupt enable
+    pub const BLIE: u8 = 1_u8 << 0;
+}
+
+/// Set in the seconds register when the oscillator has stopped (and at power
+/// on), until it's written as 0
+pub const OSCILLATOR_STOPPED: u8 = 1_u8 << 7;
+/// Set in an alarm register to leave it out of the alarm
+pub const ALARM_DISABLED: u8 = 1_u8 << 7;
+/// The hours' PM bit in 12-hour mode
+pub const PM: u8 = 1_u8 << 5;
+
+/// The bits of each register that exist, the rest read as 0
+const MASKS: [u8; REGISTERS] = [
+    0b1011_1111_u8, // Control_1
+    0b1111_1111_u8, // Control_2
+    0b1110_1111_u8, // Control_3
+    0b1111_1111_u8, // Seconds
+    0b0111_1111_u8, // Minutes
+    0b0011_1111_u8, // Hours
+    0b0011_1111_u8, // Days
+    0b0000_0111_u8, // Weekdays
+    0b0001_1111_u8, // Months
+    0b1111_1111_u8, // Years
+    0b1111_1111_u8, // Minute_alarm
+    0b1011_1111_u8, // Hour_alarm
+    0b1011_1111_u8, // Day_alarm
+    0b1000_0111_u8, // Weekday_alarm
+    0b1111_1111_u8, // Offset
+    0b1111_1111_u8, // Tmr_CLKOUT_ctrl
+    0b0000_0111_u8, // Tmr_A_freq_ctrl
+    0b1111_1111_u8, // Tmr_A_reg
+    0b0111_0111_u8, // Tmr_B_freq_ctrl
+    0b1111_1111_u8, // Tmr_B_reg
+];
+
+/// At power on and after a software reset: Saturday 2000-01-01 00:00:00 with
+/// the oscillator stop flag set, and the alarms and battery switch-over off
+const RESET: [u8; REGISTERS] = [
+    0x00_u8, 0x00_u8, 0xE0_u8, 0x80_u8, 0x00_u8, 0x00_u8, 0x01_u8, 0x06_u8, 0x01_u8, 0x00_u8,
+    0x80_u8, 0x80_u8, 0x80_u8, 0x80_u8, 0x00_u8, 0x00_u8, 0x07_u8, 0x00_u8, 0x07_u8, 0x00_u8,
+];
+
+/// Something for the RTC to get wrong during the next transfer (up to the
+/// stop condition), after which it's forgotten
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Fault {
+    /// The address isn't acknowledged
+    AddressNack,
+    /// The byte written at this index isn't acknowledged, or stored
+    DataNack(usize),
+    /// The byte read at this index has these bits flipped
+    Corrupt(usize, u8),
+}
+
+/// How a transfer failed, like the errors of AVR's TWI
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BusError {
+    AddressNack,
+    DataNack,
+}
+
+pub struct Pcf8523 {
+    registers: [u8; REGISTERS],
+    /// The register read or written next, incremented after each
+    pointer: u8,
+    /// Whether the bus is addressed to this, and if so whether it's being read
+    selected: Option<bool>,
+    /// The first byte written after being addressed sets the pointer
+    pointer_written: bool,
+    /// Millis into the current second
+    prescaler: u32,
+    /// The time doesn't count while it's being read or written, but catches up
+    /// afterwards
+    pending_ticks: u32,
+    faults: Vec<Fault>,
+    /// Bytes written and read since the start condition
+    written: usize,
+    read: usize,
+}
+impl Pcf8523 {
+    /// The RTC as it powers on
+    pub fn new() -> Self {
+        Self {
+            registers: RESET,
+            pointer: 0_u8,
+            selected: None,
+            pointer_written: false,
+            prescaler: 0_u32,
+            pending_ticks: 0_u32,
+            faults: Vec::new(),
+            written: 0_usize,
+            read: 0_usize,
+        }
+    }
+
+    /// The RTC running since it was set to `time`
+    pub fn with_time(time: &Time) -> Self {
+        let mut rtc = Self::new();
+        rtc.set_time(time);
+        rtc
+    }
+
+    pub fn register(&self, address: u8) -> u8 {
+        self.registers[address as usize]
+    }
+
+    fn is_set(&self, address: u8, bits: u8) -> bool {
+        self.register(address) & bits != 0_u8
+    }
+
+    /// The time, whichever hour mode it's kept in
+    pub fn time(&self) -> Time {
+        Time {
+            seconds: bcd_decode(self.register(register::SECONDS) & !OSCILLATOR_STOPPED),
+            minutes: bcd_decode(self.register(register::MINUTES)),
+            hours: self.decode_hours(self.register(register::HOURS)),
+            day: bcd_decode(self.register(register::DAYS)),
+            day_of_week: self.register(register::WEEKDAYS),
+            month: bcd_decode(self.register(register::MONTHS)),
+            year: bcd_decode(self.register(register::YEARS)),
+        }
+    }
+
+    /// Set the time like a driver would, clearing the oscillator stop flag
+    pub fn set_time(&mut self, time: &Time) {
+        self.store_time(time);
+        self.prescaler = 0_u32;
+    }
+
+    fn store_time(&mut self, time: &Time) {
+        let hours = self.encode_hours(time.hours);
+        for (address, value) in [
+            (register::SECONDS, bcd_encode(time.seconds)),
+            (register::MINUTES, bcd_encode(time.minutes)),
+            (register::HOURS, hours),
+            (register::DAYS, bcd_encode(time.day)),
+            (register::WEEKDAYS, time.day_of_week),
+            (register::MONTHS, bcd_encode(time.month)),
+            (register::YEARS, bcd_encode(time.year)),
+        ] {
+            self.registers[address as usize] = value;
+        }
+    }
+
+    /// Hours [0, 23] from the hours register in either mode
+    fn decode_hours(&self, hours: u8) -> u8 {
+        if self.is_set(register::CONTROL_1, control_1::HOUR_12) {
+            let pm = hours & PM != 0_u8;
+            bcd_decode(hours & !PM) % 12_u8 + if pm { 12_u8 } else { 0_u8 }
+        } else {
+            bcd_decode(hours)
+        }
+    }
+
+    fn encode_hours(&self, hours: u8) -> u8 {
+        if self.is_set(register::CONTROL_1, control_1::HOUR_12) {
+            let pm = if hours >= 12_u8 { PM } else { 0_u8 };
+            match hours % 12_u8 {
+                0_u8 => bcd_encode(12_u8) | pm,
+                hours => bcd_encode(hours) | pm,
+            }
+        } else {
+            bcd_encode(hours)
+        }
+    }
+
+    /// Whether the oscillator stop flag is set
+    pub fn oscillator_stopped(&self) -> bool {
+        self.is_set(register::SECONDS, OSCILLATOR_STOPPED)
+    }
+
+    /// Lose power long enough for the oscillator to stop, e.g. with a flat
+    /// backup battery. The time stays as it was.
+    pub fn stop_oscillator(&mut self) {
+        self.registers[register::SECONDS as usize] |= OSCILLATOR_STOPPED;
+    }
+
+    /// Whether the backup battery is low, unless battery low detection is off
+    pub fn set_battery_low(&mut self, low: bool) {
+        let detection_off = self.register(register::CONTROL_3) & control_3::PM == control_3::PM;
+        let control_3 = &mut self.registers[register::CONTROL_3 as usize];
+        if low && !detection_off {
+            *control_3 |= control_3::BLF;
+        } else {
+            *control_3 &= !control_3::BLF;
+        }
+    }
+
+    /// Whether the interrupt pin (INT1) is pulled low by an enabled flag
+    pub fn interrupt(&self) -> bool {
+        let control_1 = self.register(register::CONTROL_1);
+        let control_2 = self.register(register::CONTROL_2);
+        let control_3 = self.register(register::CONTROL_3);
+        (control_1 & control_1::AIE != 0_u8 && control_2 & control_2::AF != 0_u8)
+            || (control_1 & control_1::SIE != 0_u8 && control_2 & control_2::SF != 0_u8)
+            || (control_3 & control_3::BSIE != 0_u8 && control_3 & control_3::BSF != 0_u8)
+            || (control_3 & control_3::BLIE != 0_u8 && control_3 & control_3::BLF != 0_u8)
+    }
+
+    /// The correction in the offset register, in ppm. In mode 0 it's made
+    /// every two hours, and in mode 1 every minute.
+    pub fn offset_ppm(&self) -> f32 {
+        let offset = self.register(register::OFFSET);
+        // 7-bit two's complement
+        let steps = ((offset << 1) as i8 >> 1) as f32;
+        if offset & 0b1000_0000_u8 == 0_u8 {
+            steps * 4.34_f32
+        } else {
+            steps * 4.069_f32
+        }
+    }
+
+    /// Let `ms` millis pass, counting the seconds as the oscillator would
+    pub fn elapse(&mut self, ms: u32) {
+        if self.is_set(register::CONTROL_1, control_1::STOP) {
+            return;
+        }
+        self.prescaler += ms;
+        while self.prescaler >= 1_000_u32 {
+            self.prescaler -= 1_000_u32;
+            self.tick();
+        }
+    }
+
+    /// Count a second
+    pub fn tick(&mut self) {
+        if self.is_set(register::CONTROL_1, control_1::STOP) {
+            return;
+        }
+        if self.selected.is_some() {
+            self.pending_ticks += 1_u32;
+            return;
+        }
+
+        let mut time = self.time();
+        time.seconds += 1_u8;
+        if time.seconds == 60_u8 {
+            time.seconds = 0_u8;
+            time.minutes += 1_u8;
+        }
+        if time.minutes == 60_u8 {
+            time.minutes = 0_u8;
+            time.hours += 1_u8;
+        }
+        if time.hours == 24_u8 {
+            time.hours = 0_u8;
+            time.day += 1_u8;
+            time.day_of_week = (time.day_of_week + 1_u8) % 7_u8;
+        }
+        if time.day > days_in_month(time.year, time.month) {
+            time.day = 1_u8;
+            time.month += 1_u8;
+        }
+        if time.month == 13_u8 {
+            time.month = 1_u8;
+            time.year = (time.year + 1_u8) % 100_u8;
+        }
+        let oscillator_stopped = self.register(register::SECONDS) & OSCILLATOR_STOPPED;
+        self.store_time(&time);
+        self.registers[register::SECONDS as usize] |= oscillator_stopped;
+
+        if self.is_set(register::CONTROL_1, control_1::SIE) {
+            self.registers[register::CONTROL_2 as usize] |= control_2::SF;
+        }
+        if time.seconds == 0_u8 && self.alarm_matches() {
+            self.registers[register::CONTROL_2 as usize] |= control_2::AF;
+        }
+    }
+
+    /// Whether every enabled alarm register matches the time, and any are
+    fn alarm_matches(&self) -> bool {
+        let alarms = [
+            (register::MINUTE_ALARM, register::MINUTES),
+            (register::HOUR_ALARM, register::HOURS),
+            (register::DAY_ALARM, register::DAYS),
+            (register::WEEKDAY_ALARM, register::WEEKDAYS),
+        ];
+        let enabled = alarms
+            .iter()
+            .filter(|(alarm, _)| !self.is_set(*alarm, ALARM_DISABLED));
+        enabled.clone().count() > 0_usize
+            && enabled
+                .into_iter()
+                .all(|&(alarm, time)| self.register(alarm) & !ALARM_DISABLED == self.register(time))
+    }
+
+    /// Make the next transfer go wrong
+    pub fn inject(&mut self, fault: Fault) {
+        self.faults.push(fault);
+    }
+
+    /// Take the fault for this point of the transfer, if there is one
+    fn fault(&mut self, matches: impl Fn(&Fault) -> bool) -> Option<Fault> {
+        let index = self.faults.iter().position(matches)?;
+        Some(self.faults.remove(index))
+    }
+
+    /// A start (or repeated start) condition with the address byte, returning
+    /// whether it's acknowledged
+    pub fn start(&mut self, address_byte: u8) -> bool {
+        self.pointer_written = false;
+        self.selected = None;
+        if address_byte >> 1 != ADDRESS || self.fault(|f| *f == Fault::AddressNack).is_some() {
+            return false;
+        }
+        self.selected = Some(address_byte & 1_u8 == 1_u8);
+        true
+    }
+
+    /// A byte written by the controller, returning whether it's acknowledged
+    pub fn write(&mut self, byte: u8) -> bool {
+        if self.selected != Some(false) {
+            return false;
+        }
+        let index = self.written;
+        self.written += 1_usize;
+        if self.fault(|f| *f == Fault::DataNack(index)).is_some() {
+            return false;
+        }
+
+        if !self.pointer_written {
+            self.pointer_written = true;
+            self.pointer = byte % REGISTERS as u8;
+            return true;
+        }
+        self.write_register(self.pointer, byte);
+        self.advance();
+        true
+    }
+
+    fn write_register(&mut self, address: u8, byte: u8) {
+        let old = self.register(address);
+        let byte = byte & MASKS[address as usize];
+        self.registers[address as usize] = match address {
+            register::CONTROL_1 if byte == control_1::SOFTWARE_RESET & MASKS[0] => {
+                self.registers = RESET;
+                self.prescaler = 0_u32;
+                return;
+            }
+            // Flags can only be cleared, and the watchdog's only by reading it
+            register::CONTROL_2 => {
+                let flags = control_2::CTAF | control_2::CTBF | control_2::SF | control_2::AF;
+                (old & control_2::WTAF) | (old & byte & flags) | (byte & !(flags | control_2::WTAF))
+            }
+            register::CONTROL_3 => {
+                (old & control_3::BLF)
+                    | (old & byte & control_3::BSF)
+                    | (byte & !(control_3::BLF | control_3::BSF))
+            }
+            // Writing the seconds restarts the second
+            register::SECONDS => {
+                self.prescaler = 0_u32;
+                byte
+            }
+            _ => byte,
+        };
+    }
+
+    /// A byte read by the controller
+    pub fn read(&mut self) -> u8 {
+        let index = self.read;
+        self.read += 1_usize;
+        let mut value = self.register(self.pointer);
+        if self.pointer == register::CONTROL_2 {
+            self.registers[register::CONTROL_2 as usize] &= !control_2::WTAF;
+        }
+        if let Some(Fault::Corrupt(_, flipped)) =
+            self.fault(|f| matches!(f, Fault::Corrupt(i, _) if *i == index))
+        {
+            value ^= flipped;
+        }
+        self.advance();
+        value
+    }
+
+    pub fn stop(&mut self) {
+        self.selected = None;
+        self.faults.clear();
+        self.written = 0_usize;
+        self.read = 0_usize;
+        for _ in 0_u32..core::mem::take(&mut self.pending_ticks) {
+            self.tick();
+        }
+    }
+
+    /// The pointer wraps around after the last register
+    fn advance(&mut self) {
+        self.pointer = (self.pointer + 1_u8) % REGISTERS as u8;
+    }
+
+    fn transmit(&mut self, address: u8, bytes: &[u8]) -> Result<(), BusError> {
+        if !self.start(address << 1) {
+            return Err(BusError::AddressNack);
+        }
+        for &byte in bytes {
+            if !self.write(byte) {
+                return Err(BusError::DataNack);
+            }
+        }
+        Ok(())
+    }
+
+    fn receive(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), BusError> {
+        if !self.start(address << 1 | 1_u8) {
+            return Err(BusError::AddressNack);
+        }
+        for byte in buffer {
+            *byte = self.read();
+        }
+        Ok(())
+    }
+}
+impl Default for Pcf8523 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Write for Pcf8523 {
+    type Error = BusError;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), BusError> {
+        let result = self.transmit(address, bytes);
+        self.stop();
+        result
+    }
+}
+impl Read for Pcf8523 {
+    type Error = BusError;
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), BusError> {
+        let result = self.receive(address, buffer);
+        self.stop();
+        result
+    }
+}
+impl WriteRead for Pcf8523 {
+    type Error = BusError;
+
+    fn write_read(&mut self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), BusError> {
+        let result = self
+            .transmit(address, bytes)
+            .and_then(|()| self.receive(address, buffer));
+        self.stop();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(hours: u8, minutes: u8, seconds: u8) -> Time {
+        Time {
+            hours,
+            minutes,
+            seconds,
+            day: 31_u8,
+            day_of_week: 0_u8,
+            month: 12_u8,
+            year: 23_u8,
+        }
+    }
+
+    fn write_registers(rtc: &mut Pcf8523, address: u8, values: &[u8]) {
+        let mut bytes = vec![address];
+        bytes.extend_from_slice(values);
+        Write::write(rtc, ADDRESS, &bytes).unwrap();
+    }
+
+    #[test]
+    fn powers_on() {
+        let rtc = Pcf8523::new();
+        assert!(rtc.oscillator_stopped());
+        assert_eq!(
+            rtc.time(),
+            Time {
+                day: 1_u8,
+                day_of_week: 6_u8,
+                month: 1_u8,
+                year: 0_u8,
+                ..time(0_u8, 0_u8, 0_u8)
+            }
+        );
+        assert!(!Pcf8523::with_time(&time(1_u8, 2_u8, 3_u8)).oscillator_stopped());
+    }
+
+    #[test]
+    fn ticks() {
+        let mut rtc = Pcf8523::with_time(&time(23_u8, 59_u8, 58_u8));
+        rtc.elapse(999_u32);
+        assert_eq!(rtc.time(), time(23_u8, 59_u8, 58_u8));
+        rtc.elapse(1_u32);
+        assert_eq!(rtc.time(), time(23_u8, 59_u8, 59_u8));
+        rtc.tick();
+        assert_eq!(
+            rtc.time(),
+            Time {
+                day: 1_u8,
+                day_of_week: 1_u8,
+                month: 1_u8,
+                year: 24_u8,
+                ..time(0_u8, 0_u8, 0_u8)
+            }
+        );
+
+        // Writing the seconds restarts the second
+        rtc.elapse(600_u32);
+        write_registers(&mut rtc, register::SECONDS, &[0x30_u8]);
+        rtc.elapse(600_u32);
+        assert_eq!(rtc.time().seconds, 30_u8);
+
+        // Stopped
+        write_registers(&mut rtc, register::CONTROL_1, &[control_1::STOP]);
+        rtc.elapse(5_000_u32);
+        assert_eq!(rtc.time().seconds, 30_u8);
+    }
+
+    #[test]
+    fn bus() {
+        let mut rtc = Pcf8523::with_time(&time(12_u8, 34_u8, 56_u8));
+        // Someone else's address
+        assert!(!rtc.start(0x50_u8 << 1));
+        assert!(!rtc.write(register::SECONDS));
+        rtc.stop();
+
+        // Set the pointer, then read from it with a repeated start
+        assert!(rtc.start(ADDRESS << 1));
+        assert!(rtc.write(register::SECONDS));
+        assert!(rtc.start(ADDRESS << 1 | 1_u8));
+        let read = [(); 3].map(|_| rtc.read());
+        rtc.stop();
+        assert_eq!(read, [0x56_u8, 0x34_u8, 0x12_u8]);
+
+        // Writes go on from the pointer, too
+        write_registers(&mut rtc, register::MINUTES, &[0x01_u8, 0x02_u8]);
+        assert_eq!(rtc.time(), time(2_u8, 1_u8, 56_u8));
+        // Not while reading
+        assert!(rtc.start(ADDRESS << 1 | 1_u8));
+        assert!(!rtc.write(0x00_u8));
+        rtc.stop();
+
+        // Bits that don't exist read as 0
+        write_registers(&mut rtc, register::WEEKDAYS, &[0xFF_u8]);
+        assert_eq!(rtc.register(register::WEEKDAYS), 0x07_u8);
+    }
+
+    #[test]
+    fn pointer_wraps() {
+        let mut rtc = Pcf8523::new();
+        let mut read = [0_u8; 2];
+        rtc.write_read(ADDRESS, &[register::TMR_B_REG], &mut read)
+            .unwrap();
+        assert_eq!(read, [RESET[REGISTERS - 1], RESET[0]]);
+    }
+
+    #[test]
+    fn flags() {
+        let mut rtc = Pcf8523::with_time(&time(6_u8, 59_u8, 59_u8));
+        write_registers(
+            &mut rtc,
+            register::CONTROL_1,
+            &[control_1::AIE | control_1::SIE],
+        );
+        // 07:00 every day
+        write_registers(
+            &mut rtc,
+            register::MINUTE_ALARM,
+            &[0x00_u8, 0x07_u8, ALARM_DISABLED, ALARM_DISABLED],
+        );
+        assert!(!rtc.interrupt());
+        rtc.tick();
+        assert_eq!(
+            rtc.register(register::CONTROL_2),
+            control_2::SF | control_2::AF
+        );
+        assert!(rtc.interrupt());
+
+        // Writing 1 leaves a flag as it is, and 0 clears it
+        write_registers(&mut rtc, register::CONTROL_2, &[control_2::AF]);
+        assert_eq!(rtc.register(register::CONTROL_2), control_2::AF);
+        write_registers(&mut rtc, register::CONTROL_2, &[0x00_u8]);
+        assert!(!rtc.interrupt());
+        // Not the next minute
+        rtc.elapse(60_000_u32);
+        assert_eq!(rtc.register(register::CONTROL_2), control_2::SF);
+
+        // The battery flag can't be written, and isn't set with detection off
+        rtc.set_battery_low(true);
+        assert_eq!(rtc.register(register::CONTROL_3) & control_3::BLF, 0_u8);
+        write_registers(&mut rtc, register::CONTROL_3, &[control_3::BLIE]);
+        rtc.set_battery_low(true);
+        write_registers(&mut rtc, register::CONTROL_3, &[control_3::BLIE]);
+        assert_eq!(
+            rtc.register(register::CONTROL_3),
+            control_3::BLF | control_3::BLIE
+        );
+        assert!(rtc.interrupt());
+
+        // The oscillator stop flag stays until the time is set
+        rtc.stop_oscillator();
+        rtc.tick();
+        assert!(rtc.oscillator_stopped());
+        write_registers(&mut rtc, register::SECONDS, &[0x00_u8]);
+        assert!(!rtc.oscillator_stopped());
+
+        write_registers(&mut rtc, register::CONTROL_1, &[control_1::SOFTWARE_RESET]);
+        assert!(rtc.oscillator_stopped());
+        assert_eq!(rtc.register(register::CONTROL_3), RESET[2]);
+    }
+
+    #[test]
+    fn twelve_hour_mode() {
+        let mut rtc = Pcf8523::with_time(&time(11_u8, 59_u8, 59_u8));
+        write_registers(&mut rtc, register::CONTROL_1, &[control_1::HOUR_12]);
+        write_registers(&mut rtc, register::HOURS, &[0x11_u8]);
+        rtc.tick();
+        assert_eq!(rtc.register(register::HOURS), PM | 0x12_u8);
+        assert_eq!(rtc.time().hours, 12_u8);
+        write_registers(
+            &mut rtc,
+            register::SECONDS,
+            &[0x59_u8, 0x59_u8, PM | 0x11_u8],
+        );
+        rtc.elapse(1_000_u32);
+        assert_eq!(rtc.register(register::HOURS), 0x12_u8);
+        assert_eq!(rtc.time().hours, 0_u8);
+    }
+
+    #[test]
+    fn offset() {
+        let mut rtc = Pcf8523::new();
+        write_registers(&mut rtc, register::OFFSET, &[0b0111_1111_u8]);
+        assert_eq!(rtc.offset_ppm(), -4.34_f32);
+        write_registers(&mut rtc, register::OFFSET, &[0b1000_0010_u8]);
+        assert_eq!(rtc.offset_ppm(), 2_f32 * 4.069_f32);
+    }
+
+    #[test]
+    fn held_while_read() {
+        let mut rtc = Pcf8523::with_time(&time(12_u8, 0_u8, 0_u8));
+        assert!(rtc.start(ADDRESS << 1));
+        rtc.elapse(1_000_u32);
+        assert_eq!(rtc.time().seconds, 0_u8);
+        rtc.stop();
+        assert_eq!(rtc.time().seconds, 1_u8);
+    }
+
+    #[test]
+    fn faults() {
+        let mut rtc = Pcf8523::with_time(&time(12_u8, 34_u8, 56_u8));
+        let mut read = [0_u8; 3];
+
+        rtc.inject(Fault::AddressNack);
+        assert_eq!(
+            rtc.write_read(ADDRESS, &[register::SECONDS], &mut read),
+            Err(BusError::AddressNack)
+        );
+        // Only for that transfer
+        rtc.write_read(ADDRESS, &[register::SECONDS], &mut read)
+            .unwrap();
+
+        rtc.inject(Fault::DataNack(2_usize));
+        assert_eq!(
+            Write::write(&mut rtc, ADDRESS, &[register::SECONDS, 0x00_u8, 0x00_u8]),
+            Err(BusError::DataNack)
+        );
+        assert_eq!(rtc.time(), time(12_u8, 34_u8, 0_u8));
+
+        rtc.inject(Fault::Corrupt(1_usize, 0xF0_u8));
+        rtc.write_read(ADDRESS, &[register::SECONDS], &mut read)
+            .unwrap();
+        assert_eq!(read, [0x00_u8, 0x34_u8 ^ 0xF0_u8, 0x12_u8]);
+    }
+}
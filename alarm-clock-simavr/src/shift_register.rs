@@ -0,0 +1,111 @@
+//! Latching shift registers, as seen from their serial input, clock and latch
+//! pins
+
+/// The pins a shift register is driven through
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Pin {
+    SerialInput,
+    Clock,
+    Latch,
+}
+
+/// `N` outputs' worth of daisy-chained shift registers, like the TPIC6595s.
+/// The serial input is shifted in on the clock's rising edge and copied to
+/// the outputs on the latch's rising edge.
+pub struct VirtualShiftRegister<const N: usize> {
+    serial_input: bool,
+    clock: bool,
+    latch: bool,
+    /// Shifted in but not yet latched, with the last bit shifted in first
+    shifted: [bool; N],
+    /// What the outputs are driven to, first output of the first register first
+    pub outputs: [bool; N],
+    /// How many times the outputs have been latched
+    pub latches: usize,
+}
+impl<const N: usize> VirtualShiftRegister<N> {
+    pub fn new() -> Self {
+        Self {
+            serial_input: false,
+            clock: false,
+            latch: false,
+            shifted: [false; N],
+            outputs: [false; N],
+            latches: 0_usize,
+        }
+    }
+
+    /// Follow one of the pins changing, returning whether the outputs were
+    /// just latched
+    pub fn set_pin(&mut self, pin: Pin, high: bool) -> bool {
+        match pin {
+            Pin::SerialInput => self.serial_input = high,
+            Pin::Clock => {
+                if high && !self.clock {
+                    self.shifted.rotate_right(1_usize);
+                    self.shifted[0] = self.serial_input;
+                }
+                self.clock = high;
+            }
+            Pin::Latch => {
+                let rising = high && !self.latch;
+                self.latch = high;
+                if rising {
+                    self.outputs = self.shifted;
+                    self.latches += 1_usize;
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
+impl<const N: usize> Default for VirtualShiftRegister<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Shift out like the firmware: last output first, then latch
+    fn shift_out<const N: usize>(
+        register: &mut VirtualShiftRegister<N>,
+        outputs: [bool; N],
+    ) -> bool {
+        for output in outputs.iter().rev() {
+            register.set_pin(Pin::SerialInput, *output);
+            register.set_pin(Pin::Clock, true);
+            register.set_pin(Pin::SerialInput, false);
+            register.set_pin(Pin::Clock, false);
+        }
+        register.set_pin(Pin::Latch, false);
+        register.set_pin(Pin::Latch, true)
+    }
+
+    #[test]
+    fn shifts_and_latches() {
+        let mut register = VirtualShiftRegister::<8>::new();
+        let outputs = [true, false, false, true, true, false, true, false];
+        assert!(shift_out(&mut register, outputs));
+        assert_eq!(register.outputs, outputs);
+        assert_eq!(register.latches, 1_usize);
+
+        // Nothing shows until it's latched
+        register.set_pin(Pin::Latch, false);
+        register.set_pin(Pin::SerialInput, true);
+        register.set_pin(Pin::Clock, true);
+        register.set_pin(Pin::Clock, false);
+        assert_eq!(register.outputs, outputs);
+        // Only a rising edge latches
+        assert!(!register.set_pin(Pin::Clock, false));
+        assert!(register.set_pin(Pin::Latch, true));
+        assert!(!register.set_pin(Pin::Latch, true));
+        assert_eq!(
+            register.outputs,
+            [true, true, false, false, true, true, false, true]
+        );
+    }
+}
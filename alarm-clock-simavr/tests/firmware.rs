@@ -0,0 +1,145 @@
+//! The real firmware, run in simavr. Only built if simavr is installed (see
+//! the build script), and only run once the firmware is built: `cargo build
+//! --release` in `alarm-clock`, or set `ALARM_CLOCK_ELF` to an ELF to test.
+
+#![cfg(simavr)]
+
+use alarm_clock_core::time::Time;
+use alarm_clock_simavr::board::{Board, Button, CYCLES_PER_MS};
+
+/// Long enough for the firmware to start up and show the time
+const BOOT_MS: u64 = 2_000_u64;
+const GREETING: &str = "Hello from the Alarm Clock!";
+
+/// The firmware loaded with the RTC running, if it's been built
+fn load() -> Option<Board> {
+    let Some(elf) = Board::firmware() else {
+        eprintln!("The firmware isn't built, skipping");
+        return None;
+    };
+    let time = Time {
+        hours: 21_u8,
+        minutes: 43_u8,
+        seconds: 0_u8,
+        day: 14_u8,
+        day_of_week: 4_u8,
+        month: 3_u8,
+        year: 24_u8,
+    };
+    Some(Board::new(&elf, Some(&time)).expect("Failed to load the firmware"))
+}
+
+/// The firmware booted with the RTC running, if it's been built
+fn boot() -> Option<Board> {
+    let mut board = load()?;
+    board.run_for(BOOT_MS).expect("The firmware stopped");
+    Some(board)
+}
+
+#[test]
+fn boots() {
+    let Some(board) = boot() else { return };
+    let serial = board.serial_output();
+    assert!(serial.contains(GREETING), "{serial:?}");
+    assert!(serial.contains("Boot #"), "{serial:?}");
+
+    // The RTC's time is kept through resets
+    let time = board.devices.rtc.as_ref().unwrap().time();
+    assert_eq!((time.hours, time.minutes), (21_u8, 43_u8));
+    assert_eq!(&board.displayed_time()[..5], "21:43");
+    assert_eq!(board.devices.lcd.line(0_u8), "Thursday        ");
+    assert_eq!(board.devices.lcd.line(1_u8), "14/03/2024      ");
+}
+
+#[test]
+fn resets_a_stopped_rtc() {
+    let Some(mut board) = load() else { return };
+    board.devices.rtc.as_mut().unwrap().stop_oscillator();
+    board.run_for(BOOT_MS).expect("The firmware stopped");
+
+    // The time was lost, so the firmware sets its own
+    let rtc = board.devices.rtc.as_ref().unwrap();
+    assert!(!rtc.oscillator_stopped());
+    let time = rtc.time();
+    assert_eq!((time.hours, time.minutes), (5_u8, 0_u8));
+    assert_eq!(&board.displayed_time()[..5], "05:00");
+    // The idle screen's date page, with the moon (a custom character) for
+    // quiet hours
+    assert_eq!(board.devices.lcd.line(0_u8), "Sunday         ?");
+    assert_eq!(board.devices.lcd.line(1_u8), "01/01/2023      ");
+}
+
+#[test]
+fn sets_the_time_over_serial() {
+    let Some(mut board) = boot() else { return };
+    board.send("settime 12:34:56\r");
+    board.run_for(1_500_u64).unwrap();
+    assert!(board.serial_output().contains("Time set"));
+
+    let time = board.devices.rtc.as_ref().unwrap().time();
+    assert_eq!(
+        (time.hours, time.minutes, time.seconds),
+        (12_u8, 34_u8, 57_u8)
+    );
+    assert_eq!(board.displayed_time(), "12:34 57");
+    // The date is kept
+    assert_eq!(board.devices.lcd.line(0_u8), "Thursday        ");
+
+    // Everything set has been shifted out
+    board.send("status\r");
+    board.run_for(500_u64).unwrap();
+    let serial = board.serial_output();
+    let outputs = serial
+        .lines()
+        .find_map(|line| line.strip_prefix("Seconds display: "))
+        .expect("No seconds display in the status");
+    assert_eq!(outputs.len(), 17_usize, "{outputs:?}");
+}
+
+/// The buttons are on port B, so their pin change interrupt is PCINT0's
+#[test]
+fn buttons_dont_reset() {
+    let Some(mut board) = boot() else { return };
+    for button in [Button::Rotary, Button::Snooze] {
+        board.press(button);
+        board.run_for(200_u64).unwrap();
+        board.release(button);
+        board.run_for(200_u64).unwrap();
+    }
+    assert_eq!(board.serial_output().matches(GREETING).count(), 1_usize);
+}
+
+/// Both controls are read from the same pin change interrupt, so the snooze
+/// button has to see it as well as the rotary encoder
+#[test]
+fn snoozes_the_alarm() {
+    let Some(mut board) = boot() else { return };
+    board.send("settime 06:59:58\r");
+    board.run_for(200_u64).unwrap();
+    board.send("alarm add 07:00\r");
+    board.run_for(200_u64).unwrap();
+    board.send("alarm on\r");
+    board.run_for(3_000_u64).unwrap();
+    assert!(board.serial_output().contains("Alarm!"));
+
+    board.press(Button::Snooze);
+    board.run_for(200_u64).unwrap();
+    board.release(Button::Snooze);
+    board.run_for(200_u64).unwrap();
+    board.send("status\r");
+    board.run_for(500_u64).unwrap();
+    let serial = board.serial_output();
+    assert!(serial.contains("Mode: snoozed"), "{serial:?}");
+    assert!(serial.contains("snoozes: 1"), "{serial:?}");
+}
+
+/// The millis interrupt shifts out a digit every time it runs, so it has to
+/// be done well before the next milli
+#[test]
+fn millis_interrupt_fits() {
+    let Some(mut board) = boot() else { return };
+    board.run_for(1_000_u64).unwrap();
+    let times = board.devices.millis_interrupt;
+    assert!(times.count >= BOOT_MS, "{times:?}");
+    assert!(times.max_cycles < CYCLES_PER_MS, "{times:?}");
+}
@@ -0,0 +1,110 @@
+//! The firmware's RTC driver against the virtual PCF8523
+
+use alarm_clock_core::{
+    rtc::{Error, Pcf8523},
+    time::Time,
+};
+use alarm_clock_simavr::pcf8523::{self, BusError, Fault};
+
+const TIME: Time = Time {
+    hours: 12_u8,
+    minutes: 34_u8,
+    seconds: 56_u8,
+    day: 29_u8,
+    day_of_week: 4_u8,
+    month: 2_u8,
+    year: 24_u8,
+};
+
+#[test]
+fn reads_and_sets_the_time() {
+    let mut driver = Pcf8523::new(pcf8523::Pcf8523::with_time(&TIME));
+    assert_eq!(driver.read_time(), Ok(TIME));
+    driver.i2c.elapse(1_000_u32);
+    assert_eq!(driver.read_time().map(|time| time.seconds), Ok(57_u8));
+
+    let later = Time {
+        hours: 23_u8,
+        day: 1_u8,
+        month: 3_u8,
+        ..TIME
+    };
+    driver.set_time(&later).unwrap();
+    assert_eq!(driver.i2c.time(), later);
+    assert_eq!(driver.read_time(), Ok(later));
+}
+
+#[test]
+fn oscillator_stopped() {
+    // At power on the flag is set, but the time is still read
+    let mut driver = Pcf8523::new(pcf8523::Pcf8523::new());
+    assert!(driver.i2c.oscillator_stopped());
+    assert_eq!(driver.oscillator_stopped(), Ok(true));
+    assert_eq!(driver.read_time().map(|time| time.year), Ok(0_u8));
+
+    driver.set_time(&TIME).unwrap();
+    assert!(!driver.i2c.oscillator_stopped());
+    assert_eq!(driver.oscillator_stopped(), Ok(false));
+    driver.i2c.stop_oscillator();
+    assert_eq!(driver.oscillator_stopped(), Ok(true));
+    assert_eq!(driver.read_time(), Ok(TIME));
+}
+
+#[test]
+fn bus_errors() {
+    let mut driver = Pcf8523::new(pcf8523::Pcf8523::with_time(&TIME));
+    driver.i2c.inject(Fault::AddressNack);
+    assert_eq!(driver.read_time(), Err(Error::Bus(BusError::AddressNack)));
+    // The hours aren't written
+    driver.i2c.inject(Fault::DataNack(3_usize));
+    let midnight = Time {
+        hours: 0_u8,
+        minutes: 0_u8,
+        seconds: 0_u8,
+        ..TIME
+    };
+    assert_eq!(
+        driver.set_time(&midnight),
+        Err(Error::Bus(BusError::DataNack))
+    );
+    assert_eq!(
+        driver.read_time(),
+        Ok(Time {
+            hours: 12_u8,
+            ..midnight
+        })
+    );
+}
+
+#[test]
+fn corrupted_reads() {
+    let mut driver = Pcf8523::new(pcf8523::Pcf8523::with_time(&TIME));
+    // Not BCD
+    driver.i2c.inject(Fault::Corrupt(2_usize, 0xF0_u8));
+    assert_eq!(driver.read_time(), Err(Error::InvalidTime));
+    // February 30th
+    driver
+        .i2c
+        .inject(Fault::Corrupt(3_usize, 0x29_u8 ^ 0x30_u8));
+    assert_eq!(driver.read_time(), Err(Error::InvalidTime));
+    // Weekday 7
+    driver
+        .i2c
+        .inject(Fault::Corrupt(4_usize, 0x04_u8 ^ 0x07_u8));
+    assert_eq!(driver.read_time(), Err(Error::InvalidTime));
+    assert_eq!(driver.read_time(), Ok(TIME));
+}
+
+#[test]
+fn battery_low() {
+    let mut driver = Pcf8523::new(pcf8523::Pcf8523::with_time(&TIME));
+    // Detection is off until it's enabled
+    driver.i2c.set_battery_low(true);
+    assert_eq!(driver.battery_low(), Ok(false));
+    driver.enable_battery_switch_over().unwrap();
+    driver.i2c.set_battery_low(true);
+    assert_eq!(driver.battery_low(), Ok(true));
+    driver.i2c.set_battery_low(false);
+    assert_eq!(driver.battery_low(), Ok(false));
+    assert_eq!(driver.read_time(), Ok(TIME));
+}
@@ -0,0 +1,15 @@
+//! Generates the dictionary of log messages for the `binary-log` feature
+
+#[path = "build/log_scan.rs"]
+mod log_scan;
+
+use std::{env, fs, path::Path};
+
+fn main() {
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=build/log_scan.rs");
+
+    let messages = log_scan::scan(Path::new("src")).expect("Failed to scan the sources");
+    let out = Path::new(&env::var("OUT_DIR").expect("OUT_DIR not set")).join("log_messages.rs");
+    fs::write(out, log_scan::generate(&messages)).expect("Failed to write the log messages");
+}
@@ -0,0 +1,140 @@
+//! Finds the format strings of every logging macro call in the firmware's
+//! sources so they can be left out of flash when logging in binary.
+//!
+//! This is shared by the firmware's build script (which turns a format string
+//! into its index at compile time) and the log decoder's (which turns the index
+//! back into the format string), so both end up with the same dictionary when
+//! built from the same sources.
+
+use std::{fs, io, path::Path};
+
+/// The logging macros that take a tag and then a format string
+const MACROS: [&str; 5] = ["error!(", "warning!(", "info!(", "debug!(", "trace!("];
+
+/// Every distinct format string in the `.rs` files under `dir`, in the order
+/// they're found (files are visited in name order)
+pub fn scan(dir: &Path) -> io::Result<Vec<String>> {
+    let mut messages = Vec::new();
+    let mut paths = Vec::new();
+    collect_sources(dir, &mut paths)?;
+    paths.sort();
+    for path in paths {
+        for message in scan_source(&fs::read_to_string(&path)?) {
+            if !messages.contains(&message) {
+                messages.push(message);
+            }
+        }
+    }
+    Ok(messages)
+}
+
+fn collect_sources(dir: &Path, paths: &mut Vec<std::path::PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_sources(&path, paths)?;
+        } else if path.extension().is_some_and(|extension| extension == "rs") {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// The format strings of the logging macro calls in a source file, in order.
+/// Calls whose format string isn't a plain string literal are skipped (and
+/// will fail to compile in binary mode).
+pub fn scan_source(source: &str) -> Vec<String> {
+    let mut calls = Vec::new();
+    for name in MACROS {
+        for (start, _) in source.match_indices(name) {
+            // Not the tail of a longer name
+            let preceding = source[..start].chars().next_back();
+            if preceding.is_some_and(|c| c.is_alphanumeric() || c == '_') {
+                continue;
+            }
+            // Nor in a comment, like the examples in docs
+            let line_start = source[..start].rfind('\n').map_or(0, |n| n + 1);
+            if source[line_start..start].contains("//") {
+                continue;
+            }
+            let arguments = &source[start + name.len()..];
+            if let Some(message) = skip_argument(arguments).and_then(string_literal) {
+                calls.push((start, message));
+            }
+        }
+    }
+    calls.sort();
+    calls.into_iter().map(|(_, message)| message).collect()
+}
+
+/// Skip the first macro argument (the tag), returning what follows its comma
+fn skip_argument(arguments: &str) -> Option<&str> {
+    let mut depth = 0_usize;
+    for (n, c) in arguments.char_indices() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' if depth == 0 => return None,
+            ')' | ']' | '}' => depth -= 1,
+            ',' if depth == 0 => return Some(&arguments[n + 1..]),
+            '"' => return None,
+            _ => (),
+        }
+    }
+    None
+}
+
+/// Parse the value of the string literal at the start of `text` (after
+/// whitespace)
+fn string_literal(text: &str) -> Option<String> {
+    let mut chars = text.trim_start().strip_prefix('"')?.chars().peekable();
+    let mut value = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(value),
+            '\\' => match chars.next()? {
+                'n' => value.push('\n'),
+                'r' => value.push('\r'),
+                't' => value.push('\t'),
+                '0' => value.push('\0'),
+                '\\' => value.push('\\'),
+                '"' => value.push('"'),
+                '\'' => value.push('\''),
+                // A line continuation also skips the next line's indentation
+                '\n' => while chars.next_if(|c| c.is_whitespace()).is_some() {},
+                _ => return None,
+            },
+            c => value.push(c),
+        }
+    }
+}
+
+/// FNV-1a over the messages, so the decoder can tell if it has the dictionary
+/// the firmware was built with
+pub fn hash(messages: &[String]) -> u32 {
+    let mut hash = 0x811C_9DC5_u32;
+    for message in messages {
+        for byte in message.bytes().chain([0_u8]) {
+            hash ^= byte as u32;
+            hash = hash.wrapping_mul(0x0100_0193_u32);
+        }
+    }
+    hash
+}
+
+/// Rust source defining `MESSAGES` and `HASH`
+pub fn generate(messages: &[String]) -> String {
+    let mut source = String::from("/// Generated by build/log_scan.rs\n");
+    source.push_str(&format!(
+        "pub const MESSAGES: [&str; {}] = [\n",
+        messages.len()
+    ));
+    for message in messages {
+        source.push_str(&format!("    {message:?},\n"));
+    }
+    source.push_str("];\n");
+    source.push_str(&format!(
+        "pub const HASH: u32 = {:#010X}_u32;\n",
+        hash(messages)
+    ));
+    source
+}
@@ -0,0 +1,403 @@
+//! Binary log frames for the `binary-log` feature, in the spirit of defmt
+//!
+//! Instead of formatting messages on the clock, only the index of the format
+//! string in a dictionary (generated at build time by `build/log_scan.rs`) and
+//! the raw arguments are sent. The format strings never make it into flash,
+//! and the log decoder formats the messages on the host instead.
+//!
+//! Frames are mixed in with the console's plain text, so they start with a
+//! byte that never appears in ASCII:
+//! `[FRAME_START, length, contents...]` where the contents are
+//! `[message low, message high, tag, level, arguments...]`, COBS-encoded, and
+//! the length counts them once encoded. Each argument is a type byte followed
+//! by its value in little endian (strings are prefixed by their length).
+//!
+//! The protocol's packets share the serial port, delimited by zeros. Encoding
+//! the contents leaves log frames without any zeros, so host tools reading
+//! packets pass over them like the rest of the text.
+
+use alarm_clock_protocol::cobs;
+
+pub const FRAME_START: u8 = 0xFF_u8;
+/// A whole frame, once encoded
+pub const MAX_FRAME_LENGTH: usize = 64_usize;
+/// The contents before they're encoded, which adds a byte
+const MAX_CONTENTS_LENGTH: usize = MAX_FRAME_LENGTH - 3_usize;
+const HEADER_LENGTH: usize = 4_usize;
+/// In place of a message index for the frame sent at boot, which has the
+/// dictionary's hash as its only argument
+pub const DICTIONARY_MESSAGE: u16 = 0xFFFF_u16;
+
+const TYPE_U8: u8 = 0_u8;
+const TYPE_U16: u8 = 1_u8;
+const TYPE_U32: u8 = 2_u8;
+const TYPE_I8: u8 = 3_u8;
+const TYPE_I16: u8 = 4_u8;
+const TYPE_I32: u8 = 5_u8;
+const TYPE_BOOL: u8 = 6_u8;
+const TYPE_CHAR: u8 = 7_u8;
+const TYPE_STR: u8 = 8_u8;
+
+/// The index of `format` in `messages`, for looking up a message's index at
+/// compile time
+pub const fn find_message(messages: &[&str], format: &str) -> Option<u16> {
+    let mut n = 0_usize;
+    while n < messages.len() {
+        let (a, b) = (messages[n].as_bytes(), format.as_bytes());
+        if a.len() == b.len() {
+            let mut i = 0_usize;
+            while i < a.len() && a[i] == b[i] {
+                i += 1;
+            }
+            if i == a.len() {
+                return Some(n as u16);
+            }
+        }
+        n += 1;
+    }
+    None
+}
+
+/// A frame being built. Arguments that don't fit are left off.
+pub struct Frame {
+    contents: [u8; MAX_CONTENTS_LENGTH],
+    length: usize,
+    encoded: [u8; MAX_FRAME_LENGTH],
+}
+impl Frame {
+    pub fn new(message: u16, tag: u8, level: u8) -> Self {
+        let mut frame = Self {
+            contents: [0_u8; MAX_CONTENTS_LENGTH],
+            length: HEADER_LENGTH,
+            encoded: [0_u8; MAX_FRAME_LENGTH],
+        };
+        let message = message.to_le_bytes();
+        frame.contents[..HEADER_LENGTH].copy_from_slice(&[message[0], message[1], tag, level]);
+        frame
+    }
+
+    /// The frame announcing which dictionary the firmware was built with
+    pub fn dictionary(hash: u32) -> Self {
+        let mut frame = Self::new(DICTIONARY_MESSAGE, 0_u8, 0_u8);
+        hash.encode(&mut frame);
+        frame
+    }
+
+    /// Append an argument's type and value, unless it doesn't fit
+    fn push(&mut self, argument_type: u8, value: &[u8]) {
+        if self.length + 1 + value.len() > MAX_CONTENTS_LENGTH {
+            return;
+        }
+        self.contents[self.length] = argument_type;
+        self.contents[self.length + 1..self.length + 1 + value.len()].copy_from_slice(value);
+        self.length += 1 + value.len();
+    }
+
+    /// The frame as it's sent
+    pub fn bytes(&mut self) -> &[u8] {
+        // The contents always fit, as they're kept short enough to
+        let length =
+            cobs::encode(&self.contents[..self.length], &mut self.encoded[2..]).unwrap_or_default();
+        self.encoded[0] = FRAME_START;
+        self.encoded[1] = length as u8;
+        &self.encoded[..2 + length]
+    }
+}
+
+/// Decode the contents of a frame (everything after its length byte) into
+/// `out`, for `DecodedFrame::decode`
+pub fn decode_contents<'a>(
+    encoded: &[u8],
+    out: &'a mut [u8; MAX_FRAME_LENGTH],
+) -> Result<&'a [u8], DecodeError> {
+    let length = cobs::decode(encoded, out).ok_or(DecodeError::Cobs)?;
+    Ok(&out[..length])
+}
+
+/// Log arguments that can be sent in a frame
+pub trait Argument {
+    fn encode(&self, frame: &mut Frame);
+}
+impl<T: Argument + ?Sized> Argument for &T {
+    fn encode(&self, frame: &mut Frame) {
+        (**self).encode(frame)
+    }
+}
+macro_rules! integer_argument {
+    ($($t:ty => $type:expr),*) => {
+        $(
+            impl Argument for $t {
+                fn encode(&self, frame: &mut Frame) {
+                    frame.push($type, &self.to_le_bytes());
+                }
+            }
+        )*
+    };
+}
+integer_argument!(u8 => TYPE_U8, u16 => TYPE_U16, u32 => TYPE_U32, i8 => TYPE_I8, i16 => TYPE_I16, i32 => TYPE_I32);
+impl Argument for bool {
+    fn encode(&self, frame: &mut Frame) {
+        frame.push(TYPE_BOOL, &[*self as u8]);
+    }
+}
+impl Argument for char {
+    fn encode(&self, frame: &mut Frame) {
+        frame.push(TYPE_CHAR, &(*self as u32).to_le_bytes());
+    }
+}
+impl Argument for str {
+    fn encode(&self, frame: &mut Frame) {
+        // Long strings are cut short rather than left off
+        let space = MAX_CONTENTS_LENGTH.saturating_sub(frame.length + 2_usize);
+        let length = self.len().min(space);
+        let mut value = [0_u8; MAX_CONTENTS_LENGTH];
+        value[0] = length as u8;
+        value[1..1 + length].copy_from_slice(&self.as_bytes()[..length]);
+        frame.push(TYPE_STR, &value[..1 + length]);
+    }
+}
+
+/// A decoded argument
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Value<'a> {
+    Unsigned(u32),
+    Signed(i32),
+    Bool(bool),
+    Char(char),
+    Str(&'a str),
+}
+impl core::fmt::Display for Value<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Value::Unsigned(value) => write!(f, "{value}"),
+            Value::Signed(value) => write!(f, "{value}"),
+            Value::Bool(value) => write!(f, "{value}"),
+            Value::Char(value) => write!(f, "{value}"),
+            Value::Str(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DecodeError {
+    /// The frame is shorter than its header or an argument
+    Truncated,
+    UnknownType(u8),
+    BadString,
+    /// The contents aren't valid COBS
+    Cobs,
+}
+
+/// A frame's contents, once decoded by `decode_contents`
+pub struct DecodedFrame<'a> {
+    pub message: u16,
+    pub tag: u8,
+    pub level: u8,
+    arguments: &'a [u8],
+}
+impl<'a> DecodedFrame<'a> {
+    pub fn decode(contents: &'a [u8]) -> Result<Self, DecodeError> {
+        if contents.len() < HEADER_LENGTH {
+            return Err(DecodeError::Truncated);
+        }
+        Ok(Self {
+            message: u16::from_le_bytes([contents[0], contents[1]]),
+            tag: contents[2],
+            level: contents[3],
+            arguments: &contents[4..],
+        })
+    }
+
+    pub fn arguments(&self) -> Arguments<'a> {
+        Arguments {
+            bytes: self.arguments,
+        }
+    }
+
+    /// Format the message, substituting `{}` and `{:?}` with the arguments
+    pub fn format(&self, format: &str, out: &mut impl core::fmt::Write) -> core::fmt::Result {
+        let mut arguments = self.arguments();
+        let mut rest = format;
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix("{{") {
+                out.write_char('{')?;
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix("}}") {
+                out.write_char('}')?;
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix("{}").or(rest.strip_prefix("{:?}")) {
+                match arguments.next() {
+                    Some(Ok(value)) => write!(out, "{value}")?,
+                    Some(Err(_)) => out.write_str("<?>")?,
+                    None => out.write_str("<missing>")?,
+                }
+                rest = after;
+            } else {
+                let mut chars = rest.chars();
+                out.write_char(chars.next().unwrap_or_default())?;
+                rest = chars.as_str();
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct Arguments<'a> {
+    bytes: &'a [u8],
+}
+impl<'a> Iterator for Arguments<'a> {
+    type Item = Result<Value<'a>, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (&argument_type, rest) = self.bytes.split_first()?;
+        let length = match argument_type {
+            TYPE_U8 | TYPE_I8 | TYPE_BOOL => 1_usize,
+            TYPE_U16 | TYPE_I16 => 2_usize,
+            TYPE_U32 | TYPE_I32 | TYPE_CHAR => 4_usize,
+            TYPE_STR => 1_usize + *rest.first().unwrap_or(&0_u8) as usize,
+            _ => {
+                self.bytes = &[];
+                return Some(Err(DecodeError::UnknownType(argument_type)));
+            }
+        };
+        if rest.len() < length {
+            self.bytes = &[];
+            return Some(Err(DecodeError::Truncated));
+        }
+        let (value, rest) = rest.split_at(length);
+        self.bytes = rest;
+
+        let mut le = [0_u8; 4];
+        le[..length.min(4)].copy_from_slice(&value[..length.min(4)]);
+        let unsigned = u32::from_le_bytes(le);
+        Some(Ok(match argument_type {
+            TYPE_U8 | TYPE_U16 | TYPE_U32 => Value::Unsigned(unsigned),
+            TYPE_I8 => Value::Signed(value[0] as i8 as i32),
+            TYPE_I16 => Value::Signed(i16::from_le_bytes([value[0], value[1]]) as i32),
+            TYPE_I32 => Value::Signed(unsigned as i32),
+            TYPE_BOOL => Value::Bool(value[0] != 0_u8),
+            TYPE_CHAR => {
+                Value::Char(char::from_u32(unsigned).unwrap_or(char::REPLACEMENT_CHARACTER))
+            }
+            _ => match core::str::from_utf8(&value[1..]) {
+                Ok(value) => Value::Str(value),
+                Err(_) => return Some(Err(DecodeError::BadString)),
+            },
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use heapless::String;
+
+    const MESSAGES: [&str; 3] = ["Chiming", "Playing {}", "Read time: {}{}:{}{}:{}{}"];
+
+    fn round_trip(frame: &mut Frame, format: &str) -> String<128> {
+        let bytes = frame.bytes();
+        assert_eq!(bytes[0], FRAME_START);
+        assert_eq!(bytes[1] as usize, bytes.len() - 2);
+        assert!(!bytes.contains(&0_u8));
+        let mut contents = [0_u8; MAX_FRAME_LENGTH];
+        let mut out = String::new();
+        DecodedFrame::decode(decode_contents(&bytes[2..], &mut contents).unwrap())
+            .unwrap()
+            .format(format, &mut out)
+            .unwrap();
+        out
+    }
+
+    #[test]
+    fn finding_messages() {
+        const PLAYING: Option<u16> = find_message(&MESSAGES, "Playing {}");
+        assert_eq!(PLAYING, Some(1_u16));
+        assert_eq!(find_message(&MESSAGES, "Chiming"), Some(0_u16));
+        assert_eq!(find_message(&MESSAGES, "Chimin"), None);
+        assert_eq!(find_message(&MESSAGES, "Playing"), None);
+    }
+
+    #[test]
+    fn arguments() {
+        let mut frame = Frame::new(2_u16, 1_u8, 4_u8);
+        for digit in ['1', '2', '3', '4', '5', '6'] {
+            digit.encode(&mut frame);
+        }
+        let mut contents = [0_u8; MAX_FRAME_LENGTH];
+        let contents = decode_contents(&frame.bytes()[2..], &mut contents).unwrap();
+        let decoded = DecodedFrame::decode(contents).unwrap();
+        assert_eq!(
+            (decoded.message, decoded.tag, decoded.level),
+            (2_u16, 1_u8, 4_u8)
+        );
+        assert_eq!(round_trip(&mut frame, MESSAGES[2]), "Read time: 12:34:56");
+
+        let mut frame = Frame::new(0_u16, 0_u8, 0_u8);
+        (-5_i8).encode(&mut frame);
+        (-300_i16).encode(&mut frame);
+        (-70_000_i32).encode(&mut frame);
+        300_u16.encode(&mut frame);
+        70_000_u32.encode(&mut frame);
+        true.encode(&mut frame);
+        "Tetris".encode(&mut frame);
+        assert_eq!(
+            round_trip(&mut frame, "{} {} {} {} {} {:?} {{{}}}"),
+            "-5 -300 -70000 300 70000 true {Tetris}"
+        );
+    }
+
+    #[test]
+    fn missing_and_malformed_arguments() {
+        let mut frame = Frame::new(1_u16, 6_u8, 1_u8);
+        assert_eq!(round_trip(&mut frame, "Playing {}"), "Playing <missing>");
+
+        let mut frame = Frame::new(1_u16, 6_u8, 1_u8);
+        "Tetris".encode(&mut frame);
+        let mut contents = [0_u8; MAX_FRAME_LENGTH];
+        let contents = decode_contents(&frame.bytes()[2..], &mut contents).unwrap();
+        let decoded = DecodedFrame::decode(&contents[..contents.len() - 1]).unwrap();
+        assert_eq!(
+            decoded.arguments().next(),
+            Some(Err(DecodeError::Truncated))
+        );
+        assert_eq!(
+            DecodedFrame::decode(&[9_u8, 0_u8, 0_u8, 0_u8, 42_u8])
+                .unwrap()
+                .arguments()
+                .next(),
+            Some(Err(DecodeError::UnknownType(42_u8)))
+        );
+        assert!(DecodedFrame::decode(&[1_u8, 0_u8]).is_err());
+        let mut contents = [0_u8; MAX_FRAME_LENGTH];
+        assert_eq!(
+            decode_contents(&[5_u8, 1_u8], &mut contents).err(),
+            Some(DecodeError::Cobs)
+        );
+    }
+
+    #[test]
+    fn long_arguments_are_cut_short() {
+        let mut frame = Frame::new(0_u16, 0_u8, 0_u8);
+        let long = "This message is far too long to fit in one frame, so it is cut";
+        long.encode(&mut frame);
+        // No room left for anything else
+        42_u8.encode(&mut frame);
+        assert_eq!(frame.bytes().len(), MAX_FRAME_LENGTH);
+        let out = round_trip(&mut frame, "{} {}");
+        assert!(long.starts_with(out.trim_end_matches(" <missing>")));
+        assert!(out.ends_with(" <missing>"));
+    }
+
+    #[test]
+    fn dictionary_frame() {
+        let mut frame = Frame::dictionary(0xDEAD_BEEF_u32);
+        let mut contents = [0_u8; MAX_FRAME_LENGTH];
+        let contents = decode_contents(&frame.bytes()[2..], &mut contents).unwrap();
+        let decoded = DecodedFrame::decode(contents).unwrap();
+        assert_eq!(decoded.message, DICTIONARY_MESSAGE);
+        assert_eq!(
+            decoded.arguments().next(),
+            Some(Ok(Value::Unsigned(0xDEAD_BEEF_u32)))
+        );
+    }
+}
@@ -0,0 +1,119 @@
+//! Optional chime on the hour (and half hour) with quiet hours when it's
+//! suppressed. The chime never plays over an active alarm or while a menu is
+//! being edited; only when the clock is idle.
+
+use alarm_clock_core::{
+    hal::ToneOutput,
+    sound::{ChimeMode, ChimeSettings, ChimeStyle},
+    time::Time,
+};
+
+use crate::log::{debug, Tag};
+
+/// Chime if the slot was reached within this many seconds (so a failed RTC read
+/// doesn't make us miss it entirely)
+const CHIME_WINDOW_SECONDS: u8 = 3_u8;
+
+/// (frequency in Hz or `None` for silence, duration in ms)
+type ChimeStep = (Option<u16>, u16);
+
+const BEEP: [ChimeStep; 1] = [(Some(2_093_u16), 60_u16)];
+/// E, C, D, G (the first bar of the Westminster quarters)
+const WESTMINSTER: [ChimeStep; 8] = [
+    (Some(1_319_u16), 350_u16),
+    (None, 50_u16),
+    (Some(1_047_u16), 350_u16),
+    (None, 50_u16),
+    (Some(1_175_u16), 350_u16),
+    (None, 50_u16),
+    (Some(784_u16), 700_u16),
+    (None, 50_u16),
+];
+/// Only half of the pattern is played on the half hour
+const WESTMINSTER_HALF_HOUR_STEPS: usize = 4_usize;
+
+pub struct Chime {
+    steps: &'static [ChimeStep],
+    step: usize,
+    /// Millis when the current step ends
+    deadline: u32,
+    /// The (hours, minutes) slot that was last chimed so it only chimes once
+    last_slot: Option<(u8, u8)>,
+}
+impl Chime {
+    pub fn new() -> Self {
+        Self {
+            steps: &[],
+            step: 0_usize,
+            deadline: 0_u32,
+            last_slot: None,
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.step < self.steps.len()
+    }
+
+    /// Start the chime if an hour or half hour was just reached. Only call this
+    /// while the clock is idle.
+    pub fn check(
+        &mut self,
+        time: &Time,
+        settings: &ChimeSettings,
+        now: u32,
+        buzzer: &mut impl ToneOutput,
+    ) {
+        let on_the_hour = time.minutes == 0_u8;
+        let on_the_half_hour = time.minutes == 30_u8 && settings.mode == ChimeMode::HalfHourly;
+        let slot = Some((time.hours, time.minutes));
+        if settings.mode == ChimeMode::Off
+            || !(on_the_hour || on_the_half_hour)
+            || time.seconds >= CHIME_WINDOW_SECONDS
+            || self.last_slot == slot
+        {
+            return;
+        }
+        self.last_slot = slot;
+
+        if settings.is_quiet(time.hours) {
+            debug!(Tag::Chime, "Quiet hours, not chiming");
+            return;
+        }
+
+        debug!(Tag::Chime, "Chiming");
+        self.steps = match (settings.style, on_the_hour) {
+            (ChimeStyle::Beep, _) => &BEEP,
+            (ChimeStyle::Westminster, true) => &WESTMINSTER,
+            (ChimeStyle::Westminster, false) => &WESTMINSTER[..WESTMINSTER_HALF_HOUR_STEPS],
+        };
+        self.step = 0_usize;
+        self.start_step(now, buzzer);
+    }
+
+    /// Silence the chime immediately, e.g. because the alarm is about to sound
+    pub fn stop(&mut self, buzzer: &mut impl ToneOutput) {
+        if self.is_playing() {
+            self.step = self.steps.len();
+            buzzer.set_tone(None);
+        }
+    }
+
+    /// Advance to the next step of the chime if it's time to. Non-blocking.
+    pub fn update(&mut self, now: u32, buzzer: &mut impl ToneOutput) {
+        if !self.is_playing() || (now.wrapping_sub(self.deadline) as i32) < 0_i32 {
+            return;
+        }
+        self.step += 1_usize;
+        if self.is_playing() {
+            self.start_step(now, buzzer);
+        } else {
+            buzzer.set_tone(None);
+        }
+    }
+
+    fn start_step(&mut self, now: u32, buzzer: &mut impl ToneOutput) {
+        let (frequency, duration_ms) = self.steps[self.step];
+        buzzer.set_tone(frequency);
+        self.deadline = now.wrapping_add(duration_ms as u32);
+    }
+}
@@ -1,77 +1,69 @@
-//! Universal `println` macro for writing to the serial connection
+//! Universal `println` macro for writing to the serial connection, and reading
+//! back from it for the shell
 
-use crate::shared::UsbSerial;
-use avr_device::interrupt::{self, Mutex};
-use core::cell::RefCell;
-
-pub static CONSOLE: Mutex<RefCell<Option<UsbSerial>>> = interrupt::Mutex::new(RefCell::new(None));
+use crate::{
+    interrupts::{serial_init, serial_read, serial_write},
+    shared::UsbSerial,
+};
+use ufmt::{uDisplay, uWrite, Formatter};
 
 pub const DIGIT_LOOKUP: [char; 16] = [
     '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', 'A', 'B', 'C', 'D', 'E', 'F',
 ];
 
+/// Writes to the serial connection by queueing bytes for the USART interrupt
+pub struct Console;
+impl uWrite for Console {
+    type Error = core::convert::Infallible;
+
+    fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
+        for byte in s.bytes() {
+            serial_write(byte);
+        }
+        Ok(())
+    }
+}
+
 macro_rules! print {
     ($($t:tt)*) => {
-        avr_device::interrupt::free(
-            |critical_section| {
-                if let Some(console) = crate::console::CONSOLE.borrow(critical_section).borrow_mut().as_mut() {
-                    let _ = ufmt::uwrite!(console, $($t)*);
-                }
-            },
-        )
+        {
+            let _ = ufmt::uwrite!(crate::console::Console, $($t)*);
+        }
     };
 }
 
 macro_rules! println {
     ($($t:tt)*) => {
-        avr_device::interrupt::free(
-            |critical_section| {
-                arduino_hal::delay_ms(1);
-                if let Some(console) = crate::console::CONSOLE.borrow(critical_section).borrow_mut().as_mut() {
-                    let _ = ufmt::uwriteln!(console, $($t)*);
-                };
-            },
-        )
+        {
+            let _ = ufmt::uwriteln!(crate::console::Console, $($t)*);
+        }
     };
 }
 
-macro_rules! debug {
-    ($($t:tt)*) => {
-        // TODO: Call println! macro from here and compile time check
-        if crate::shared::DEBUG {
-            avr_device::interrupt::free(
-                |critical_section| {
-                    if let Some(console) = crate::console::CONSOLE.borrow(critical_section).borrow_mut().as_mut() {
-                        let _ = ufmt::uwriteln!(console, $($t)*);
-                    };
-                }
-            )
-        }
-    };
+pub fn set_console(console: UsbSerial) {
+    serial_init(console);
 }
 
-macro_rules! trace {
-    ($($t:tt)*) => {
-        // TODO: Call println! macro from here and compile time check
-        if crate::shared::TRACE {
-            avr_device::interrupt::free(
-                |critical_section| {
-                    if let Some(console) = crate::console::CONSOLE.borrow(critical_section).borrow_mut().as_mut() {
-                        let _ = ufmt::uwriteln!(console, $($t)*);
-                    };
-                }
-            )
-        }
+/// Write raw bytes, such as protocol frames or binary log frames
+pub fn write_bytes(bytes: &[u8]) {
+    for byte in bytes {
+        serial_write(*byte);
     }
 }
 
-pub fn set_console(console: UsbSerial) {
-    interrupt::free(|cs| {
-        *CONSOLE.borrow(cs).borrow_mut() = Some(console);
-    })
+/// Read a byte from the console if one has been received. Never blocks.
+pub fn read_byte() -> Option<u8> {
+    serial_read()
+}
+
+/// A number zero-padded to two digits, as ufmt has no padding
+pub struct TwoDigits(pub u8);
+impl uDisplay for TwoDigits {
+    fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+        f.write_char(DIGIT_LOOKUP[(self.0 / 10_u8 % 10_u8) as usize])?;
+        f.write_char(DIGIT_LOOKUP[(self.0 % 10_u8) as usize])
+    }
 }
 
-pub(crate) use debug;
 pub(crate) use print;
 pub(crate) use println;
-pub(crate) use trace;
@@ -0,0 +1,51 @@
+//! The rotary encoder and snooze button together, read as a stream of
+//! `InputEvent`s
+
+use alarm_clock_core::hal::{InputEvent, InputSource};
+use heapless::Deque;
+
+use crate::{
+    interrupts::changed_state, rotary_encoder::RotaryEncoder, snooze_button::SnoozeButton,
+};
+
+pub struct Controls {
+    pub rotary_encoder: RotaryEncoder,
+    pub snooze_button: SnoozeButton,
+    /// Events from the last reading that haven't been taken yet
+    pending: Deque<InputEvent, 2>,
+    /// Whether the controls were read since the last time we caught up
+    read: bool,
+}
+impl Controls {
+    pub fn new(rotary_encoder: RotaryEncoder, snooze_button: SnoozeButton) -> Self {
+        Self {
+            rotary_encoder,
+            snooze_button,
+            pending: Deque::new(),
+            read: false,
+        }
+    }
+}
+impl InputSource for Controls {
+    /// The controls are read once per run of events, so looping until `None`
+    /// doesn't spin forever
+    fn next_event(&mut self) -> Option<InputEvent> {
+        if !self.read {
+            self.read = true;
+            // Reading the flag clears it, so both have to be given the one
+            // reading
+            let changed = changed_state();
+            self.rotary_encoder.update(changed);
+            self.snooze_button.update(changed);
+            if let Some(event) = self.rotary_encoder.event() {
+                let _ = self.pending.push_back(event);
+            }
+            if self.snooze_button.pressed() {
+                let _ = self.pending.push_back(InputEvent::Snooze);
+            }
+        }
+        let event = self.pending.pop_front();
+        self.read = event.is_some();
+        event
+    }
+}
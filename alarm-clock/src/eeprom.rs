@@ -0,0 +1,28 @@
+//! The ATmega328P's EEPROM, behind `alarm_clock_core::eeprom::Eeprom` so the
+//! settings and history can use it
+
+use arduino_hal::pac::EEPROM;
+
+pub struct OnboardEeprom {
+    eeprom: arduino_hal::Eeprom,
+}
+impl OnboardEeprom {
+    pub fn new(eeprom: EEPROM) -> Self {
+        Self {
+            eeprom: arduino_hal::Eeprom::new(eeprom),
+        }
+    }
+}
+impl alarm_clock_core::eeprom::Eeprom for OnboardEeprom {
+    fn capacity(&self) -> u16 {
+        self.eeprom.capacity()
+    }
+
+    fn read_byte(&self, address: u16) -> u8 {
+        self.eeprom.read_byte(address)
+    }
+
+    fn write_byte(&mut self, address: u16, byte: u8) {
+        self.eeprom.write_byte(address, byte)
+    }
+}
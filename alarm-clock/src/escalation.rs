@@ -0,0 +1,112 @@
+//! Escalating alarm sound which starts with sparse, short chirps and ramps up
+//! to a continuous tone over the profile's ramp period. The alarm LED pulses
+//! along with the chirps.
+//!
+//! Like the melody player, this should be updated as often as possible from
+//! the main loop.
+
+use embedded_hal::digital::v2::OutputPin;
+
+use alarm_clock_core::{hal::ToneOutput, sound::EscalationProfile};
+
+use crate::{
+    log::{debug, Tag},
+    pins,
+};
+
+/// Progress through the ramp is a fixed point fraction out of this
+const PROGRESS_MAX: u32 = 256_u32;
+/// Time between the start of each chirp
+const START_PERIOD_MS: u16 = 2_000_u16;
+const END_PERIOD_MS: u16 = 250_u16;
+/// Fraction of the period that the chirp is sounding, out of `PROGRESS_MAX`
+const START_DUTY: u16 = 8_u16;
+const END_DUTY: u16 = PROGRESS_MAX as u16;
+
+/// Linearly interpolate from `start` to `end` by `progress` out of `PROGRESS_MAX`
+fn lerp(start: u16, end: u16, progress: u32) -> u16 {
+    let delta = (end as i32 - start as i32) * progress as i32 / PROGRESS_MAX as i32;
+    (start as i32 + delta) as u16
+}
+
+pub struct EscalatingPlayer {
+    profile: Option<EscalationProfile>,
+    /// Millis when the alarm started sounding
+    start: u32,
+    /// Millis when the current chirp started
+    cycle_start: u32,
+    period_ms: u16,
+    on_ms: u16,
+    sounding: bool,
+}
+impl EscalatingPlayer {
+    pub fn new() -> Self {
+        Self {
+            profile: None,
+            start: 0_u32,
+            cycle_start: 0_u32,
+            period_ms: 0_u16,
+            on_ms: 0_u16,
+            sounding: false,
+        }
+    }
+
+    pub fn play(&mut self, profile: EscalationProfile, now: u32) {
+        debug!(
+            Tag::Escalate,
+            "Escalating over {} seconds", profile.ramp_seconds
+        );
+        self.profile = Some(profile);
+        self.start = now;
+        // Start a chirp on the next update
+        self.cycle_start = now;
+        self.period_ms = 0_u16;
+    }
+
+    pub fn stop(&mut self, buzzer: &mut impl ToneOutput, alarm_led: &mut pins::leds::Alarm) {
+        self.profile = None;
+        self.sounding = false;
+        buzzer.set_tone(None);
+        let _ = alarm_led.set_low();
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.profile.is_some()
+    }
+
+    /// Start or end the current chirp if it's time to. Non-blocking.
+    pub fn update(
+        &mut self,
+        now: u32,
+        buzzer: &mut impl ToneOutput,
+        alarm_led: &mut pins::leds::Alarm,
+    ) {
+        let Some(profile) = self.profile else {
+            return;
+        };
+        let into_cycle = now.wrapping_sub(self.cycle_start);
+
+        if into_cycle >= self.period_ms as u32 {
+            // Next chirp, escalated by how far along we are
+            let ms_per_step = (profile.ramp_seconds as u32 * 1_000_u32 / PROGRESS_MAX).max(1_u32);
+            let progress = (now.wrapping_sub(self.start) / ms_per_step).min(PROGRESS_MAX);
+            self.period_ms = lerp(START_PERIOD_MS, END_PERIOD_MS, progress);
+            self.on_ms = (self.period_ms as u32 * lerp(START_DUTY, END_DUTY, progress) as u32
+                / PROGRESS_MAX) as u16;
+            self.cycle_start = now;
+
+            buzzer.set_tone(Some(lerp(
+                profile.start_frequency,
+                profile.end_frequency,
+                progress,
+            )));
+            let _ = alarm_led.set_high();
+            self.sounding = true;
+        } else if self.sounding && into_cycle >= self.on_ms as u32 && self.on_ms < self.period_ms {
+            // Gap between chirps (there isn't one once fully escalated)
+            buzzer.set_tone(None);
+            let _ = alarm_led.set_low();
+            self.sounding = false;
+        }
+    }
+}
@@ -1,23 +1,33 @@
 //! Interrupts
 
-use arduino_hal::{pac::TC0, pins, Peripherals};
+use alarm_clock_core::hal::ToneOutput;
+use arduino_hal::{
+    pac::{TC0, TC2},
+    pins, Peripherals,
+};
 use avr_device::{
-    atmega328p::exint::{pcicr::PCICR_SPEC, pcmsk0::PCMSK0_SPEC},
-    generic::Reg,
+    atmega328p::EXINT,
     interrupt::{self, Mutex},
 };
 use core::{
-    cell::Cell,
-    sync::atomic::{AtomicBool, Ordering::SeqCst},
+    cell::{Cell, RefCell},
+    sync::atomic::{AtomicBool, AtomicU8, Ordering::SeqCst},
 };
+use heapless::Deque;
 
-use crate::{pins, shared::PinState::PinState, time_display::HOUR_MINUTE_DISPLAY};
+use crate::{
+    pins,
+    shared::{PinState::PinState, UsbSerial},
+    time_display::HOUR_MINUTE_DISPLAY,
+};
 
-pub use millis::{millis, millis_init};
+pub use millis::{display_time_us, millis, millis_init, MILLIS_PERIOD_US};
 pub use rotary_encoder_and_snooze::{
     changed_state, get_rotary_encoder_state, get_snooze_button_pressed, rotary_encoder_init,
     snooze_button_init, RotaryEncoderState,
 };
+pub use serial::{serial_dropped, serial_init, serial_read, serial_write};
+pub use tone::{tone_init, Buzzer};
 
 /// This millisecond interrupt was usurped from Rahix's amazing blog:
 /// https://blog.rahix.de/005-avr-hal-millis/
@@ -27,8 +37,15 @@ mod millis {
     const PRESCALER: u32 = 64_u32;
     const TIMER_COUNTS: u32 = 250_u32;
     const MILLIS_INCREMENT: u32 = PRESCALER * TIMER_COUNTS / 16_000_u32; // 16MHz
+    /// How long each timer count is
+    const COUNT_US: u16 = (PRESCALER / 16_u32) as u16;
+    /// The time between interrupts, which updating the display has to fit in
+    pub const MILLIS_PERIOD_US: u16 = COUNT_US * TIMER_COUNTS as u16;
 
     static MILLIS_COUNTER: Mutex<Cell<u32>> = Mutex::new(Cell::new(0_u32));
+    /// The most timer counts an interrupt has taken, from the compare match
+    /// to the display being updated
+    static DISPLAY_COUNTS_MAX: AtomicU8 = AtomicU8::new(0_u8);
 
     pub fn millis_init(tc0: TC0) {
         // Configure the timer for the above interval (in CTC mode)
@@ -69,9 +86,23 @@ mod millis {
                     display.display(critical_section);
                 }
             }
+
+            // The timer was reset by the compare match, so it's counted up
+            // since the interrupt was raised
+            let counts = unsafe { &*TC0::ptr() }.tcnt0.read().bits();
+            if counts > DISPLAY_COUNTS_MAX.load(SeqCst) {
+                DISPLAY_COUNTS_MAX.store(counts, SeqCst);
+            }
         })
     }
 
+    /// The longest the millisecond interrupt has taken to update the hours and
+    /// minutes display, to within a timer count (4us). It has to stay well
+    /// under `MILLIS_PERIOD_US` to leave time for everything else.
+    pub fn display_time_us() -> u16 {
+        (DISPLAY_COUNTS_MAX.load(SeqCst) as u16 + 1_u16) * COUNT_US
+    }
+
     /// Milliseconds since the interrupt timer was configured for all times that interrupts were allowed
     pub fn millis() -> u32 {
         interrupt::free(|critical_section| MILLIS_COUNTER.borrow(critical_section).get())
@@ -90,52 +121,70 @@ mod rotary_encoder_and_snooze {
     /// Whether the rotary button is pressed (tied to GND)
     static ROTARY_BUTTON: AtomicBool = AtomicBool::new(false);
 
+    /// Enable a pin change interrupt [0, 23], i.e. PCINTn
+    unsafe fn enable_pin_change_interrupt(exint: &EXINT, interrupt: u8) {
+        let mask = 0b1_u8 << (interrupt % 8_u8);
+        match interrupt / 8_u8 {
+            0_u8 => exint.pcmsk0.modify(|r, w| w.bits(r.bits() | mask)),
+            1_u8 => exint.pcmsk1.modify(|r, w| w.bits(r.bits() | mask)),
+            _ => exint.pcmsk2.modify(|r, w| w.bits(r.bits() | mask)),
+        }
+        exint
+            .pcicr
+            .modify(|r, w| unsafe { w.bits(r.bits() | 0b1_u8 << (interrupt / 8_u8)) });
+    }
+
     /// Safety note: The caller must ensure that the A pin and Button pin are
-    /// pin change interrupts 4 and 5 respectively of mask 0!
+    /// the ones in `pins`, so they're pin change interrupts 5 (or 19 with
+    /// `spi-displays`) and 4 respectively!
     pub unsafe fn rotary_encoder_init(
-        pcicr: &Reg<PCICR_SPEC>,
-        pcmsk0: &Reg<PCMSK0_SPEC>,
+        exint: &EXINT,
         _a: &pins::rotary_encoder::A,
         _button: &pins::rotary_encoder::Button,
     ) {
-        // Enable mask 0 interrupt
-        let mut enabled_interrupts = pcicr.read().bits() as u8;
-        enabled_interrupts |= 0b1 << 0;
-        pcicr.write(|w| unsafe { w.bits(enabled_interrupts) });
-
-        // Configure mask 0
-        let mut mask_0_bits = pcmsk0.read().bits() as u8;
-        mask_0_bits |= 0b1_u8 << 4; // Button: PCINT4
-        mask_0_bits |= 0b1_u8 << 5; // A: PCINT5
-        pcmsk0.write(|w| w.bits(mask_0_bits));
-    }
-
-    /// Safety note: The caller must ensure that the button pin is
-    /// pin change interrupt 3 of mask 0!
-    pub unsafe fn snooze_button_init(
-        pcicr: &Reg<PCICR_SPEC>,
-        pcmsk0: &Reg<PCMSK0_SPEC>,
-        _button: &pins::snooze::Button,
-    ) {
-        // Enable mask 0 interrupt
-        let mut enabled_interrupts = pcicr.read().bits() as u8;
-        enabled_interrupts |= 0b1 << 0;
-        pcicr.write(|w| unsafe { w.bits(enabled_interrupts) });
+        enable_pin_change_interrupt(exint, 4_u8); // Button: PCINT4
+        #[cfg(not(feature = "spi-displays"))]
+        enable_pin_change_interrupt(exint, 5_u8); // A: PCINT5
+        #[cfg(feature = "spi-displays")]
+        enable_pin_change_interrupt(exint, 19_u8); // A: PCINT19
+    }
+
+    /// Safety note: The caller must ensure that the button pin is the one in
+    /// `pins`, so it's pin change interrupt 3 (or 18 with `spi-displays`)!
+    pub unsafe fn snooze_button_init(exint: &EXINT, _button: &pins::snooze::Button) {
+        #[cfg(not(feature = "spi-displays"))]
+        enable_pin_change_interrupt(exint, 3_u8); // Button: PCINT3
+        #[cfg(feature = "spi-displays")]
+        enable_pin_change_interrupt(exint, 18_u8); // Button: PCINT18
+    }
 
-        // Configure mask 0
-        let mut mask_0_bits = pcmsk0.read().bits() as u8;
-        mask_0_bits |= 0b1_u8 << 3; // Button: PCINT3
-        pcmsk0.write(|w| w.bits(mask_0_bits));
+    /// The buttons and the encoder's A pin are all on port B, which is pin
+    /// change interrupt 0 (only the rotary button is with `spi-displays`)
+    #[avr_device::interrupt(atmega328p)]
+    #[allow(non_snake_case)]
+    fn PCINT0() {
+        read_inputs();
     }
 
+    /// With `spi-displays`, the snooze button and the encoder's A pin are moved
+    /// to port D (for the SPI pins), which is pin change interrupt 2
+    #[cfg(feature = "spi-displays")]
     #[avr_device::interrupt(atmega328p)]
     #[allow(non_snake_case)]
     fn PCINT2() {
+        read_inputs();
+    }
+
+    fn read_inputs() {
         let peripherals = unsafe { Peripherals::steal() };
         let pins = pins!(peripherals);
         CHANGED_STATE.store(true, SeqCst);
+        #[cfg(not(feature = "spi-displays"))]
+        let (a, snooze) = (pins.d13, pins.d11);
+        #[cfg(feature = "spi-displays")]
+        let (a, snooze) = (pins.d3, pins.d2);
         ROTARY_PIN_A.store(
-            { pins.d13.into_pull_up_input() as pins::rotary_encoder::A }.is_high(),
+            { a.into_pull_up_input() as pins::rotary_encoder::A }.is_high(),
             SeqCst,
         );
         ROTARY_PIN_B.store(
@@ -147,7 +196,7 @@ mod rotary_encoder_and_snooze {
             SeqCst,
         );
         SNOOZE_BUTTON.store(
-            { pins.d11.into_pull_up_input() as pins::snooze::Button }.is_low(), // tied to gnd
+            { snooze.into_pull_up_input() as pins::snooze::Button }.is_low(), // tied to gnd
             SeqCst,
         );
     }
@@ -180,3 +229,206 @@ mod rotary_encoder_and_snooze {
         }
     }
 }
+
+/// Square wave generation for the piezo buzzer. The buzzer isn't on a PWM pin,
+/// so timer 2 runs in CTC mode and its compare interrupt toggles the pin.
+mod tone {
+    use super::*;
+
+    const CLOCK_FREQUENCY: u32 = 16_000_000_u32; // 16MHz
+    /// Timer 2's prescalers, from the most precise. The slowest still counts
+    /// RTTTL's lowest note (C1, 33Hz) in 8 bits.
+    const PRESCALERS: [u32; 5] = [32_u32, 64_u32, 128_u32, 256_u32, 1_024_u32];
+    /// PC3
+    const BUZZER_MASK: u8 = 0b1_u8 << 3;
+
+    static TIMER: Mutex<RefCell<Option<TC2>>> = Mutex::new(RefCell::new(None));
+
+    /// Plays tones on the buzzer pin, once set up by `tone_init`
+    pub struct Buzzer {
+        _private: (),
+    }
+
+    pub fn tone_init(tc2: TC2, _buzzer: &pins::buzzer::Buzzer) -> Buzzer {
+        tc2.tccr2a.write(|w| w.wgm2().ctc());
+        tc2.tccr2b.write(|w| w.cs2().no_clock());
+        interrupt::free(|critical_section| {
+            TIMER.borrow(critical_section).replace(Some(tc2));
+        });
+        Buzzer { _private: () }
+    }
+
+    impl ToneOutput for Buzzer {
+        fn set_tone(&mut self, frequency: Option<u16>) {
+            interrupt::free(|critical_section| {
+                let timer = TIMER.borrow(critical_section).borrow();
+                let Some(tc2) = timer.as_ref() else {
+                    return;
+                };
+
+                match frequency {
+                    Some(frequency) if frequency > 0_u16 => {
+                        // The pin is toggled on every compare match, so twice per
+                        // period. The count has to fit in 8 bits, and the fastest
+                        // prescaler it fits with is the most precise.
+                        let toggle_frequency = 2_u32 * frequency as u32;
+                        let (prescaler, counts) = PRESCALERS
+                            .into_iter()
+                            .map(|prescaler| {
+                                (prescaler, CLOCK_FREQUENCY / prescaler / toggle_frequency)
+                            })
+                            .find(|&(_, counts)| counts <= 256_u32)
+                            .unwrap_or((1_024_u32, 256_u32));
+                        let counts = counts.max(1_u32);
+                        tc2.ocr2a.write(|w| w.bits((counts - 1_u32) as u8));
+                        tc2.tcnt2.write(|w| w.bits(0_u8));
+                        tc2.tccr2b.write(|w| match prescaler {
+                            32_u32 => w.cs2().prescale_32(),
+                            64_u32 => w.cs2().prescale_64(),
+                            128_u32 => w.cs2().prescale_128(),
+                            256_u32 => w.cs2().prescale_256(),
+                            _ => w.cs2().prescale_1024(),
+                        });
+                        tc2.timsk2.write(|w| w.ocie2a().set_bit());
+                    }
+                    _ => {
+                        tc2.timsk2.write(|w| w.ocie2a().clear_bit());
+                        tc2.tccr2b.write(|w| w.cs2().no_clock());
+                        // Don't leave the piezo driven high
+                        let peripherals = unsafe { Peripherals::steal() };
+                        peripherals
+                            .PORTC
+                            .portc
+                            .modify(|r, w| unsafe { w.bits(r.bits() & !BUZZER_MASK) });
+                    }
+                }
+            })
+        }
+    }
+
+    #[avr_device::interrupt(atmega328p)]
+    #[allow(non_snake_case)]
+    fn TIMER2_COMPA() {
+        // Writing a one to a PINx bit toggles that output
+        let peripherals = unsafe { Peripherals::steal() };
+        peripherals
+            .PORTC
+            .pinc
+            .write(|w| unsafe { w.bits(BUZZER_MASK) });
+    }
+}
+
+/// Buffered serial, so printing only has to queue bytes up rather than wait on
+/// the UART with interrupts disabled (which starves the display multiplexing).
+/// The data register empty interrupt sends queued bytes, and the receive
+/// complete interrupt queues received bytes until they're read.
+mod serial {
+    use super::*;
+
+    const TX_BUFFER_SIZE: usize = 128_usize;
+    const RX_BUFFER_SIZE: usize = 64_usize;
+
+    /// Only kept so nothing else can configure the USART
+    static USART: Mutex<RefCell<Option<UsbSerial>>> = Mutex::new(RefCell::new(None));
+    static TX_BUFFER: Mutex<RefCell<Deque<u8, TX_BUFFER_SIZE>>> =
+        Mutex::new(RefCell::new(Deque::new()));
+    static RX_BUFFER: Mutex<RefCell<Deque<u8, RX_BUFFER_SIZE>>> =
+        Mutex::new(RefCell::new(Deque::new()));
+    /// Received bytes that didn't fit in the buffer
+    static RX_DROPPED: Mutex<Cell<u16>> = Mutex::new(Cell::new(0_u16));
+
+    /// Take over the (already configured) USART and start receiving
+    pub fn serial_init(usart: UsbSerial) {
+        let peripherals = unsafe { Peripherals::steal() };
+        peripherals
+            .USART0
+            .ucsr0b
+            .modify(|_, w| w.rxcie0().set_bit());
+        interrupt::free(|critical_section| {
+            USART.borrow(critical_section).replace(Some(usart));
+        });
+    }
+
+    /// Queue a byte to be sent. If the buffer is full this waits for room,
+    /// sending bytes by polling if interrupts are disabled (e.g. before they
+    /// are enabled at boot).
+    pub fn serial_write(byte: u8) {
+        loop {
+            let queued = interrupt::free(|critical_section| {
+                let queued = TX_BUFFER
+                    .borrow(critical_section)
+                    .borrow_mut()
+                    .push_back(byte)
+                    .is_ok();
+                if queued {
+                    let peripherals = unsafe { Peripherals::steal() };
+                    peripherals
+                        .USART0
+                        .ucsr0b
+                        .modify(|_, w| w.udrie0().set_bit());
+                }
+                queued
+            });
+            if queued {
+                return;
+            }
+
+            let peripherals = unsafe { Peripherals::steal() };
+            if peripherals.CPU.sreg.read().i().bit_is_clear() {
+                while peripherals.USART0.ucsr0a.read().udre0().bit_is_clear() {}
+                interrupt::free(send_next);
+            }
+        }
+    }
+
+    /// Take the next received byte, if any
+    pub fn serial_read() -> Option<u8> {
+        interrupt::free(|critical_section| {
+            RX_BUFFER.borrow(critical_section).borrow_mut().pop_front()
+        })
+    }
+
+    /// Received bytes dropped because they weren't read in time
+    pub fn serial_dropped() -> u16 {
+        interrupt::free(|critical_section| RX_DROPPED.borrow(critical_section).get())
+    }
+
+    /// Move the next queued byte into the data register, or stop the data
+    /// register empty interrupt if there are none left. The data register
+    /// must be empty.
+    fn send_next(critical_section: interrupt::CriticalSection) {
+        let peripherals = unsafe { Peripherals::steal() };
+        match TX_BUFFER.borrow(critical_section).borrow_mut().pop_front() {
+            Some(byte) => peripherals.USART0.udr0.write(|w| w.bits(byte)),
+            None => peripherals
+                .USART0
+                .ucsr0b
+                .modify(|_, w| w.udrie0().clear_bit()),
+        }
+    }
+
+    #[avr_device::interrupt(atmega328p)]
+    #[allow(non_snake_case)]
+    fn USART_UDRE() {
+        interrupt::free(send_next)
+    }
+
+    #[avr_device::interrupt(atmega328p)]
+    #[allow(non_snake_case)]
+    fn USART_RX() {
+        interrupt::free(|critical_section| {
+            let peripherals = unsafe { Peripherals::steal() };
+            // Reading the data register clears the interrupt
+            let byte = peripherals.USART0.udr0.read().bits();
+            if RX_BUFFER
+                .borrow(critical_section)
+                .borrow_mut()
+                .push_back(byte)
+                .is_err()
+            {
+                let dropped = RX_DROPPED.borrow(critical_section);
+                dropped.set(dropped.get().saturating_add(1_u16));
+            }
+        })
+    }
+}
@@ -0,0 +1,51 @@
+//! The 16x2 character LCD, driven through a shift register
+
+use ag_lcd::LcdDisplay;
+use alarm_clock_core::hal::CharacterDisplay;
+use arduino_hal::delay_us;
+use core::convert::Infallible;
+use embedded_hal::{blocking::delay::DelayUs, digital::v2::OutputPin};
+
+/// Commands are given a moment to settle, as the shift register is slow
+const SETTLE_US: u32 = 100_u32;
+
+pub struct Lcd<T, D>
+where
+    T: OutputPin<Error = Infallible> + Sized,
+    D: DelayUs<u16> + Sized,
+{
+    display: LcdDisplay<T, D>,
+}
+impl<T, D> Lcd<T, D>
+where
+    T: OutputPin<Error = Infallible> + Sized,
+    D: DelayUs<u16> + Sized,
+{
+    pub fn new(display: LcdDisplay<T, D>) -> Self {
+        Self { display }
+    }
+}
+impl<T, D> CharacterDisplay for Lcd<T, D>
+where
+    T: OutputPin<Error = Infallible> + Sized,
+    D: DelayUs<u16> + Sized,
+{
+    fn clear(&mut self) {
+        self.display.clear();
+    }
+
+    fn set_position(&mut self, column: u8, row: u8) {
+        delay_us(SETTLE_US);
+        self.display.set_position(column, row);
+        delay_us(SETTLE_US);
+    }
+
+    fn print(&mut self, text: &str) {
+        self.display.print(text);
+    }
+
+    fn create_char(&mut self, code: u8, bitmap: [u8; 8]) {
+        self.display.set_character(code, bitmap);
+        delay_us(SETTLE_US);
+    }
+}
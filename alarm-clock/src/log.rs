@@ -0,0 +1,86 @@
+//! Logging with levels and per-module tags. The levels, tags, and filters are
+//! in `alarm_clock_core::log`; these are the macros that send the messages.
+//!
+//! Messages more verbose than `shared::MAX_LOG_LEVEL` are compiled out
+//! entirely. The rest are filtered at runtime by each tag's level, which can be
+//! changed from the shell.
+//!
+//! ```ignore
+//! debug!(Tag::Rtc, "Read time: {}", seconds);
+//! ```
+//! prints `[DEBUG] [RTC] Read time: 42`.
+//!
+//! With the `binary-log` feature, messages are sent as frames from
+//! `binary_log` instead and formatted on the host by `log-decoder`.
+
+pub use alarm_clock_core::log::*;
+
+#[cfg(not(feature = "binary-log"))]
+macro_rules! log {
+    ($level:expr, $tag:expr, $($t:tt)*) => {
+        if ($level as u8) <= (crate::shared::MAX_LOG_LEVEL as u8)
+            && crate::log::enabled($tag, $level)
+        {
+            crate::console::print!("[{}] [{}] ", $level.name(), $tag.name());
+            crate::console::println!($($t)*);
+        }
+    };
+}
+
+/// Sends the message's index in the dictionary and its arguments instead
+#[cfg(feature = "binary-log")]
+macro_rules! log {
+    ($level:expr, $tag:expr, $format:literal $(, $argument:expr)* $(,)?) => {
+        if ($level as u8) <= (crate::shared::MAX_LOG_LEVEL as u8)
+            && crate::log::enabled($tag, $level)
+        {
+            const MESSAGE: u16 =
+                match crate::binary_log::find_message(&crate::log_messages::MESSAGES, $format) {
+                    Some(message) => message,
+                    None => panic!("Log message missing from the dictionary"),
+                };
+            let mut frame = crate::binary_log::Frame::new(MESSAGE, $tag as u8, $level as u8);
+            $(crate::binary_log::Argument::encode(&$argument, &mut frame);)*
+            crate::console::write_bytes(frame.bytes());
+        }
+    };
+}
+
+macro_rules! error {
+    ($tag:expr, $($t:tt)*) => {
+        crate::log::log!(crate::log::Level::Error, $tag, $($t)*)
+    };
+}
+
+macro_rules! warning {
+    ($tag:expr, $($t:tt)*) => {
+        crate::log::log!(crate::log::Level::Warn, $tag, $($t)*)
+    };
+}
+
+#[allow(unused_macros)]
+macro_rules! info {
+    ($tag:expr, $($t:tt)*) => {
+        crate::log::log!(crate::log::Level::Info, $tag, $($t)*)
+    };
+}
+
+macro_rules! debug {
+    ($tag:expr, $($t:tt)*) => {
+        crate::log::log!(crate::log::Level::Debug, $tag, $($t)*)
+    };
+}
+
+macro_rules! trace {
+    ($tag:expr, $($t:tt)*) => {
+        crate::log::log!(crate::log::Level::Trace, $tag, $($t)*)
+    };
+}
+
+pub(crate) use debug;
+pub(crate) use error;
+#[allow(unused_imports)]
+pub(crate) use info;
+pub(crate) use log;
+pub(crate) use trace;
+pub(crate) use warning;
@@ -7,32 +7,77 @@
 #![feature(stmt_expr_attributes)]
 
 use ag_lcd::{Blink, Cursor, Display as LcdDisplayMode, LcdDisplay, Lines};
-use arduino_hal::{default_serial, delay_ms, delay_us, prelude::_void_ResultVoidExt, Delay, I2c};
+use alarm_clock_core::{
+    app::App,
+    calendar,
+    commands::{run_command, Reply, RequestReader},
+    hal::{Board, ClockSource},
+    history::History,
+    idle_screen::PAGE_NAMES,
+    settings::{Settings, MAX_ALARMS},
+    shell::{self, LineEditor, LineEvent, ParseError, HELP, PROMPT},
+    sound::EscalationProfile,
+    state::{
+        AlarmEvent, AlarmInputs, AlarmSound, DateSetState, Menu, OperationalMode, State,
+        TimeSetState,
+    },
+};
+use alarm_clock_protocol::{encode_packet, MAX_FRAME_LENGTH};
+use arduino_hal::{
+    default_serial, delay_ms, delay_us,
+    hal::wdt::{Timeout as WatchdogTimeout, Wdt},
+    prelude::_void_ResultVoidExt,
+    Delay, I2c,
+};
 use avr_device::{atmega328p::exint::pcicr::PCICR_SPEC, generic::Reg, interrupt};
-use console::{println, set_console};
+use chime::Chime;
+use console::{print, println, set_console, Console, TwoDigits};
+use controls::Controls;
 use core::{cell::RefCell, fmt::Write, marker::PhantomData};
+use eeprom::OnboardEeprom;
 use embedded_hal::digital::v2::OutputPin;
+use escalation::EscalatingPlayer;
 use heapless::String;
-use pins::{RotaryEncoderPins, ShiftRegisterPins};
+use interrupts::Buzzer;
+use lcd::Lcd;
+use melody::MelodyPlayer;
+use pins::RotaryEncoderPins;
+#[cfg(not(feature = "spi-displays"))]
+use pins::ShiftRegisterPins;
 use rotary_encoder::RotaryEncoder;
 use rtc::RTC;
-use shared::{Time, TimeDigits, UsbSerial};
-use shift_register::ShiftRegister;
+use shared::UsbSerial;
 use shift_register_driver::sipo::ShiftRegister8 as DecomposableShiftRegister;
 use snooze_button::SnoozeButton;
-use state::{DateSetState, Menu, OperationalMode, State, StateLogic, TimeSetState};
-use time_display::{Display as TimeDisplayTrait, HoursMinutes, Seconds};
+use time_display::{HoursMinutes, Seconds};
 use ufmt::uwriteln;
+#[cfg(feature = "spi-displays")]
+use {alarm_clock_core::shift_register::SpiShiftRegister, spi::Spi};
 
 use crate::{
-    console::debug,
     interrupts::millis,
+    log::{debug, warning, Tag},
     shared::{MILLIS_OVERFLOW_UPDATE_MARGIN, UPDATE_DELTATIME},
     time_display::{DIGITS, HOUR_MINUTE_DISPLAY},
 };
 
+// Decoding is only used by `log-decoder`
+#[cfg(feature = "binary-log")]
+#[allow(dead_code)]
+mod binary_log;
+mod chime;
 pub mod console;
+mod controls;
+mod eeprom;
+mod escalation;
 pub mod interrupts;
+mod lcd;
+mod log;
+#[cfg(feature = "binary-log")]
+mod log_messages {
+    include!(concat!(env!("OUT_DIR"), "/log_messages.rs"));
+}
+mod melody;
 pub mod panic;
 pub mod pins;
 mod rotary_encoder;
@@ -40,7 +85,8 @@ mod rtc;
 pub mod shared;
 pub mod shift_register;
 mod snooze_button;
-pub mod state;
+#[cfg(feature = "spi-displays")]
+mod spi;
 mod time_display;
 
 #[arduino_hal::entry]
@@ -49,24 +95,46 @@ fn main() -> ! {
     let pins = arduino_hal::pins!(peripherals);
     let mut serial: UsbSerial = default_serial!(peripherals, pins, shared::BAUD_RATE);
     set_console(serial);
+    #[cfg(feature = "binary-log")]
+    console::write_bytes(binary_log::Frame::dictionary(log_messages::HASH).bytes());
 
     let mut state = State::new();
 
     println!("Hello from the Alarm Clock!");
 
+    // Settings are loaded before anything uses them
+    debug!(Tag::Main, "Loading settings");
+    let mut eeprom = OnboardEeprom::new(peripherals.EEPROM);
+    let default_settings = state.settings();
+    match Settings::load(&eeprom, &default_settings) {
+        Ok(settings) => state.apply_settings(&settings),
+        Err(_) => {
+            println!("No valid settings saved, using defaults");
+            default_settings.store(&mut eeprom);
+        }
+    }
+    let mut history = History::load(&eeprom);
+    history.record_boot(&mut eeprom);
+    println!("Boot #{}", history.counters().boots);
+
     // Set up pin handles
+    #[cfg(not(feature = "spi-displays"))]
     let hours_minute_display_shift_register_pins = ShiftRegisterPins {
         serial_input: pins.d2.into_output() as pins::hours_minutes_display::SerialIn,
         clock: pins.d3.into_output() as pins::hours_minutes_display::Clock,
         latch: pins.d4.into_output() as pins::hours_minutes_display::Latch,
     };
+    #[cfg(not(feature = "spi-displays"))]
     let seconds_display_shift_register_pins = ShiftRegisterPins {
         serial_input: pins.d5.into_output() as pins::seconds_display::SerialIn,
         clock: pins.d6.into_output() as pins::seconds_display::Clock,
         latch: pins.d7.into_output() as pins::seconds_display::Latch,
     };
     let rotary_encoder_pins = RotaryEncoderPins {
+        #[cfg(not(feature = "spi-displays"))]
         a: pins.d13.into_pull_up_input(),
+        #[cfg(feature = "spi-displays")]
+        a: pins.d3.into_pull_up_input(),
         b: pins.a0.into_pull_up_input(),
         button: pins.d12.into_pull_up_input(),
     };
@@ -75,7 +143,18 @@ fn main() -> ! {
         clock: pins.d9.into_output() as pins::character_lcd::Clock,
         latch: pins.d10.into_output() as pins::character_lcd::Latch,
     };
+    #[cfg(not(feature = "spi-displays"))]
     let snooze_button_pin = pins.d11.into_pull_up_input() as pins::snooze::Button;
+    #[cfg(feature = "spi-displays")]
+    let snooze_button_pin = pins.d2.into_pull_up_input() as pins::snooze::Button;
+    // Both displays share the bus, each with its own latch
+    #[cfg(feature = "spi-displays")]
+    let display_spi = Spi::new(
+        peripherals.SPI,
+        pins.d13.into_output(),
+        pins.d11.into_output(),
+        &character_lcd_shift_register_pins.latch,
+    );
     let mut buzzer_pin = {
         let mut pin = pins.a3.into_output() as pins::buzzer::Buzzer;
         pin.set_low();
@@ -90,50 +169,52 @@ fn main() -> ! {
 
     // Intialize interrupts
     interrupts::millis_init(peripherals.TC0);
+    let mut buzzer = interrupts::tone_init(peripherals.TC2, &buzzer_pin);
     unsafe {
         interrupts::rotary_encoder_init(
-            &peripherals.EXINT.pcicr,
-            &peripherals.EXINT.pcmsk0,
+            &peripherals.EXINT,
             &rotary_encoder_pins.a,
             &rotary_encoder_pins.button,
         );
-        interrupts::snooze_button_init(
-            &peripherals.EXINT.pcicr,
-            &peripherals.EXINT.pcmsk0,
-            &snooze_button_pin,
-        );
+        interrupts::snooze_button_init(&peripherals.EXINT, &snooze_button_pin);
     };
     unsafe { avr_device::interrupt::enable() };
 
     // Time initialization
-    debug!("[DEBUG] I2C & RTC initialization");
+    debug!(Tag::Main, "I2C & RTC initialization");
     let mut i2c = I2c::new(peripherals.TWI, iic_pins.sda, iic_pins.scl, 1);
-    let mut rtc = RTC::new(i2c);
+    let rtc = RTC::new(i2c);
 
     // Display initialization
-    debug!("[DEBUG] Hours & minutes display initialization");
+    debug!(Tag::Main, "Hours & minutes display initialization");
 
     // Plop the hours minute display to the global so the interrupt handler can access it
     interrupt::free(|critical_section| {
-        let hours_minutes_display = HoursMinutes::new(
-            ShiftRegister::<16_usize, _, _, _>::from_pins(hours_minute_display_shift_register_pins),
-        );
+        #[cfg(not(feature = "spi-displays"))]
+        let shift_register =
+            shift_register::hours_minutes(hours_minute_display_shift_register_pins);
+        #[cfg(feature = "spi-displays")]
+        let shift_register = SpiShiftRegister::new(display_spi, pins.d4.into_output());
+        let hours_minutes_display = HoursMinutes::new(shift_register);
         HOUR_MINUTE_DISPLAY
             .borrow(critical_section)
             .replace(Some(hours_minutes_display));
     });
-    debug!("[DEBUG] Seconds display initialization");
-    let mut seconds_display = Seconds::new(ShiftRegister::<{ 2 * 8_usize }, _, _, _>::from_pins(
-        seconds_display_shift_register_pins,
-    ));
-    debug!("[DEBUG] Character LCD shift register initialization");
+    set_brightness(state.brightness);
+    debug!(Tag::Main, "Seconds display initialization");
+    #[cfg(not(feature = "spi-displays"))]
+    let seconds_display =
+        Seconds::new(shift_register::seconds(seconds_display_shift_register_pins));
+    #[cfg(feature = "spi-displays")]
+    let seconds_display = Seconds::new(SpiShiftRegister::new(display_spi, pins.d7.into_output()));
+    debug!(Tag::Main, "Character LCD shift register initialization");
     let mut character_lcd_shift_register = DecomposableShiftRegister::new(
         character_lcd_shift_register_pins.clock,
         character_lcd_shift_register_pins.latch,
         character_lcd_shift_register_pins.serial_input,
     );
     let mut character_lcd_pins = character_lcd_shift_register.decompose();
-    let mut character_lcd: LcdDisplay<_, _> = match character_lcd_pins {
+    let character_lcd: LcdDisplay<_, _> = match character_lcd_pins {
         // Refer to KiCad schematic for pin layout
         [_, rs, _, enabled, db4, db5, db6, db7] => {
             LcdDisplay::new(rs, enabled, arduino_hal::Delay::new())
@@ -146,73 +227,362 @@ fn main() -> ! {
     };
 
     // Controls initialization
-    debug!("[DEBUG] Rotary encoder initialization");
-    let mut rotary_encoder = RotaryEncoder::from_pins(rotary_encoder_pins);
-    debug!("[DEBUG] Snooze button initialization");
-    let mut snooze_button = SnoozeButton::new(snooze_button_pin);
+    debug!(Tag::Main, "Rotary encoder initialization");
+    let rotary_encoder = RotaryEncoder::from_pins(rotary_encoder_pins);
+    debug!(Tag::Main, "Snooze button initialization");
+    let snooze_button = SnoozeButton::new(snooze_button_pin);
 
-    interrupt::free(|critical_section| {
-        rtc.set_time(&state.time, &critical_section);
-    });
+    // Sound initialization
+    debug!(Tag::Main, "Melody player initialization");
+    let mut melody_player = MelodyPlayer::new();
+    let mut escalating_player = EscalatingPlayer::new();
+    let mut chime = Chime::new();
+
+    debug!(Tag::Main, "Shell initialization");
+    let mut line_editor = LineEditor::new();
+    let mut requests = RequestReader::new();
+    print!("{}", PROMPT);
+
+    let mut app = App::new(
+        state,
+        rtc,
+        seconds_display,
+        Lcd::new(character_lcd),
+        Controls::new(rotary_encoder, snooze_button),
+    );
+    // The RTC keeps the time through resets and on its backup battery, so it's
+    // only set if that time was lost
+    if app.clock.oscillator_stopped() || !app.read_time() {
+        warning!(Tag::Rtc, "RTC time lost, setting it");
+        app.clock.set_time(&app.state.time);
+    }
 
     // Main loop
     loop {
-        delay_ms(UPDATE_DELTATIME);
-        debug!("[DEBUG] Loop iteration");
-
-        // Update time
-        if let Some(new_time) = rtc.read_time(&mut state.digits) {
-            state.time = new_time;
+        // The sound players need to be updated as often as possible
+        let now = millis();
+        melody_player.update(now, &mut buzzer);
+        escalating_player.update(now, &mut buzzer, &mut alarm_led_pin);
+        chime.update(now, &mut buzzer);
+        // So is the shell, as the UART only holds a couple of bytes. Host tools
+        // send framed requests, which start with a delimiter the shell never
+        // sees.
+        if let Some(byte) = console::read_byte() {
+            if requests.wants(byte) {
+                if let Some(response) = requests.push(
+                    byte,
+                    &mut app.state,
+                    &mut app.clock,
+                    &mut eeprom,
+                    &mut history,
+                    &mut Uno,
+                ) {
+                    let mut out = [0_u8; MAX_FRAME_LENGTH];
+                    if let Ok(frame) = encode_packet(response.id, &response.message, &mut out) {
+                        console::write_bytes(frame);
+                    }
+                }
+                continue;
+            }
+            match line_editor.push(byte) {
+                LineEvent::Nothing => (),
+                LineEvent::Echo(byte) => print!("{}", byte as char),
+                LineEvent::Erase => print!("\x08 \x08"),
+                LineEvent::Clear(length) => {
+                    for _ in 0_usize..length {
+                        print!("\x08 \x08");
+                    }
+                }
+                LineEvent::Cancel => print!("^C\n{}", PROMPT),
+                LineEvent::Submit => {
+                    println!("");
+                    match shell::parse(line_editor.line()) {
+                        Ok(command) => {
+                            let reply = run_command(
+                                command,
+                                &mut app.state,
+                                &mut app.clock,
+                                &mut eeprom,
+                                &mut history,
+                                &mut Uno,
+                            );
+                            print_reply(reply, &app.state, &history, &app.seconds);
+                        }
+                        Err(ParseError::Empty) => (),
+                        Err(error) => println!("Error: {}", error.as_str()),
+                    }
+                    line_editor.clear();
+                    print!("{}", PROMPT);
+                }
+            }
+        }
+        if (now.wrapping_sub(app.state.next_update) as i32) < 0_i32 {
+            continue;
         }
+        app.state.next_update = now.wrapping_add(UPDATE_DELTATIME as u32);
+        debug!(Tag::Main, "Loop iteration");
+
+        let alarm_event = app.update(now);
         interrupt::free(|critical_section| {
             DIGITS
                 .borrow(critical_section)
-                .replace(state.digits.clone());
+                .replace(app.state.digits.clone());
         });
 
-        character_lcd.clear();
-        character_lcd.set_position(0, 0);
-        delay_us(100_u32);
-        character_lcd.print("alarmed clock");
-        delay_us(100_u32);
-        character_lcd.set_position(0, 1);
-        delay_us(100_u32);
-        let mut buf = [0_u8; 4];
-        character_lcd.print(
-            char::from_digit(state.digits.hours.0 as u32, 10_u32)
-                .unwrap()
-                .encode_utf8(&mut buf),
-        );
-        character_lcd.print(
-            char::from_digit(state.digits.hours.1 as u32, 10_u32)
-                .unwrap()
-                .encode_utf8(&mut buf),
-        );
-        character_lcd.print(":");
-        character_lcd.print(
-            char::from_digit(state.digits.minutes.0 as u32, 10_u32)
-                .unwrap()
-                .encode_utf8(&mut buf),
-        );
-        character_lcd.print(
-            char::from_digit(state.digits.minutes.1 as u32, 10_u32)
-                .unwrap()
-                .encode_utf8(&mut buf),
-        );
-        character_lcd.print(":");
-        character_lcd.print(
-            char::from_digit(state.digits.seconds.0 as u32, 10_u32)
-                .unwrap()
-                .encode_utf8(&mut buf),
-        );
-        character_lcd.print(
-            char::from_digit(state.digits.seconds.1 as u32, 10_u32)
-                .unwrap()
-                .encode_utf8(&mut buf),
-        );
+        // Alarm
+        match alarm_event {
+            Some(AlarmEvent::Started(sound)) => {
+                println!("Alarm!");
+                start_alarm_sound(
+                    sound,
+                    app.state.custom_escalation,
+                    now,
+                    &mut melody_player,
+                    &mut escalating_player,
+                    &mut buzzer,
+                );
+            }
+            Some(AlarmEvent::SnoozeOver(sound)) => {
+                debug!(Tag::Main, "Snooze over");
+                start_alarm_sound(
+                    sound,
+                    app.state.custom_escalation,
+                    now,
+                    &mut melody_player,
+                    &mut escalating_player,
+                    &mut buzzer,
+                );
+            }
+            Some(AlarmEvent::Dismissed) => {
+                debug!(Tag::Main, "Alarm dismissed");
+                melody_player.stop(&mut buzzer);
+                escalating_player.stop(&mut buzzer, &mut alarm_led_pin);
+            }
+            Some(AlarmEvent::Snoozed) => {
+                debug!(Tag::Main, "Alarm snoozed");
+                history.record_snooze(&mut eeprom);
+                melody_player.stop(&mut buzzer);
+                escalating_player.stop(&mut buzzer, &mut alarm_led_pin);
+            }
+            Some(AlarmEvent::RangOut(missed_alarm)) => {
+                println!("Alarm rang out, nobody home?");
+                history.record_missed_alarm(&mut eeprom, &missed_alarm);
+                melody_player.stop(&mut buzzer);
+                escalating_player.stop(&mut buzzer, &mut alarm_led_pin);
+            }
+            Some(AlarmEvent::MissedAlarmAcknowledged) => {
+                debug!(Tag::Main, "Missed alarm acknowledged");
+            }
+            None => (),
+        }
+
+        // Chime, but never over the alarm or while a menu is being edited
+        match app.state.mode {
+            OperationalMode::Idle => {
+                chime.check(&app.state.time, &app.state.chime, now, &mut buzzer)
+            }
+            _ => chime.stop(&mut buzzer),
+        }
+    }
+}
 
-        rotary_encoder.update();
-        snooze_button.update();
-        seconds_display.display(&state);
+fn start_alarm_sound(
+    alarm_sound: AlarmSound,
+    custom_escalation: EscalationProfile,
+    now: u32,
+    melody_player: &mut MelodyPlayer,
+    escalating_player: &mut EscalatingPlayer,
+    buzzer: &mut Buzzer,
+) {
+    match alarm_sound {
+        AlarmSound::Melody(tune) => melody_player.play(tune, now, buzzer),
+        AlarmSound::Escalating(profile) => escalating_player.play(profile, now),
+        AlarmSound::CustomEscalation => escalating_player.play(custom_escalation, now),
+    }
+}
+
+/// Set the hours and minutes display brightness from [1, 8]
+fn set_brightness(brightness: u8) {
+    interrupt::free(|critical_section| {
+        if let Some(display) = HOUR_MINUTE_DISPLAY
+            .borrow(critical_section)
+            .borrow_mut()
+            .as_mut()
+        {
+            display.set_brightness(brightness);
+        }
+    });
+}
+
+/// The rest of the board, for the shell and host tools
+struct Uno;
+impl Board for Uno {
+    fn millis(&self) -> u32 {
+        millis()
+    }
+
+    fn serial_dropped(&self) -> u16 {
+        interrupts::serial_dropped()
+    }
+
+    fn set_brightness(&mut self, brightness: u8) {
+        set_brightness(brightness);
+    }
+}
+
+/// Reset the clock by letting the watchdog time out
+fn reset() -> ! {
+    // Safety: Nothing else uses the watchdog
+    let peripherals = unsafe { arduino_hal::Peripherals::steal() };
+    let mut watchdog = Wdt::new(peripherals.WDT, &peripherals.CPU.mcusr);
+    let _ = watchdog.start(WatchdogTimeout::Ms16);
+    loop {}
+}
+
+fn on_off(on: bool) -> &'static str {
+    match on {
+        true => "on",
+        false => "off",
+    }
+}
+
+/// Print the reply to a command from the shell
+fn print_reply(reply: Reply, state: &State, history: &History, seconds: &Seconds) {
+    match reply {
+        Reply::Help => println!("{}", HELP),
+        Reply::Time(time) => println!(
+            "{}:{}:{}",
+            TwoDigits(time.hours),
+            TwoDigits(time.minutes),
+            TwoDigits(time.seconds)
+        ),
+        Reply::TimeSet => println!("Time set"),
+        Reply::Date(time) => println!(
+            "20{}-{}-{} {}",
+            TwoDigits(time.year),
+            TwoDigits(time.month),
+            TwoDigits(time.day),
+            calendar::DAY_NAMES[(time.day_of_week % 7_u8) as usize]
+        ),
+        Reply::DateSet => println!("Date set"),
+        Reply::Sync(now) => println!(
+            "SYNC 20{}-{}-{} {}:{}:{}",
+            TwoDigits(now.year),
+            TwoDigits(now.month),
+            TwoDigits(now.day),
+            TwoDigits(now.hours),
+            TwoDigits(now.minutes),
+            TwoDigits(now.seconds)
+        ),
+        Reply::AlarmList => {
+            println!("Alarms are {}", on_off(state.alarm_enabled));
+            for (n, alarm) in state.alarms.iter().enumerate() {
+                if let Some(alarm) = alarm {
+                    println!(
+                        "{}: {}:{} {} {}",
+                        n,
+                        TwoDigits(alarm.hours),
+                        TwoDigits(alarm.minutes),
+                        on_off(alarm.enabled),
+                        alarm.sound.name()
+                    );
+                }
+            }
+        }
+        Reply::AlarmAdded(n) => println!("Added alarm {}", n),
+        Reply::AlarmDeleted(n) => println!("Deleted alarm {}", n),
+        Reply::AlarmsEnabled(enabled) => println!("Alarms are {}", on_off(enabled)),
+        Reply::AlarmEnabled { alarm, enabled } => {
+            println!("Alarm {} is {}", alarm, on_off(enabled))
+        }
+        Reply::Status => {
+            println!(
+                "Mode: {}",
+                match state.mode {
+                    OperationalMode::Idle => "idle",
+                    OperationalMode::Alarm => "alarm",
+                    OperationalMode::Snoozed => "snoozed",
+                    _ => "setting",
+                }
+            );
+            println!(
+                "Alarms: {}, {} set",
+                on_off(state.alarm_enabled),
+                state.alarms.iter().flatten().count()
+            );
+            let custom = &state.custom_escalation;
+            println!(
+                "Custom escalation: {} s, {} to {} Hz",
+                custom.ramp_seconds, custom.start_frequency, custom.end_frequency
+            );
+            println!("Brightness: {}", state.brightness);
+            print!("Seconds display: ");
+            let _ = seconds.expected_outputs().write_to(&mut Console);
+            println!("");
+            match state.rtc_battery_low {
+                true => println!("RTC battery: low"),
+                false => println!("RTC battery: ok"),
+            }
+            let counters = history.counters();
+            println!("Boots: {}, snoozes: {}", counters.boots, counters.snoozes);
+            println!("Serial bytes dropped: {}", interrupts::serial_dropped());
+            println!(
+                "Display update: up to {}us of {}us",
+                interrupts::display_time_us(),
+                interrupts::MILLIS_PERIOD_US
+            );
+            match counters.last_sync {
+                Some(sync) => println!(
+                    "Last sync: 20{}-{}-{} {}:{}",
+                    TwoDigits(sync.year),
+                    TwoDigits(sync.month),
+                    TwoDigits(sync.day),
+                    TwoDigits(sync.hours),
+                    TwoDigits(sync.minutes)
+                ),
+                None => println!("Last sync: never"),
+            }
+            if let Some(missed_alarm) = state.missed_alarm {
+                println!(
+                    "Missed alarm at {}:{} after {} snoozes",
+                    TwoDigits(missed_alarm.time.hours),
+                    TwoDigits(missed_alarm.time.minutes),
+                    missed_alarm.snoozes
+                );
+            }
+        }
+        Reply::Brightness(brightness) => println!("Brightness: {}", brightness),
+        Reply::Screen(screen) => {
+            print!("Pages:");
+            for (page, name) in PAGE_NAMES.iter().enumerate() {
+                if screen.pages & 1_u8 << page != 0_u8 {
+                    print!(" {}", *name);
+                }
+            }
+            println!("");
+            println!("Date format: {}", screen.date_format.name());
+            println!("Rotate every {}s", screen.page_seconds);
+        }
+        Reply::LogList => {
+            for tag in Tag::ALL {
+                println!("{}: {}", tag.name(), log::filter(tag).name());
+            }
+        }
+        Reply::LogSet(level) => {
+            if level as u8 > shared::MAX_LOG_LEVEL as u8 {
+                println!(
+                    "Note: only messages up to {} are compiled in",
+                    shared::MAX_LOG_LEVEL.name()
+                );
+            }
+        }
+        Reply::LogSaved => println!("Log levels saved"),
+        Reply::Reset => {
+            println!("Resetting");
+            reset();
+        }
+        Reply::UnknownSound => println!("Error: unknown sound, see `help`"),
+        Reply::AlarmsFull => println!("Error: all {} alarms are set", MAX_ALARMS),
+        Reply::NoAlarm(n) => println!("Error: no alarm {}", n),
     }
 }
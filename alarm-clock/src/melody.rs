@@ -0,0 +1,159 @@
+//! The built-in alarm tunes' data and a non-blocking player for them
+//!
+//! The tunes (see `alarm_clock_core::sound::Tune`) are RTTTL strings stored in
+//! program memory and are parsed a byte
+//! at a time as they are played. The player should be updated as often as
+//! possible from the main loop; the actual square wave is generated by the
+//! tone interrupt (see `interrupts.rs`).
+
+use alarm_clock_core::{hal::ToneOutput, rtttl::Rtttl, sound::Tune};
+use avr_progmem::{progmem, wrapper::ProgMem};
+
+use crate::log::{debug, error, Tag};
+
+/// Every tune is padded with NULs to this length so they all share one type
+const TUNE_LENGTH: usize = 256_usize;
+/// Silence between notes so repeated notes don't blur together
+const NOTE_GAP_MS: u16 = 15_u16;
+/// Silence before a tune loops around
+const REPEAT_GAP_MS: u16 = 1_000_u16;
+
+const fn pad(tune: &[u8]) -> [u8; TUNE_LENGTH] {
+    let mut padded = [0_u8; TUNE_LENGTH];
+    let mut idx = 0_usize;
+    while idx < tune.len() {
+        padded[idx] = tune[idx];
+        idx += 1_usize;
+    }
+    padded
+}
+
+progmem! {
+    static progmem NOKIA: [u8; TUNE_LENGTH] = pad(
+        b"Nokia:d=4,o=5,b=225:8e6,8d6,f#,g#,8c#6,8b,d,e,8b,8a,c#,e,2a",
+    );
+    static progmem SIMPSONS: [u8; TUNE_LENGTH] = pad(
+        b"The Simpsons:d=4,o=5,b=160:c.6,e6,f#6,8a6,g.6,e6,c6,8a,8f#,8f#,8f#,2g,8p,8p,8f#,8f#,8f#,8g,a#.,8c6,8c6,8c6,c6",
+    );
+    static progmem ENTERTAINER: [u8; TUNE_LENGTH] = pad(
+        b"Entertainer:d=4,o=5,b=140:8d,8d#,8e,c6,8e,c6,8e,2c.6,8c6,8d6,8d#6,8e6,8c6,8d6,e6,8b,d6,2c6,p,8d,8d#,8e,c6,8e,c6,8e,2c.6,8p,8a,8g,8f#,8a,8c6,e6,8d6,8c6,8a,2d6",
+    );
+    static progmem TETRIS: [u8; TUNE_LENGTH] = pad(
+        b"Tetris:d=4,o=5,b=160:e6,8b,8c6,8d6,16e6,16d6,8c6,8b,a,8a,8c6,e6,8d6,8c6,b,8b,8c6,d6,e6,c6,a,2a,8p,d6,8f6,a6,8g6,8f6,e6,8e6,8c6,e6,8d6,8c6,b,8b,8c6,d6,e6,c6,a,a",
+    );
+}
+
+fn progmem(tune: Tune) -> &'static ProgMem<[u8; TUNE_LENGTH]> {
+    match tune {
+        Tune::Nokia => &NOKIA,
+        Tune::Simpsons => &SIMPSONS,
+        Tune::Entertainer => &ENTERTAINER,
+        Tune::Tetris => &TETRIS,
+    }
+}
+
+/// Loads a tune out of program memory one byte at a time, stopping at the padding
+pub struct TuneBytes {
+    tune: Tune,
+    idx: usize,
+}
+impl Iterator for TuneBytes {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        if self.idx >= TUNE_LENGTH {
+            return None;
+        }
+        match progmem(self.tune).load_at(self.idx) {
+            0_u8 => None,
+            byte => {
+                self.idx += 1_usize;
+                Some(byte)
+            }
+        }
+    }
+}
+
+enum Phase {
+    /// A note (or pause) is sounding until the deadline
+    Note,
+    /// The gap after a note, after which the next note is fetched
+    Gap,
+}
+
+pub struct MelodyPlayer {
+    tune: Tune,
+    rtttl: Option<Rtttl<TuneBytes>>,
+    phase: Phase,
+    /// Millis when the current phase ends
+    deadline: u32,
+}
+impl MelodyPlayer {
+    pub fn new() -> Self {
+        Self {
+            tune: Tune::default(),
+            rtttl: None,
+            phase: Phase::Gap,
+            deadline: 0_u32,
+        }
+    }
+
+    /// Start playing a tune from the beginning, looping it until stopped
+    pub fn play(&mut self, tune: Tune, now: u32, buzzer: &mut impl ToneOutput) {
+        debug!(Tag::Melody, "Playing {}", tune.name());
+        self.tune = tune;
+        self.restart(now, buzzer);
+    }
+
+    pub fn stop(&mut self, buzzer: &mut impl ToneOutput) {
+        self.rtttl = None;
+        buzzer.set_tone(None);
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.rtttl.is_some()
+    }
+
+    /// Advance to the next note if the current one is done. Non-blocking.
+    pub fn update(&mut self, now: u32, buzzer: &mut impl ToneOutput) {
+        // Wrapping comparison so the millis overflow doesn't stall the tune
+        if self.rtttl.is_none() || (now.wrapping_sub(self.deadline) as i32) < 0_i32 {
+            return;
+        }
+
+        match self.phase {
+            Phase::Note => {
+                buzzer.set_tone(None);
+                self.phase = Phase::Gap;
+                self.deadline = now.wrapping_add(NOTE_GAP_MS as u32);
+            }
+            Phase::Gap => match self.rtttl.as_mut().and_then(|rtttl| rtttl.next()) {
+                Some(Ok(note)) => {
+                    buzzer.set_tone(note.frequency);
+                    self.phase = Phase::Note;
+                    self.deadline = now.wrapping_add(note.duration_ms as u32);
+                }
+                Some(Err(_)) => {
+                    error!(Tag::Melody, "Bad note in tune {}", self.tune.name());
+                    self.stop(buzzer);
+                }
+                None => {
+                    self.restart(now, buzzer);
+                    self.deadline = now.wrapping_add(REPEAT_GAP_MS as u32);
+                }
+            },
+        }
+    }
+
+    fn restart(&mut self, now: u32, buzzer: &mut impl ToneOutput) {
+        self.phase = Phase::Gap;
+        self.deadline = now;
+        self.rtttl = Rtttl::new(TuneBytes {
+            tune: self.tune,
+            idx: 0_usize,
+        })
+        .map_err(|_| error!(Tag::Melody, "Bad header in tune {}", self.tune.name()))
+        .ok();
+        buzzer.set_tone(None);
+    }
+}
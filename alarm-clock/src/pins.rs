@@ -1,4 +1,20 @@
 //! Pin types to improve type safety
+//!
+//! With the `spi-displays` feature, the time displays' shift registers are
+//! shifted by the hardware SPI instead, so they're rewired:
+//!
+//! | Pin | Default                      | `spi-displays`                       |
+//! |-----|------------------------------|--------------------------------------|
+//! | D2  | Hours & minutes serial input | Snooze button                        |
+//! | D3  | Hours & minutes clock        | Rotary encoder A                     |
+//! | D5  | Seconds serial input         | (unused)                             |
+//! | D6  | Seconds clock                | (unused)                             |
+//! | D11 | Snooze button                | MOSI, to both displays' serial input |
+//! | D13 | Rotary encoder A             | SCK, to both displays' clock         |
+//!
+//! D12 (MISO) stays the rotary button, as the SPI only reads it. D10 (SS) has
+//! to be an output for the SPI to stay the master, which it is as the
+//! character LCD's latch.
 
 use arduino_hal::{
     hal::port,
@@ -11,18 +27,29 @@ use embedded_hal::digital::v2::{InputPin, OutputPin};
 
 pub mod hours_minutes_display {
     use super::*;
+    #[cfg(not(feature = "spi-displays"))]
     pub type SerialIn = Pin<Output, port::PD2>;
+    #[cfg(not(feature = "spi-displays"))]
     pub type Clock = Pin<Output, port::PD3>;
     pub type Latch = Pin<Output, port::PD4>;
 }
 
 pub mod seconds_display {
     use super::*;
+    #[cfg(not(feature = "spi-displays"))]
     pub type SerialIn = Pin<Output, port::PD5>;
+    #[cfg(not(feature = "spi-displays"))]
     pub type Clock = Pin<Output, port::PD6>;
     pub type Latch = Pin<Output, port::PD7>;
 }
 
+#[cfg(feature = "spi-displays")]
+pub mod spi {
+    use super::*;
+    pub type Mosi = Pin<Output, port::PB3>;
+    pub type Sck = Pin<Output, port::PB5>;
+}
+
 pub mod character_lcd {
     use super::*;
     pub type SerialIn = Pin<Output, port::PB0>;
@@ -32,14 +59,20 @@ pub mod character_lcd {
 
 pub mod rotary_encoder {
     use super::*;
+    #[cfg(not(feature = "spi-displays"))]
     pub type A = Pin<Input<PullUp>, port::PB5>;
+    #[cfg(feature = "spi-displays")]
+    pub type A = Pin<Input<PullUp>, port::PD3>;
     pub type B = Pin<Input<PullUp>, port::PC0>;
     pub type Button = Pin<Input<PullUp>, port::PB4>;
 }
 
 pub mod snooze {
     use super::*;
+    #[cfg(not(feature = "spi-displays"))]
     pub type Button = Pin<Input<PullUp>, port::PB3>;
+    #[cfg(feature = "spi-displays")]
+    pub type Button = Pin<Input<PullUp>, port::PD2>;
 }
 
 pub mod buzzer {
@@ -3,15 +3,14 @@
 //! See the rotary encoder interrupt in `interrupts.rs` as that is where the encoder
 //! state is set.
 
+use alarm_clock_core::hal::{Direction, InputEvent};
+
 use crate::{
-    console::{debug, println},
-    interrupts::{changed_state, get_rotary_encoder_state, RotaryEncoderState},
+    console::println,
+    interrupts::{get_rotary_encoder_state, RotaryEncoderState},
+    log::{debug, Tag},
     pins::{self, RotaryEncoderPins},
-    shared::{
-        PinState::{PinState, HIGH, LOW},
-        TimeDigits,
-    },
-    shift_register::ShiftRegister,
+    shared::PinState::{PinState, HIGH, LOW},
 };
 use arduino_hal::{
     hal::port::{self, Dynamic},
@@ -29,6 +28,8 @@ pub struct RotaryEncoder {
     button: pins::rotary_encoder::Button,
     state: RotaryEncoderState,
     pub changed: bool,
+    /// Whether the last change moved either phase (rather than the button)
+    turned: bool,
 }
 impl RotaryEncoder {
     pub fn new(
@@ -41,6 +42,7 @@ impl RotaryEncoder {
             b,
             button,
             changed: false,
+            turned: false,
             state: RotaryEncoderState {
                 a: false,
                 b: false,
@@ -57,20 +59,23 @@ impl RotaryEncoder {
         )
     }
 
-    pub fn update(&mut self) {
+    /// `changed` is whether the pin change interrupt ran since the last update
+    pub fn update(&mut self, changed: bool) {
         let state = get_rotary_encoder_state();
         // Only detect rotary encoder changes, ignore snooze press
-        self.changed = changed_state()
+        self.changed = changed
             && (self.state.a != state.a
                 || self.state.b != state.b
                 || self.state.button != state.button);
         debug!(
-            "[DEBUG] [ROT ENC] Rotary encoder update, changed: {}",
+            Tag::RotEnc,
+            "Rotary encoder update, changed: {}",
             match self.changed {
                 true => "YES",
                 false => "NO",
             }
         );
+        self.turned = self.state.a != state.a || self.state.b != state.b;
         self.state = state;
     }
 
@@ -85,4 +90,20 @@ impl RotaryEncoder {
         self.changed = false;
         ret
     }
+
+    /// What the last update changed, if anything (and not yet read)
+    pub fn event(&mut self) -> Option<InputEvent> {
+        if !core::mem::take(&mut self.changed) {
+            return None;
+        }
+        match (self.state.button, self.turned) {
+            (true, _) => Some(InputEvent::Push),
+            (false, true) if self.state.a != self.state.b => {
+                Some(InputEvent::Turn(Direction::Clockwise))
+            }
+            (false, true) => Some(InputEvent::Turn(Direction::CounterClockwise)),
+            // Released
+            (false, false) => None,
+        }
+    }
 }
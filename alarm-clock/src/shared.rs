@@ -8,8 +8,11 @@ use arduino_hal::{
     Usart,
 };
 
-pub const DEBUG: bool = false;
-pub const TRACE: bool = false;
+use crate::log::Level;
+
+/// Log messages more verbose than this are compiled out. Keep trace messages
+/// out unless needed, as some are logged from interrupts.
+pub const MAX_LOG_LEVEL: Level = Level::Debug;
 pub const BAUD_RATE: u32 = 57_600_u32;
 pub const UPDATE_DELTATIME: u16 = 100_u16;
 /// At the expense of waiting a bit longer at start time, we can ensure that
@@ -23,49 +26,3 @@ pub mod PinState {
     pub const LOW: bool = false;
     pub const HIGH: bool = true;
 }
-
-#[derive(Clone)]
-pub struct TimeDigits {
-    pub hours: (u8, u8),
-    pub minutes: (u8, u8),
-    pub seconds: (u8, u8),
-}
-impl Default for TimeDigits {
-    fn default() -> Self {
-        Self {
-            hours: (0_u8, 0_u8),
-            minutes: (0_u8, 0_u8),
-            seconds: (0_u8, 0_u8),
-        }
-    }
-}
-
-/// Time, as reported from the realtime clock
-pub struct Time {
-    /// Ranges from [0, 23]
-    pub hours: u8,
-    /// Ranges from [0, 59]
-    pub minutes: u8,
-    /// Ranges from [0, 59]
-    pub seconds: u8,
-    /// Ranges from [1, 31]
-    pub day: u8,
-    /// The day of the week from [0, 6] where 0 is Sunday and 6 is Saturday
-    pub day_of_week: u8,
-    pub month: u8,
-    /// The year from 20[00-99] (Y2.1K!)
-    pub year: u8,
-}
-impl Default for Time {
-    fn default() -> Self {
-        Self {
-            hours: 5_u8,
-            minutes: 0_u8,
-            seconds: 0_u8,
-            day: 1_u8,
-            day_of_week: 0_u8,
-            month: 1_u8,
-            year: 23_u8,
-        }
-    }
-}
@@ -1,128 +1,72 @@
-//! An arbitrarily-lengthed latching shift register that supports updating all
-//! output drains at once, unlike the `shift-register-driver` crate (see issue #1
-//! of their crate)
+//! The time displays' shift registers, driven by
+//! `alarm_clock_core::shift_register`: bit-banged by writing PORTD directly,
+//! or through the hardware SPI with the `spi-displays` feature
 
-use core::{
-    cell::RefCell,
-    mem::{self, MaybeUninit},
-    ptr,
-    sync::atomic::{AtomicBool, Ordering::SeqCst},
-};
+#[cfg(feature = "spi-displays")]
+use alarm_clock_core::shift_register::SpiShiftRegister;
+#[cfg(not(feature = "spi-displays"))]
+use alarm_clock_core::{hal::OutputPort, shift_register::PortShiftRegister};
+#[cfg(not(feature = "spi-displays"))]
+use arduino_hal::pac::PORTD;
 
-use crate::{
-    console::{debug, trace},
-    pins::ShiftRegisterPins,
-    shared::PinState::{PinState, HIGH, LOW},
-};
-use arduino_hal::{
-    delay_us,
-    hal::port::Dynamic,
-    port::{mode::Output, Pin},
-};
-use avr_device::interrupt::{free as interrupt_free, CriticalSection};
-use embedded_hal::digital::v2::OutputPin;
+use crate::pins;
+#[cfg(not(feature = "spi-displays"))]
+use crate::pins::ShiftRegisterPins;
+#[cfg(feature = "spi-displays")]
+use crate::spi::Spi;
 
-// Defined on page 4 of https://www.ti.com/lit/ds/symlink/tpic6595.pdf
-// The serial input width is 20ns (Tsu + Th) so we don't need to explicitly account for it
-const SERIAL_RISING_EDGE_PADDING_NS: u32 = 10_u32; // Tsu
-const SERIAL_FALLING_EDGE_PADDING_NS: u32 = SERIAL_RISING_EDGE_PADDING_NS; // Th
-const CLOCK_WIDTH_NS: u32 = 20_u32; // Tw
-const LATCH_WIDTH_NS: u32 = CLOCK_WIDTH_NS;
-
-/// Generic shift register with automatic software latching for every N bits
-pub struct ShiftRegister<const N: usize, SerialInput, Clock, Latch>
-where
-    SerialInput: OutputPin,
-    Clock: OutputPin,
-    Latch: OutputPin,
-{
-    pub serial_input_pin: SerialInput,
-    pub clock_pin: Clock,
-    pub latch_pin: Latch,
-    pub is_latched: bool,
-    current_shifted_bit: usize,
-    bit_array: [PinState; N],
+/// PORTD, which both time displays' pins are on. Every copy is the same
+/// register, and writes aren't atomic, so it must only be written with
+/// interrupts off (the millisecond interrupt writes it too).
+#[cfg(not(feature = "spi-displays"))]
+#[derive(Clone, Copy)]
+pub struct PortD {
+    _private: (),
 }
-impl<const N: usize, SerialInput, Clock, Latch> ShiftRegister<N, SerialInput, Clock, Latch>
-where
-    SerialInput: OutputPin,
-    Clock: OutputPin,
-    Latch: OutputPin,
-{
-    pub fn new(serial_input_pin: SerialInput, clock_pin: Clock, latch_pin: Latch) -> Self {
-        Self {
-            serial_input_pin,
-            clock_pin,
-            latch_pin,
-            is_latched: false,
-            bit_array: [LOW; N],
-            current_shifted_bit: 0_usize,
-        }
-    }
-
-    pub fn from_pins(shift_register_pins: ShiftRegisterPins<SerialInput, Clock, Latch>) -> Self {
-        Self::new(
-            shift_register_pins.serial_input,
-            shift_register_pins.clock,
-            shift_register_pins.latch,
-        )
+#[cfg(not(feature = "spi-displays"))]
+impl OutputPort for PortD {
+    fn read(&self) -> u8 {
+        unsafe { &*PORTD::ptr() }.portd.read().bits()
     }
 
-    /// Shift a bit out. If the bit shifted is equal to N then it will latch.
-    pub fn shift_out(&mut self, state: PinState, update_bit_array: bool) {
-        // Rising edge of serial in pin (setup)
-        if state {
-            let _ = self.serial_input_pin.set_high();
-        } else {
-            let _ = self.serial_input_pin.set_low();
-        }
-        delay_us(SERIAL_RISING_EDGE_PADDING_NS);
-
-        // Rising edge of clock pulse
-        let _ = self.clock_pin.set_high();
-
-        // Falling edge of serial in pin (tie to GND again)
-        delay_us(SERIAL_FALLING_EDGE_PADDING_NS);
-        let _ = self.serial_input_pin.set_low();
-
-        // Falling edge of clock pulse
-        delay_us(CLOCK_WIDTH_NS - SERIAL_FALLING_EDGE_PADDING_NS);
-        let _ = self.clock_pin.set_low();
-
-        // Latch if all bits shifted out
-        let current = self.current_shifted_bit + 1;
-        self.current_shifted_bit = current;
-        if current == N {
-            self.latch();
-            return;
-        }
-
-        // No latching, update bit array
-        if update_bit_array {
-            self.bit_array.rotate_right(1_usize);
-            self.bit_array[0] = state;
-            self.is_latched = false
-        }
+    fn write(&mut self, bits: u8) {
+        unsafe { &*PORTD::ptr() }.portd.write(|w| w.bits(bits));
     }
+}
 
-    /// Latch the shift register and reset the shift register
-    pub fn latch(&mut self) {
-        trace!("[TRACE] [SHIFT REG] Latching");
-        self.is_latched = true;
+#[cfg(not(feature = "spi-displays"))]
+pub type HoursMinutesShiftRegister = PortShiftRegister<2_usize, PortD>;
+#[cfg(feature = "spi-displays")]
+pub type HoursMinutesShiftRegister =
+    SpiShiftRegister<2_usize, Spi, pins::hours_minutes_display::Latch>;
 
-        let _ = self.latch_pin.set_low();
-        delay_us(LATCH_WIDTH_NS);
-        let _ = self.latch_pin.set_high();
+#[cfg(not(feature = "spi-displays"))]
+pub type SecondsShiftRegister = PortShiftRegister<2_usize, PortD>;
+#[cfg(feature = "spi-displays")]
+pub type SecondsShiftRegister = SpiShiftRegister<2_usize, Spi, pins::seconds_display::Latch>;
 
-        self.current_shifted_bit = 0_usize;
-    }
+/// The hours and minutes display on PD2 (serial input), PD3 (clock) and PD4
+/// (latch). The pins are taken so they stay outputs and nothing else uses
+/// them.
+#[cfg(not(feature = "spi-displays"))]
+pub fn hours_minutes(
+    _pins: ShiftRegisterPins<
+        pins::hours_minutes_display::SerialIn,
+        pins::hours_minutes_display::Clock,
+        pins::hours_minutes_display::Latch,
+    >,
+) -> HoursMinutesShiftRegister {
+    PortShiftRegister::new(PortD { _private: () }, 1_u8 << 2, 1_u8 << 3, 1_u8 << 4)
+}
 
-    /// Shift out a bit array so that the first output on the first shift
-    /// register is equal to the first bit in the array
-    pub fn set_bit_array(&mut self, bit_array: [PinState; N]) {
-        for state in bit_array.iter().rev() {
-            self.shift_out(*state, false);
-        }
-        self.bit_array = bit_array;
-    }
+/// The seconds display on PD5 (serial input), PD6 (clock) and PD7 (latch)
+#[cfg(not(feature = "spi-displays"))]
+pub fn seconds(
+    _pins: ShiftRegisterPins<
+        pins::seconds_display::SerialIn,
+        pins::seconds_display::Clock,
+        pins::seconds_display::Latch,
+    >,
+) -> SecondsShiftRegister {
+    PortShiftRegister::new(PortD { _private: () }, 1_u8 << 5, 1_u8 << 6, 1_u8 << 7)
 }
@@ -2,14 +2,11 @@
 //! the interrupt handler. See `interrupts.rs` for more!
 
 use crate::{
-    console::{debug, println},
-    interrupts::{changed_state, get_snooze_button_pressed, RotaryEncoderState},
+    console::println,
+    interrupts::{get_snooze_button_pressed, RotaryEncoderState},
+    log::{debug, Tag},
     pins::{self, RotaryEncoderPins},
-    shared::{
-        PinState::{PinState, HIGH, LOW},
-        TimeDigits,
-    },
-    shift_register::ShiftRegister,
+    shared::PinState::{PinState, HIGH, LOW},
 };
 use arduino_hal::{
     hal::port::{self, Dynamic},
@@ -35,12 +32,14 @@ impl SnoozeButton {
         }
     }
 
-    pub fn update(&mut self) {
+    /// `changed` is whether the pin change interrupt ran since the last update
+    pub fn update(&mut self, changed: bool) {
         let state = get_snooze_button_pressed();
         // Only detect snooze button presses, ignore rotary encoder changes
-        self.changed = changed_state() && (self.state != state);
+        self.changed = changed && (self.state != state);
         debug!(
-            "[DEBUG] [SNOOZE] Snooze button update, changed: {}",
+            Tag::Snooze,
+            "Snooze button update, changed: {}",
             match self.changed {
                 true => "YES",
                 false => "NO",
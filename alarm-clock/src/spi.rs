@@ -0,0 +1,43 @@
+//! The hardware SPI, as the bus the time displays' shift registers share when
+//! built with `spi-displays`
+
+use arduino_hal::pac::SPI;
+use core::convert::Infallible;
+use embedded_hal::blocking::spi;
+
+use crate::pins;
+
+/// The SPI as a master at 8MHz (the clock over 2), in mode 0 and MSB first.
+/// Every copy drives the same peripheral, so one copy's write mustn't be
+/// interrupted by another's; see `time_display::Seconds`.
+#[derive(Clone, Copy)]
+pub struct Spi {
+    _private: (),
+}
+impl Spi {
+    /// Take over the bus. SS (the character LCD's latch) has to already be an
+    /// output, or pulling it low would drop the SPI out of master mode.
+    pub fn new(
+        spi: SPI,
+        _sck: pins::spi::Sck,
+        _mosi: pins::spi::Mosi,
+        _ss: &pins::character_lcd::Latch,
+    ) -> Self {
+        spi.spsr.write(|w| w.spi2x().set_bit());
+        spi.spcr.write(|w| w.spe().set_bit().mstr().set_bit());
+        Self { _private: () }
+    }
+}
+impl spi::Write<u8> for Spi {
+    type Error = Infallible;
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Infallible> {
+        let spi = unsafe { &*SPI::ptr() };
+        for word in words {
+            spi.spdr.write(|w| w.bits(*word));
+            // A byte takes 16 cycles, so there's no point in giving up the CPU
+            while spi.spsr.read().spif().bit_is_clear() {}
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,18 @@
+//! Generates the same dictionary of log messages as the firmware's build script
+
+#[path = "../alarm-clock/build/log_scan.rs"]
+mod log_scan;
+
+use std::{env, fs, path::Path};
+
+const FIRMWARE_SOURCES: &str = "../alarm-clock/src";
+
+fn main() {
+    println!("cargo:rerun-if-changed={FIRMWARE_SOURCES}");
+    println!("cargo:rerun-if-changed=../alarm-clock/build/log_scan.rs");
+
+    let messages =
+        log_scan::scan(Path::new(FIRMWARE_SOURCES)).expect("Failed to scan the firmware's sources");
+    let out = Path::new(&env::var("OUT_DIR").expect("OUT_DIR not set")).join("log_messages.rs");
+    fs::write(out, log_scan::generate(&messages)).expect("Failed to write the log messages");
+}
@@ -0,0 +1,177 @@
+//! Splits the clock's output into plain text and log frames, leaving out the
+//! protocol's packets
+
+use crate::binary_log::{
+    decode_contents, DecodedFrame, DICTIONARY_MESSAGE, FRAME_START, MAX_FRAME_LENGTH,
+};
+use alarm_clock_core::log::{Level, Tag};
+use alarm_clock_protocol::DELIMITER;
+use std::io::{self, Write};
+
+/// The shortest frame after its length byte: a message, tag and level, plus
+/// the byte encoding them adds
+const MIN_FRAME_LENGTH: usize = 5_usize;
+
+enum State {
+    Text,
+    Length,
+    /// Collecting this many more bytes of a frame
+    Frame(usize),
+    /// Skipping a packet for a host tool, up to its closing delimiter
+    Packet,
+}
+
+pub struct Decoder<'a> {
+    messages: &'a [&'a str],
+    hash: u32,
+    state: State,
+    frame: Vec<u8>,
+}
+impl<'a> Decoder<'a> {
+    /// A decoder for firmware built with `messages`, whose hash is `hash`
+    pub fn new(messages: &'a [&'a str], hash: u32) -> Self {
+        Self {
+            messages,
+            hash,
+            state: State::Text,
+            frame: Vec::with_capacity(MAX_FRAME_LENGTH),
+        }
+    }
+
+    /// Decode the next byte from the clock, writing any text or finished log
+    /// message to `out`
+    pub fn push(&mut self, byte: u8, out: &mut impl Write) -> io::Result<()> {
+        match self.state {
+            State::Text if byte == FRAME_START => self.state = State::Length,
+            State::Text if byte == DELIMITER => self.state = State::Packet,
+            State::Text => out.write_all(&[byte])?,
+            State::Packet if byte == DELIMITER => self.state = State::Text,
+            State::Packet => (),
+            State::Length => {
+                let length = byte as usize;
+                if (MIN_FRAME_LENGTH..=MAX_FRAME_LENGTH - 2_usize).contains(&length) {
+                    self.frame.clear();
+                    self.state = State::Frame(length);
+                } else {
+                    writeln!(out, "<bad frame length {length}>")?;
+                    self.state = State::Text;
+                }
+            }
+            State::Frame(remaining) => {
+                self.frame.push(byte);
+                if remaining > 1_usize {
+                    self.state = State::Frame(remaining - 1_usize);
+                } else {
+                    self.state = State::Text;
+                    self.write_frame(out)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write_frame(&self, out: &mut impl Write) -> io::Result<()> {
+        let mut contents = [0_u8; MAX_FRAME_LENGTH];
+        let frame = match decode_contents(&self.frame, &mut contents).and_then(DecodedFrame::decode)
+        {
+            Ok(frame) => frame,
+            Err(e) => return writeln!(out, "<bad frame: {e:?}>"),
+        };
+
+        if frame.message == DICTIONARY_MESSAGE {
+            let hash = frame.arguments().next().and_then(Result::ok);
+            if hash != Some(crate::binary_log::Value::Unsigned(self.hash)) {
+                writeln!(
+                    out,
+                    "<the firmware's log messages don't match the decoder's; \
+                     rebuild the decoder from the same sources>"
+                )?;
+            }
+            return Ok(());
+        }
+
+        let level = Level::from_u8(frame.level).map_or("?", |level| level.name());
+        let tag = Tag::ALL
+            .get(frame.tag as usize)
+            .map_or("?", |tag| tag.name());
+        write!(out, "[{level}] [{tag}] ")?;
+        match self.messages.get(frame.message as usize) {
+            Some(format) => {
+                let mut message = String::new();
+                // Writing to a string never fails
+                let _ = frame.format(format, &mut message);
+                writeln!(out, "{message}")
+            }
+            None => writeln!(out, "<unknown message {}>", frame.message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary_log::{Argument, Frame};
+    use alarm_clock_protocol::Response;
+
+    const MESSAGES: [&str; 2] = ["Chiming", "Read time: {}{}:{}{}:{}{}"];
+    const HASH: u32 = 0x1234_5678_u32;
+
+    fn decode(bytes: &[u8]) -> String {
+        let mut decoder = Decoder::new(&MESSAGES, HASH);
+        let mut out = Vec::new();
+        for byte in bytes {
+            decoder.push(*byte, &mut out).unwrap();
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn text_and_frames() {
+        let mut bytes = b"Hello from the Alarm Clock!\n".to_vec();
+        bytes.extend_from_slice(Frame::dictionary(HASH).bytes());
+        bytes.extend_from_slice(Frame::new(0_u16, Tag::Chime as u8, Level::Debug as u8).bytes());
+        bytes.extend_from_slice(b"> ");
+        let mut frame = Frame::new(1_u16, Tag::Rtc as u8, Level::Debug as u8);
+        for digit in "123456".chars() {
+            digit.encode(&mut frame);
+        }
+        bytes.extend_from_slice(frame.bytes());
+
+        assert_eq!(
+            decode(&bytes),
+            "Hello from the Alarm Clock!\n\
+             [DEBUG] [CHIME] Chiming\n\
+             > [DEBUG] [RTC] Read time: 12:34:56\n"
+        );
+    }
+
+    #[test]
+    fn packets_are_left_out() {
+        let mut out = [0_u8; alarm_clock_protocol::MAX_FRAME_LENGTH];
+        // The ID is sent as is, so the packet has a byte like `FRAME_START`
+        let packet =
+            alarm_clock_protocol::encode_packet(0xFF_u8, &Response::Done, &mut out).unwrap();
+        assert!(packet.contains(&FRAME_START));
+        let mut bytes = b"> ".to_vec();
+        bytes.extend_from_slice(packet);
+        bytes.extend_from_slice(Frame::new(0_u16, Tag::Chime as u8, Level::Debug as u8).bytes());
+        assert_eq!(decode(&bytes), "> [DEBUG] [CHIME] Chiming\n");
+    }
+
+    #[test]
+    fn mismatched_dictionary() {
+        let out = decode(Frame::dictionary(!HASH).bytes());
+        assert!(out.contains("don't match"));
+    }
+
+    #[test]
+    fn bad_frames() {
+        let mut bytes = Frame::new(7_u16, 42_u8, 9_u8).bytes().to_vec();
+        bytes.extend_from_slice(&[FRAME_START, 200_u8]);
+        bytes.extend_from_slice(b"ok\n");
+        assert_eq!(
+            decode(&bytes),
+            "[?] [?] <unknown message 7>\n<bad frame length 200>\nok\n"
+        );
+    }
+}
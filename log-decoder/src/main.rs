@@ -0,0 +1,107 @@
+//! Decodes the logs of firmware built with the `binary-log` feature
+//!
+//! Reads the clock's serial output from a file (such as the serial port, after
+//! `stty -F /dev/ttyACM0 57600 raw`) or standard input, passing plain text
+//! through and formatting log frames as the firmware would have.
+
+#[path = "../../alarm-clock/src/binary_log.rs"]
+#[allow(dead_code)]
+mod binary_log;
+mod decoder;
+mod log_messages {
+    include!(concat!(env!("OUT_DIR"), "/log_messages.rs"));
+}
+
+use decoder::Decoder;
+use std::{
+    env,
+    fs::File,
+    io::{self, BufReader, Read},
+    process::ExitCode,
+};
+
+const USAGE: &str = "Usage: log-decoder [PATH]\n\
+                     Decodes the alarm clock's binary logs from PATH, or standard input if omitted";
+
+fn main() -> ExitCode {
+    let arguments = env::args().skip(1).collect::<Vec<_>>();
+    let input: Box<dyn Read> = match arguments.as_slice() {
+        [] => Box::new(io::stdin()),
+        [flag] if flag == "-h" || flag == "--help" => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        [path] => match File::open(path) {
+            Ok(file) => Box::new(file),
+            Err(e) => {
+                eprintln!("Failed to open {path}: {e}");
+                return ExitCode::FAILURE;
+            }
+        },
+        _ => {
+            eprintln!("{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut decoder = Decoder::new(&log_messages::MESSAGES, log_messages::HASH);
+    // Standard output is line buffered, so lines show up as they arrive
+    let mut stdout = io::stdout().lock();
+    for byte in BufReader::new(input).bytes() {
+        if let Err(e) = byte.and_then(|byte| decoder.push(byte, &mut stdout)) {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+#[path = "../../alarm-clock/build/log_scan.rs"]
+#[allow(dead_code)]
+mod log_scan;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use binary_log::find_message;
+
+    #[test]
+    fn scanning() {
+        let source = r#"
+            //! debug!(Tag::Rtc, "In a comment");
+            debug!(Tag::Main, "Loading settings");
+            mydebug!(Tag::Main, "Not a logging macro");
+            error!(
+                Tag::Rtc,
+                "Error when reading time: {:?}", e
+            );
+            trace!(Tag::ALL[0], "Quoted \"tag\"\n");
+            info!(Tag::Chime, "Split \
+                               line");
+            debug!(Tag::Main, "Loading settings");
+            warning!(Tag::Main, FORMAT);
+        "#;
+        assert_eq!(
+            log_scan::scan_source(source),
+            [
+                "Loading settings",
+                "Error when reading time: {:?}",
+                "Quoted \"tag\"\n",
+                "Split line",
+                "Loading settings",
+            ]
+        );
+    }
+
+    #[test]
+    fn firmware_dictionary() {
+        let messages = &log_messages::MESSAGES;
+        for (n, message) in messages.iter().enumerate() {
+            assert_eq!(find_message(messages, message), Some(n as u16));
+        }
+        assert!(find_message(messages, "Read time: {}{}:{}{}:{}{}").is_some());
+        let owned = messages.map(String::from);
+        assert_eq!(log_scan::hash(&owned), log_messages::HASH);
+    }
+}